@@ -0,0 +1,487 @@
+//! The `#[given]`, `#[when]` and `#[then]` attributes of `cucumber_rust`,
+//! re-exported by it with the `macros` feature.
+//!
+//! An annotated function stays as it is, so that it can be documented and
+//! unit tested, and a struct of the same name is declared alongside it which
+//! implements `cucumber_rust::StepDefinition` to add the step to a
+//! `StepsBuilder`. The step is also submitted to the inventory of steps
+//! collected at link time.
+//!
+//! `#[derive(World)]` assembles a world from components.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{
+    parse_macro_input, parse_quote, AttributeArgs, Data, DeriveInput, Field, FnArg, Index, ItemFn,
+    Lit, Meta, NestedMeta, Pat, Type, TypeReference,
+};
+
+/// Defines a Given step: `#[given("I am trying out Cucumber")]`, or
+/// `#[given(regex = r"^I have (\d+) cukes$")]`, or
+/// `#[given(expression = "I have {int} cukes")]`. Further patterns of the same
+/// kind are aliases of the first: `#[given("I log in", "I am logged in")]`.
+/// A step needing a data table or a docstring declares it with
+/// `requires = "table"` or `requires = "docstring"`.
+#[proc_macro_attribute]
+pub fn given(args: TokenStream, input: TokenStream) -> TokenStream {
+    step("Given", args, input)
+}
+
+/// Defines a When step, as `#[given]` does a Given one.
+#[proc_macro_attribute]
+pub fn when(args: TokenStream, input: TokenStream) -> TokenStream {
+    step("When", args, input)
+}
+
+/// Defines a Then step, as `#[given]` does a Given one.
+#[proc_macro_attribute]
+pub fn then(args: TokenStream, input: TokenStream) -> TokenStream {
+    step("Then", args, input)
+}
+
+/// Implements `cucumber_rust::World` for a struct whose fields are
+/// `cucumber_rust::Component`s, set up in order and torn down in reverse,
+/// and `cucumber_rust::Has` for the type of each field. Fields which are not
+/// components are marked `#[world(skip)]`.
+#[proc_macro_derive(World, attributes(world))]
+pub fn derive_world(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match expand_world(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// How the text of steps is matched.
+enum Pattern {
+    Literal(String),
+    Regex(String),
+    Expression(String),
+}
+
+/// What an argument of the step function after the world is given.
+enum Argument {
+    Step,
+    Matches,
+    Captures,
+    Fixture,
+    Capture(Box<Type>),
+}
+
+fn step(ty: &str, args: TokenStream, input: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(args as AttributeArgs);
+    let function = parse_macro_input!(input as ItemFn);
+
+    match expand(ty, &args, &function) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand(
+    ty: &str,
+    args: &[NestedMeta],
+    function: &ItemFn,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let requirement = requirement(args)?;
+    let doc = doc(function);
+    let mut patterns = patterns(args)?;
+    let aliases = patterns.split_off(1);
+    let pattern = patterns.remove(0);
+    let name = &function.sig.ident;
+    let vis = &function.vis;
+    let ty = syn::Ident::new(ty, Span::call_site());
+
+    if let Some(asyncness) = &function.sig.asyncness {
+        return Err(syn::Error::new_spanned(
+            asyncness,
+            "asynchronous step functions are not supported",
+        ));
+    }
+
+    let mut inputs = function.sig.inputs.iter();
+    let world = match inputs.next() {
+        Some(FnArg::Typed(arg)) => match &*arg.ty {
+            Type::Reference(TypeReference {
+                mutability: Some(_),
+                elem,
+                ..
+            }) => elem.clone(),
+            other => {
+                return Err(syn::Error::new_spanned(
+                    other,
+                    "the first argument of a step function must be `&mut` its world",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &function.sig,
+                "a step function must take `&mut` its world first",
+            ))
+        }
+    };
+
+    let mut values = vec![];
+    let mut captures = 0usize;
+    let mut has_matches = false;
+    for input in inputs {
+        values.push(match argument(input)? {
+            Argument::Step => quote!(step),
+            Argument::Matches => {
+                has_matches = true;
+                quote!(matches)
+            }
+            Argument::Captures => match &pattern {
+                Pattern::Regex(regex) => {
+                    has_matches = true;
+                    quote!(&::cucumber_rust::Captures::new(#regex, matches))
+                }
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        input,
+                        "only regular expressions have named groups, use `regex = \"...\"`",
+                    ))
+                }
+            },
+            Argument::Fixture => quote!(::cucumber_rust::fixture()),
+            Argument::Capture(ty) => {
+                captures += 1;
+                let type_name = quote!(#ty).to_string();
+                quote!(::cucumber_rust::parse_capture::<#ty>(matches, #captures, #type_name))
+            }
+        });
+    }
+    if let Pattern::Literal(_) = pattern {
+        if captures > 0 || has_matches {
+            return Err(syn::Error::new_spanned(
+                &function.sig,
+                "steps matched by their exact text capture nothing, \
+                 use `regex = \"...\"` or `expression = \"...\"`",
+            ));
+        }
+    }
+
+    let step_type = quote!(::cucumber_rust::StepType::#ty);
+    let original = pattern.text().to_string();
+    let mut alias_texts = vec![];
+    for alias in &aliases {
+        if std::mem::discriminant(alias) != std::mem::discriminant(&pattern) {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "every pattern of a step must be of the same kind as its first",
+            ));
+        }
+        alias_texts.push(alias.text());
+    }
+    let register = match pattern {
+        Pattern::Literal(text) => quote! {
+            steps.add_normal(#step_type, #text, |world, step| {
+                ::cucumber_rust::step_result(#name(world, #(#values),*))
+            });
+        },
+        Pattern::Regex(regex) if has_matches => quote! {
+            steps.add_regex(#step_type, #regex, |world, matches, step| {
+                ::cucumber_rust::step_result(#name(world, #(#values),*))
+            });
+        },
+        Pattern::Expression(expression) if has_matches => quote! {
+            steps.add_expression(#step_type, #expression, |world, matches, step| {
+                ::cucumber_rust::step_result(#name(world, #(#values),*))
+            });
+        },
+        Pattern::Regex(regex) => quote! {
+            steps.add_typed(
+                #step_type,
+                #regex,
+                ::cucumber_rust::TypedStep::new(#captures, |world, matches, step| {
+                    ::cucumber_rust::step_result(#name(world, #(#values),*))
+                }),
+            );
+        },
+        Pattern::Expression(expression) => quote! {
+            steps.add_typed_expression(
+                #step_type,
+                #expression,
+                ::cucumber_rust::TypedStep::new(#captures, |world, matches, step| {
+                    ::cucumber_rust::step_result(#name(world, #(#values),*))
+                }),
+            );
+        },
+    };
+
+    // A function generic over its world is a step for any world meeting its
+    // bounds, which is only known where it is added to a `StepsBuilder`.
+    let mut generics = function.sig.generics.clone();
+    let submit = if generics.params.is_empty() {
+        quote! {
+            ::cucumber_rust::inventory::submit! {
+                #![crate = ::cucumber_rust]
+                ::cucumber_rust::Registration::new::<#world, #name>()
+            }
+        }
+    } else {
+        generics
+            .make_where_clause()
+            .predicates
+            .push(parse_quote!(#world: ::cucumber_rust::World));
+        quote!()
+    };
+    let (impl_generics, _, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+        #function
+
+        #[allow(non_camel_case_types, dead_code)]
+        #[doc(hidden)]
+        #vis struct #name {}
+
+        impl #impl_generics ::cucumber_rust::StepDefinition<#world> for #name #where_clause {
+            #[allow(unused_variables)]
+            fn register(steps: &mut ::cucumber_rust::StepsBuilder<#world>) {
+                steps.file(file!());
+                #doc
+                #requirement
+                #register
+                #( steps.alias(#step_type, #alias_texts, #original); )*
+            }
+        }
+
+        #submit
+    })
+}
+
+impl Pattern {
+    fn text(&self) -> &str {
+        match self {
+            Pattern::Literal(text) | Pattern::Regex(text) | Pattern::Expression(text) => text,
+        }
+    }
+}
+
+/// The patterns of the step, the first one and then its aliases.
+fn patterns(args: &[NestedMeta]) -> syn::Result<Vec<Pattern>> {
+    let args = args
+        .iter()
+        .filter(|arg| requires(arg).is_none())
+        .collect::<Vec<_>>();
+    if args.is_empty() {
+        return Err(syn::Error::new(
+            Span::call_site(),
+            "expected the text of the step, `regex = \"...\"` or `expression = \"...\"`",
+        ));
+    }
+    args.into_iter().map(pattern).collect()
+}
+
+/// The value of a `requires = "..."` argument.
+fn requires(arg: &NestedMeta) -> Option<&Lit> {
+    match arg {
+        NestedMeta::Meta(Meta::NameValue(pair)) if pair.path.is_ident("requires") => {
+            Some(&pair.lit)
+        }
+        _ => None,
+    }
+}
+
+/// Describes the step with the doc comment of the function, if it has one.
+fn doc(function: &ItemFn) -> proc_macro2::TokenStream {
+    let lines = function
+        .attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("doc"))
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(Meta::NameValue(pair)) => match pair.lit {
+                Lit::Str(line) => Some(line.value()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let doc = lines
+        .iter()
+        .map(|line| match line.chars().next() {
+            Some(' ') => &line[1..],
+            _ => line.as_str(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let doc = doc.trim();
+    if doc.is_empty() {
+        quote!()
+    } else {
+        quote!(steps.doc(#doc);)
+    }
+}
+
+/// Declares what the step needs besides its text, if it says so.
+fn requirement(args: &[NestedMeta]) -> syn::Result<proc_macro2::TokenStream> {
+    let mut tokens = quote!();
+    for lit in args.iter().filter_map(requires) {
+        let variant = match lit {
+            Lit::Str(value) if value.value() == "table" => quote!(Table),
+            Lit::Str(value) if value.value() == "docstring" => quote!(Docstring),
+            other => {
+                return Err(syn::Error::new_spanned(
+                    other,
+                    "expected `requires = \"table\"` or `requires = \"docstring\"`",
+                ))
+            }
+        };
+        tokens = quote!(steps.requires(::cucumber_rust::Requirement::#variant););
+    }
+    Ok(tokens)
+}
+
+fn pattern(arg: &NestedMeta) -> syn::Result<Pattern> {
+    match arg {
+        NestedMeta::Lit(Lit::Str(text)) => Ok(Pattern::Literal(text.value())),
+        NestedMeta::Meta(Meta::NameValue(pair)) => {
+            let value = match &pair.lit {
+                Lit::Str(value) => value.value(),
+                other => return Err(syn::Error::new_spanned(other, "expected a string")),
+            };
+            if pair.path.is_ident("regex") {
+                Ok(Pattern::Regex(value))
+            } else if pair.path.is_ident("expression") {
+                Ok(Pattern::Expression(value))
+            } else {
+                Err(syn::Error::new_spanned(
+                    &pair.path,
+                    "expected `regex` or `expression`",
+                ))
+            }
+        }
+        other => Err(syn::Error::new_spanned(
+            other,
+            "expected the text of the step, `regex = \"...\"` or `expression = \"...\"`",
+        )),
+    }
+}
+
+/// Tells apart the step, the captures as strings, fixtures and typed captures
+/// by the type of the argument.
+fn argument(arg: &FnArg) -> syn::Result<Argument> {
+    let arg = match arg {
+        FnArg::Typed(arg) => arg,
+        FnArg::Receiver(receiver) => {
+            return Err(syn::Error::new_spanned(
+                receiver,
+                "step functions cannot take `self`",
+            ))
+        }
+    };
+    match &*arg.pat {
+        Pat::Ident(_) | Pat::Wild(_) => {}
+        other => {
+            return Err(syn::Error::new_spanned(
+                other,
+                "expected the name of the argument",
+            ))
+        }
+    }
+
+    if let Type::Reference(reference) = &*arg.ty {
+        match &*reference.elem {
+            Type::Path(path) if is_named(&path.path, "Step") => return Ok(Argument::Step),
+            Type::Path(path) if is_named(&path.path, "Captures") => return Ok(Argument::Captures),
+            Type::Slice(_) => return Ok(Argument::Matches),
+            _ => {}
+        }
+    }
+    match &*arg.ty {
+        Type::Path(path) if is_named(&path.path, "Fixture") => Ok(Argument::Fixture),
+        _ => Ok(Argument::Capture(arg.ty.clone())),
+    }
+}
+
+fn is_named(path: &syn::Path, name: &str) -> bool {
+    match path.segments.last() {
+        Some(segment) => segment.ident == name,
+        None => false,
+    }
+}
+
+fn expand_world(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "only structs can derive `World`",
+            ))
+        }
+    };
+
+    let mut members = vec![];
+    let mut types = vec![];
+    for (index, field) in fields.iter().enumerate() {
+        if is_skipped(field)? {
+            continue;
+        }
+        members.push(match &field.ident {
+            Some(ident) => quote!(#ident),
+            None => {
+                let index = Index::from(index);
+                quote!(#index)
+            }
+        });
+        types.push(&field.ty);
+    }
+    let reversed = members.iter().rev().collect::<Vec<_>>();
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::cucumber_rust::World for #name #ty_generics #where_clause {
+            fn setup(&mut self) -> ::cucumber_rust::StepFuture<'_> {
+                Box::pin(async move {
+                    #(::cucumber_rust::Component::setup(&mut self.#members).await;)*
+                })
+            }
+
+            fn teardown(&mut self) -> ::cucumber_rust::StepFuture<'_> {
+                Box::pin(async move {
+                    #(::cucumber_rust::Component::teardown(&mut self.#reversed).await;)*
+                })
+            }
+        }
+
+        #(
+            impl #impl_generics ::cucumber_rust::Has<#types> for #name #ty_generics #where_clause {
+                fn component(&mut self) -> &mut #types {
+                    &mut self.#members
+                }
+            }
+        )*
+    })
+}
+
+/// Whether the field is marked `#[world(skip)]`.
+fn is_skipped(field: &Field) -> syn::Result<bool> {
+    let mut skipped = false;
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("world"))
+    {
+        match attr.parse_meta()? {
+            Meta::List(list) => {
+                for nested in &list.nested {
+                    match nested {
+                        NestedMeta::Meta(Meta::Path(path)) if path.is_ident("skip") => {
+                            skipped = true
+                        }
+                        other => return Err(syn::Error::new_spanned(other, "expected `skip`")),
+                    }
+                }
+            }
+            other => return Err(syn::Error::new_spanned(other, "expected `#[world(skip)]`")),
+        }
+    }
+    Ok(skipped)
+}
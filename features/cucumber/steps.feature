@@ -0,0 +1,99 @@
+Feature: Example steps
+
+  Scenario: plain steps
+    Given I am trying out Cucumber
+    When I consider what I am doing
+    Then I am interested in ATDD
+
+  Scenario: a step alias
+    Given I am trying out Cucumber
+    When I think about what I am doing
+    Then I am interested in ATDD
+
+  Scenario: captured values
+    Then we can implement rules with regex
+    And we can also match 42 olika types
+
+  Scenario: an anchored pattern
+    Then the pattern of this step is anchored
+
+  Scenario: named captures
+    Then Ann buys 3 cucumbers
+
+  Scenario: a Cucumber Expression
+    Then we can match 3 "small" cucumbers with an expression
+
+  Scenario: a step returning a Result
+    Then steps can return a Result
+
+  # Pending steps fail a strict run: quarantined, the scenario is reported
+  # without failing it.
+  @quarantine
+  Scenario: a pending step
+    Then we can leave a step for later
+
+  Scenario: the context of a step
+    Then the step knows where it runs
+    And the before hook left the name of the scenario
+
+  @foo
+  Scenario: foo
+    Then the before hook left the name of the scenario
+
+  Scenario Outline: asynchronous steps
+    When we wait for <what>
+    Then we waited for 42
+
+    Examples:
+      | what       |
+      | something  |
+      | a function |
+
+  Scenario: a data table
+    Then we can use data tables to provide more parameters
+      | key | value |
+      | a   | fizz  |
+      | b   | buzz  |
+
+  Scenario: rows of a data table
+    Then we can compare data tables with the expected rows
+      | key | value |
+      | a   | fizz  |
+      | b   | buzz  |
+
+  @xfail
+  Scenario: rows of a data table which differ
+    Then we can compare data tables with the expected rows
+      | key | value |
+      | a   | fizz  |
+      | c   | buzz  |
+
+  @xfail
+  Scenario: a data table is required
+    Then we can use data tables to provide more parameters
+
+  Scenario: soft assertions
+    Then the answer is 42 and the question is 54
+
+  @xfail
+  Scenario: soft assertions which fail
+    Then the answer is 41 and the question is 53
+
+  Scenario: steps shared between worlds
+    Given I am trying out Cucumber
+    Then foo is Some string
+    When we forget about foo
+    And I consider what I am doing
+    Then foo is .
+
+  Scenario: a frozen clock
+    Given the current time is 2020-01-31T12:00:00Z
+    When 5 minutes pass
+    Then the clock reads 2020-01-31T12:05:00Z
+
+  Rule: Scenarios can be grouped in rules
+
+    Scenario: a scenario inside a rule
+      Given I am trying out Cucumber
+      When I consider what I am doing
+      Then I am interested in ATDD
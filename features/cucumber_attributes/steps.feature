@@ -0,0 +1,31 @@
+Feature: Steps of attributes
+
+  Scenario: plain steps
+    Given I am trying out Cucumber
+    When I consider what I am doing
+    Then I am interested in ATDD
+
+  Scenario: captured values
+    Then we can implement rules with regex
+    And we can also match 42 olika types
+
+  Scenario: named captures
+    Then Bob sells 3 cucumbers
+
+  Scenario: a Cucumber Expression
+    Then we can match 3 "small" cucumbers with an expression
+
+  Scenario: a docstring
+    Then we can read a docstring
+      """
+      Some text
+      """
+
+  @xfail
+  Scenario: a docstring is required
+    Then we can read a docstring
+
+  Scenario: components of the world
+    When we note cucumbers
+    And we note gherkins
+    Then we have noted 2 words
@@ -0,0 +1,13 @@
+Feature: Steps of a builder
+
+  Scenario: plain steps
+    Given I am trying out Cucumber
+    When I consider what I am doing
+    Then I am interested in ATDD
+
+  Scenario: captured values
+    Then we can implement rules with regex
+    And we can also match 42 olika types
+
+  Scenario: typed steps
+    Then we counted 2 cucumbers
@@ -0,0 +1,73 @@
+//! Around hooks, which are given the rest of a scenario to run, e.g. inside
+//! a transaction or a tracing span, and clean up whatever its outcome.
+
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::{AroundFn, Scenario};
+
+/// The rest of a scenario, given to an around hook: the next around hooks,
+/// then the before hooks, the steps and the after hooks.
+pub struct Proceed<'a> {
+    next: &'a mut dyn FnMut() -> Option<bool>,
+    outcome: Option<bool>,
+    panic: Option<Box<dyn Any + Send>>,
+}
+
+impl Proceed<'_> {
+    /// Runs the rest of the scenario and returns whether it passed. Calling
+    /// it again runs the scenario again, e.g. to retry it, and the last run
+    /// decides its outcome; each run is reported.
+    ///
+    /// A panic outside of the steps, e.g. while creating the world, is held
+    /// until the hook returns, so that the code following this call runs.
+    pub fn proceed(&mut self) -> bool {
+        if self.panic.is_some() {
+            return false;
+        }
+        let next = &mut self.next;
+        match panic::catch_unwind(AssertUnwindSafe(next)) {
+            Ok(outcome) => {
+                if outcome.is_some() {
+                    self.outcome = outcome;
+                }
+                outcome.unwrap_or(true)
+            }
+            Err(payload) => {
+                self.panic = Some(payload);
+                self.outcome = Some(false);
+                false
+            }
+        }
+    }
+
+    /// Whether the scenario ran, which it does not if an around hook never
+    /// proceeds.
+    pub fn has_run(&self) -> bool {
+        self.outcome.is_some()
+    }
+}
+
+/// Runs `scenario` through `hooks`, the first one outermost, and returns
+/// whether it passed, or `None` if a hook did not let it run.
+pub(crate) fn run(
+    hooks: &[AroundFn],
+    scenario: &Scenario,
+    scenario_fn: &mut dyn FnMut() -> bool,
+) -> Option<bool> {
+    let (hook, rest) = match hooks.split_first() {
+        Some(split) => split,
+        None => return Some(scenario_fn()),
+    };
+    let mut next = || run(rest, scenario, scenario_fn);
+    let mut proceed = Proceed {
+        next: &mut next,
+        outcome: None,
+        panic: None,
+    };
+    hook(scenario, &mut proceed);
+    if let Some(payload) = proceed.panic {
+        panic::resume_unwind(payload);
+    }
+    proceed.outcome
+}
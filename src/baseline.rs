@@ -0,0 +1,114 @@
+//! Comparing how long scenarios took with a previous run's JSON report, for
+//! `--baseline`, to catch scenarios which became slower.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::durations;
+use crate::report::{Regression, Report};
+
+/// How much slower than in the baseline, in percent, a scenario may get
+/// before it is reported.
+pub(crate) const DEFAULT_THRESHOLD: f64 = 20.0;
+
+/// Scenarios getting slower by less than this are not reported, however
+/// large the ratio, as short ones vary too much from run to run.
+const MIN_SLOWDOWN: Duration = Duration::from_millis(100);
+
+pub(crate) struct Baseline(BTreeMap<String, Duration>);
+
+impl Baseline {
+    pub(crate) fn load(path: &Path) -> io::Result<Baseline> {
+        let file = BufReader::new(File::open(path)?);
+        let report: Report = serde_json::from_reader(file).map_err(io::Error::from)?;
+        Ok(Baseline(average_durations(&report)))
+    }
+
+    /// The scenarios of `report` which took more than `threshold` percent
+    /// longer than in the baseline, the worst first. Repeated scenarios are
+    /// compared by their average duration.
+    pub(crate) fn regressions(&self, report: &Report, threshold: f64) -> Vec<Regression> {
+        let mut names = BTreeMap::new();
+        for feature in &report.features {
+            for scenario in &feature.scenarios {
+                let key = durations::key(Path::new(&feature.path), scenario.line);
+                names.insert(
+                    key,
+                    (feature.path.clone(), scenario.line, scenario.name.clone()),
+                );
+            }
+        }
+
+        let mut regressions = average_durations(report)
+            .into_iter()
+            .filter_map(|(key, duration)| {
+                let baseline = *self.0.get(&key)?;
+                if duration < baseline + MIN_SLOWDOWN
+                    || duration.as_secs_f64() <= baseline.as_secs_f64() * (1.0 + threshold / 100.0)
+                {
+                    return None;
+                }
+                let (path, line, name) = names.remove(&key)?;
+                Some(Regression {
+                    path,
+                    line,
+                    name,
+                    baseline,
+                    duration,
+                })
+            })
+            .collect::<Vec<_>>();
+        regressions.sort_by(|a, b| {
+            b.slowdown_percent()
+                .partial_cmp(&a.slowdown_percent())
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        regressions
+    }
+}
+
+fn average_durations(report: &Report) -> BTreeMap<String, Duration> {
+    let mut totals: BTreeMap<String, (Duration, u32)> = BTreeMap::new();
+    for feature in &report.features {
+        for scenario in &feature.scenarios {
+            let key = durations::key(Path::new(&feature.path), scenario.line);
+            let total = totals.entry(key).or_insert((Duration::from_secs(0), 0));
+            total.0 += scenario.duration();
+            total.1 += 1;
+        }
+    }
+
+    totals
+        .into_iter()
+        .map(|(key, (total, runs))| (key, total / runs))
+        .collect()
+}
+
+/// Prints the table of `regressions` against the baseline at `path`.
+pub(crate) fn print_regressions(path: &Path, regressions: &[Regression], threshold: f64) {
+    println!(
+        "{} scenarios slower than in {} by more than {}%:",
+        regressions.len(),
+        path.display(),
+        threshold
+    );
+    println!(
+        "  {:>10}  {:>10}  {:>7}  scenario",
+        "baseline", "now", "change"
+    );
+    for regression in regressions {
+        println!(
+            "  {:>9.3}s  {:>9.3}s  {:>+6.0}%  {}  {}:{}",
+            regression.baseline.as_secs_f64(),
+            regression.duration.as_secs_f64(),
+            regression.slowdown_percent(),
+            regression.name,
+            durations::relative(Path::new(&regression.path)),
+            regression.line
+        );
+    }
+    println!();
+}
@@ -0,0 +1,104 @@
+//! The groups of a step's regular expression by name, as `(?P<count>\d+)`,
+//! instead of by position, for steps capturing more than a couple of values.
+//!
+//! A struct can also be deserialized from them with serde, its fields named
+//! by the groups, each value being parsed as the cells of data tables are.
+
+use std::ops::Index;
+
+use regex::Regex;
+use serde::de::DeserializeOwned;
+
+use crate::table;
+use crate::FromCapture;
+
+/// The values of the named groups of a step's regular expression, in the
+/// order of the groups. A group which did not take part in the match has an
+/// empty value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Captures {
+    names: Vec<String>,
+    values: Vec<String>,
+}
+
+impl Captures {
+    /// The named groups of `regex` with their values in `matches`, the
+    /// whole match first, as given to step handlers.
+    ///
+    /// # Panics
+    ///
+    /// If `regex` is not a valid regular expression.
+    pub fn new(regex: &str, matches: &[String]) -> Captures {
+        let regex = Regex::new(regex)
+            .unwrap_or_else(|_| panic!("`{}` is not a valid regular expression", regex));
+        let (names, values) = regex
+            .capture_names()
+            .zip(matches)
+            .filter_map(|(name, value)| Some((name?.to_string(), value.clone())))
+            .unzip();
+        Captures { names, values }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.names
+            .iter()
+            .position(|n| n == name)
+            .map(|i| self.values[i].as_str())
+    }
+
+    /// Converts the value of the group `name`, as typed step handlers do.
+    pub fn parse<T: FromCapture>(&self, name: &str) -> Result<T, String> {
+        let value = self
+            .get(name)
+            .ok_or_else(|| format!("no capture group is named `{}`", name))?;
+        T::from_capture(value).map_err(|reason| {
+            let mut message = format!(
+                "Failed to parse `{}` with value '{}' to type {}",
+                name,
+                value,
+                std::any::type_name::<T>()
+            );
+            if !reason.is_empty() {
+                message.push_str(&format!(": {}", reason));
+            }
+            message
+        })
+    }
+
+    /// Deserializes the groups into a `T`, usually a struct whose fields are
+    /// named by them.
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T, String> {
+        table::deserialize_pairs(&self.names, &self.values).map_err(|e| match e.column {
+            Some(column) => format!("`{}`: {}", column, e.message),
+            None => e.message,
+        })
+    }
+
+    /// The names of the groups and their values, in the order of the groups.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.names
+            .iter()
+            .map(String::as_str)
+            .zip(self.values.iter().map(String::as_str))
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+impl Index<&str> for Captures {
+    type Output = str;
+
+    /// # Panics
+    ///
+    /// If no group is named `name`.
+    fn index(&self, name: &str) -> &str {
+        self.get(name)
+            .unwrap_or_else(|| panic!("no capture group is named `{}`", name))
+    }
+}
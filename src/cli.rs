@@ -1,21 +1,381 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
 use clap::{App, Arg};
+use globset::Glob;
 use regex::Regex;
 
+use crate::output::FormatterSpec;
+use crate::shuffle;
+
 #[derive(Debug)]
 pub enum CliError {
     InvalidFilterRegex,
+    InvalidFormatter(String),
+    InvalidMetadata(String),
+    InvalidJobs(String),
+    InvalidSeed(String),
+    InvalidTarget(String),
+    InvalidShard(String),
+    InvalidRepeat(String),
+    InvalidGlob(String),
+    InvalidMaxFailures(String),
+    InvalidTimeBudget(String),
+    InvalidThreshold(String),
+}
+
+/// A feature file to run, optionally narrowed down to the scenarios at some
+/// lines, as in `features/login.feature:42`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeatureTarget {
+    pub path: PathBuf,
+    /// Empty to run the whole file.
+    pub lines: Vec<usize>,
+}
+
+impl FromStr for FeatureTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<FeatureTarget, String> {
+        let mut path = s;
+        let mut lines = vec![];
+
+        while let Some(i) = path.rfind(':') {
+            match path[i + 1..].parse() {
+                Ok(line) => {
+                    lines.insert(0, line);
+                    path = &path[..i];
+                }
+                Err(_) => break,
+            }
+        }
+
+        if path.is_empty() {
+            return Err(format!("no feature file given in `{}`", s));
+        }
+
+        Ok(FeatureTarget {
+            path: PathBuf::from(path),
+            lines,
+        })
+    }
+}
+
+/// The part of the suite run by one of several machines, given as `index/count`
+/// with `index` starting at 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shard {
+    pub index: usize,
+    pub count: usize,
+}
+
+impl FromStr for Shard {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Shard, String> {
+        let mut chunks = s.splitn(2, '/');
+        match (chunks.next().map(str::parse), chunks.next().map(str::parse)) {
+            (Some(Ok(index)), Some(Ok(count))) if index >= 1 && index <= count => {
+                Ok(Shard { index, count })
+            }
+            _ => Err(format!("`{}` is not a shard like 1/4", s)),
+        }
+    }
+}
+
+impl fmt::Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
 }
 
 #[derive(Default)]
 pub struct CliOptions {
-    pub feature: Option<String>,
+    /// Globs of the feature files to run, instead of the configured ones.
+    pub include: Vec<String>,
+    /// Globs of feature files not to run, matched against paths relative to
+    /// the current directory.
+    pub exclude: Vec<String>,
+    /// Feature files given as arguments, if any. An empty rerun file gives
+    /// an empty list, which runs nothing.
+    pub targets: Option<Vec<FeatureTarget>>,
     pub filter: Option<Regex>,
     pub tag: Option<String>,
     pub suppress_output: bool,
+    /// Whether undefined steps fail the run instead of skipping their scenario.
+    pub strict: bool,
+    /// Whether only `@wip` scenarios run, the run failing if any of them pass.
+    pub wip: bool,
+    /// Whether each scenario runs in a child process of its own.
+    pub isolate: bool,
+    /// Whether the CPU time and peak memory of scenarios are recorded.
+    pub resource_usage: bool,
+    /// Whether failures record a backtrace even without `RUST_BACKTRACE`.
+    pub backtraces: bool,
+    /// Whether to prompt what to do after a failed step, when running serially.
+    pub pause_on_failure: bool,
+    /// Whether to list the step definitions instead of running anything.
+    pub list_steps: bool,
+    /// Whether to list the step definitions no step matched after the run.
+    pub unused_steps: bool,
+    /// Where to write the step definitions as JSON, for editors and other
+    /// tools, instead of running anything.
+    pub export_steps: Option<PathBuf>,
+    pub formatters: Vec<FormatterSpec>,
+    pub serve: Option<String>,
+    pub metadata: BTreeMap<String, String>,
+    pub jobs: Option<usize>,
+    pub feature_jobs: Option<usize>,
+    pub random_order: bool,
+    /// Run features by path and scenarios by line; overrides a random order.
+    pub sorted: bool,
+    /// Run the scenarios which failed in the previous run before the others.
+    pub failed_first: bool,
+    /// The scenarios which failed in the previous run, when running them first.
+    pub previous_failures: Vec<FeatureTarget>,
+    /// Only run scenarios impacted by changes since this git revision.
+    pub changed_since: Option<String>,
+    /// The files changed since `changed_since`, as absolute paths.
+    pub changed_files: Option<BTreeSet<PathBuf>>,
+    /// Set when scenarios run in a random order.
+    pub seed: Option<u64>,
+    pub shard: Option<Shard>,
+    /// How many times each scenario runs.
+    pub repeat: Option<usize>,
+    /// How many scenarios may fail before the rest of the run is given up.
+    pub max_failures: Option<usize>,
+    /// How long scenarios are started for.
+    pub time_budget: Option<Duration>,
+    /// A previous JSON report to compare the durations of scenarios with.
+    pub baseline: Option<PathBuf>,
+    /// How much slower than in the baseline, in percent, scenarios may get.
+    pub regression_threshold: Option<f64>,
+    /// The tag of scenarios which run alone, `serial` if not set.
+    pub serial_tag: Option<String>,
+    /// Scenarios whose failures do not fail the run, read from `--quarantine`.
+    pub quarantine: Vec<FeatureTarget>,
+    /// The tag of scenarios whose failures do not fail the run, `quarantine`
+    /// if not set.
+    pub quarantine_tag: Option<String>,
+    /// The tags of scenarios which are skipped without running, `skip` and
+    /// `ignore` if not set.
+    pub skip_tags: Option<Vec<String>>,
+    /// How long scenarios with a tag may run.
+    pub tag_timeouts: BTreeMap<String, Duration>,
+    /// How many scenarios with a tag may run at once.
+    pub tag_limits: BTreeMap<String, usize>,
+    #[cfg(feature = "webhook")]
+    pub webhook: Option<String>,
+}
+
+impl CliOptions {
+    /// The lines to run in the feature file at `path`, or `None` to run all
+    /// of it.
+    pub(crate) fn lines_for(&self, path: &Path) -> Option<Vec<usize>> {
+        let mut lines = vec![];
+        for target in self.targets.iter().flatten().filter(|t| t.path == path) {
+            if target.lines.is_empty() {
+                return None;
+            }
+            lines.extend_from_slice(&target.lines);
+        }
+
+        if lines.is_empty() {
+            None
+        } else {
+            Some(lines)
+        }
+    }
+
+    /// Whether `path` changed since `--changed-since`, or `true` without it.
+    /// Relative paths, such as those given by `file!()`, match the end of
+    /// changed ones.
+    pub(crate) fn changed(&self, path: &Path) -> bool {
+        let changed = match self.changed_files.as_ref() {
+            Some(changed) => changed,
+            None => return true,
+        };
+        if path.is_absolute() {
+            changed.contains(path)
+        } else {
+            changed.iter().any(|c| c.ends_with(path))
+        }
+    }
+
+    /// Whether the scenario at `line` of the feature file at `path` failed in
+    /// the previous run, or any scenario of that file if `line` is `None`.
+    pub(crate) fn failed_before(&self, path: &Path, line: Option<usize>) -> bool {
+        self.previous_failures
+            .iter()
+            .filter(|t| t.path == path)
+            .any(|t| match line {
+                Some(line) => t.lines.contains(&line),
+                None => true,
+            })
+    }
+
+    /// Whether the scenario at `line` of the feature file at `path` belongs to
+    /// the shard to run. Scenarios are assigned by a hash of their location
+    /// relative to the working directory, so that every machine agrees.
+    pub(crate) fn in_shard(&self, path: &Path, line: usize) -> bool {
+        let shard = match self.shard {
+            Some(shard) => shard,
+            None => return true,
+        };
+
+        let cwd = env::current_dir().unwrap_or_default();
+        let path = path.strip_prefix(&cwd).unwrap_or(path);
+        let key = format!("{}:{}", path.display(), line);
+        shuffle::hash(&key) % shard.count as u64 == (shard.index - 1) as u64
+    }
+
+    /// Whether `scenario`, its rule or its feature is tagged to run alone.
+    pub(crate) fn is_serial(
+        &self,
+        feature: &gherkin::Feature,
+        rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+    ) -> bool {
+        let tag = self.serial_tag.as_deref().unwrap_or("serial");
+        is_tagged(feature, rule, scenario, tag)
+    }
+
+    /// Why `scenario` is skipped, if it, its rule or its feature has a skip
+    /// tag: the argument of the tag, as in `@skip(flaky-on-ci)`, or the tag.
+    pub(crate) fn skip_reason(
+        &self,
+        feature: &gherkin::Feature,
+        rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+    ) -> Option<String> {
+        let defaults = ["skip".to_string(), "ignore".to_string()];
+        let skip_tags = match self.skip_tags.as_ref() {
+            Some(tags) => tags.as_slice(),
+            None => &defaults,
+        };
+
+        let tags = scenario
+            .tags
+            .iter()
+            .chain(rule.and_then(|r| r.tags.as_ref()))
+            .chain(feature.tags.as_ref())
+            .flatten();
+        for tag in tags.map(|tag| tag_name(tag)) {
+            for skip_tag in skip_tags {
+                if tag == skip_tag {
+                    return Some(format!("tagged @{}", tag));
+                }
+                if let Some(reason) = tag_argument(tag, skip_tag) {
+                    return Some(reason.to_string());
+                }
+            }
+        }
+        None
+    }
+
+    /// The shortest timeout of the tags of `scenario`, its rule and its feature.
+    pub(crate) fn timeout(
+        &self,
+        feature: &gherkin::Feature,
+        rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+    ) -> Option<Duration> {
+        self.tag_timeouts
+            .iter()
+            .filter(|(tag, _)| is_tagged(feature, rule, scenario, tag))
+            .map(|(_, &timeout)| timeout)
+            .min()
+    }
+
+    /// Whether `scenario`, its rule or its feature is tagged `@xfail` or
+    /// `@allow-failure`, i.e. expected to fail.
+    pub(crate) fn is_xfail(
+        &self,
+        feature: &gherkin::Feature,
+        rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+    ) -> bool {
+        is_tagged(feature, rule, scenario, "xfail")
+            || is_tagged(feature, rule, scenario, "allow-failure")
+    }
+
+    /// Whether the failures of `scenario` are quarantined, either by a tag on
+    /// it, its rule or its feature, or by the quarantine file.
+    pub(crate) fn is_quarantined(
+        &self,
+        feature: &gherkin::Feature,
+        rule: Option<&gherkin::Rule>,
+        path: &Path,
+        scenario: &gherkin::Scenario,
+    ) -> bool {
+        let tag = self.quarantine_tag.as_deref().unwrap_or("quarantine");
+        let line = scenario.position.0;
+        is_tagged(feature, rule, scenario, tag)
+            || self
+                .quarantine
+                .iter()
+                .filter(|t| t.path == path)
+                .any(|t| t.lines.is_empty() || t.lines.contains(&line))
+    }
+
+    /// Whether to prompt after failed steps: the world only lives in this
+    /// process, and only one scenario may use the terminal at a time.
+    pub(crate) fn pauses_on_failure(&self) -> bool {
+        self.pause_on_failure
+            && !self.isolate
+            && self.jobs.unwrap_or(1) <= 1
+            && self.feature_jobs.unwrap_or(1) <= 1
+    }
+
+    /// Whether step output is captured: output capture redirects the whole
+    /// process' stdout and stderr, so it is left off when running in parallel.
+    pub(crate) fn captures_output(&self) -> bool {
+        self.suppress_output
+            && self.jobs.unwrap_or(1) <= 1
+            && self.feature_jobs.unwrap_or(1) <= 1
+    }
+}
+
+/// `tag` without the `@` the parser keeps in front of it.
+pub(crate) fn tag_name(tag: &str) -> &str {
+    tag.trim_start_matches('@')
+}
+
+/// Whether `scenario`, its rule or its feature is tagged `tag`.
+pub(crate) fn is_tagged(
+    feature: &gherkin::Feature,
+    rule: Option<&gherkin::Rule>,
+    scenario: &gherkin::Scenario,
+    tag: &str,
+) -> bool {
+    let has_tag = |tags: &Option<Vec<String>>| {
+        tags.as_ref()
+            .map(|tags| tags.iter().any(|t| tag_name(t) == tag))
+            .unwrap_or(false)
+    };
+
+    has_tag(&scenario.tags)
+        || rule.map(|r| has_tag(&r.tags)).unwrap_or(false)
+        || has_tag(&feature.tags)
+}
+
+/// The argument of a tag such as `order(10)`, if `tag` is `name(...)`.
+pub(crate) fn tag_argument<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let tag = tag_name(tag);
+    if tag.starts_with(name) && tag[name.len()..].starts_with('(') && tag.ends_with(')') {
+        Some(&tag[name.len() + 1..tag.len() - 1])
+    } else {
+        None
+    }
 }
 
 pub fn make_app() -> Result<CliOptions, CliError> {
-    let matches = App::new("cucumber")
+    let app = App::new("cucumber")
         .version(env!("CARGO_PKG_VERSION"))
         .author("Brendan Molloy <brendan@bbqsrc.net>")
         .about("Run the tests, pet a dog!")
@@ -23,8 +383,9 @@ pub fn make_app() -> Result<CliOptions, CliError> {
             Arg::with_name("filter")
                 .short("e")
                 .long("expression")
+                .visible_alias("name")
                 .value_name("regex")
-                .help("Regex to select scenarios from")
+                .help("Only run scenarios whose name matches this regex; others are reported as filtered out")
                 .takes_value(true),
         )
         .arg(
@@ -32,8 +393,25 @@ pub fn make_app() -> Result<CliOptions, CliError> {
                 .short("f")
                 .long("feature")
                 .value_name("feature")
-                .help("Specific feature file(s) to use with a glob (optional)")
-                .takes_value(true),
+                .help("Specific feature file(s) to use with a glob, e.g. features/**/smoke_*.feature (repeatable)")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("exclude")
+                .long("exclude")
+                .value_name("glob")
+                .help("Feature file(s) not to run, e.g. features/wip/** (repeatable)")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("target")
+                .value_name("path[:line]")
+                .help("Feature file(s) to run, optionally only the scenario at a line, e.g. features/login.feature:42, @rerun.txt to run the targets listed in a file, or - to read a feature from stdin")
+                .multiple(true),
         )
         .arg(
             Arg::with_name("tag")
@@ -48,7 +426,183 @@ pub fn make_app() -> Result<CliOptions, CliError> {
                 .long("nocapture")
                 .help("Use this flag to disable suppression of output from tests"),
         )
-        .get_matches();
+        .arg(
+            Arg::with_name("strict")
+                .long("strict")
+                .help("Fail the run when a step is not implemented"),
+        )
+        .arg(
+            Arg::with_name("wip")
+                .long("wip")
+                .help("Only run @wip scenarios, and fail the run if any of them passes"),
+        )
+        .arg(
+            Arg::with_name("isolate")
+                .long("isolate")
+                .help("Run each scenario in a child process, so a crash only fails that scenario"),
+        )
+        .arg(
+            Arg::with_name("resource-usage")
+                .long("resource-usage")
+                .help("Record the CPU time of each scenario in reports, and its peak memory with --isolate"),
+        )
+        .arg(
+            Arg::with_name("backtrace")
+                .long("backtrace")
+                .help("Record a backtrace with failed steps, as RUST_BACKTRACE=1 does"),
+        )
+        .arg(
+            Arg::with_name("pause-on-failure")
+                .long("pause-on-failure")
+                .help("Pause after a failed step to re-run it, continue or abort, when running serially"),
+        )
+        .arg(
+            Arg::with_name("list-steps")
+                .long("list-steps")
+                .help("List the step definitions, with their source file, instead of running the features"),
+        )
+        .arg(
+            Arg::with_name("unused-steps")
+                .long("unused-steps")
+                .help("List the step definitions which matched no step, with their source file, after the run"),
+        )
+        .arg(
+            Arg::with_name("export-steps")
+                .long("export-steps")
+                .value_name("steps.json")
+                .help("Write the step definitions as JSON, for editors, instead of running the features")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .value_name("formatter:destination")
+                .help("Also write a report, e.g. junit:target/junit.xml or message:- (repeatable)")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("serve")
+                .long("serve")
+                .value_name("address")
+                .help("Serve a live HTML report while running, e.g. 127.0.0.1:7878")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("metadata")
+                .long("metadata")
+                .value_name("key=value")
+                .help("Record a detail about the environment in reports, e.g. git=abc123 (repeatable)")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("jobs")
+                .short("j")
+                .long("jobs")
+                .value_name("n")
+                .help("Run up to n scenarios at once; step output is not captured when n > 1")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("feature-jobs")
+                .long("feature-jobs")
+                .value_name("n")
+                .help("Run up to n feature files at once; step output is not captured when n > 1")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("random-order")
+                .long("random-order")
+                .help("Run features and scenarios in a random order"),
+        )
+        .arg(
+            Arg::with_name("sorted")
+                .long("sorted")
+                .help("Run features by path and scenarios by line, even if a seed is set"),
+        )
+        .arg(
+            Arg::with_name("failed-first")
+                .long("failed-first")
+                .help("Run the scenarios which failed in the previous run before the others"),
+        )
+        .arg(
+            Arg::with_name("changed-since")
+                .long("changed-since")
+                .value_name("rev")
+                .help("Only run the scenarios whose feature file or step definitions changed since a git revision")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .value_name("seed")
+                .env("CUCUMBER_SEED")
+                .help("Run in the random order given by this seed, as shown in the run header")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("repeat")
+                .long("repeat")
+                .value_name("n")
+                .help("Run each scenario n times, each with a fresh world, and show pass rates")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("max-failures")
+                .long("max-failures")
+                .value_name("n")
+                .help("Stop running scenarios once n of them failed")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("time-budget")
+                .long("time-budget")
+                .value_name("duration")
+                .help("Stop starting scenarios after this long, e.g. 90s, 20m or 1h, and exit with status 124")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("quarantine")
+                .long("quarantine")
+                .value_name("file")
+                .help("Run the scenarios listed in this file, as path[:line], without failing the run when they fail")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("baseline")
+                .long("baseline")
+                .value_name("report.json")
+                .help("Report the scenarios which got slower than in this JSON report of a previous run")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("regression-threshold")
+                .long("regression-threshold")
+                .value_name("percent")
+                .help("How much slower than in the baseline scenarios may get before being reported, 20% by default")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("shard")
+                .long("shard")
+                .value_name("i/n")
+                .help("Only run the i-th of n parts of the suite, e.g. 2/4 to split it across CI machines")
+                .takes_value(true),
+        );
+
+    #[cfg(feature = "webhook")]
+    let app = app.arg(
+        Arg::with_name("webhook")
+            .long("webhook")
+            .value_name("url")
+            .help("POST a JSON summary of the run to this URL when finished")
+            .takes_value(true),
+    );
+
+    let matches = app.get_matches();
 
     let filter = if let Some(filter) = matches.value_of("filter") {
         let regex = Regex::new(filter).map_err(|_| CliError::InvalidFilterRegex)?;
@@ -57,15 +611,204 @@ pub fn make_app() -> Result<CliOptions, CliError> {
         None
     };
 
-    let feature = matches.value_of("feature").map(|v| v.to_string());
+    let include = globs(matches.values_of("feature"))?;
+    let exclude = globs(matches.values_of("exclude"))?;
+    let targets = match matches.values_of("target") {
+        Some(values) => {
+            let mut targets = vec![];
+            for v in values {
+                if v.starts_with('@') {
+                    targets.extend(read_targets(v.trim_start_matches('@'))?);
+                } else {
+                    targets.push(v.parse().map_err(CliError::InvalidTarget)?);
+                }
+            }
+            Some(targets)
+        }
+        None => None,
+    };
     let tag = matches.value_of("tag").map(|v| v.to_string());
 
     let suppress_output = !matches.is_present("nocapture");
+    let strict = matches.is_present("strict");
+    let wip = matches.is_present("wip");
+    let backtraces = matches.is_present("backtrace");
+    let pause_on_failure = matches.is_present("pause-on-failure");
+    let list_steps = matches.is_present("list-steps");
+    let export_steps = matches.value_of("export-steps").map(PathBuf::from);
+    let unused_steps = matches.is_present("unused-steps");
+    let isolate = matches.is_present("isolate");
+    let resource_usage = matches.is_present("resource-usage");
+
+    let formatters = matches
+        .values_of("format")
+        .map(|values| {
+            values
+                .map(|v| v.parse().map_err(CliError::InvalidFormatter))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .unwrap_or(Ok(vec![]))?;
+
+    let serve = matches.value_of("serve").map(|v| v.to_string());
+
+    let metadata = matches
+        .values_of("metadata")
+        .map(|values| {
+            values
+                .map(|v| {
+                    let mut chunks = v.splitn(2, '=');
+                    match (chunks.next(), chunks.next()) {
+                        (Some(key), Some(value)) if !key.is_empty() => {
+                            Ok((key.to_string(), value.to_string()))
+                        }
+                        _ => Err(CliError::InvalidMetadata(v.to_string())),
+                    }
+                })
+                .collect::<Result<BTreeMap<_, _>, _>>()
+        })
+        .unwrap_or(Ok(BTreeMap::new()))?;
+
+    let jobs = parse_count(matches.value_of("jobs"), CliError::InvalidJobs)?;
+    let feature_jobs = parse_count(matches.value_of("feature-jobs"), CliError::InvalidJobs)?;
+    let repeat = parse_count(matches.value_of("repeat"), CliError::InvalidRepeat)?;
+    let max_failures = parse_count(
+        matches.value_of("max-failures"),
+        CliError::InvalidMaxFailures,
+    )?;
+    let time_budget = match matches.value_of("time-budget") {
+        Some(v) => {
+            Some(parse_duration(v).ok_or_else(|| CliError::InvalidTimeBudget(v.to_string()))?)
+        }
+        None => None,
+    };
+
+    let baseline = matches.value_of("baseline").map(PathBuf::from);
+    let regression_threshold = match matches.value_of("regression-threshold") {
+        Some(v) => match v.trim_end_matches('%').parse::<f64>() {
+            Ok(percent) if percent >= 0.0 => Some(percent),
+            _ => return Err(CliError::InvalidThreshold(v.to_string())),
+        },
+        None => None,
+    };
+
+    let random_order = matches.is_present("random-order");
+    let sorted = matches.is_present("sorted");
+    let failed_first = matches.is_present("failed-first");
+    let changed_since = matches.value_of("changed-since").map(|v| v.to_string());
+    let quarantine = match matches.value_of("quarantine") {
+        Some(v) => read_targets(v)?,
+        None => vec![],
+    };
+    let seed = match matches.value_of("seed") {
+        Some(v) => Some(v.parse().map_err(|_| CliError::InvalidSeed(v.to_string()))?),
+        None => None,
+    };
+
+    let shard = match matches.value_of("shard") {
+        Some(v) => Some(v.parse().map_err(CliError::InvalidShard)?),
+        None => None,
+    };
+
+    #[cfg(feature = "webhook")]
+    let webhook = matches.value_of("webhook").map(|v| v.to_string());
 
     Ok(CliOptions {
-        feature,
+        include,
+        exclude,
+        targets,
         filter,
         tag,
         suppress_output,
+        strict,
+        wip,
+        backtraces,
+        pause_on_failure,
+        list_steps,
+        export_steps,
+        unused_steps,
+        isolate,
+        resource_usage,
+        formatters,
+        serve,
+        metadata,
+        jobs,
+        feature_jobs,
+        random_order,
+        sorted,
+        failed_first,
+        previous_failures: vec![],
+        changed_since,
+        changed_files: None,
+        seed,
+        shard,
+        repeat,
+        max_failures,
+        time_budget,
+        baseline,
+        regression_threshold,
+        serial_tag: None,
+        quarantine,
+        quarantine_tag: None,
+        skip_tags: None,
+        tag_timeouts: BTreeMap::new(),
+        tag_limits: BTreeMap::new(),
+        #[cfg(feature = "webhook")]
+        webhook,
     })
 }
+
+fn globs(values: Option<clap::Values<'_>>) -> Result<Vec<String>, CliError> {
+    values
+        .into_iter()
+        .flatten()
+        .map(|v| match Glob::new(v) {
+            Ok(_) => Ok(v.to_string()),
+            Err(e) => Err(CliError::InvalidGlob(format!("{}: {}", v, e))),
+        })
+        .collect()
+}
+
+/// Parses a duration in seconds, minutes or hours, as in `90s`, `20m` or `1h`;
+/// a bare number is in seconds.
+fn parse_duration(value: &str) -> Option<Duration> {
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => value.split_at(i),
+        None => (value, "s"),
+    };
+    let number: u64 = number.parse().ok()?;
+    let seconds = match unit {
+        "s" => number,
+        "m" => number * 60,
+        "h" => number * 3600,
+        _ => return None,
+    };
+    if seconds == 0 {
+        return None;
+    }
+    Some(Duration::from_secs(seconds))
+}
+
+fn parse_count(
+    value: Option<&str>,
+    error: fn(String) -> CliError,
+) -> Result<Option<usize>, CliError> {
+    match value {
+        Some(v) => match v.parse() {
+            Ok(n) if n > 0 => Ok(Some(n)),
+            _ => Err(error(v.to_string())),
+        },
+        None => Ok(None),
+    }
+}
+
+/// Reads whitespace-separated targets from a file, such as one written by the
+/// rerun formatter.
+pub(crate) fn read_targets(path: &str) -> Result<Vec<FeatureTarget>, CliError> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| CliError::InvalidTarget(format!("could not read {}: {}", path, e)))?;
+
+    contents
+        .split_whitespace()
+        .map(|v| v.parse().map_err(CliError::InvalidTarget))
+        .collect()
+}
@@ -0,0 +1,216 @@
+//! A clock scenarios control, so that code depending on the time gives the
+//! same results on every run, and steps to set it from feature files.
+//!
+//! ```ignore
+//! impl Default for MyWorld {
+//!     fn default() -> MyWorld {
+//!         MyWorld { shop: Shop::new(cucumber::Clock::current()) }
+//!     }
+//! }
+//! ```
+//!
+//! ```gherkin
+//! Given the current time is 2020-01-31T23:59:00Z
+//! When 2 minutes pass
+//! ```
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{Context, Steps, StepsBuilder, World};
+
+/// The time as seen by a scenario: the system time until it is set, then
+/// frozen at that time, moving only when advanced or slept on.
+///
+/// Clones share the time they tell, so that the code under test can be given
+/// one while steps set the time of another.
+#[derive(Debug, Clone, Default)]
+pub struct Clock {
+    frozen: Arc<Mutex<Option<SystemTime>>>,
+}
+
+impl Clock {
+    /// The clock of the scenario running on this thread, from its hooks, its
+    /// steps or `Default::default` of its world. Each scenario starts with
+    /// one following the system time.
+    ///
+    /// # Panics
+    ///
+    /// When no scenario is running on this thread.
+    pub fn current() -> Clock {
+        Context::with(|context| context.get_or_default::<Clock>().clone())
+    }
+
+    pub fn now(&self) -> SystemTime {
+        self.frozen.lock().unwrap().unwrap_or_else(SystemTime::now)
+    }
+
+    /// Waits for `duration`, or advances a frozen clock by it right away.
+    pub fn sleep(&self, duration: Duration) {
+        {
+            let mut frozen = self.frozen.lock().unwrap();
+            if let Some(now) = frozen.as_mut() {
+                *now += duration;
+                return;
+            }
+        }
+        thread::sleep(duration);
+    }
+
+    /// Freezes the clock at `time`.
+    pub fn set(&self, time: SystemTime) {
+        *self.frozen.lock().unwrap() = Some(time);
+    }
+
+    /// Moves a frozen clock forward by `duration`, or freezes one following
+    /// the system time that far ahead of it.
+    pub fn advance(&self, duration: Duration) {
+        let mut frozen = self.frozen.lock().unwrap();
+        let now = frozen.unwrap_or_else(SystemTime::now);
+        *frozen = Some(now + duration);
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen.lock().unwrap().is_some()
+    }
+
+    /// Makes the clock follow the system time again.
+    pub fn resume(&self) {
+        *self.frozen.lock().unwrap() = None;
+    }
+}
+
+/// Steps controlling the clock of the scenario, for any world:
+///
+/// - `Given the current time is 2020-01-31T12:00:00Z`, or any time
+///   `parse_time` reads, freezes it;
+/// - `Given 5 minutes passed` or `When 2 days pass`, in seconds, minutes,
+///   hours or days, advances it.
+pub fn steps<W: World>() -> Steps<W> {
+    let mut steps = StepsBuilder::new();
+    steps.file(file!());
+    steps.given_regex(
+        r"^the (?:current )?time is (.+)$",
+        |_world, matches, _step| {
+            let time = parse_time(&matches[1]).unwrap_or_else(|e| panic!("{}", e));
+            Clock::current().set(time);
+        },
+    );
+    steps.given_regex(PASSED, |_world, matches, _step| {
+        Clock::current().advance(parse_duration(&matches[1], &matches[2]));
+    });
+    steps.when_regex(PASSED, |_world, matches, _step| {
+        Clock::current().advance(parse_duration(&matches[1], &matches[2]));
+    });
+    steps.build()
+}
+
+const PASSED: &str = r"^(\d+) (seconds?|minutes?|hours?|days?) (?:pass|passes|passed)$";
+
+fn parse_duration(count: &str, unit: &str) -> Duration {
+    let count: u64 = count.parse().expect("the pattern only matches digits");
+    let seconds = match unit.trim_end_matches('s') {
+        "second" => 1,
+        "minute" => 60,
+        "hour" => 60 * 60,
+        _ => 24 * 60 * 60,
+    };
+    Duration::from_secs(count * seconds)
+}
+
+/// Reads a time such as `2020-01-31T12:00:00Z`: a date, then optionally a
+/// time of the day after a `T` or a space, with or without seconds and their
+/// fraction, then an offset such as `+02:00`, UTC when omitted or `Z`.
+pub fn parse_time(text: &str) -> Result<SystemTime, String> {
+    let invalid = || format!("`{}` is not a time such as 2020-01-31T12:00:00Z", text);
+    let text = text.trim();
+    if text.len() < 10 || !text.is_char_boundary(10) {
+        return Err(invalid());
+    }
+    let (date, rest) = text.split_at(10);
+
+    let date = numbers(date, '-').ok_or_else(invalid)?;
+    let (year, month, day) = match *date.as_slice() {
+        [year, month, day] if (1..=12).contains(&month) && (1..=31).contains(&day) => {
+            (year, month, day)
+        }
+        _ => return Err(invalid()),
+    };
+
+    let (mut seconds, mut nanos, mut offset) = (0, 0, 0);
+    let mut chars = rest.chars();
+    match chars.next() {
+        None => {}
+        Some('T') | Some(' ') => {
+            let time = chars.as_str();
+            let (time, zone) = match time.find(&['Z', '+', '-'][..]) {
+                Some(i) => time.split_at(i),
+                None => (time, ""),
+            };
+            let (time, fraction) = match time.find('.') {
+                Some(i) => (&time[..i], &time[i + 1..]),
+                None => (time, ""),
+            };
+            seconds = match *numbers(time, ':').ok_or_else(invalid)?.as_slice() {
+                [h, m] if h < 24 && m < 60 => h * 3600 + m * 60,
+                [h, m, s] if h < 24 && m < 60 && s <= 60 => h * 3600 + m * 60 + s,
+                _ => return Err(invalid()),
+            };
+            if !fraction.is_empty() {
+                if fraction.len() > 9 || !fraction.chars().all(|c| c.is_ascii_digit()) {
+                    return Err(invalid());
+                }
+                nanos = format!("{:0<9}", fraction).parse().map_err(|_| invalid())?;
+            }
+            offset = match zone {
+                "" | "Z" => 0,
+                _ => {
+                    let (sign, zone) = zone.split_at(1);
+                    let sign = match sign {
+                        "+" => 1,
+                        "-" => -1,
+                        _ => return Err(invalid()),
+                    };
+                    match *numbers(zone, ':').ok_or_else(invalid)?.as_slice() {
+                        [h, m] if h < 24 && m < 60 => sign * (h * 3600 + m * 60),
+                        _ => return Err(invalid()),
+                    }
+                }
+            };
+        }
+        Some(_) => return Err(invalid()),
+    }
+
+    let since_epoch = days_from_civil(year, month, day) * 86400 + seconds - offset;
+    let time = if since_epoch >= 0 {
+        UNIX_EPOCH + Duration::from_secs(since_epoch as u64)
+    } else {
+        UNIX_EPOCH - Duration::from_secs((-since_epoch) as u64)
+    };
+    Ok(time + Duration::from_nanos(nanos))
+}
+
+/// The numbers of `text` separated by `separator`, each of digits only.
+fn numbers(text: &str, separator: char) -> Option<Vec<i64>> {
+    text.split(separator)
+        .map(|n| {
+            if n.is_empty() || !n.chars().all(|c| c.is_ascii_digit()) {
+                None
+            } else {
+                n.parse().ok()
+            }
+        })
+        .collect()
+}
+
+/// The number of days from 1970-01-01 to a date of the proleptic Gregorian
+/// calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
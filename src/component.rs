@@ -0,0 +1,23 @@
+//! Worlds assembled from components, such as an HTTP client or a database
+//! connection, so that step libraries can require only the part they use.
+
+use crate::StepFuture;
+
+/// A part of a world, set up before the first step of a scenario and torn
+/// down after the last one, as a world is. `#[derive(World)]` assembles a
+/// world from components.
+pub trait Component: Default {
+    fn setup(&mut self) -> StepFuture<'_> {
+        Box::pin(async {})
+    }
+
+    fn teardown(&mut self) -> StepFuture<'_> {
+        Box::pin(async {})
+    }
+}
+
+/// A world having the component `C`, which a step library can require with
+/// `steps!(W: Has<HttpClient> => { ... })`.
+pub trait Has<C: Component> {
+    fn component(&mut self) -> &mut C;
+}
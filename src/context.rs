@@ -0,0 +1,308 @@
+//! What is running, for step code to tell: the feature, scenario and step,
+//! with the tags and example values which apply to them, and the values
+//! hooks and steps keep for the rest of the scenario.
+//!
+//! The runner sets the context of each step on the thread running it, which
+//! is also the one asynchronous steps are polled on.
+
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use gherkin::{Feature, Rule, Scenario, Step};
+
+use crate::cli;
+use crate::report::{Failure, Status};
+use crate::TestResult;
+
+thread_local! {
+    static CURRENT: RefCell<Option<StepContext>> = const { RefCell::new(None) };
+    static VALUES: RefCell<Option<Context>> = const { RefCell::new(None) };
+    static TAGS: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+    static OUTCOME: RefCell<Option<ScenarioOutcome>> = const { RefCell::new(None) };
+    static FEATURE: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+}
+
+/// The context of the running step, from `StepContext::current()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepContext {
+    pub feature: String,
+    pub path: PathBuf,
+    pub rule: Option<String>,
+    pub scenario: String,
+    /// The tags of the scenario, then those of its rule and its feature,
+    /// without their `@`.
+    pub tags: Vec<String>,
+    /// For outline examples, the header and values of the example row.
+    pub examples: Vec<(String, String)>,
+    /// The position of the step in the scenario from 0, background steps
+    /// included.
+    pub step_index: usize,
+    pub step_count: usize,
+    pub step_line: usize,
+    /// When the scenario times out, if a timeout applies to it.
+    pub deadline: Option<Instant>,
+}
+
+impl StepContext {
+    /// The context of the step running on this thread.
+    ///
+    /// # Panics
+    ///
+    /// When no step is running on this thread.
+    pub fn current() -> StepContext {
+        CURRENT
+            .with(|current| current.borrow().clone())
+            .expect("StepContext::current called outside of a step")
+    }
+
+    /// The context of the step running on this thread, if any.
+    pub(crate) fn try_current() -> Option<StepContext> {
+        CURRENT.with(|current| current.borrow().clone())
+    }
+
+    /// Whether the scenario has `tag`, directly or from its rule or feature.
+    /// The `@` of `tag` is optional.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == cli::tag_name(tag))
+    }
+
+    /// The value of `header` in the example row, for outline examples.
+    pub fn example(&self, header: &str) -> Option<&str> {
+        self.examples
+            .iter()
+            .find(|(h, _)| h == header)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The context of the steps of `scenario`, run with the values of
+    /// `example`, before any of them runs.
+    pub(crate) fn scenario(
+        feature: &Feature,
+        rule: Option<&Rule>,
+        path: &Path,
+        scenario: &Scenario,
+        example: &[(String, String)],
+    ) -> StepContext {
+        let tags = scenario
+            .tags
+            .iter()
+            .chain(rule.and_then(|r| r.tags.as_ref()))
+            .chain(feature.tags.as_ref())
+            .flatten()
+            .map(|tag| cli::tag_name(tag).to_string())
+            .collect();
+        let background = feature.background.as_ref().map_or(0, |bg| bg.steps.len());
+
+        StepContext {
+            feature: feature.name.clone(),
+            path: path.to_path_buf(),
+            rule: rule.map(|r| r.name.clone()),
+            scenario: scenario.name.clone(),
+            tags,
+            examples: example.to_vec(),
+            step_index: 0,
+            step_count: background + scenario.steps.len(),
+            step_line: 0,
+            deadline: None,
+        }
+    }
+
+    /// Makes this the context of `step`, the `index`th of the scenario, until
+    /// `leave` is called.
+    pub(crate) fn enter(&self, index: usize, step: &Step) {
+        let context = StepContext {
+            step_index: index,
+            step_line: step.position.0,
+            ..self.clone()
+        };
+        CURRENT.with(|current| *current.borrow_mut() = Some(context));
+    }
+}
+
+/// How the scenario running on this thread went, for its after hooks to
+/// tell, e.g. to take a screenshot only when it failed.
+#[derive(Debug, Clone)]
+pub struct ScenarioOutcome {
+    /// The status of its steps, failed if a hook or the teardown failed.
+    pub status: Status,
+    /// The first step which failed, if any.
+    pub failed_step: Option<Step>,
+    /// Why the scenario failed, if it did.
+    pub failure: Option<Failure>,
+}
+
+impl ScenarioOutcome {
+    /// How the scenario running on this thread went, once its steps are over:
+    /// from its after hooks, and around hooks once they proceeded.
+    pub fn current() -> Option<ScenarioOutcome> {
+        OUTCOME.with(|outcome| outcome.borrow().clone())
+    }
+
+    pub fn is_failed(&self) -> bool {
+        self.status.is_failure()
+    }
+
+    pub(crate) fn new() -> ScenarioOutcome {
+        ScenarioOutcome {
+            status: Status::Passed,
+            failed_step: None,
+            failure: None,
+        }
+    }
+
+    /// Takes the result of `step`, of the feature file at `path`, into account.
+    pub(crate) fn record(&mut self, path: &Path, step: &Step, result: &TestResult) {
+        let status = Status::from(result);
+        if status.is_failure() && self.failure.is_none() {
+            let location = format!("{}:{}", path.display(), step.position.0);
+            let failure = match result {
+                TestResult::Fail(panic_info, _, _) => panic_info.clone().into(),
+                TestResult::Crashed(exit_status, _, _) => Failure {
+                    message: format!("Scenario process crashed ({})", exit_status),
+                    location,
+                    backtrace: None,
+                },
+                TestResult::Ambiguous(matches) => Failure {
+                    message: matches.join("\n"),
+                    location,
+                    backtrace: None,
+                },
+                _ => Failure {
+                    message: status.as_str().to_string(),
+                    location,
+                    backtrace: None,
+                },
+            };
+            self.failed_step = Some(step.clone());
+            self.failure = Some(failure);
+        }
+        self.status = self.status.merge(status);
+    }
+
+    /// Fails the scenario outside of its steps, e.g. in a hook.
+    pub(crate) fn fail(&mut self, failure: Failure) {
+        if self.failure.is_none() {
+            self.failure = Some(failure);
+        }
+        self.status = self.status.merge(Status::Failed);
+    }
+}
+
+/// Values of any type, at most one of each, kept from the start of a
+/// scenario to its end so that hooks and steps can share them without a
+/// field in every world, e.g. a request ID or a temporary directory.
+///
+/// ```ignore
+/// cucumber::Context::with(|context| context.insert(RequestId::new()));
+/// let id = cucumber::Context::with(|context| context.get::<RequestId>().cloned());
+/// ```
+#[derive(Default)]
+pub struct Context {
+    values: HashMap<TypeId, Box<dyn Any>>,
+}
+
+impl Context {
+    /// Runs `f` with the context of the scenario running on this thread.
+    ///
+    /// # Panics
+    ///
+    /// When no scenario is running on this thread, or when called within `f`.
+    pub fn with<R, F: FnOnce(&mut Context) -> R>(f: F) -> R {
+        VALUES.with(|values| {
+            let mut values = values.borrow_mut();
+            let context = values
+                .as_mut()
+                .expect("Context::with called outside of a scenario");
+            f(context)
+        })
+    }
+
+    /// Keeps `value`, returning the value of the same type it replaces.
+    pub fn insert<T: 'static>(&mut self, value: T) -> Option<T> {
+        self.values
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|previous| previous.downcast().ok())
+            .map(|previous| *previous)
+    }
+
+    pub fn get<T: 'static>(&self) -> Option<&T> {
+        self.values
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref())
+    }
+
+    pub fn get_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.values
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_mut())
+    }
+
+    /// The value of type `T`, inserting `T::default()` first if there is none.
+    pub fn get_or_default<T: Default + 'static>(&mut self) -> &mut T {
+        self.values
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(T::default()))
+            .downcast_mut()
+            .expect("value stored under the wrong type")
+    }
+
+    pub fn remove<T: 'static>(&mut self) -> Option<T> {
+        self.values
+            .remove(&TypeId::of::<T>())
+            .and_then(|value| value.downcast().ok())
+            .map(|value| *value)
+    }
+
+    pub fn contains<T: 'static>(&self) -> bool {
+        self.values.contains_key(&TypeId::of::<T>())
+    }
+}
+
+/// Starts with an empty `Context` for a scenario with `tags`, its own and
+/// those of its rule and feature, before its hooks run.
+pub(crate) fn begin(tags: Vec<String>) {
+    VALUES.with(|values| *values.borrow_mut() = Some(Context::default()));
+    TAGS.with(|current| *current.borrow_mut() = Some(tags));
+}
+
+/// The tags of the scenario running on this thread, inherited ones included,
+/// from before its first hook to after its last.
+pub(crate) fn scenario_tags() -> Option<Vec<String>> {
+    TAGS.with(|tags| tags.borrow().clone())
+}
+
+/// Makes `outcome` that of the scenario running on this thread, until it is
+/// over.
+pub(crate) fn set_outcome(outcome: ScenarioOutcome) {
+    OUTCOME.with(|current| *current.borrow_mut() = Some(outcome));
+}
+
+/// Makes the feature file at `path` the one running on this thread, for the
+/// state kept per feature, until `leave_feature` is called.
+pub(crate) fn enter_feature(path: &Path) {
+    FEATURE.with(|feature| *feature.borrow_mut() = Some(path.to_path_buf()));
+}
+
+/// The path of the feature running on this thread, from its first hook or
+/// scenario on.
+pub(crate) fn feature_path() -> Option<PathBuf> {
+    FEATURE.with(|feature| feature.borrow().clone())
+}
+
+pub(crate) fn leave_feature() {
+    FEATURE.with(|feature| *feature.borrow_mut() = None);
+}
+
+/// Clears the context once the scenario is over, dropping its values.
+pub(crate) fn leave() {
+    CURRENT.with(|current| *current.borrow_mut() = None);
+    TAGS.with(|tags| *tags.borrow_mut() = None);
+    OUTCOME.with(|outcome| *outcome.borrow_mut() = None);
+    // Taken out first, so that values dropped now may use `Context::with`
+    // without finding it borrowed.
+    let values = VALUES.with(|values| values.borrow_mut().take());
+    drop(values);
+}
@@ -0,0 +1,97 @@
+//! Parsing docstrings by the media type they declare, such as
+//! ```` ```json ````, into typed values with serde.
+//!
+//! The parser keeps what follows the opening delimiter as the first line of
+//! the docstring, so a media type is that line when it is not blank.
+
+use std::fmt;
+
+use gherkin::Step;
+use serde::de::DeserializeOwned;
+
+/// Why a docstring could not be parsed, and where.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocstringError {
+    /// The line at fault in the feature file, when known.
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for DocstringError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+impl std::error::Error for DocstringError {}
+
+/// Typed access to docstrings.
+pub trait DocstringExt {
+    /// The media type the docstring declares, as `json` for ```` ```json ````.
+    fn docstring_type(&self) -> Option<&str>;
+
+    /// The docstring without its media type.
+    fn docstring_content(&self) -> Option<&str>;
+
+    /// Parses the docstring according to its media type, JSON when it
+    /// declares none. YAML and TOML need the `yaml` and `toml` features.
+    fn docstring_as<T: DeserializeOwned>(&self) -> Result<T, DocstringError>;
+}
+
+impl DocstringExt for Step {
+    fn docstring_type(&self) -> Option<&str> {
+        let first = self.docstring.as_ref()?.lines().next()?.trim();
+        if first.is_empty() {
+            None
+        } else {
+            Some(first)
+        }
+    }
+
+    fn docstring_content(&self) -> Option<&str> {
+        let docstring = self.docstring.as_ref()?;
+        Some(match docstring.find('\n') {
+            Some(newline) => &docstring[newline + 1..],
+            None if self.docstring_type().is_some() => "",
+            None => docstring,
+        })
+    }
+
+    fn docstring_as<T: DeserializeOwned>(&self) -> Result<T, DocstringError> {
+        let content = self.docstring_content().ok_or_else(|| DocstringError {
+            line: Some(self.position.0),
+            message: "the step has no docstring".to_string(),
+        })?;
+        // The content starts on the line after the opening delimiter, which
+        // follows the step.
+        let line = |content_line: usize| Some(self.position.0 + 1 + content_line);
+
+        match self.docstring_type().unwrap_or("json") {
+            "json" | "application/json" => {
+                serde_json::from_str(content).map_err(|e| DocstringError {
+                    line: line(e.line()),
+                    message: e.to_string(),
+                })
+            }
+            #[cfg(feature = "yaml")]
+            "yaml" | "yml" | "application/yaml" => {
+                serde_yaml::from_str(content).map_err(|e| DocstringError {
+                    line: e.location().and_then(|location| line(location.line())),
+                    message: e.to_string(),
+                })
+            }
+            #[cfg(feature = "toml")]
+            "toml" | "application/toml" => toml::from_str(content).map_err(|e| DocstringError {
+                line: e.line_col().and_then(|(l, _)| line(l + 1)),
+                message: e.to_string(),
+            }),
+            other => Err(DocstringError {
+                line: Some(self.position.0 + 1),
+                message: format!("unsupported docstring media type `{}`", other),
+            }),
+        }
+    }
+}
@@ -0,0 +1,91 @@
+//! Durations of scenarios in previous runs, kept so that parallel runs can
+//! start the longest scenarios first instead of ending on a slow one.
+//!
+//! Durations are stored as JSON, in milliseconds, by `path:line` relative to
+//! the working directory, and merged after every run so that running part
+//! of the suite does not forget the rest.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use crate::output::structured::ReportWriter;
+use crate::report::Report;
+
+#[derive(Debug, Default)]
+pub(crate) struct Durations(BTreeMap<String, u64>);
+
+impl Durations {
+    /// Reads the durations at `path`; a missing or invalid file gives none.
+    pub(crate) fn load(path: &Path) -> Durations {
+        let durations = File::open(path)
+            .ok()
+            .and_then(|file| serde_json::from_reader(BufReader::new(file)).ok())
+            .unwrap_or_default();
+        Durations(durations)
+    }
+
+    /// How long the scenario at `line` of the feature file at `path` took.
+    pub(crate) fn scenario(&self, path: &Path, line: usize) -> Option<u64> {
+        self.0.get(&key(path, line)).cloned()
+    }
+
+    /// How long the scenarios of the feature file at `path` took altogether.
+    pub(crate) fn feature(&self, path: &Path) -> Option<u64> {
+        let prefix = format!("{}:", relative(path));
+        let mut durations = self
+            .0
+            .iter()
+            .filter(|(k, _)| {
+                k.starts_with(&prefix) && k[prefix.len()..].bytes().all(|b| b.is_ascii_digit())
+            })
+            .map(|(_, ms)| *ms)
+            .peekable();
+        durations.peek()?;
+        Some(durations.sum())
+    }
+}
+
+pub(crate) fn relative(path: &Path) -> String {
+    let cwd = env::current_dir().unwrap_or_default();
+    path.strip_prefix(&cwd)
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
+
+pub(crate) fn key(path: &Path, line: usize) -> String {
+    format!("{}:{}", relative(path), line)
+}
+
+/// Merges the durations of the scenarios of a run into the file at `path`.
+pub(crate) struct DurationsWriter {
+    path: PathBuf,
+}
+
+impl DurationsWriter {
+    pub(crate) fn new(path: PathBuf) -> DurationsWriter {
+        DurationsWriter { path }
+    }
+}
+
+impl ReportWriter for DurationsWriter {
+    fn write_report(&mut self, report: &Report) -> io::Result<()> {
+        let mut durations = Durations::load(&self.path).0;
+        for feature in &report.features {
+            for scenario in &feature.scenarios {
+                let duration = scenario.duration();
+                let ms = duration.as_secs() * 1000 + u64::from(duration.subsec_millis());
+                durations.insert(key(Path::new(&feature.path), scenario.line), ms);
+            }
+        }
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = BufWriter::new(File::create(&self.path)?);
+        serde_json::to_writer_pretty(file, &durations).map_err(io::Error::from)
+    }
+}
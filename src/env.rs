@@ -0,0 +1,73 @@
+//! Environment variables set for a scenario by its tags, such as
+//! `@env(FEATURE_FLAG=on)`, to test behaviour depending on configuration.
+
+use std::env;
+use std::ffi::OsString;
+
+use crate::cli::tag_argument;
+
+/// The variables set by the `@env(NAME=value)` tags of a scenario, given its
+/// tags then those of its rule and feature, in the order to set them in: so
+/// that the scenario overrides its rule, which overrides its feature. A tag
+/// without a value, `@env(NAME)`, unsets the variable.
+pub(crate) fn from_tags(tags: &[String]) -> Vec<(String, Option<String>)> {
+    tags.iter()
+        .rev()
+        .filter_map(|tag| tag_argument(tag, "env"))
+        .map(|variable| match variable.find('=') {
+            Some(i) => (
+                variable[..i].to_string(),
+                Some(variable[i + 1..].to_string()),
+            ),
+            None => (variable.to_string(), None),
+        })
+        .collect()
+}
+
+/// Whether `scenario`, its rule or its feature has an `@env` tag.
+pub(crate) fn is_set_by(
+    feature: &gherkin::Feature,
+    rule: Option<&gherkin::Rule>,
+    scenario: &gherkin::Scenario,
+) -> bool {
+    scenario
+        .tags
+        .iter()
+        .chain(rule.and_then(|r| r.tags.as_ref()))
+        .chain(feature.tags.as_ref())
+        .flatten()
+        .any(|tag| tag_argument(tag, "env").is_some())
+}
+
+/// Variables set for a scenario, restored to their previous values when
+/// dropped.
+pub(crate) struct Vars {
+    previous: Vec<(String, Option<OsString>)>,
+}
+
+impl Vars {
+    pub(crate) fn set(vars: Vec<(String, Option<String>)>) -> Vars {
+        let mut previous: Vec<(String, Option<OsString>)> = vec![];
+        for (name, value) in vars {
+            if !previous.iter().any(|(n, _)| *n == name) {
+                previous.push((name.clone(), env::var_os(&name)));
+            }
+            match value {
+                Some(value) => env::set_var(&name, value),
+                None => env::remove_var(&name),
+            }
+        }
+        Vars { previous }
+    }
+}
+
+impl Drop for Vars {
+    fn drop(&mut self) {
+        for (name, value) in self.previous.drain(..) {
+            match value {
+                Some(value) => env::set_var(name, value),
+                None => env::remove_var(name),
+            }
+        }
+    }
+}
@@ -0,0 +1,151 @@
+//! Retrying a check until it passes, for outcomes which take a while to be
+//! observed, such as a message reaching a queue or a service starting.
+
+use std::fmt::Display;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::StepContext;
+
+/// Calls `check` every `interval` until it returns `Ok`, for at most
+/// `timeout`, or until the scenario times out if that comes first:
+///
+/// ```ignore
+/// let order = cucumber::eventually(Duration::from_secs(5), Duration::from_millis(100), || {
+///     world.client.order(id)?.ok_or("the order was not created")
+/// });
+/// ```
+///
+/// # Panics
+///
+/// When the time is up, with the last error of `check`.
+pub fn eventually<T, E, F>(timeout: Duration, interval: Duration, mut check: F) -> T
+where
+    E: Display,
+    F: FnMut() -> Result<T, E>,
+{
+    let deadline = Deadline::new(timeout);
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        match check() {
+            Ok(value) => return value,
+            Err(e) => match deadline.wait(interval) {
+                Some(wait) => thread::sleep(wait),
+                None => deadline.give_up(attempts, e),
+            },
+        }
+    }
+}
+
+/// `eventually` for asynchronous checks, waiting without blocking the thread
+/// so that it works with any executor.
+///
+/// # Panics
+///
+/// When the time is up, with the last error of `check`.
+pub async fn eventually_async<T, E, F, Fut>(
+    timeout: Duration,
+    interval: Duration,
+    mut check: F,
+) -> T
+where
+    E: Display,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let deadline = Deadline::new(timeout);
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        match check().await {
+            Ok(value) => return value,
+            Err(e) => match deadline.wait(interval) {
+                Some(wait) => Delay::new(wait).await,
+                None => deadline.give_up(attempts, e),
+            },
+        }
+    }
+}
+
+/// When to stop retrying.
+struct Deadline {
+    started: Instant,
+    deadline: Instant,
+}
+
+impl Deadline {
+    /// Gives up after `timeout`, or once the running scenario times out.
+    fn new(timeout: Duration) -> Deadline {
+        let started = Instant::now();
+        let mut deadline = started + timeout;
+        if let Some(scenario_deadline) = StepContext::try_current().and_then(|c| c.deadline) {
+            deadline = deadline.min(scenario_deadline);
+        }
+        Deadline { started, deadline }
+    }
+
+    /// How long to wait before the next attempt, if there is time for one.
+    fn wait(&self, interval: Duration) -> Option<Duration> {
+        let now = Instant::now();
+        if now >= self.deadline {
+            return None;
+        }
+        Some(interval.min(self.deadline - now))
+    }
+
+    fn give_up<E: Display>(&self, attempts: usize, error: E) -> ! {
+        panic!(
+            "Still failing after {:.1}s and {} attempt{}: {}",
+            self.started.elapsed().as_secs_f64(),
+            attempts,
+            if attempts == 1 { "" } else { "s" },
+            error
+        )
+    }
+}
+
+/// A future ready after some time, woken by a thread sleeping until then,
+/// which needs no timer of a runtime.
+struct Delay {
+    until: Instant,
+    waker: Option<Arc<Mutex<Waker>>>,
+}
+
+impl Delay {
+    fn new(duration: Duration) -> Delay {
+        Delay {
+            until: Instant::now() + duration,
+            waker: None,
+        }
+    }
+}
+
+impl Future for Delay {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let now = Instant::now();
+        if now >= self.until {
+            return Poll::Ready(());
+        }
+        match self.waker {
+            // The executor may poll again with another waker.
+            Some(ref waker) => *waker.lock().unwrap() = cx.waker().clone(),
+            None => {
+                let waker = Arc::new(Mutex::new(cx.waker().clone()));
+                let wait = self.until - now;
+                self.waker = Some(waker.clone());
+                thread::spawn(move || {
+                    thread::sleep(wait);
+                    waker.lock().unwrap().wake_by_ref();
+                });
+            }
+        }
+        Poll::Pending
+    }
+}
@@ -0,0 +1,71 @@
+//! Pluggable runtimes for asynchronous steps.
+//!
+//! The runner drives every asynchronous step and world setup through an
+//! [`Executor`](trait.Executor.html). Without further configuration this is
+//! a [`ThreadExecutor`](struct.ThreadExecutor.html), or a
+//! [`TokioExecutor`](struct.TokioExecutor.html) with the `tokio-runtime`
+//! feature. Users of async-std, smol or a custom runtime can plug in theirs
+//! with [`CucumberBuilder::executor`](../struct.CucumberBuilder.html#method.executor).
+
+use std::future::Future;
+use std::pin::Pin;
+use std::thread;
+
+use futures_executor;
+
+use crate::StepFuture;
+
+/// A future which may be moved to another thread.
+pub type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+pub trait Executor: Send + Sync {
+    /// Runs `future` to completion on the current thread.
+    fn block_on(&self, future: StepFuture<'_>);
+
+    /// Starts `future` in the background without waiting for it.
+    fn spawn(&self, future: BoxFuture);
+}
+
+/// Polls futures on the current thread, and spawns each background task on a
+/// thread of its own. Enough for futures which do not depend on a specific
+/// runtime.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ThreadExecutor;
+
+impl Executor for ThreadExecutor {
+    fn block_on(&self, future: StepFuture<'_>) {
+        futures_executor::block_on(future)
+    }
+
+    fn spawn(&self, future: BoxFuture) {
+        thread::spawn(move || futures_executor::block_on(future));
+    }
+}
+
+/// Runs futures on a multi-threaded Tokio runtime owned by the executor.
+#[cfg(feature = "tokio-runtime")]
+pub struct TokioExecutor {
+    runtime: tokio::runtime::Runtime,
+}
+
+#[cfg(feature = "tokio-runtime")]
+impl TokioExecutor {
+    pub fn new() -> std::io::Result<TokioExecutor> {
+        Ok(TokioExecutor {
+            runtime: tokio::runtime::Runtime::new()?,
+        })
+    }
+}
+
+#[cfg(feature = "tokio-runtime")]
+impl Executor for TokioExecutor {
+    fn block_on(&self, future: StepFuture<'_>) {
+        // Steps are polled on the current thread, within the runtime's context
+        // so that its IO and timers are available to them.
+        self.runtime.enter(|| futures_executor::block_on(future))
+    }
+
+    fn spawn(&self, future: BoxFuture) {
+        self.runtime.spawn(future);
+    }
+}
@@ -0,0 +1,329 @@
+//! Cucumber Expressions, an alternative to regular expressions for matching
+//! steps: `I have {int} cucumber(s) in my belly/stomach`.
+//!
+//! An expression is compiled to a regular expression with one group per
+//! parameter, whose captured text is then converted by its parameter type,
+//! such as unquoting a `{string}`.
+//!
+//! Parameter types of their own, such as `{user}`, only tell what text they
+//! match: the typed arguments of step handlers convert it with `FromCapture`.
+
+use std::collections::BTreeMap;
+
+use regex::Regex;
+
+/// The parameter types known to expressions, and the regular expression
+/// each matches, without any capturing group of its own.
+const PARAMETER_TYPES: &[(&str, &str)] = &[
+    ("int", r"-?\d+"),
+    ("float", r"[-+]?(?:\d+\.?\d*|\.\d+)(?:[eE][-+]?\d+)?"),
+    ("word", r"[^\s]+"),
+    ("string", r#""(?:[^"\\]|\\.)*"|'(?:[^'\\]|\\.)*'"#),
+    ("", r".*"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Parameter {
+    Quoted,
+    Verbatim,
+}
+
+/// A compiled Cucumber Expression.
+#[derive(Debug, Clone)]
+pub(crate) struct Expression {
+    source: String,
+    regex: Regex,
+    parameters: Vec<Parameter>,
+}
+
+impl Expression {
+    /// Compiles `expression`, whose parameters may also be of the `custom`
+    /// types, given by name with the regular expression they match.
+    pub(crate) fn new(
+        expression: &str,
+        custom: &BTreeMap<String, String>,
+    ) -> Result<Expression, String> {
+        let mut pattern = String::from("^");
+        let mut parameters = vec![];
+        let mut chars = expression.chars().peekable();
+
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() {
+                chars.next();
+                pattern.push_str(&regex::escape(&c.to_string()));
+            } else if c == '{' {
+                chars.next();
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => return Err(format!("unclosed parameter `{{{}`", name)),
+                    }
+                }
+                let regex = match custom.get(&name) {
+                    Some(regex) => regex.as_str(),
+                    None => match PARAMETER_TYPES.iter().find(|(n, _)| *n == name) {
+                        Some((_, regex)) => regex,
+                        None => return Err(format!("undefined parameter type `{{{}}}`", name)),
+                    },
+                };
+                pattern.push_str(&format!("({})", regex));
+                parameters.push(if name == "string" {
+                    Parameter::Quoted
+                } else {
+                    Parameter::Verbatim
+                });
+            } else {
+                pattern.push_str(&alternation(&mut chars)?);
+            }
+        }
+        pattern.push('$');
+
+        let regex = Regex::new(&pattern).map_err(|e| e.to_string())?;
+        Ok(Expression {
+            source: expression.to_string(),
+            regex,
+            parameters,
+        })
+    }
+
+    /// The expression as it was written.
+    pub(crate) fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub(crate) fn regex(&self) -> &Regex {
+        &self.regex
+    }
+
+    /// Converts the groups captured by the regular expression, the whole
+    /// match first, to the values of the parameters.
+    pub(crate) fn convert(&self, mut captures: Vec<String>) -> Vec<String> {
+        for (capture, parameter) in captures.iter_mut().skip(1).zip(&self.parameters) {
+            if *parameter == Parameter::Quoted {
+                *capture = unquote(capture);
+            }
+        }
+        captures
+    }
+}
+
+/// Compiles the text up to the next whitespace or parameter, made of
+/// alternatives separated by `/`, each of which may have optional parts in
+/// parentheses.
+fn alternation(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> Result<String, String> {
+    let mut alternatives = vec![String::new()];
+    let mut optional: Option<String> = None;
+
+    while let Some(&c) = chars.peek() {
+        if optional.is_none() && (c.is_whitespace() || c == '{') {
+            break;
+        }
+        chars.next();
+
+        let literal = match c {
+            '\\' => match chars.next() {
+                Some(escaped) => escaped,
+                None => return Err("the expression ends with an escape".to_string()),
+            },
+            '(' if optional.is_some() => return Err("optional text cannot be nested".to_string()),
+            '(' => {
+                optional = Some(String::new());
+                continue;
+            }
+            ')' => match optional.take() {
+                Some(text) if text.is_empty() => {
+                    return Err("optional text cannot be empty".to_string())
+                }
+                Some(text) => {
+                    let last = alternatives.last_mut().unwrap();
+                    last.push_str(&format!("(?:{})?", text));
+                    continue;
+                }
+                None => return Err("unmatched `)`".to_string()),
+            },
+            '{' => return Err("parameters cannot be optional".to_string()),
+            '/' if optional.is_some() => {
+                return Err("alternation is not allowed in optional text".to_string())
+            }
+            '/' => {
+                alternatives.push(String::new());
+                continue;
+            }
+            c => c,
+        };
+
+        let escaped = regex::escape(&literal.to_string());
+        match optional {
+            Some(ref mut text) => text.push_str(&escaped),
+            None => alternatives.last_mut().unwrap().push_str(&escaped),
+        }
+    }
+
+    if optional.is_some() {
+        return Err("unclosed optional text".to_string());
+    }
+    if alternatives.len() == 1 {
+        return Ok(alternatives.pop().unwrap());
+    }
+    if alternatives
+        .iter()
+        .any(|alternative| alternative.is_empty())
+    {
+        return Err("alternatives cannot be empty".to_string());
+    }
+    Ok(format!("(?:{})", alternatives.join("|")))
+}
+
+/// The text of a `{string}`, without its quotes or the escaping of quotes.
+fn unquote(capture: &str) -> String {
+    let quote = match capture.chars().next() {
+        Some(quote) => quote,
+        None => return String::new(),
+    };
+    capture[1..capture.len() - 1].replace(&format!("\\{}", quote), &quote.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile(expression: &str) -> Result<Expression, String> {
+        Expression::new(expression, &BTreeMap::new())
+    }
+
+    /// The values of the parameters of `expression` matched by `text`.
+    fn arguments(expression: &str, text: &str) -> Option<Vec<String>> {
+        let expression = compile(expression).unwrap();
+        let captures = expression.regex().captures(text)?;
+        let captures = captures
+            .iter()
+            .map(|c| c.map_or(String::new(), |c| c.as_str().to_string()))
+            .collect();
+        Some(expression.convert(captures).split_off(1))
+    }
+
+    #[test]
+    fn matches_the_whole_text() {
+        assert_eq!(arguments("I am hungry", "I am hungry"), Some(vec![]));
+        assert_eq!(arguments("I am hungry", "I am hungry now"), None);
+        assert_eq!(arguments("I am hungry", "so I am hungry"), None);
+    }
+
+    #[test]
+    fn converts_builtin_parameters() {
+        assert_eq!(
+            arguments("I have {int} cucumbers", "I have -3 cucumbers"),
+            Some(vec!["-3".to_string()])
+        );
+        assert_eq!(
+            arguments("it costs {float}", "it costs .5"),
+            Some(vec![".5".to_string()])
+        );
+        assert_eq!(
+            arguments("I am {word}", "I am here"),
+            Some(vec!["here".to_string()])
+        );
+        assert_eq!(
+            arguments("I say {}", "I say hello world"),
+            Some(vec!["hello world".to_string()])
+        );
+        assert_eq!(
+            arguments("I have {int} cucumbers", "I have many cucumbers"),
+            None
+        );
+    }
+
+    #[test]
+    fn unquotes_strings() {
+        assert_eq!(
+            arguments("I say {string}", r#"I say "hi \"there\"""#),
+            Some(vec![r#"hi "there""#.to_string()])
+        );
+        assert_eq!(
+            arguments("I say {string}", "I say 'it\\'s'"),
+            Some(vec!["it's".to_string()])
+        );
+        assert_eq!(arguments("I say {string}", "I say hi"), None);
+    }
+
+    #[test]
+    fn uses_custom_parameters() {
+        let mut custom = BTreeMap::new();
+        custom.insert("user".to_string(), r"the \w+ user".to_string());
+        let expression = Expression::new("{user} logs in", &custom).unwrap();
+        assert!(expression.regex().is_match("the admin user logs in"));
+        assert!(!expression.regex().is_match("someone logs in"));
+    }
+
+    #[test]
+    fn optional_text() {
+        let expression = compile("I have {int} cucumber(s)").unwrap();
+        assert!(expression.regex().is_match("I have 1 cucumber"));
+        assert!(expression.regex().is_match("I have 2 cucumbers"));
+        assert!(!expression.regex().is_match("I have 2 cucumberss"));
+    }
+
+    #[test]
+    fn alternation() {
+        let expression = compile("in my belly/stomach/tummy").unwrap();
+        assert!(expression.regex().is_match("in my belly"));
+        assert!(expression.regex().is_match("in my tummy"));
+        assert!(!expression.regex().is_match("in my belly/stomach"));
+        assert!(!expression.regex().is_match("in my"));
+    }
+
+    #[test]
+    fn alternation_with_optional_text() {
+        let expression = compile("the cucumber(s)/gherkin(s) grow(s)").unwrap();
+        assert!(expression.regex().is_match("the cucumbers grow"));
+        assert!(expression.regex().is_match("the gherkin grows"));
+        assert!(!expression.regex().is_match("the tomato grows"));
+    }
+
+    #[test]
+    fn escapes() {
+        let expression = compile(r"I have \{int\} \(or more\) a\/b").unwrap();
+        assert!(expression.regex().is_match("I have {int} (or more) a/b"));
+        assert!(!expression.regex().is_match("I have 1 or more a"));
+    }
+
+    #[test]
+    fn regex_characters_are_literal() {
+        let expression = compile("it costs $5.00 [maybe]?").unwrap();
+        assert!(expression.regex().is_match("it costs $5.00 [maybe]?"));
+        assert!(!expression.regex().is_match("it costs $5x00 m?"));
+    }
+
+    #[test]
+    fn rejects_malformed_expressions() {
+        let errors = [
+            (
+                "I have {int cucumbers",
+                "unclosed parameter `{int cucumbers`",
+            ),
+            ("I have {number}", "undefined parameter type `{number}`"),
+            ("I have cucumber(s", "unclosed optional text"),
+            ("I have cucumbers)", "unmatched `)`"),
+            ("I have cucumber()", "optional text cannot be empty"),
+            ("I have cu((c)umber)", "optional text cannot be nested"),
+            ("I have ({int})", "parameters cannot be optional"),
+            (
+                "I have (a/b)",
+                "alternation is not allowed in optional text",
+            ),
+            ("in my belly/", "alternatives cannot be empty"),
+            ("in my /belly", "alternatives cannot be empty"),
+            (r"ends with \", "the expression ends with an escape"),
+        ];
+        for (expression, error) in errors.iter() {
+            assert_eq!(
+                compile(expression).err().as_deref(),
+                Some(*error),
+                "{}",
+                expression
+            );
+        }
+    }
+}
@@ -0,0 +1,53 @@
+//! State built once per feature and shared by its scenarios, such as a
+//! server each of their worlds connects a fresh client to.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use lazy_static::lazy_static;
+
+use crate::context;
+
+lazy_static! {
+    static ref WORLDS: Mutex<HashMap<PathBuf, Arc<dyn Any + Send + Sync>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Keeps `world` as that of the feature at `path`, until `end` is called.
+pub(crate) fn insert(path: &Path, world: Arc<dyn Any + Send + Sync>) {
+    WORLDS.lock().unwrap().insert(path.to_path_buf(), world);
+}
+
+/// Drops the world of the feature at `path`, once its scenarios are over.
+pub(crate) fn end(path: &Path) {
+    let world = WORLDS.lock().unwrap().remove(path);
+    drop(world);
+}
+
+/// The world of the feature running on this thread, built by the function
+/// given to `CucumberBuilder::feature_world`, from a step, a hook or
+/// `Default::default` of the world. It is shared by the scenarios of the
+/// feature, which may run at the same time: state they change goes behind a
+/// lock.
+///
+/// # Panics
+///
+/// Out of a feature, or if its world is not a `T`.
+pub fn feature_world<T: Send + Sync + 'static>() -> Arc<T> {
+    try_feature_world().unwrap_or_else(|| {
+        panic!(
+            "no feature world of type `{}`; add it with `CucumberBuilder::feature_world`",
+            std::any::type_name::<T>()
+        )
+    })
+}
+
+/// The world of the feature running on this thread, if there is one and it
+/// is a `T`.
+pub fn try_feature_world<T: Send + Sync + 'static>() -> Option<Arc<T>> {
+    let path = context::feature_path()?;
+    let world = WORLDS.lock().unwrap().get(&path)?.clone();
+    world.downcast().ok()
+}
@@ -0,0 +1,167 @@
+//! Values built on demand from constructors registered by type, such as a
+//! database pool or an HTTP client, and kept for a run, a feature or a
+//! scenario, so that steps ask for what they use instead of the world
+//! holding everything.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+
+use lazy_static::lazy_static;
+
+use crate::context::{self, Context};
+
+type Value = Arc<dyn Any + Send + Sync>;
+
+/// How long a fixture is kept once built.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FixtureScope {
+    /// Until the end of the run.
+    Run,
+    /// Until the scenarios of the feature are over.
+    Feature,
+    /// Until the scenario is over, its after hooks included.
+    Scenario,
+}
+
+struct Constructor {
+    scope: FixtureScope,
+    build: Box<dyn Fn() -> Value + Send + Sync>,
+}
+
+lazy_static! {
+    static ref CONSTRUCTORS: RwLock<HashMap<TypeId, Arc<Constructor>>> =
+        RwLock::new(HashMap::new());
+    static ref RUN: Mutex<HashMap<TypeId, Value>> = Mutex::new(HashMap::new());
+    static ref FEATURES: Mutex<HashMap<(PathBuf, TypeId), Value>> = Mutex::new(HashMap::new());
+}
+
+/// A fixture of type `T`, shared with every step of its scope.
+pub struct Fixture<T>(Arc<T>);
+
+impl<T> Clone for Fixture<T> {
+    fn clone(&self) -> Self {
+        Fixture(self.0.clone())
+    }
+}
+
+impl<T> Deref for Fixture<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Fixture<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Registers `constructor` as the one of `T`, replacing any previous one.
+pub(crate) fn register<T: Send + Sync + 'static>(scope: FixtureScope, constructor: fn() -> T) {
+    let constructor = Constructor {
+        scope,
+        build: Box::new(move || Arc::new(constructor()) as Value),
+    };
+    CONSTRUCTORS
+        .write()
+        .unwrap()
+        .insert(TypeId::of::<T>(), Arc::new(constructor));
+}
+
+/// Drops the fixtures of the feature at `path`, once its scenarios are over.
+pub(crate) fn end_feature(path: &Path) {
+    let dropped = {
+        let mut features = FEATURES.lock().unwrap();
+        let keys = features
+            .keys()
+            .filter(|(feature, _)| feature == path)
+            .cloned()
+            .collect::<Vec<_>>();
+        keys.into_iter()
+            .filter_map(|key| features.remove(&key))
+            .collect::<Vec<_>>()
+    };
+    // Dropped out of the lock, in case a fixture asks for another as it goes.
+    drop(dropped);
+}
+
+/// Drops the run fixtures, once the run is over.
+pub(crate) fn end_run() {
+    let dropped = std::mem::take(&mut *RUN.lock().unwrap());
+    drop(dropped);
+}
+
+/// The fixture of type `T` for the step, hook or constructor running on this
+/// thread, built by the constructor given to `CucumberBuilder::fixture` the
+/// first time it is asked for in its scope. Constructors may ask for other
+/// fixtures.
+///
+/// Step functions of the `given`, `when` and `then` attributes get it by
+/// taking a `Fixture<T>` argument.
+///
+/// # Panics
+///
+/// If no constructor of `T` was given, or when asked for out of its scope,
+/// e.g. a scenario fixture from a feature hook.
+pub fn fixture<T: Send + Sync + 'static>() -> Fixture<T> {
+    let type_name = std::any::type_name::<T>();
+    let key = TypeId::of::<T>();
+    let constructor = CONSTRUCTORS
+        .read()
+        .unwrap()
+        .get(&key)
+        .cloned()
+        .unwrap_or_else(|| {
+            panic!(
+                "no fixture of type `{}`; add it with `CucumberBuilder::fixture`",
+                type_name
+            )
+        });
+
+    // Built without holding any lock, as constructors may ask for fixtures.
+    // When two threads build the same fixture at once, the first one kept is
+    // the one both get.
+    let value = match constructor.scope {
+        FixtureScope::Run => {
+            let cached = RUN.lock().unwrap().get(&key).cloned();
+            cached.unwrap_or_else(|| {
+                let value = (constructor.build)();
+                RUN.lock().unwrap().entry(key).or_insert(value).clone()
+            })
+        }
+        FixtureScope::Feature => {
+            let feature = context::feature_path().unwrap_or_else(|| {
+                panic!(
+                    "the feature fixture `{}` was asked for out of a feature",
+                    type_name
+                )
+            });
+            let key = (feature, key);
+            let cached = FEATURES.lock().unwrap().get(&key).cloned();
+            cached.unwrap_or_else(|| {
+                let value = (constructor.build)();
+                FEATURES.lock().unwrap().entry(key).or_insert(value).clone()
+            })
+        }
+        FixtureScope::Scenario => {
+            // Kept in the `Context` of the scenario, and dropped with it.
+            let cached = Context::with(|context| context.get::<Fixture<T>>().cloned());
+            return cached.unwrap_or_else(|| {
+                let value = Fixture::<T>(
+                    (constructor.build)()
+                        .downcast()
+                        .expect("fixture built with the wrong type"),
+                );
+                Context::with(|context| context.insert(value.clone()));
+                value
+            });
+        }
+    };
+    Fixture(value.downcast().expect("fixture built with the wrong type"))
+}
@@ -0,0 +1,36 @@
+//! Finding the files changed since a git revision, to run only the scenarios
+//! they impact with `--changed-since`.
+
+use std::collections::BTreeSet;
+use std::io;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// The files changed since `rev`, committed or not, including untracked
+/// ones, as absolute paths.
+pub(crate) fn changed_files(rev: &str) -> io::Result<BTreeSet<PathBuf>> {
+    let root = PathBuf::from(git(&["rev-parse", "--show-toplevel"])?.trim());
+    let changed = git(&["diff", "--name-only", rev])?;
+    let untracked = git(&["ls-files", "--others", "--exclude-standard", "--full-name"])?;
+
+    Ok(changed
+        .lines()
+        .chain(untracked.lines())
+        .map(|line| {
+            let path = root.join(line);
+            path.canonicalize().unwrap_or(path)
+        })
+        .collect())
+}
+
+fn git(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("git").args(args).output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "`git {}` failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
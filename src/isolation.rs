@@ -0,0 +1,312 @@
+//! Running each scenario in a child process of its own.
+//!
+//! The parent re-runs its own executable with the scenario to run and the path
+//! of a JSON report in the environment. The child runs that scenario alone
+//! and writes the report, which the parent then reads back. A child which
+//! aborts or gets killed leaves no report, and its scenario is reported as
+//! crashed, with the exit status, instead of taking the whole run down. The
+//! child also records each step as it finishes, so that the crash is put on
+//! the step it happened in.
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{self, Child, Command, ExitStatus, Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use crate::cli::FeatureTarget;
+use crate::output::structured::ReportWriter;
+use crate::panic_trap::{self, PanicDetails};
+use crate::report::{self, Report, Status};
+use crate::{Captured, TestResult, Timing};
+
+const SCENARIO_VAR: &str = "CUCUMBER_ISOLATED_SCENARIO";
+const REPORT_VAR: &str = "CUCUMBER_ISOLATED_REPORT";
+
+static NEXT_REPORT: AtomicUsize = AtomicUsize::new(0);
+
+/// The scenario to run and where to report it, when this process is a child
+/// started by [`run`](fn.run.html).
+pub(crate) fn child() -> Option<(FeatureTarget, PathBuf)> {
+    let target = env::var(SCENARIO_VAR).ok()?;
+    let report = env::var_os(REPORT_VAR)?;
+    let target = target
+        .parse()
+        .unwrap_or_else(|e| panic!("Invalid {}: {}", SCENARIO_VAR, e));
+    Some((target, PathBuf::from(report)))
+}
+
+/// Whether this process is a child running a single scenario.
+pub(crate) fn is_child() -> bool {
+    env::var_os(SCENARIO_VAR).is_some()
+}
+
+/// Where the child started with `report` records its steps as they finish.
+pub(crate) fn progress_path(report: &Path) -> PathBuf {
+    report.with_extension("steps")
+}
+
+/// Writes each step to the progress file as a line of JSON, as soon as it
+/// finishes, for the parent to know how far a child which crashed went.
+pub(crate) struct ProgressWriter {
+    file: File,
+}
+
+impl ProgressWriter {
+    pub(crate) fn new(file: File) -> ProgressWriter {
+        ProgressWriter { file }
+    }
+}
+
+impl ReportWriter for ProgressWriter {
+    fn write_report(&mut self, _report: &Report) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn step_finished(&mut self, step: &report::Step) -> io::Result<()> {
+        serde_json::to_writer(&mut self.file, step)?;
+        writeln!(&mut self.file)?;
+        self.file.flush()
+    }
+}
+
+/// What a child process left behind.
+pub(crate) struct Outcome {
+    /// The steps of the scenario, or `None` if the child did not report back.
+    pub steps: Option<Vec<report::Step>>,
+    /// The steps the child recorded as they finished, up to where it crashed
+    /// if it did not report back.
+    pub finished_steps: Vec<report::Step>,
+    /// What the child used, if it reported back.
+    pub resources: Option<report::ResourceUsage>,
+    /// Why tearing the world down failed, if the child reported it.
+    pub teardown_failure: Option<report::Failure>,
+    /// The hooks which failed, if the child reported them.
+    pub hook_failures: Vec<report::HookFailure>,
+    pub status: ExitStatus,
+    /// Set when the child was killed for running longer than this.
+    pub timed_out: Option<Duration>,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+/// Runs the scenario at `line` of the feature file at `path` in a child process,
+/// giving it `stdin` if the feature was read from there, and killing it if it
+/// runs for longer than `timeout`.
+pub(crate) fn run(
+    path: &Path,
+    line: usize,
+    stdin: Option<&str>,
+    timeout: Option<Duration>,
+) -> io::Result<Outcome> {
+    let report_path = env::temp_dir().join(format!(
+        "cucumber-{}-{}.json",
+        process::id(),
+        NEXT_REPORT.fetch_add(1, Ordering::SeqCst)
+    ));
+
+    let mut command = Command::new(env::current_exe()?);
+    if panic_trap::capturing_backtraces() {
+        command.env("RUST_BACKTRACE", "1");
+    }
+    let mut child = command
+        .env(SCENARIO_VAR, format!("{}:{}", path.display(), line))
+        .env(REPORT_VAR, &report_path)
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let (Some(source), Some(mut pipe)) = (stdin, child.stdin.take()) {
+        pipe.write_all(source.as_bytes())?;
+    }
+    let (output, timed_out) = match timeout {
+        Some(timeout) => wait_with_timeout(child, timeout)?,
+        None => (child.wait_with_output()?, None),
+    };
+
+    let report = File::open(&report_path)
+        .ok()
+        .and_then(|file| serde_json::from_reader::<_, Report>(BufReader::new(file)).ok());
+    let _ = fs::remove_file(&report_path);
+    let progress_path = progress_path(&report_path);
+    let finished_steps = fs::read_to_string(&progress_path)
+        .map(|progress| {
+            progress
+                .lines()
+                .map_while(|line| serde_json::from_str(line).ok())
+                .collect()
+        })
+        .unwrap_or_default();
+    let _ = fs::remove_file(&progress_path);
+
+    let feature = report.and_then(|report| report.features.into_iter().next());
+    // The child ran the feature hooks around this scenario alone, so their
+    // failures are reported as the scenario's.
+    let (before_feature, after_feature): (Vec<_>, Vec<_>) = feature
+        .as_ref()
+        .map(|f| f.hook_failures.clone())
+        .unwrap_or_default()
+        .into_iter()
+        .partition(|f| f.hook == report::Hook::Before);
+    let scenario = feature.and_then(|feature| feature.scenarios.into_iter().next());
+    let resources = scenario.as_ref().and_then(|s| s.resources.clone());
+    let teardown_failure = scenario.as_ref().and_then(|s| s.teardown_failure.clone());
+    let hook_failures = before_feature
+        .into_iter()
+        .chain(
+            scenario
+                .as_ref()
+                .map(|s| s.hook_failures.clone())
+                .unwrap_or_default(),
+        )
+        .chain(after_feature)
+        .collect();
+    let steps = scenario.map(|scenario| scenario.steps);
+
+    Ok(Outcome {
+        steps,
+        finished_steps,
+        resources,
+        teardown_failure,
+        hook_failures,
+        status: output.status,
+        timed_out,
+        stdout: output.stdout,
+        stderr: output.stderr,
+    })
+}
+
+/// Waits for `child` as `wait_with_output` does, but kills it once it ran for
+/// `timeout`, which is then returned along with its output.
+fn wait_with_timeout(
+    mut child: Child,
+    timeout: Duration,
+) -> io::Result<(Output, Option<Duration>)> {
+    let stdout = read_in_background(child.stdout.take());
+    let stderr = read_in_background(child.stderr.take());
+
+    let deadline = Instant::now() + timeout;
+    let mut timed_out = None;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            child.kill()?;
+            timed_out = Some(timeout);
+            break child.wait()?;
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+
+    let output = Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    };
+    Ok((output, timed_out))
+}
+
+fn read_in_background<R: Read + Send + 'static>(pipe: Option<R>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buffer = vec![];
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buffer);
+        }
+        buffer
+    })
+}
+
+impl Outcome {
+    /// The results of the steps, in order. A child which did not report back
+    /// crashed at the first step it recorded no result for; the remaining
+    /// steps are left to the caller.
+    pub(crate) fn results(self) -> Vec<(TestResult, Timing, Captured)> {
+        match self.steps {
+            Some(steps) => steps.iter().map(step_result).collect(),
+            None => {
+                let mut results = self
+                    .finished_steps
+                    .iter()
+                    .map(step_result)
+                    .collect::<Vec<_>>();
+                let captured = Captured {
+                    stdout: self.stdout.clone(),
+                    stderr: self.stderr.clone(),
+                };
+                let status = match self.timed_out {
+                    Some(timeout) => format!("timed out after {}s", timeout.as_secs_f64()),
+                    None => self.status.to_string(),
+                };
+                results.push((
+                    TestResult::Crashed(status, self.stdout, self.stderr),
+                    Timing::zero(),
+                    captured,
+                ));
+                results
+            }
+        }
+    }
+}
+
+fn step_result(step: &report::Step) -> (TestResult, Timing, Captured) {
+    let captured = Captured {
+        stdout: step.stdout.clone().unwrap_or_default().into_bytes(),
+        stderr: step.stderr.clone().unwrap_or_default().into_bytes(),
+    };
+
+    let result = match step.status {
+        Status::Passed => TestResult::Pass,
+        Status::Skipped => TestResult::Skipped,
+        Status::Unimplemented => TestResult::Unimplemented,
+        Status::Failed => {
+            let details = match step.failure.as_ref() {
+                Some(failure) => PanicDetails {
+                    payload: failure.message.clone(),
+                    location: failure.location.clone(),
+                    backtrace: failure.backtrace.clone(),
+                },
+                None => PanicDetails {
+                    payload: "Opaque panic payload".to_owned(),
+                    location: "Unknown panic location".to_owned(),
+                    backtrace: None,
+                },
+            };
+            TestResult::Fail(details, captured.stdout.clone(), captured.stderr.clone())
+        }
+        Status::Pending => TestResult::Pending(
+            step.failure
+                .as_ref()
+                .map(|f| f.message.clone())
+                .unwrap_or_default(),
+        ),
+        Status::Ambiguous => TestResult::Ambiguous(
+            step.failure
+                .as_ref()
+                .map(|f| f.message.lines().map(str::to_string).collect())
+                .unwrap_or_default(),
+        ),
+        Status::Crashed => TestResult::Crashed(
+            step.failure
+                .as_ref()
+                .map(|f| f.message.clone())
+                .unwrap_or_default(),
+            captured.stdout.clone(),
+            captured.stderr.clone(),
+        ),
+    };
+
+    let timing = Timing {
+        started: UNIX_EPOCH + Duration::from_millis(step.started_ms),
+        duration: step.duration(),
+    };
+
+    (result, timing, captured)
+}
@@ -9,37 +9,150 @@
 pub extern crate gherkin;
 pub extern crate globwalk;
 
+mod around;
+mod baseline;
+mod captures;
+mod component;
 pub mod cli;
+pub mod clock;
+pub mod executor;
+mod context;
+mod docstring;
+mod env;
+mod durations;
+mod eventually;
+mod expression;
+mod feature_world;
+mod fixture;
+mod git;
 mod hashable_regex;
+mod isolation;
 mod output;
 mod panic_trap;
+mod parallel;
+mod pause;
+mod regex_options;
+#[cfg(feature = "macros")]
+mod registration;
+mod resources;
+mod rule_background;
+mod services;
+mod shared;
+mod shuffle;
+mod table;
+mod table_diff;
+mod tag_expression;
+mod watchdog;
+pub mod report;
+#[cfg(feature = "verify")]
+pub mod verify;
 
 use crate::cli::make_app;
 use crate::globwalk::{glob, GlobWalkerBuilder};
-use std::collections::BTreeMap;
-use std::convert::TryFrom;
-use std::fs::File;
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{self, File};
+use std::future::Future;
 use std::io::{stderr, Read, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
 use std::process;
-
-use gherkin::Feature;
-pub use gherkin::{Scenario, Step, StepType};
-use regex::Regex;
-
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+
+use globset::{Glob, GlobSetBuilder};
+pub use gherkin::{Feature, Scenario, Step, StepType};
+use regex::{Regex, RegexBuilder};
+pub use termcolor::Color;
+
+#[cfg(feature = "tokio-runtime")]
+pub use crate::executor::TokioExecutor;
+pub use crate::executor::{Executor, ThreadExecutor};
+pub use crate::around::Proceed;
+pub use crate::captures::Captures;
+pub use crate::component::{Component, Has};
+pub use crate::clock::Clock;
+pub use crate::context::{Context, ScenarioOutcome, StepContext};
+use crate::baseline::Baseline;
+pub use crate::docstring::{DocstringError, DocstringExt};
+pub use crate::eventually::{eventually, eventually_async};
+use crate::durations::{Durations, DurationsWriter};
+use crate::expression::Expression;
 use crate::hashable_regex::HashableRegex;
-pub use crate::output::{debug::DebugOutput, default::DefaultOutput, OutputVisitor};
+#[cfg(feature = "macros")]
+pub use crate::registration::Registration;
+#[cfg(feature = "macros")]
+pub use cucumber_rust_codegen::{given, then, when, World};
+#[cfg(feature = "macros")]
+#[doc(hidden)]
+pub use inventory;
+#[cfg(feature = "sqlite")]
+pub use crate::output::sqlite::SqliteWriter;
+#[cfg(feature = "webhook")]
+pub use crate::output::webhook::WebhookWriter;
+pub use crate::output::{
+    csv::{CsvOutput, CsvWriter},
+    debug::DebugOutput,
+    default::{DefaultOutput, Theme},
+    json::{JsonOutput, JsonWriter},
+    junit::{JUnitOutput, JUnitWriter},
+    live::LiveHtmlWriter,
+    messages::{MessagesFormat, MessagesOutput},
+    rerun::{RerunOutput, RerunWriter},
+    structured::{ReportOutput, ReportWriter},
+    stubs::{StubsOutput, StubsWriter},
+    Destination, FormatterKind, FormatterSpec, OutputVisitor,
+};
 use crate::panic_trap::{PanicDetails, PanicTrap};
+pub use crate::regex_options::RegexOptions;
+pub use crate::feature_world::{feature_world, try_feature_world};
+pub use crate::fixture::{fixture, Fixture, FixtureScope};
+pub use crate::services::{Readiness, Service};
+pub use crate::shared::{shared, try_shared};
+pub use crate::report::RunSummary;
+use crate::parallel::{Finished, Recorder, Slots};
+use crate::shuffle::Rng;
+pub use crate::table::{TableError, TableExt};
+pub use crate::table_diff::{DiffRow, TableDiff, TableMatch};
+pub use crate::tag_expression::TagExpression;
+
+pub trait World: Default {
+    /// Finishes preparing a new world asynchronously, e.g. by connecting to a
+    /// service, before its first step runs.
+    fn setup(&mut self) -> StepFuture<'_> {
+        Box::pin(async {})
+    }
 
-pub trait World: Default {}
+    /// Releases what the world holds outside of the process, e.g. stops a
+    /// container or drops a temporary schema, once its scenario is over. It
+    /// runs even if a step or `setup` failed; a failure is reported apart
+    /// from the steps, and fails the scenario.
+    fn teardown(&mut self) -> StepFuture<'_> {
+        Box::pin(async {})
+    }
+}
 
 type HelperFn = fn(&Scenario) -> ();
+type AroundFn = fn(&Scenario, &mut Proceed<'_>);
+type FeatureHookFn = fn(&Feature);
+type FeatureWorldFn = Box<dyn Fn(&Feature) -> Arc<dyn std::any::Any + Send + Sync> + Send + Sync>;
 
 type TestFn<W> = fn(&mut W, &Step) -> ();
 type RegexTestFn<W> = fn(&mut W, &[String], &Step) -> ();
 
+/// The future returned by an asynchronous step.
+pub type StepFuture<'a> = Pin<Box<dyn Future<Output = ()> + 'a>>;
+
+type AsyncTestFn<W> = for<'a> fn(&'a mut W, &'a Step) -> StepFuture<'a>;
+type AsyncRegexTestFn<W> = for<'a> fn(&'a mut W, &'a [String], &'a Step) -> StepFuture<'a>;
+
 type TestBag<W> = BTreeMap<&'static str, TestFn<W>>;
 type RegexBag<W> = BTreeMap<HashableRegex, RegexTestFn<W>>;
+type AsyncTestBag<W> = BTreeMap<&'static str, AsyncTestFn<W>>;
+type AsyncRegexBag<W> = BTreeMap<HashableRegex, AsyncRegexTestFn<W>>;
 
 #[derive(Default)]
 pub struct Steps<W: World> {
@@ -47,6 +160,78 @@ pub struct Steps<W: World> {
     when: TestBag<W>,
     then: TestBag<W>,
     regex: RegexSteps<W>,
+    asynchronous: AsyncSteps<W>,
+    /// Drives asynchronous steps; the default one is started on first use.
+    executor: Mutex<Option<Arc<dyn Executor>>>,
+    /// Held exclusively by serial scenarios, and shared by all others.
+    exclusive: RwLock<()>,
+    /// Feature files held in memory, by the path they are reported under.
+    sources: BTreeMap<PathBuf, String>,
+    /// Failed scenarios so far, counted for `--max-failures`.
+    failures: AtomicUsize,
+    /// When scenarios stop being started, for `--time-budget`.
+    deadline: Option<Instant>,
+    /// Scenarios left out because the run was stopped early.
+    not_run: AtomicUsize,
+    /// How long scenarios took in previous runs, when running in parallel.
+    durations: Durations,
+    /// The source file of each step definition, by type and pattern, when
+    /// known.
+    files: HashMap<(StepType, String), &'static str>,
+    /// The description of each step definition, by type and pattern, when
+    /// given.
+    docs: HashMap<(StepType, String), String>,
+    /// The pattern each alias was added for, by the type and pattern of the
+    /// alias.
+    aliases: HashMap<(StepType, String), String>,
+    /// What steps need to have to run, by type and pattern, when declared.
+    requirements: HashMap<(StepType, String), Requirement>,
+    /// The Cucumber Expressions steps were defined with, by the regular
+    /// expression they were compiled to.
+    expressions: BTreeMap<String, Expression>,
+    /// Prints the world when paused after a failure, if it implements `Debug`.
+    world_debug: Option<fn(&W) -> String>,
+    /// Hooks running the rest of each scenario themselves, outermost first.
+    around: Vec<AroundFn>,
+    /// Hooks running once before the scenarios of each feature, and once
+    /// after them.
+    before_feature: Vec<FeatureHookFn>,
+    after_feature: Vec<FeatureHookFn>,
+    /// Builds the state shared by the scenarios of each feature, before its
+    /// hooks run.
+    feature_world: Option<FeatureWorldFn>,
+    /// The external services of the run. Kept here to be stopped only once
+    /// the builder, and so the after run hooks, are done with.
+    services: services::Services,
+    /// Creates the worlds of the scenarios matching a tag expression, the
+    /// first one matching taking precedence over `Default::default`.
+    world_factories: Vec<(TagExpression, fn() -> W)>,
+    /// Whether the text of steps is normalized before matching definitions.
+    normalize: bool,
+    /// Set when the run was aborted from the pause-on-failure prompt.
+    aborted: AtomicBool,
+    /// Limits on the scenarios running at once, by tag.
+    slots: BTreeMap<String, Slots>,
+    /// The definitions steps were matched to, for `--unused-steps`.
+    used: Mutex<UsedSteps>,
+}
+
+/// The definitions matched by steps of each type, by pattern.
+#[derive(Default)]
+struct UsedSteps {
+    given: BTreeSet<String>,
+    when: BTreeSet<String>,
+    then: BTreeSet<String>,
+}
+
+impl UsedSteps {
+    fn for_type(&mut self, ty: StepType) -> &mut BTreeSet<String> {
+        match ty {
+            StepType::Given => &mut self.given,
+            StepType::When => &mut self.when,
+            StepType::Then => &mut self.then,
+        }
+    }
 }
 
 #[derive(Default)]
@@ -56,6 +241,21 @@ struct RegexSteps<W: World> {
     then: RegexBag<W>,
 }
 
+#[derive(Default)]
+struct AsyncSteps<W: World> {
+    given: AsyncTestBag<W>,
+    when: AsyncTestBag<W>,
+    then: AsyncTestBag<W>,
+    regex: AsyncRegexSteps<W>,
+}
+
+#[derive(Default)]
+struct AsyncRegexSteps<W: World> {
+    given: AsyncRegexBag<W>,
+    when: AsyncRegexBag<W>,
+    then: AsyncRegexBag<W>,
+}
+
 pub enum TestCaseType<'a, W: 'a + World> {
     Normal(&'a TestFn<W>),
     Regex(
@@ -63,6 +263,12 @@ pub enum TestCaseType<'a, W: 'a + World> {
         Vec<String>,
         &'a hashable_regex::HashableRegex,
     ),
+    Async(&'a AsyncTestFn<W>),
+    AsyncRegex(
+        &'a AsyncRegexTestFn<W>,
+        Vec<String>,
+        &'a hashable_regex::HashableRegex,
+    ),
 }
 
 impl<'a, W: 'a + World> std::fmt::Debug for TestCaseType<'a, W> {
@@ -72,16 +278,230 @@ impl<'a, W: 'a + World> std::fmt::Debug for TestCaseType<'a, W> {
             TestCaseType::Regex(_test, args, regex) => {
                 write!(f, "Regex(fn(), {:?}, {})", &args, regex)
             }
+            TestCaseType::Async(_test) => write!(f, "Async(fn())"),
+            TestCaseType::AsyncRegex(_test, args, regex) => {
+                write!(f, "AsyncRegex(fn(), {:?}, {})", &args, regex)
+            }
+        }
+    }
+}
+
+/// How the steps of a scenario went, before `--strict` and expected failures
+/// are taken into account.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ScenarioRun {
+    Passed,
+    Failed,
+    /// An undefined or pending step kept it from running to the end.
+    Incomplete,
+    /// An around hook did not let it run.
+    Skipped,
+}
+
+/// A scenario selected to run, or the start or end of the outline it was
+/// expanded from.
+// Most entries are scenarios, which boxing would only add to.
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone)]
+enum Entry<'a> {
+    Outline(&'a gherkin::Scenario),
+    /// A scenario, with the header and values of its example row when it was
+    /// expanded from an outline.
+    Scenario(Cow<'a, gherkin::Scenario>, Vec<(String, String)>),
+    OutlineEnd(&'a gherkin::Scenario),
+    FilteredOut(&'a gherkin::Scenario),
+}
+
+impl<'a> Entry<'a> {
+    fn scenario(&self) -> Option<&gherkin::Scenario> {
+        match self {
+            Entry::Scenario(scenario, _) => Some(scenario),
+            _ => None,
+        }
+    }
+
+    fn example(&self) -> &[(String, String)] {
+        match self {
+            Entry::Scenario(_, example) => example,
+            _ => &[],
+        }
+    }
+
+    /// The scenario or outline the entry comes from.
+    fn source(&self) -> &gherkin::Scenario {
+        match self {
+            Entry::Outline(scenario)
+            | Entry::OutlineEnd(scenario)
+            | Entry::FilteredOut(scenario) => scenario,
+            Entry::Scenario(scenario, _) => scenario,
+        }
+    }
+
+    fn line(&self) -> usize {
+        match self {
+            Entry::Outline(scenario) | Entry::OutlineEnd(scenario) | Entry::FilteredOut(scenario) => {
+                scenario.position.0
+            }
+            Entry::Scenario(scenario, _) => scenario.position.0,
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum TestResult {
     Skipped,
     Unimplemented,
     Pass,
     Fail(PanicDetails, Vec<u8>, Vec<u8>),
+    /// The process running the scenario died, e.g. on an abort or a signal.
+    /// Holds its exit status and output.
+    Crashed(String, Vec<u8>, Vec<u8>),
+    /// The step matches more than one definition, listed by their pattern
+    /// and source file.
+    Ambiguous(Vec<String>),
+    /// The step called `pending!`, with this reason.
+    Pending(String),
+}
+
+/// When a step started running and how long it took.
+#[derive(Debug, Clone, Copy)]
+pub struct Timing {
+    pub started: SystemTime,
+    pub duration: Duration,
+}
+
+impl Timing {
+    fn zero() -> Timing {
+        Timing {
+            started: SystemTime::now(),
+            duration: Duration::from_secs(0),
+        }
+    }
+}
+
+/// A step handler taking the values captured by its pattern as arguments of
+/// their own types, as made by `typed_step!`, with how many it takes.
+pub struct TypedStep<W> {
+    arity: usize,
+    test_fn: RegexTestFn<W>,
+}
+
+impl<W> TypedStep<W> {
+    #[doc(hidden)]
+    pub fn new(arity: usize, test_fn: RegexTestFn<W>) -> TypedStep<W> {
+        TypedStep { arity, test_fn }
+    }
+}
+
+/// A step defined by a function with the `#[given]`, `#[when]` or `#[then]`
+/// attribute, implemented by a struct named after the function.
+pub trait StepDefinition<W: World> {
+    fn register(steps: &mut StepsBuilder<W>);
+}
+
+/// What a step can return: nothing, or a `Result` whose error fails the step
+/// with its message, as a panic would but without unwinding.
+pub trait StepResult {
+    fn into_error(self) -> Option<String>;
+}
+
+impl StepResult for () {
+    fn into_error(self) -> Option<String> {
+        None
+    }
+}
+
+impl<E: std::fmt::Display> StepResult for Result<(), E> {
+    fn into_error(self) -> Option<String> {
+        self.err().map(|e| e.to_string())
+    }
+}
+
+/// Fails the running step with the error in `result`, if any. The step
+/// macros call it with what steps return; steps added to a `StepsBuilder`
+/// directly can call it themselves to return a `Result`.
+pub fn step_result<R: StepResult>(result: R) {
+    if let Some(message) = result.into_error() {
+        panic_trap::record_error(message);
+    }
+}
+
+/// Converts a captured value into an argument of typed step handlers, for
+/// every step taking one. Any type implementing `FromStr` does; others, such
+/// as domain objects looked up by name, can implement it directly:
+///
+/// ```ignore
+/// impl FromCapture for User {
+///     fn from_capture(capture: &str) -> Result<User, String> {
+///         User::named(capture).ok_or_else(|| format!("no user is named {}", capture))
+///     }
+/// }
+/// ```
+pub trait FromCapture: Sized {
+    fn from_capture(capture: &str) -> Result<Self, String>;
+}
+
+impl<T: std::str::FromStr> FromCapture for T {
+    /// Fails without a reason, as `FromStr` errors need not be printable.
+    fn from_capture(capture: &str) -> Result<T, String> {
+        capture.parse().map_err(|_| String::new())
+    }
+}
+
+/// Converts the value captured at `index` for a typed step handler.
+#[doc(hidden)]
+pub fn parse_capture<T: FromCapture>(matches: &[String], index: usize, ty: &str) -> T {
+    let value = &matches[index];
+    T::from_capture(value).unwrap_or_else(|reason| {
+        let mut message = format!(
+            "Failed to parse argument {} with value '{}' to type {}",
+            index, value, ty
+        );
+        if !reason.is_empty() {
+            message.push_str(&format!(": {}", reason));
+        }
+        panic!("{}", message)
+    })
+}
+
+/// Output captured from a step while output suppression was enabled.
+#[derive(Debug, Clone, Default)]
+pub struct Captured {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+/// What a step definition needs the step to have besides its text, as
+/// declared with `StepsBuilder::requires`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Requirement {
+    Table,
+    Docstring,
+}
+
+/// How the steps of a definition are matched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatternKind {
+    /// By their exact text.
+    Text,
+    Regex,
+    Expression,
+}
+
+/// A registered step definition, as listed by `Steps::definitions`.
+#[derive(Debug, Clone)]
+pub struct StepDefinitionInfo {
+    pub ty: StepType,
+    /// The text, regular expression or Cucumber Expression of the steps.
+    pub pattern: String,
+    pub kind: PatternKind,
+    pub is_async: bool,
+    /// The source file of the definition, when known.
+    pub file: Option<&'static str>,
+    /// The description given with `StepsBuilder::doc`, if any.
+    pub doc: Option<String>,
+    /// The pattern of the step this one is an alias of, if any.
+    pub alias_of: Option<String>,
 }
 
 #[derive(Default)]
@@ -90,6 +510,13 @@ where
     W: World,
 {
     steps: Steps<W>,
+    file: Option<&'static str>,
+    doc: Option<String>,
+    requirement: Option<Requirement>,
+    /// The parameter types of Cucumber Expressions added with
+    /// `parameter_type`, by name.
+    parameter_types: BTreeMap<String, String>,
+    regex_options: RegexOptions,
 }
 
 impl<W: World> StepsBuilder<W> {
@@ -127,27 +554,361 @@ impl<W: World> StepsBuilder<W> {
         self
     }
 
+    pub fn given_async(&mut self, name: &'static str, test_fn: AsyncTestFn<W>) -> &mut Self {
+        self.add_async(StepType::Given, name, test_fn);
+        self
+    }
+
+    pub fn when_async(&mut self, name: &'static str, test_fn: AsyncTestFn<W>) -> &mut Self {
+        self.add_async(StepType::When, name, test_fn);
+        self
+    }
+
+    pub fn then_async(&mut self, name: &'static str, test_fn: AsyncTestFn<W>) -> &mut Self {
+        self.add_async(StepType::Then, name, test_fn);
+        self
+    }
+
+    pub fn given_regex_async(
+        &mut self,
+        regex: &'static str,
+        test_fn: AsyncRegexTestFn<W>,
+    ) -> &mut Self {
+        self.add_async_regex(StepType::Given, regex, test_fn);
+        self
+    }
+
+    pub fn when_regex_async(
+        &mut self,
+        regex: &'static str,
+        test_fn: AsyncRegexTestFn<W>,
+    ) -> &mut Self {
+        self.add_async_regex(StepType::When, regex, test_fn);
+        self
+    }
+
+    pub fn then_regex_async(
+        &mut self,
+        regex: &'static str,
+        test_fn: AsyncRegexTestFn<W>,
+    ) -> &mut Self {
+        self.add_async_regex(StepType::Then, regex, test_fn);
+        self
+    }
+
+    pub fn given_expression(
+        &mut self,
+        expression: &'static str,
+        test_fn: RegexTestFn<W>,
+    ) -> &mut Self {
+        self.add_expression(StepType::Given, expression, test_fn);
+        self
+    }
+
+    pub fn when_expression(
+        &mut self,
+        expression: &'static str,
+        test_fn: RegexTestFn<W>,
+    ) -> &mut Self {
+        self.add_expression(StepType::When, expression, test_fn);
+        self
+    }
+
+    pub fn then_expression(
+        &mut self,
+        expression: &'static str,
+        test_fn: RegexTestFn<W>,
+    ) -> &mut Self {
+        self.add_expression(StepType::Then, expression, test_fn);
+        self
+    }
+
+    pub fn given_typed(&mut self, regex: &'static str, step: TypedStep<W>) -> &mut Self {
+        self.add_typed(StepType::Given, regex, step);
+        self
+    }
+
+    pub fn when_typed(&mut self, regex: &'static str, step: TypedStep<W>) -> &mut Self {
+        self.add_typed(StepType::When, regex, step);
+        self
+    }
+
+    pub fn then_typed(&mut self, regex: &'static str, step: TypedStep<W>) -> &mut Self {
+        self.add_typed(StepType::Then, regex, step);
+        self
+    }
+
+    /// Adds the step defined by a function with the `#[given]`, `#[when]` or
+    /// `#[then]` attribute: `builder.definition::<i_am_trying_out_cucumber>()`.
+    pub fn definition<D: StepDefinition<W>>(&mut self) -> &mut Self {
+        D::register(self);
+        self
+    }
+
+    /// Adds every step defined with the `#[given]`, `#[when]` and `#[then]`
+    /// attributes for this world, in any module or crate.
+    #[cfg(feature = "macros")]
+    pub fn collected(&mut self) -> &mut Self
+    where
+        W: 'static,
+    {
+        registration::register_all(self);
+        self
+    }
+
+    /// Records that the steps added from now on are defined in `file`, as
+    /// given by `file!()`, so that `--changed-since` can tell which scenarios
+    /// use them. The `steps!` macro does this.
+    pub fn file(&mut self, file: &'static str) -> &mut Self {
+        self.file = Some(file);
+        self
+    }
+
+    /// Describes the next step added, for tools listing the steps with
+    /// `Steps::definitions`. Called again before the step is added, adds a
+    /// line to the description, as each line of a doc comment does.
+    pub fn doc(&mut self, doc: &str) -> &mut Self {
+        match self.doc {
+            Some(ref mut lines) => {
+                lines.push('\n');
+                lines.push_str(doc);
+            }
+            None => self.doc = Some(doc.to_string()),
+        }
+        self
+    }
+
+    /// Declares that the next step added needs a data table or a docstring:
+    /// a step without one fails with a message saying so, before its handler
+    /// runs.
+    pub fn requires(&mut self, requirement: Requirement) -> &mut Self {
+        self.requirement = Some(requirement);
+        self
+    }
+
+    /// Records where the step of type `ty` added under `pattern` comes from.
+    ///
+    /// # Panics
+    ///
+    /// If a step of that type was already added under `pattern`.
+    fn record_file(&mut self, ty: StepType, pattern: &str) {
+        self.steps.check_unique(ty, pattern, self.file);
+        if let Some(file) = self.file {
+            self.steps.files.insert((ty, pattern.to_string()), file);
+        }
+        if let Some(doc) = self.doc.take() {
+            self.steps.docs.insert((ty, pattern.to_string()), doc);
+        }
+        if let Some(requirement) = self.requirement.take() {
+            self.steps
+                .requirements
+                .insert((ty, pattern.to_string()), requirement);
+        }
+    }
+
     pub fn add_normal(
         &mut self,
         ty: StepType,
         name: &'static str,
         test_fn: TestFn<W>,
     ) -> &mut Self {
+        self.record_file(ty, name);
         self.steps.test_bag_mut_for(ty).insert(name, test_fn);
         self
     }
 
     pub fn add_regex(&mut self, ty: StepType, regex: &str, test_fn: RegexTestFn<W>) -> &mut Self {
-        let regex = Regex::new(regex)
+        let regex = self.compile_regex(ty, regex);
+        self.steps.regex_bag_mut_for(ty).insert(regex, test_fn);
+        self
+    }
+
+    /// Adds a step whose handler takes the values captured by `regex` as
+    /// typed arguments.
+    ///
+    /// # Panics
+    ///
+    /// If `regex` does not capture as many values as the handler takes.
+    pub fn add_typed(&mut self, ty: StepType, regex: &str, step: TypedStep<W>) -> &mut Self {
+        let compiled = self.compile_regex(ty, regex);
+        check_arity(regex, &compiled, step.arity);
+        self.steps.regex_bag_mut_for(ty).insert(compiled, step.test_fn);
+        self
+    }
+
+    /// Adds a step matched by a Cucumber Expression whose handler takes the
+    /// values of its parameters as typed arguments.
+    ///
+    /// # Panics
+    ///
+    /// If the expression does not have as many parameters as the handler
+    /// takes.
+    pub fn add_typed_expression(
+        &mut self,
+        ty: StepType,
+        expression: &str,
+        step: TypedStep<W>,
+    ) -> &mut Self {
+        let regex = self.compile_expression(ty, expression);
+        check_arity(expression, &regex, step.arity);
+        self.steps.regex_bag_mut_for(ty).insert(regex, step.test_fn);
+        self
+    }
+
+    /// Adds a step matched by a Cucumber Expression, such as
+    /// `I have {int} cucumber(s)`. The handler is given the values of the
+    /// parameters after the whole step, as with a regular expression.
+    pub fn add_expression(
+        &mut self,
+        ty: StepType,
+        expression: &str,
+        test_fn: RegexTestFn<W>,
+    ) -> &mut Self {
+        let regex = self.compile_expression(ty, expression);
+        self.steps.regex_bag_mut_for(ty).insert(regex, test_fn);
+        self
+    }
+
+    pub fn add_async_expression(
+        &mut self,
+        ty: StepType,
+        expression: &str,
+        test_fn: AsyncRegexTestFn<W>,
+    ) -> &mut Self {
+        let regex = self.compile_expression(ty, expression);
+        self.steps
+            .async_regex_bag_mut_for(ty)
+            .insert(regex, test_fn);
+        self
+    }
+
+    /// Adds `alias` as another pattern of the step already added with
+    /// `pattern`, sharing its handler:
+    /// `builder.alias(StepType::Given, "I am logged in", "I log in")`. The
+    /// alias is matched as the original is, by its exact text, as a regular
+    /// expression or as a Cucumber Expression.
+    ///
+    /// # Panics
+    ///
+    /// If no step of type `ty` was added with `pattern`, or if the alias does
+    /// not capture as many values as the original.
+    pub fn alias(&mut self, ty: StepType, alias: &'static str, pattern: &str) -> &mut Self {
+        let (key, original) = if let Some(&test_fn) = self.steps.test_bag_for(ty).get(pattern) {
+            self.add_normal(ty, alias, test_fn);
+            (alias.to_string(), pattern.to_string())
+        } else if let Some(&test_fn) = self.steps.async_test_bag_for(ty).get(pattern) {
+            self.add_async(ty, alias, test_fn);
+            (alias.to_string(), pattern.to_string())
+        } else {
+            let anchored = self.regex_options.pattern(pattern);
+            let original = match self
+                .steps
+                .regex_key(ty, pattern)
+                .or_else(|| self.steps.regex_key(ty, &anchored))
+            {
+                Some(original) => original.clone(),
+                None => panic!(
+                    "`{}` cannot alias `{}`, no such {:?} step was added",
+                    alias, pattern, ty
+                ),
+            };
+            let regex = if self.steps.expressions.contains_key(original.as_str()) {
+                self.compile_expression(ty, alias)
+            } else {
+                self.compile_regex(ty, alias)
+            };
+            check_arity(alias, &regex, original.captures_len() - 1);
+
+            let key = regex.as_str().to_string();
+            if let Some(&test_fn) = self.steps.regex_bag_for(ty).get(&original) {
+                self.steps.regex_bag_mut_for(ty).insert(regex, test_fn);
+            } else if let Some(&test_fn) = self.steps.async_regex_bag_for(ty).get(&original) {
+                self.steps.async_regex_bag_mut_for(ty).insert(regex, test_fn);
+            }
+            (key, original.as_str().to_string())
+        };
+        if let Some(&requirement) = self.steps.requirements.get(&(ty, original)) {
+            self.steps.requirements.insert((ty, key.clone()), requirement);
+        }
+        self.steps.aliases.insert((ty, key), pattern.to_string());
+        self
+    }
+
+    /// Defines the `{name}` parameter of Cucumber Expressions added from now
+    /// on, matching `regex`: `builder.parameter_type("user", r"the \w+ user")`.
+    /// Handlers taking the value as a typed argument convert it with
+    /// `FromCapture`.
+    ///
+    /// # Panics
+    ///
+    /// If `regex` is invalid or has capturing groups, for which `(?:...)`
+    /// can be used instead.
+    pub fn parameter_type(&mut self, name: &str, regex: &str) -> &mut Self {
+        let compiled = Regex::new(regex)
             .unwrap_or_else(|_| panic!("`{}` is not a valid regular expression", regex));
+        if compiled.captures_len() > 1 {
+            panic!(
+                "the regular expression of `{{{}}}` cannot have capturing groups, use `(?:...)`",
+                name
+            );
+        }
+        self.parameter_types
+            .insert(name.to_string(), regex.to_string());
+        self
+    }
 
+    fn compile_expression(&mut self, ty: StepType, expression: &str) -> HashableRegex {
+        let compiled = Expression::new(expression, &self.parameter_types).unwrap_or_else(|e| {
+            panic!("`{}` is not a valid Cucumber Expression: {}", expression, e)
+        });
+        let regex = compiled.regex().clone();
+        self.record_file(ty, regex.as_str());
         self.steps
-            .regex_bag_mut_for(ty)
-            .insert(HashableRegex(regex), test_fn);
+            .expressions
+            .insert(regex.as_str().to_string(), compiled);
+        HashableRegex(regex)
+    }
+
+    pub fn add_async(
+        &mut self,
+        ty: StepType,
+        name: &'static str,
+        test_fn: AsyncTestFn<W>,
+    ) -> &mut Self {
+        self.record_file(ty, name);
+        self.steps.async_test_bag_mut_for(ty).insert(name, test_fn);
+        self
+    }
+
+    pub fn add_async_regex(
+        &mut self,
+        ty: StepType,
+        regex: &str,
+        test_fn: AsyncRegexTestFn<W>,
+    ) -> &mut Self {
+        let regex = self.compile_regex(ty, regex);
+        self.steps.async_regex_bag_mut_for(ty).insert(regex, test_fn);
+        self
+    }
 
+    /// Sets how the regular expressions of the steps added from now on
+    /// match: `builder.regex_options(RegexOptions { case_insensitive: true,
+    /// ..Default::default() })`. Cucumber Expressions are not affected.
+    pub fn regex_options(&mut self, options: RegexOptions) -> &mut Self {
+        self.regex_options = options;
         self
     }
 
+    fn compile_regex(&mut self, ty: StepType, regex: &str) -> HashableRegex {
+        let pattern = self.regex_options.pattern(regex);
+        self.record_file(ty, &pattern);
+        let compiled = RegexBuilder::new(&pattern)
+            .case_insensitive(self.regex_options.case_insensitive)
+            .build()
+            .unwrap_or_else(|_| panic!("`{}` is not a valid regular expression", regex));
+        HashableRegex(compiled)
+    }
+
     pub fn build(self) -> Steps<W> {
         self.steps
     }
@@ -170,6 +931,9 @@ impl<W: World> Steps<W> {
         }
     }
 
+    // `HashableRegex` is ordered by the source of its regex, which the
+    // matching caches inside `Regex` do not change.
+    #[allow(clippy::mutable_key_type)]
     fn regex_bag_for(&self, ty: StepType) -> &RegexBag<W> {
         match ty {
             StepType::Given => &self.regex.given,
@@ -178,6 +942,7 @@ impl<W: World> Steps<W> {
         }
     }
 
+    #[allow(clippy::mutable_key_type)]
     fn regex_bag_mut_for(&mut self, ty: StepType) -> &mut RegexBag<W> {
         match ty {
             StepType::Given => &mut self.regex.given,
@@ -186,72 +951,438 @@ impl<W: World> Steps<W> {
         }
     }
 
+    fn async_test_bag_for(&self, ty: StepType) -> &AsyncTestBag<W> {
+        match ty {
+            StepType::Given => &self.asynchronous.given,
+            StepType::When => &self.asynchronous.when,
+            StepType::Then => &self.asynchronous.then,
+        }
+    }
+
+    fn async_test_bag_mut_for(&mut self, ty: StepType) -> &mut AsyncTestBag<W> {
+        match ty {
+            StepType::Given => &mut self.asynchronous.given,
+            StepType::When => &mut self.asynchronous.when,
+            StepType::Then => &mut self.asynchronous.then,
+        }
+    }
+
+    #[allow(clippy::mutable_key_type)]
+    fn async_regex_bag_for(&self, ty: StepType) -> &AsyncRegexBag<W> {
+        match ty {
+            StepType::Given => &self.asynchronous.regex.given,
+            StepType::When => &self.asynchronous.regex.when,
+            StepType::Then => &self.asynchronous.regex.then,
+        }
+    }
+
+    #[allow(clippy::mutable_key_type)]
+    fn async_regex_bag_mut_for(&mut self, ty: StepType) -> &mut AsyncRegexBag<W> {
+        match ty {
+            StepType::Given => &mut self.asynchronous.regex.given,
+            StepType::When => &mut self.asynchronous.regex.when,
+            StepType::Then => &mut self.asynchronous.regex.then,
+        }
+    }
+
     fn test_type<'a>(&'a self, step: &Step) -> Option<TestCaseType<'a, W>> {
-        if let Some(t) = self.test_bag_for(step.ty).get(&*step.value) {
+        let value = &*self.text(step);
+        if let Some(t) = self.test_bag_for(step.ty).get(value) {
             return Some(TestCaseType::Normal(t));
         }
 
+        if let Some(t) = self.async_test_bag_for(step.ty).get(value) {
+            return Some(TestCaseType::Async(t));
+        }
+
         if let Some((regex, t)) = self
             .regex_bag_for(step.ty)
             .iter()
-            .find(|(regex, _)| regex.is_match(&step.value))
+            .find(|(regex, _)| regex.is_match(value))
         {
-            let matches = regex
-                .0
-                .captures(&step.value)
-                .unwrap()
-                .iter()
-                .map(|match_| {
-                    match_
-                        .map(|match_| match_.as_str().to_owned())
-                        .unwrap_or_default()
-                })
-                .collect();
+            return Some(TestCaseType::Regex(t, self.captures(regex, value), regex));
+        }
 
-            return Some(TestCaseType::Regex(t, matches, regex));
+        if let Some((regex, t)) = self
+            .async_regex_bag_for(step.ty)
+            .iter()
+            .find(|(regex, _)| regex.is_match(value))
+        {
+            return Some(TestCaseType::AsyncRegex(
+                t,
+                self.captures(regex, value),
+                regex,
+            ));
         }
 
         None
     }
 
-    pub fn combine(iter: impl Iterator<Item = Self>) -> Self {
-        let mut combined = Self::default();
+    /// The text of `step` to match definitions against, normalized if set.
+    fn text<'s>(&self, step: &'s Step) -> Cow<'s, str> {
+        if self.normalize {
+            Cow::Owned(normalize(&step.value))
+        } else {
+            Cow::Borrowed(&step.value)
+        }
+    }
 
-        for steps in iter {
-            combined.given.extend(steps.given);
-            combined.when.extend(steps.when);
-            combined.then.extend(steps.then);
+    /// The keys of the steps of type `ty`: the text of those matched by it,
+    /// and the regular expression of others.
+    fn keys<'a>(&'a self, ty: StepType) -> impl Iterator<Item = &'a str> + 'a {
+        self.test_bag_for(ty)
+            .keys()
+            .cloned()
+            .chain(self.async_test_bag_for(ty).keys().cloned())
+            .chain(self.regex_bag_for(ty).keys().map(|regex| regex.as_str()))
+            .chain(self.async_regex_bag_for(ty).keys().map(|regex| regex.as_str()))
+    }
 
-            combined.regex.given.extend(steps.regex.given);
-            combined.regex.when.extend(steps.regex.when);
+    /// Fails fast when a step of type `ty` is added under `key` again, which
+    /// would silently replace the first one, telling where both come from.
+    fn check_unique(&self, ty: StepType, key: &str, file: Option<&str>) {
+        if !self.keys(ty).any(|k| k == key) {
+            return;
+        }
+        let pattern = match self.expressions.get(key) {
+            Some(expression) => expression.source(),
+            None => key,
+        };
+        let locations = match (self.files.get(&(ty, key.to_string())), file) {
+            (None, None) => String::new(),
+            (first, second) => format!(
+                ", in {} and in {}",
+                first.cloned().unwrap_or("an unknown file"),
+                second.unwrap_or("an unknown file")
+            ),
+        };
+        panic!(
+            "The {:?} step `{}` is defined twice{}",
+            ty, pattern, locations
+        );
+    }
+
+    /// The regular expression of the step of type `ty` added with `pattern`,
+    /// a regular expression or a Cucumber Expression.
+    fn regex_key(&self, ty: StepType, pattern: &str) -> Option<&HashableRegex> {
+        self.regex_bag_for(ty)
+            .keys()
+            .chain(self.async_regex_bag_for(ty).keys())
+            .find(|regex| match self.expressions.get(regex.as_str()) {
+                Some(expression) => expression.source() == pattern,
+                None => regex.as_str() == pattern,
+            })
+    }
+
+    /// The groups of `regex` matched by `value`, converted to the values of
+    /// the parameters when it was compiled from a Cucumber Expression.
+    fn captures(&self, regex: &HashableRegex, value: &str) -> Vec<String> {
+        let captures = captures(regex, value);
+        match self.expressions.get(regex.as_str()) {
+            Some(expression) => expression.convert(captures),
+            None => captures,
+        }
+    }
+
+    /// The source file of the step definition matching `step`, if known.
+    fn definition_file(&self, step: &Step) -> Option<&'static str> {
+        let pattern = self.definition_key(step)?.into_owned();
+        self.files.get(&(step.ty, pattern)).cloned()
+    }
+
+    /// The pattern `step` matches a definition by, which is the regular
+    /// expression a Cucumber Expression was compiled to.
+    fn definition_key<'s>(&self, step: &'s Step) -> Option<Cow<'s, str>> {
+        let value = self.text(step);
+        if self.test_bag_for(step.ty).contains_key(&*value)
+            || self.async_test_bag_for(step.ty).contains_key(&*value)
+        {
+            return Some(value);
+        }
+        self.regex_bag_for(step.ty)
+            .keys()
+            .chain(self.async_regex_bag_for(step.ty).keys())
+            .find(|regex| regex.is_match(&value))
+            .map(|regex| Cow::Owned(regex.as_str().to_string()))
+    }
+
+    /// Why `step` cannot run, when its definition requires a data table or a
+    /// docstring it does not have.
+    fn unmet_requirement(&self, step: &Step) -> Option<&'static str> {
+        if self.requirements.is_empty() {
+            return None;
+        }
+        let key = self.definition_key(step)?.into_owned();
+        match self.requirements.get(&(step.ty, key))? {
+            Requirement::Table if step.table.is_none() => Some("step requires a data table"),
+            Requirement::Docstring if step.docstring.is_none() => Some("step requires a docstring"),
+            _ => None,
+        }
+    }
+
+    /// Records the definition `step` matches as used.
+    fn mark_used(&self, step: &Step) {
+        if let Some(key) = self.definition_key(step) {
+            let mut used = self.used.lock().unwrap_or_else(PoisonError::into_inner);
+            used.for_type(step.ty).insert(key.into_owned());
+        }
+    }
+
+    /// The definitions matching `step`, by their pattern and source file,
+    /// when there are more than one.
+    fn ambiguity(&self, step: &Step) -> Option<Vec<String>> {
+        let value = &*self.text(step);
+        let exact = self
+            .test_bag_for(step.ty)
+            .keys()
+            .chain(self.async_test_bag_for(step.ty).keys())
+            .filter(|name| **name == value)
+            .cloned();
+        let regexes = self
+            .regex_bag_for(step.ty)
+            .keys()
+            .chain(self.async_regex_bag_for(step.ty).keys())
+            .filter(|regex| regex.is_match(value))
+            .map(|regex| regex.as_str());
+
+        let matches = exact
+            .chain(regexes)
+            .map(|key| {
+                let pattern = match self.expressions.get(key) {
+                    Some(expression) => expression.source(),
+                    None => key,
+                };
+                match self.files.get(&(step.ty, key.to_string())) {
+                    Some(file) => format!("`{}` in {}", pattern, file),
+                    None => format!("`{}`", pattern),
+                }
+            })
+            .collect::<Vec<_>>();
+        if matches.len() > 1 {
+            Some(matches)
+        } else {
+            None
+        }
+    }
+
+    /// Whether the feature file at `path` or the definition of one of the
+    /// steps of `scenario` changed since `--changed-since`.
+    fn impacted(
+        &self,
+        feature: &gherkin::Feature,
+        path: &Path,
+        scenario: &gherkin::Scenario,
+        options: &cli::CliOptions,
+    ) -> bool {
+        if options.changed_files.is_none() || options.changed(path) {
+            return true;
+        }
+
+        feature
+            .background
+            .iter()
+            .flat_map(|bg| bg.steps.iter())
+            .chain(scenario.steps.iter())
+            .filter_map(|step| self.definition_file(step))
+            .any(|file| options.changed(Path::new(file)))
+    }
+
+    /// Every registered step definition, the Given ones first, then the
+    /// When and Then ones, for tools such as `--list-steps`.
+    pub fn definitions(&self) -> Vec<StepDefinitionInfo> {
+        self.definitions_where(|_, _| true)
+            .into_iter()
+            .map(|(info, _)| info)
+            .collect()
+    }
+
+    /// Every definition, as `definitions` lists them, for the messages
+    /// output to tell which ones each step matches.
+    pub(crate) fn step_definitions(&self) -> Vec<output::messages::StepDefinition> {
+        self.definitions_where(|_, _| true)
+            .into_iter()
+            .map(|(info, regex)| output::messages::StepDefinition { info, regex })
+            .collect()
+    }
+
+    /// The definitions which no step of the scenarios run so far matched,
+    /// as `--unused-steps` lists them. Skipped and filtered out scenarios do
+    /// not count.
+    pub fn unused_definitions(&self) -> Vec<StepDefinitionInfo> {
+        let mut used = self.used.lock().unwrap_or_else(PoisonError::into_inner);
+        self.definitions_where(|ty, key| !used.for_type(ty).contains(key))
+            .into_iter()
+            .map(|(info, _)| info)
+            .collect()
+    }
+
+    /// The definitions, in the order of `definitions`, whose type and key
+    /// satisfy `keep`, with the regular expression of those which have one.
+    fn definitions_where<F>(&self, mut keep: F) -> Vec<(StepDefinitionInfo, Option<Regex>)>
+    where
+        F: FnMut(StepType, &str) -> bool,
+    {
+        let mut definitions = vec![];
+        for &ty in &[StepType::Given, StepType::When, StepType::Then] {
+            let exact = self
+                .test_bag_for(ty)
+                .keys()
+                .map(|name| (*name, false))
+                .chain(self.async_test_bag_for(ty).keys().map(|name| (*name, true)))
+                .filter(|(name, _)| keep(ty, name))
+                .collect::<Vec<_>>();
+            for (name, is_async) in exact {
+                let info = self.definition_info(ty, name, name, PatternKind::Text, is_async);
+                definitions.push((info, None));
+            }
+
+            let regexes = self
+                .regex_bag_for(ty)
+                .keys()
+                .map(|regex| (regex, false))
+                .chain(self.async_regex_bag_for(ty).keys().map(|regex| (regex, true)))
+                .filter(|(regex, _)| keep(ty, regex.as_str()))
+                .collect::<Vec<_>>();
+            for (regex, is_async) in regexes {
+                let key = regex.as_str();
+                let info = match self.expressions.get(key) {
+                    Some(expression) => self.definition_info(
+                        ty,
+                        key,
+                        expression.source(),
+                        PatternKind::Expression,
+                        is_async,
+                    ),
+                    None => self.definition_info(ty, key, key, PatternKind::Regex, is_async),
+                };
+                definitions.push((info, Some(regex.0.clone())));
+            }
+        }
+        definitions
+    }
+
+    /// Describes the definition registered under `key`, which is the regular
+    /// expression a Cucumber Expression was compiled to.
+    fn definition_info(
+        &self,
+        ty: StepType,
+        key: &str,
+        pattern: &str,
+        kind: PatternKind,
+        is_async: bool,
+    ) -> StepDefinitionInfo {
+        StepDefinitionInfo {
+            ty,
+            pattern: pattern.to_string(),
+            kind,
+            is_async,
+            file: self.files.get(&(ty, key.to_string())).cloned(),
+            doc: self.docs.get(&(ty, key.to_string())).cloned(),
+            alias_of: self.aliases.get(&(ty, key.to_string())).cloned(),
+        }
+    }
+
+    pub fn combine(iter: impl Iterator<Item = Self>) -> Self {
+        let mut combined = Self::default();
+
+        for steps in iter {
+            for &ty in &[StepType::Given, StepType::When, StepType::Then] {
+                for key in steps.keys(ty) {
+                    let file = steps.files.get(&(ty, key.to_string())).cloned();
+                    combined.check_unique(ty, key, file);
+                }
+            }
+            combined.files.extend(steps.files);
+            combined.docs.extend(steps.docs);
+            combined.aliases.extend(steps.aliases);
+            combined.requirements.extend(steps.requirements);
+            combined.expressions.extend(steps.expressions);
+            combined.given.extend(steps.given);
+            combined.when.extend(steps.when);
+            combined.then.extend(steps.then);
+
+            combined.regex.given.extend(steps.regex.given);
+            combined.regex.when.extend(steps.regex.when);
             combined.regex.then.extend(steps.regex.then);
+
+            let asynchronous = steps.asynchronous;
+            combined.asynchronous.given.extend(asynchronous.given);
+            combined.asynchronous.when.extend(asynchronous.when);
+            combined.asynchronous.then.extend(asynchronous.then);
+
+            combined.asynchronous.regex.given.extend(asynchronous.regex.given);
+            combined.asynchronous.regex.when.extend(asynchronous.regex.when);
+            combined.asynchronous.regex.then.extend(asynchronous.regex.then);
         }
 
         combined
     }
 
+    /// The steps defined with the `#[given]`, `#[when]` and `#[then]`
+    /// attributes for this world, in any module or crate.
+    #[cfg(feature = "macros")]
+    pub fn collected() -> Self
+    where
+        W: 'static,
+    {
+        let mut builder = StepsBuilder::new();
+        builder.collected();
+        builder.build()
+    }
+
+    /// Replaces the executor driving asynchronous steps.
+    pub fn set_executor(&mut self, executor: Arc<dyn Executor>) {
+        *self.executor.get_mut().expect("Executor mutex poisoned") = Some(executor);
+    }
+
+    fn block_on(&self, future: StepFuture<'_>) {
+        let executor = self
+            .executor
+            .lock()
+            .expect("Executor mutex poisoned")
+            .get_or_insert_with(default_executor)
+            .clone();
+
+        executor.block_on(future)
+    }
+
     fn run_test(
         &self,
         world: &mut W,
         test_type: TestCaseType<'_, W>,
         step: &Step,
         suppress_output: bool,
-    ) -> TestResult {
+    ) -> (TestResult, Captured) {
         let test_result = PanicTrap::run(suppress_output, || match test_type {
             TestCaseType::Normal(t) => t(world, &step),
             TestCaseType::Regex(t, ref c, _) => t(world, c, &step),
+            TestCaseType::Async(t) => self.block_on(t(world, step)),
+            TestCaseType::AsyncRegex(t, ref c, _) => self.block_on(t(world, c, step)),
         });
 
-        match test_result.result {
+        let captured = Captured {
+            stdout: test_result.stdout,
+            stderr: test_result.stderr,
+        };
+
+        let result = match test_result.result {
             Ok(_) => TestResult::Pass,
             Err(panic_info) => {
                 if panic_info.payload.ends_with("cucumber test skipped") {
                     TestResult::Skipped
+                } else if panic_info.payload.starts_with(PENDING) {
+                    TestResult::Pending(panic_info.payload[PENDING.len()..].to_string())
                 } else {
-                    TestResult::Fail(panic_info, test_result.stdout, test_result.stderr)
+                    TestResult::Fail(
+                        panic_info,
+                        captured.stdout.clone(),
+                        captured.stderr.clone(),
+                    )
                 }
             }
-        }
+        };
+
+        (result, captured)
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -259,104 +1390,535 @@ impl<W: World> Steps<W> {
         &self,
         feature: &gherkin::Feature,
         rule: Option<&gherkin::Rule>,
+        path: &Path,
         scenario: &gherkin::Scenario,
+        example: &[(String, String)],
         before_fns: &[HelperFn],
         after_fns: &[HelperFn],
-        suppress_output: bool,
+        options: &cli::CliOptions,
         output: &mut impl OutputVisitor,
     ) -> bool {
+        if let Some(reason) = options.skip_reason(feature, rule, scenario) {
+            Self::skip_scenario(feature, rule, scenario, example, &reason, output);
+            return true;
+        }
+
+        // Slots are taken in the order of their tags, so that scenarios
+        // waiting for several never wait for each other.
+        let _slots = self
+            .slots
+            .iter()
+            .filter(|(tag, _)| cli::is_tagged(feature, rule, scenario, tag))
+            .map(|(_, slots)| slots.take())
+            .collect::<Vec<_>>();
+        // Environment variables are those of the whole process, which other
+        // scenarios would see but for isolated ones.
+        let sets_env = !options.isolate && env::is_set_by(feature, rule, scenario);
+        let (_shared, _exclusive) = if options.is_serial(feature, rule, scenario) || sets_env {
+            let guard = self.exclusive.write().unwrap_or_else(PoisonError::into_inner);
+            (None, Some(guard))
+        } else {
+            let guard = self.exclusive.read().unwrap_or_else(PoisonError::into_inner);
+            (Some(guard), None)
+        };
+
+        if !example.is_empty() {
+            output.visit_scenario_example(rule, scenario, example);
+        }
         output.visit_scenario(rule, &scenario);
 
-        for f in before_fns.iter() {
-            f(&scenario);
+        let sample = resources::Sample::now(isolation::is_child());
+        let run = if options.isolate {
+            self.run_isolated(feature, rule, path, scenario, options, output)
+        } else {
+            self.run_in_process(
+                feature, rule, path, scenario, example, before_fns, after_fns, options, output,
+            )
+        };
+        if options.resource_usage && !options.isolate {
+            if let Some(usage) = sample.usage() {
+                output.visit_scenario_resources(rule, scenario, &usage);
+            }
+        }
+        let mut is_success = match run {
+            ScenarioRun::Passed | ScenarioRun::Skipped => true,
+            ScenarioRun::Failed => false,
+            ScenarioRun::Incomplete => !options.strict,
+        };
+        if options.wip {
+            output.visit_scenario_xfail(rule, scenario, is_success);
+            is_success = !is_success;
+        } else if options.is_xfail(feature, rule, scenario)
+            && (run == ScenarioRun::Passed || run == ScenarioRun::Failed)
+        {
+            // Only a failure is expected: a pass fails the run, for the tag
+            // to be removed.
+            output.visit_scenario_xfail(rule, scenario, run == ScenarioRun::Passed);
+            is_success = run == ScenarioRun::Failed;
+        } else if !is_success && options.is_quarantined(feature, rule, path, scenario) {
+            output.visit_scenario_quarantined(rule, scenario);
+            is_success = true;
         }
 
-        let mut world = {
-            let panic_trap = PanicTrap::run(suppress_output, W::default);
-            match panic_trap.result {
-                Ok(v) => v,
-                Err(panic_info) => {
-                    eprintln!(
-                        "Panic caught during world creation. Panic location: {}",
-                        panic_info.location
+        output.visit_scenario_end(rule, scenario);
+
+        is_success
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn run_in_process(
+        &self,
+        feature: &gherkin::Feature,
+        rule: Option<&gherkin::Rule>,
+        path: &Path,
+        scenario: &gherkin::Scenario,
+        example: &[(String, String)],
+        before_fns: &[HelperFn],
+        after_fns: &[HelperFn],
+        options: &cli::CliOptions,
+        output: &mut impl OutputVisitor,
+    ) -> ScenarioRun {
+        let suppress_output = options.captures_output();
+        let timeout = options.timeout(feature, rule, scenario);
+        let scenario_started = Instant::now();
+
+        let mut context = StepContext::scenario(feature, rule, path, scenario, example);
+        context.deadline = timeout.map(|timeout| scenario_started + timeout);
+        context::begin(context.tags.clone());
+        context::enter_feature(path);
+        // Set before the world is created, and restored once the after hooks
+        // are over.
+        let _env = env::Vars::set(env::from_tags(&context.tags));
+        let new_world = self
+            .world_factories
+            .iter()
+            .find(|(tags, _)| tags.matches(&context.tags))
+            .map_or(W::default as fn() -> W, |&(_, factory)| factory);
+        let mut is_incomplete = false;
+        let mut run = || {
+            let mut is_success = true;
+            let mut outcome = ScenarioOutcome::new();
+            for &f in before_fns.iter() {
+                let hook = report::Hook::Before;
+                if let Some(failure) = self.run_hook(hook, f, rule, scenario, suppress_output, output)
+                {
+                    outcome.fail(failure);
+                    is_success = false;
+                    break;
+                }
+            }
+
+            let mut world = {
+                let panic_trap = PanicTrap::run(suppress_output, new_world);
+                match panic_trap.result {
+                    Ok(v) => v,
+                    Err(panic_info) => {
+                        eprintln!(
+                            "Panic caught during world creation. Panic location: {}",
+                            panic_info.location
+                        );
+                        if !panic_trap.stdout.is_empty() {
+                            eprintln!("Captured output was:");
+                            Write::write(&mut stderr(), &panic_trap.stdout).unwrap();
+                        }
+                        panic!(panic_info.payload);
+                    }
+                }
+            };
+
+            let panic_trap = PanicTrap::run(suppress_output, || self.block_on(world.setup()));
+            if let Err(panic_info) = panic_trap.result {
+                eprintln!(
+                    "Panic caught during world setup. Panic location: {}",
+                    panic_info.location
+                );
+                self.teardown(&mut world, rule, scenario, suppress_output, output);
+                panic!("{}", panic_info.payload);
+            }
+
+            // The steps of a scenario whose before hooks failed are skipped.
+            let mut is_skipping = !is_success;
+            if is_skipping {
+                output.visit_scenario_skipped(rule, scenario);
+            }
+
+            let steps = feature
+                .background
+                .iter()
+                .map(|bg| bg.steps.iter())
+                .flatten()
+                .chain(scenario.steps.iter());
+
+            for (index, step) in steps.enumerate() {
+                output.visit_step(rule, &scenario, &step);
+
+                if let Some(matches) = self.ambiguity(step) {
+                    let result = TestResult::Ambiguous(matches);
+                    output.visit_step_result(
+                        rule,
+                        scenario,
+                        step,
+                        &result,
+                        &Timing::zero(),
+                        &Captured::default(),
+                    );
+                    outcome.record(path, step, &result);
+                    is_success = false;
+                    if !is_skipping {
+                        is_skipping = true;
+                        output.visit_scenario_skipped(rule, scenario);
+                    }
+                    continue;
+                }
+
+                let test_type = match self.test_type(&step) {
+                    Some(v) => {
+                        if options.unused_steps {
+                            self.mark_used(step);
+                        }
+                        output.visit_step_resolved(step, &v);
+                        v
+                    }
+                    None => {
+                        output.visit_step_result(
+                            rule,
+                            &scenario,
+                            &step,
+                            &TestResult::Unimplemented,
+                            &Timing::zero(),
+                            &Captured::default(),
+                        );
+                        outcome.record(path, step, &TestResult::Unimplemented);
+                        is_incomplete = true;
+                        if !is_skipping {
+                            is_skipping = true;
+                            output.visit_scenario_skipped(rule, &scenario);
+                        }
+                        continue;
+                    }
+                };
+
+                if is_skipping {
+                    output.visit_step_result(
+                        rule,
+                        &scenario,
+                        &step,
+                        &TestResult::Skipped,
+                        &Timing::zero(),
+                        &Captured::default(),
                     );
-                    if !panic_trap.stdout.is_empty() {
-                        eprintln!("Captured output was:");
-                        Write::write(&mut stderr(), &panic_trap.stdout).unwrap();
+                    outcome.record(path, step, &TestResult::Skipped);
+                } else {
+                    let started = SystemTime::now();
+                    let instant = Instant::now();
+                    context.enter(index, step);
+                    let (mut result, captured) = match self.unmet_requirement(step) {
+                        Some(message) => {
+                            let details = PanicDetails {
+                                payload: message.to_string(),
+                                location: format!(
+                                    "{}:{}:{}",
+                                    path.display(),
+                                    step.position.0,
+                                    step.position.1
+                                ),
+                                backtrace: None,
+                            };
+                            (TestResult::Fail(details, vec![], vec![]), Captured::default())
+                        }
+                        None => self.run_test(&mut world, test_type, &step, suppress_output),
+                    };
+                    // A step cannot be interrupted, so a scenario running past its
+                    // timeout fails once its current step is done.
+                    if let (TestResult::Pass, Some(timeout)) = (&result, timeout) {
+                        if scenario_started.elapsed() > timeout {
+                            let details = PanicDetails {
+                                payload: format!("Scenario timed out after {}s", timeout.as_secs_f64()),
+                                location: format!(
+                                    "{}:{}:{}",
+                                    path.display(),
+                                    step.position.0,
+                                    step.position.1
+                                ),
+                                backtrace: None,
+                            };
+                            result = TestResult::Fail(
+                                details,
+                                captured.stdout.clone(),
+                                captured.stderr.clone(),
+                            );
+                        }
                     }
-                    panic!(panic_info.payload);
+                    let timing = Timing {
+                        started,
+                        duration: instant.elapsed(),
+                    };
+                    output.visit_step_result(rule, &scenario, &step, &result, &timing, &captured);
+                    outcome.record(path, step, &result);
+                    match result {
+                        TestResult::Pass => {}
+                        TestResult::Fail(_, _, _) => {
+                            is_success = false;
+                            is_skipping = true;
+                            if options.pauses_on_failure() {
+                                self.pause(&mut world, step);
+                            }
+                        }
+                        TestResult::Pending(_) => {
+                            is_incomplete = true;
+                            is_skipping = true;
+                            output.visit_scenario_skipped(rule, scenario);
+                        }
+                        _ => {
+                            is_skipping = true;
+                            output.visit_scenario_skipped(rule, &scenario);
+                        }
+                    };
+                }
+            }
+
+            if let Some(failure) = self.teardown(&mut world, rule, scenario, suppress_output, output) {
+                outcome.fail(failure);
+                is_success = false;
+            }
+            drop(world);
+
+            // After hooks, and around hooks once they proceeded, can tell how
+            // the scenario went.
+            context::set_outcome(outcome.clone());
+            for &f in after_fns.iter() {
+                let hook = report::Hook::After;
+                if let Some(failure) = self.run_hook(hook, f, rule, scenario, suppress_output, output)
+                {
+                    outcome.fail(failure);
+                    is_success = false;
                 }
             }
+            context::set_outcome(outcome);
+
+            is_success
+        };
+        let run = match around::run(&self.around, scenario, &mut run) {
+            Some(true) if is_incomplete => ScenarioRun::Incomplete,
+            Some(true) => ScenarioRun::Passed,
+            Some(false) => ScenarioRun::Failed,
+            None => {
+                output.visit_scenario_skipped(rule, scenario);
+                ScenarioRun::Skipped
+            }
+        };
+        context::leave();
+
+        run
+    }
+
+    /// Tears `world` down once `scenario` is over, and returns why it failed,
+    /// once reported, if it did.
+    fn teardown(
+        &self,
+        world: &mut W,
+        rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+        suppress_output: bool,
+        output: &mut impl OutputVisitor,
+    ) -> Option<report::Failure> {
+        let panic_trap = PanicTrap::run(suppress_output, || self.block_on(world.teardown()));
+        let failure = report::Failure::from(panic_trap.result.err()?);
+        output.visit_scenario_teardown_failed(rule, scenario, &failure);
+        Some(failure)
+    }
+
+    /// Runs `hook` for `scenario`, and returns why it failed, once reported,
+    /// if it did.
+    fn run_hook(
+        &self,
+        kind: report::Hook,
+        hook: HelperFn,
+        rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+        suppress_output: bool,
+        output: &mut impl OutputVisitor,
+    ) -> Option<report::Failure> {
+        let panic_trap = PanicTrap::run(suppress_output, || hook(scenario));
+        let failure = report::Failure::from(panic_trap.result.err()?);
+        output.visit_scenario_hook_failed(rule, scenario, kind, &failure);
+        Some(failure)
+    }
+
+    /// Reports `scenario` and its steps as skipped, without running them.
+    fn skip_scenario(
+        feature: &gherkin::Feature,
+        rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+        example: &[(String, String)],
+        reason: &str,
+        output: &mut impl OutputVisitor,
+    ) {
+        if !example.is_empty() {
+            output.visit_scenario_example(rule, scenario, example);
+        }
+        output.visit_scenario(rule, scenario);
+        output.visit_scenario_ignored(rule, scenario, reason);
+        output.visit_scenario_skipped(rule, scenario);
+
+        let steps = feature
+            .background
+            .iter()
+            .flat_map(|bg| bg.steps.iter())
+            .chain(scenario.steps.iter());
+        for step in steps {
+            output.visit_step(rule, scenario, step);
+            output.visit_step_result(
+                rule,
+                scenario,
+                step,
+                &TestResult::Skipped,
+                &Timing::zero(),
+                &Captured::default(),
+            );
+        }
+
+        output.visit_scenario_end(rule, scenario);
+    }
+
+    /// Prompts what to do after the failure of `step`, with `world` still
+    /// alive, and stops the run if told to abort.
+    fn pause(&self, world: &mut W, step: &Step) {
+        let resume = pause::prompt(step, world, self.world_debug, |world| {
+            let test_type = self.test_type(step).expect("failed step has no definition");
+            match self.run_test(world, test_type, step, false).0 {
+                TestResult::Pass => Ok(()),
+                TestResult::Fail(panic_info, _, _) => Err(panic_info.payload),
+                _ => Err("the step was skipped".to_string()),
+            }
+        });
+        if let pause::Resume::Abort = resume {
+            self.aborted.store(true, Ordering::SeqCst);
+        }
+    }
+
+    /// Runs `scenario` in a child process and reports the results it sends
+    /// back as if it had run here.
+    fn run_isolated(
+        &self,
+        feature: &gherkin::Feature,
+        rule: Option<&gherkin::Rule>,
+        path: &Path,
+        scenario: &gherkin::Scenario,
+        options: &cli::CliOptions,
+        output: &mut impl OutputVisitor,
+    ) -> ScenarioRun {
+        let line = scenario.position.0;
+        let stdin = if path == Path::new(STDIN_PATH) {
+            self.sources.get(path).map(String::as_str)
+        } else {
+            None
         };
+        let timeout = options.timeout(feature, rule, scenario);
+        let outcome = isolation::run(path, line, stdin, timeout).unwrap_or_else(|e| {
+            panic!("Could not start a process for {}:{}: {}", path.display(), line, e)
+        });
+        if options.resource_usage {
+            if let Some(usage) = outcome.resources.as_ref() {
+                output.visit_scenario_resources(rule, scenario, usage);
+            }
+        }
+        let teardown_failure = outcome.teardown_failure.clone();
+        let hook_failures = outcome.hook_failures.clone();
+        let mut results = outcome.results().into_iter();
 
         let mut is_success = true;
-        let mut is_skipping = false;
+        let mut is_incomplete = false;
+        for failure in hook_failures.iter().filter(|f| f.hook == report::Hook::Before) {
+            output.visit_scenario_hook_failed(rule, scenario, failure.hook, &failure.failure);
+            is_success = false;
+        }
+        let mut is_skipping = !is_success;
+        if is_skipping {
+            output.visit_scenario_skipped(rule, scenario);
+        }
 
         let steps = feature
             .background
             .iter()
-            .map(|bg| bg.steps.iter())
-            .flatten()
+            .flat_map(|bg| bg.steps.iter())
             .chain(scenario.steps.iter());
 
         for step in steps {
-            output.visit_step(rule, &scenario, &step);
+            output.visit_step(rule, scenario, step);
+            if self.ambiguity(step).is_none() {
+                if let Some(test_type) = self.test_type(step) {
+                    if options.unused_steps {
+                        self.mark_used(step);
+                    }
+                    output.visit_step_resolved(step, &test_type);
+                }
+            }
+
+            let (result, timing, captured) = results
+                .next()
+                .unwrap_or_else(|| (TestResult::Skipped, Timing::zero(), Captured::default()));
+            output.visit_step_result(rule, scenario, step, &result, &timing, &captured);
 
-            let test_type = match self.test_type(&step) {
-                Some(v) => {
-                    output.visit_step_resolved(step, &v);
-                    v
+            match result {
+                TestResult::Pass => {}
+                TestResult::Fail(_, _, _) | TestResult::Crashed(_, _, _) => {
+                    is_success = false;
+                    is_skipping = true;
                 }
-                None => {
-                    output.visit_step_result(rule, &scenario, &step, &TestResult::Unimplemented);
+                TestResult::Ambiguous(_) => {
+                    is_success = false;
                     if !is_skipping {
                         is_skipping = true;
-                        output.visit_scenario_skipped(rule, &scenario);
+                        output.visit_scenario_skipped(rule, scenario);
                     }
-                    continue;
                 }
-            };
-
-            if is_skipping {
-                output.visit_step_result(rule, &scenario, &step, &TestResult::Skipped);
-            } else {
-                let result = self.run_test(&mut world, test_type, &step, suppress_output);
-                output.visit_step_result(rule, &scenario, &step, &result);
-                match result {
-                    TestResult::Pass => {}
-                    TestResult::Fail(_, _, _) => {
-                        is_success = false;
-                        is_skipping = true;
+                TestResult::Unimplemented | TestResult::Pending(_) | TestResult::Skipped => {
+                    if let TestResult::Unimplemented | TestResult::Pending(_) = result {
+                        is_incomplete = true;
                     }
-                    _ => {
+                    if !is_skipping {
                         is_skipping = true;
-                        output.visit_scenario_skipped(rule, &scenario);
+                        output.visit_scenario_skipped(rule, scenario);
                     }
-                };
+                }
             }
         }
-
-        for f in after_fns.iter() {
-            f(&scenario);
+        if let Some(failure) = teardown_failure {
+            output.visit_scenario_teardown_failed(rule, scenario, &failure);
+            is_success = false;
+        }
+        for failure in hook_failures.iter().filter(|f| f.hook == report::Hook::After) {
+            output.visit_scenario_hook_failed(rule, scenario, failure.hook, &failure.failure);
+            is_success = false;
         }
 
-        output.visit_scenario_end(rule, &scenario);
-
-        is_success
+        match (is_success, is_incomplete) {
+            (false, _) => ScenarioRun::Failed,
+            (true, true) => ScenarioRun::Incomplete,
+            (true, false) => ScenarioRun::Passed,
+        }
     }
 
-    #[allow(clippy::too_many_arguments)]
-    fn run_scenarios(
+    /// Lists the scenarios to run, expanding outlines into one scenario per
+    /// example row.
+    fn select_scenarios<'a>(
         &self,
         feature: &gherkin::Feature,
-        rule: Option<&gherkin::Rule>,
-        scenarios: &[gherkin::Scenario],
-        before_fns: &[HelperFn],
-        after_fns: &[HelperFn],
+        path: &Path,
+        scenarios: &'a [gherkin::Scenario],
         options: &cli::CliOptions,
-        output: &mut impl OutputVisitor,
-    ) -> bool {
-        let mut is_success = true;
+    ) -> Vec<Entry<'a>> {
+        let lines = options.lines_for(path);
+        let lines = lines.as_deref();
+        // Rows of examples have no position of their own but in the source.
+        let source = if scenarios.iter().any(|s| s.examples.is_some()) {
+            self.feature_source(path)
+        } else {
+            None
+        };
+        let source = source.as_deref();
+        let mut entries = vec![];
 
         for scenario in scenarios {
             // If a tag is specified and the scenario does not have the tag, skip the test.
@@ -379,241 +1941,1285 @@ impl<W: World> Steps<W> {
                 (None, None) => {},
             };
 
-            match &scenario.examples {
-                Some(examples) => {
-                    for (i, row) in examples.table.rows.iter().enumerate() {
+            // `--wip` only runs the work in progress, among what `--tag` selects.
+            let wip = || {
+                scenario
+                    .tags
+                    .iter()
+                    .flatten()
+                    .any(|tag| cli::tag_name(tag) == "wip")
+            };
+            if options.wip && !wip() {
+                continue;
+            }
+
+            // If lines were given, only run what is found at one of them.
+            if let Some(lines) = lines {
+                if !lines
+                    .iter()
+                    .any(|&line| scenario_has_line(source, scenario, line))
+                {
+                    continue;
+                }
+            }
+
+            // If regex filter fails, report the scenario as filtered out.
+            if let Some(ref regex) = options.filter {
+                if !regex.is_match(&scenario.name) {
+                    entries.push(Entry::FilteredOut(scenario));
+                    continue;
+                }
+            }
+
+            // So are those which changes since `--changed-since` do not impact.
+            if !self.impacted(feature, path, scenario, options) {
+                entries.push(Entry::FilteredOut(scenario));
+                continue;
+            }
+
+            match &scenario.examples {
+                Some(examples) => {
+                    let outline_index = entries.len();
+                    entries.push(Entry::Outline(scenario));
+
+                    // Unless a line points at the outline itself, only run the examples at
+                    // the given lines.
+                    let wanted = match lines {
+                        Some(lines)
+                            if !lines.iter().any(|&line| {
+                                line == scenario.position.0
+                                    || scenario.steps.iter().any(|s| s.position.0 == line)
+                            }) =>
+                        {
+                            lines.to_vec()
+                        }
+                        _ => vec![],
+                    };
+
+                    let rows = examples.table.rows.iter();
+                    let rows = rows.zip(example_lines(source, examples));
+                    for (i, (row, line)) in rows.enumerate() {
+                        if !wanted.is_empty() && !wanted.contains(&line) {
+                            continue;
+                        }
+                        if !options.in_shard(path, line) {
+                            continue;
+                        }
+
+                        let fill =
+                            |text: &str| fill_placeholders(text, &examples.table.header, row);
                         let steps = scenario
                             .steps
                             .iter()
                             .map(|step| {
                                 let mut step = step.clone();
-                                for (k, v) in examples.table.header.iter().zip(row.iter()) {
-                                    step.value = step.value.replace(&format!("<{}>", k), &v);
-                                    // Replace the values in the doc strings
-                                    step.docstring =
-                                        step.docstring.map(|x| x.replace(&format!("<{}>", k), &v));
-                                    // TODO: also replace those in the table.
+                                step.value = fill(&step.value);
+                                step.docstring = step.docstring.map(|x| fill(&x));
+                                if let Some(table) = step.table.as_mut() {
+                                    for cell in table.header.iter_mut() {
+                                        *cell = fill(cell);
+                                    }
+                                    for cell in table.rows.iter_mut().flatten() {
+                                        *cell = fill(cell);
+                                    }
                                 }
                                 step
                             })
                             .collect();
 
                         // Replace example scenario name with example values
-                        let mut scenario_name = scenario.name.clone();
-                        for (k, v) in examples.table.header.iter().zip(row.iter()) {
-                            scenario_name = scenario_name.replace(&format!("<{}>", k), &v);
-                        }
+                        let mut scenario_name = fill(&scenario.name);
                         // Graceful degradation
                         if scenario_name == scenario.name {
                             scenario_name = format!("{} {}", scenario.name, i);
                         }
 
-                        let example = Scenario {
-                            name: scenario_name,
-                            steps,
-                            examples: None,
-                            tags: scenario.tags.clone(),
-                            position: examples.table.position,
-                        };
-
-                        // If regex filter fails, skip the test.
-                        if let Some(ref regex) = options.filter {
-                            if !regex.is_match(&scenario.name) {
-                                continue;
-                            }
-                        }
+                        let example = examples
+                            .table
+                            .header
+                            .iter()
+                            .cloned()
+                            .zip(row.iter().cloned())
+                            .collect();
+                        entries.push(Entry::Scenario(
+                            Cow::Owned(Scenario {
+                                name: scenario_name,
+                                steps,
+                                examples: None,
+                                tags: scenario.tags.clone(),
+                                position: (line, examples.table.position.1),
+                            }),
+                            example,
+                        ));
+                    }
+
+                    // Leave out outlines with no example in this shard.
+                    if entries.len() == outline_index + 1 {
+                        entries.pop();
+                    } else {
+                        entries.push(Entry::OutlineEnd(scenario));
+                    }
+                }
+                None => {
+                    if options.in_shard(path, scenario.position.0) {
+                        entries.push(Entry::Scenario(Cow::Borrowed(scenario), vec![]));
+                    }
+                }
+            };
+        }
+
+        entries
+    }
+
+    fn visit_outline_entry(
+        entry: &Entry<'_>,
+        rule: Option<&gherkin::Rule>,
+        output: &mut impl OutputVisitor,
+    ) {
+        match entry {
+            Entry::Outline(outline) => output.visit_scenario_outline(rule, outline),
+            Entry::OutlineEnd(outline) => output.visit_scenario_outline_end(rule, outline),
+            Entry::FilteredOut(scenario) => output.visit_scenario_filtered_out(rule, scenario),
+            Entry::Scenario(..) => {}
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn run_scenarios(
+        &self,
+        feature: &gherkin::Feature,
+        rule: Option<&gherkin::Rule>,
+        path: &Path,
+        scenarios: &[gherkin::Scenario],
+        before_fns: &[HelperFn],
+        after_fns: &[HelperFn],
+        options: &cli::CliOptions,
+        output: &mut impl OutputVisitor,
+    ) -> bool {
+        let mut entries = self.select_scenarios(feature, path, scenarios, options);
+        if let Some(seed) = options.seed {
+            let salt = format!(
+                "{}/{}",
+                feature.name,
+                rule.map(|r| r.name.as_str()).unwrap_or("")
+            );
+            entries = shuffle_entries(entries, &mut Rng::new(seed, &salt));
+        } else if options.sorted {
+            entries = sort_entries(entries);
+        }
+        if options.failed_first {
+            entries = failed_first_entries(entries, |line| options.failed_before(path, Some(line)));
+        }
+        if let Err(cycle) = constrained_entries(&mut entries) {
+            output.visit_dependency_cycle(feature, rule, &cycle);
+            for entry in &entries {
+                match entry {
+                    Entry::Scenario(scenario, _) => output.visit_scenario_not_run(rule, scenario),
+                    entry => Self::visit_outline_entry(entry, rule, output),
+                }
+            }
+            return false;
+        }
+        if let Some(repeat) = options.repeat {
+            entries = repeat_entries(entries, repeat);
+        }
+        let jobs = options.jobs.unwrap_or(1);
+        let mut is_success = true;
 
+        if jobs <= 1 {
+            for entry in &entries {
+                match entry {
+                    Entry::Scenario(scenario, example) => {
+                        if self.stopped(options) {
+                            self.not_run.fetch_add(1, Ordering::SeqCst);
+                            output.visit_scenario_not_run(rule, scenario);
+                            continue;
+                        }
                         if !self.run_scenario(
                             &feature,
                             rule,
-                            &example,
+                            path,
+                            &scenario,
+                            example,
                             &before_fns,
                             &after_fns,
-                            options.suppress_output,
+                            options,
                             output,
                         ) {
+                            self.failures.fetch_add(1, Ordering::SeqCst);
                             is_success = false;
                         }
                     }
+                    entry => Self::visit_outline_entry(entry, rule, output),
                 }
-                None => {
-                    // If regex filter fails, skip the test.
-                    if let Some(ref regex) = options.filter {
-                        if !regex.is_match(&scenario.name) {
-                            continue;
-                        }
-                    }
+            }
 
-                    if !self.run_scenario(
-                        &feature,
-                        rule,
-                        &scenario,
-                        &before_fns,
-                        &after_fns,
-                        options.suppress_output,
-                        output,
-                    ) {
-                        is_success = false;
-                    }
+            return is_success;
+        }
+
+        let indices = entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.scenario().is_some())
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        let mut cursor = 0;
+        let dependencies = dependencies(&entries);
+        let finished = Finished::new(indices.len());
+        // Constrained scenarios start in their order, so that dependencies
+        // always start before their dependents.
+        let constrained = entries
+            .iter()
+            .any(|e| !Constraints::of(e.source()).is_empty());
+        let schedule = if constrained {
+            (0..indices.len()).collect()
+        } else {
+            schedule(indices.len(), |job| {
+                let line = entries[indices[job]].line();
+                (
+                    options.failed_first && options.failed_before(path, Some(line)),
+                    self.durations.scenario(path, line),
+                )
+            })
+        };
+
+        parallel::run_scheduled(
+            &schedule,
+            jobs,
+            |job| {
+                let _running = finished.start(job, &dependencies[job]);
+                let entry = &entries[indices[job]];
+                let scenario = entry.scenario().unwrap();
+                let mut recorder = Recorder::default();
+                if self.stopped(options) {
+                    self.not_run.fetch_add(1, Ordering::SeqCst);
+                    recorder.visit_scenario_not_run(rule, scenario);
+                    return (true, recorder);
                 }
-            };
+                let success = self.run_scenario(
+                    feature,
+                    rule,
+                    path,
+                    scenario,
+                    entry.example(),
+                    before_fns,
+                    after_fns,
+                    options,
+                    &mut recorder,
+                );
+                if !success {
+                    self.failures.fetch_add(1, Ordering::SeqCst);
+                }
+                (success, recorder)
+            },
+            |job, (success, recorder)| {
+                for entry in &entries[cursor..indices[job]] {
+                    Self::visit_outline_entry(entry, rule, output);
+                }
+                cursor = indices[job] + 1;
+
+                recorder.replay(self, rule, output);
+                if !success {
+                    is_success = false;
+                }
+            },
+        );
+
+        for entry in &entries[cursor..] {
+            Self::visit_outline_entry(entry, rule, output);
         }
 
         is_success
     }
 
-    pub fn run(
+    /// Whether as many scenarios failed as `--max-failures` allows, the
+    /// time budget ran out or the run was aborted, so that no more are started.
+    fn stopped(&self, options: &cli::CliOptions) -> bool {
+        let failed = match options.max_failures {
+            Some(max) => self.failures.load(Ordering::SeqCst) >= max,
+            None => false,
+        };
+        failed || self.out_of_time() || self.aborted.load(Ordering::SeqCst)
+    }
+
+    fn out_of_time(&self) -> bool {
+        match self.deadline {
+            Some(deadline) => Instant::now() >= deadline,
+            None => false,
+        }
+    }
+
+    /// The source of the feature at `path`, if it can be read.
+    fn feature_source(&self, path: &Path) -> Option<Cow<'_, str>> {
+        match self.sources.get(path) {
+            Some(source) => Some(Cow::Borrowed(source)),
+            None => fs::read_to_string(path).ok().map(Cow::Owned),
+        }
+    }
+
+    fn read_feature(&self, path: &Path) -> Result<Feature, gherkin::Error> {
+        if let Some(source) = self.sources.get(path) {
+            return rule_background::parse_feature(source);
+        }
+
+        let mut file = File::open(path).expect("file to open");
+        let mut buffer = String::new();
+        file.read_to_string(&mut buffer).unwrap();
+
+        rule_background::parse_feature(&buffer)
+    }
+
+    fn run_feature(
         &self,
-        feature_files: Vec<PathBuf>,
+        feature: &gherkin::Feature,
+        path: &Path,
         before_fns: &[HelperFn],
         after_fns: &[HelperFn],
-        options: cli::CliOptions,
+        options: &cli::CliOptions,
         output: &mut impl OutputVisitor,
     ) -> bool {
-        output.visit_start();
-
         let mut is_success = true;
 
-        for path in feature_files {
-            let mut file = File::open(&path).expect("file to open");
-            let mut buffer = String::new();
-            file.read_to_string(&mut buffer).unwrap();
-
-            let feature = match Feature::try_from(&*buffer) {
-                Ok(v) => v,
-                Err(e) => {
-                    output.visit_feature_error(&path, &e);
-                    is_success = false;
-                    continue;
+        output.visit_feature(feature, path);
+
+        // Feature hooks run where the scenarios do: in the process of each
+        // one when isolated.
+        let runs_hooks = !options.isolate && self.has_scenarios_to_run(feature, path, options);
+        context::enter_feature(path);
+        let mut setup_failure = None;
+        if runs_hooks {
+            // The feature world is built as if by a first before feature hook.
+            if let Some(init) = &self.feature_world {
+                let panic_trap = PanicTrap::run(options.captures_output(), || init(feature));
+                match panic_trap.result {
+                    Ok(world) => feature_world::insert(path, world),
+                    Err(panic_info) => setup_failure = Some(report::Failure::from(panic_info)),
                 }
-            };
+            }
+            for &hook in self.before_feature.iter() {
+                if setup_failure.is_some() {
+                    break;
+                }
+                let panic_trap = PanicTrap::run(options.captures_output(), || hook(feature));
+                if let Err(panic_info) = panic_trap.result {
+                    setup_failure = Some(report::Failure::from(panic_info));
+                }
+            }
+            if let Some(failure) = &setup_failure {
+                output.visit_feature_hook_failed(feature, report::Hook::Before, failure);
+                is_success = false;
+            }
+        }
 
-            output.visit_feature(&feature, &path);
+        let mut rules = feature.rules.iter().collect::<Vec<_>>();
+        if options.sorted {
+            rules.sort_by_key(|rule| rule.position.0);
+        }
+
+        if let Some(failure) = setup_failure {
+            let reason = format!("a before feature hook failed: {}", failure.message);
+            self.skip_scenarios(
+                feature,
+                None,
+                path,
+                &feature.scenarios,
+                &reason,
+                options,
+                output,
+            );
+            for rule in rules {
+                output.visit_rule(rule);
+                self.skip_scenarios(
+                    feature,
+                    Some(rule),
+                    path,
+                    &rule.scenarios,
+                    &reason,
+                    options,
+                    output,
+                );
+                output.visit_rule_end(rule);
+            }
+        } else {
             if !self.run_scenarios(
-                &feature,
+                feature,
                 None,
+                path,
                 &feature.scenarios,
                 before_fns,
                 after_fns,
-                &options,
+                options,
                 output,
             ) {
                 is_success = false;
             }
 
-            for rule in &feature.rules {
-                output.visit_rule(&rule);
+            for rule in rules {
+                output.visit_rule(rule);
                 if !self.run_scenarios(
-                    &feature,
-                    Some(&rule),
+                    feature,
+                    Some(rule),
+                    path,
                     &rule.scenarios,
                     before_fns,
                     after_fns,
-                    &options,
+                    options,
                     output,
                 ) {
                     is_success = false;
                 }
-                output.visit_rule_end(&rule);
+                output.visit_rule_end(rule);
             }
-            output.visit_feature_end(&feature);
         }
 
-        output.visit_finish();
+        if runs_hooks {
+            for &hook in self.after_feature.iter() {
+                let panic_trap = PanicTrap::run(options.captures_output(), || hook(feature));
+                if let Err(panic_info) = panic_trap.result {
+                    let failure = report::Failure::from(panic_info);
+                    output.visit_feature_hook_failed(feature, report::Hook::After, &failure);
+                    is_success = false;
+                }
+            }
+        }
+        fixture::end_feature(path);
+        feature_world::end(path);
+        context::leave_feature();
+        output.visit_feature_end(feature);
 
         is_success
     }
-}
 
-#[doc(hidden)]
-pub fn tag_rule_applies(scenario: &Scenario, rule: &str) -> bool {
-    if let Some(ref tags) = &scenario.tags {
-        let tags: Vec<&str> = tags.iter().map(|s| s.as_str()).collect();
-        let rule_chunks = rule.split(' ');
-        // TODO: implement a sane parser for this
-        for rule in rule_chunks {
-            if rule == "and" || rule == "or" {
-                // TODO: implement handling for this
-                continue;
-            }
+    /// Whether any scenario of `feature`, or of its rules, is selected to run.
+    fn has_scenarios_to_run(
+        &self,
+        feature: &gherkin::Feature,
+        path: &Path,
+        options: &cli::CliOptions,
+    ) -> bool {
+        if self.feature_world.is_none()
+            && self.before_feature.is_empty()
+            && self.after_feature.is_empty()
+        {
+            return false;
+        }
+        feature
+            .rules
+            .iter()
+            .map(|rule| &rule.scenarios)
+            .chain(Some(&feature.scenarios))
+            .any(|scenarios| {
+                self.select_scenarios(feature, path, scenarios, options)
+                    .iter()
+                    .any(|entry| entry.scenario().is_some())
+            })
+    }
 
-            if !tags.contains(&rule) {
-                return false;
+    /// Reports the scenarios selected among `scenarios` as skipped for
+    /// `reason`, without running them.
+    #[allow(clippy::too_many_arguments)]
+    fn skip_scenarios(
+        &self,
+        feature: &gherkin::Feature,
+        rule: Option<&gherkin::Rule>,
+        path: &Path,
+        scenarios: &[gherkin::Scenario],
+        reason: &str,
+        options: &cli::CliOptions,
+        output: &mut impl OutputVisitor,
+    ) {
+        for entry in self.select_scenarios(feature, path, scenarios, options) {
+            match entry.scenario() {
+                Some(scenario) => {
+                    Self::skip_scenario(feature, rule, scenario, entry.example(), reason, output)
+                }
+                None => Self::visit_outline_entry(&entry, rule, output),
             }
         }
+    }
 
-        true
-    } else {
-        true
+    pub fn run(
+        &self,
+        feature_files: Vec<PathBuf>,
+        before_fns: &[HelperFn],
+        after_fns: &[HelperFn],
+        options: cli::CliOptions,
+        output: &mut impl OutputVisitor,
+    ) -> bool {
+        self.run_with(feature_files, before_fns, after_fns, &options, output)
     }
-}
 
-#[macro_export]
-macro_rules! before {
-    (
-        $fnname:ident: $tagrule:tt => $scenariofn:expr
-    ) => {
-        fn $fnname(scenario: &$crate::Scenario) {
-            let scenario_closure: fn(&$crate::Scenario) -> () = $scenariofn;
-            let tag_rule: &str = $tagrule;
+    /// Runs as `run` does, with `options` borrowed, so that the watchdog of
+    /// the time budget can read them while the run goes on.
+    fn run_with(
+        &self,
+        feature_files: Vec<PathBuf>,
+        before_fns: &[HelperFn],
+        after_fns: &[HelperFn],
+        options: &cli::CliOptions,
+        output: &mut impl OutputVisitor,
+    ) -> bool {
+        output.visit_start(&options.metadata);
+        if self.services.failed() {
+            output.visit_services(&self.services.reports());
+            output.visit_finish();
+            return false;
+        }
 
-            // TODO check tags
-            if $crate::tag_rule_applies(scenario, tag_rule) {
-                scenario_closure(scenario);
+        let mut feature_files = feature_files;
+        if let Some(seed) = options.seed {
+            Rng::new(seed, "").shuffle(&mut feature_files);
+        } else if options.sorted {
+            feature_files.sort();
+        }
+        if options.failed_first {
+            feature_files.sort_by_key(|path| !options.failed_before(path, None));
+        }
+
+        let mut is_success = true;
+        let feature_jobs = options.feature_jobs.unwrap_or(1);
+
+        if feature_jobs <= 1 {
+            for path in feature_files {
+                let feature = match self.read_feature(&path) {
+                    Ok(v) => v,
+                    Err(e) => {
+                        output.visit_feature_error(&path, &e);
+                        is_success = false;
+                        continue;
+                    }
+                };
+
+                if !self.run_feature(&feature, &path, before_fns, after_fns, options, output) {
+                    is_success = false;
+                }
             }
+        } else {
+            let schedule = schedule(feature_files.len(), |job| {
+                let path = &feature_files[job];
+                (
+                    options.failed_first && options.failed_before(path, None),
+                    self.durations.feature(path),
+                )
+            });
+            parallel::run_scheduled(
+                &schedule,
+                feature_jobs,
+                |job| {
+                    let path = &feature_files[job];
+                    self.read_feature(path).map(|feature| {
+                        let mut recorder = Recorder::default();
+                        let success = self.run_feature(
+                            &feature,
+                            path,
+                            before_fns,
+                            after_fns,
+                            options,
+                            &mut recorder,
+                        );
+                        (success, recorder)
+                    })
+                },
+                |job, result| match result {
+                    Ok((success, recorder)) => {
+                        recorder.replay(self, None, output);
+                        if !success {
+                            is_success = false;
+                        }
+                    }
+                    Err(e) => {
+                        output.visit_feature_error(&feature_files[job], &e);
+                        is_success = false;
+                    }
+                },
+            );
         }
-    };
 
-    (
-        $fnname:ident => $scenariofn:expr
-    ) => {
-        before!($fnname: "" => $scenariofn);
-    };
-}
+        let not_run = self.not_run.load(Ordering::SeqCst);
+        if not_run > 0 {
+            if self.out_of_time() {
+                eprintln!("Time budget exceeded, {} scenarios not run", not_run);
+            } else if self.aborted.load(Ordering::SeqCst) {
+                eprintln!("Run aborted, {} scenarios not run", not_run);
+            } else {
+                eprintln!(
+                    "Stopped after {} failed scenarios, {} not run",
+                    self.failures.load(Ordering::SeqCst),
+                    not_run
+                );
+            }
+            is_success = false;
+        }
 
-// This is just a remap of before.
-#[macro_export]
-macro_rules! after {
-    (
-        $fnname:ident: $tagrule:tt => $stepfn:expr
-    ) => {
-        before!($fnname: $tagrule => $stepfn);
-    };
+        if !self.services.is_empty() {
+            let services = self.services.reports();
+            if services.iter().any(|service| service.error.is_some()) {
+                is_success = false;
+            }
+            output.visit_services(&services);
+        }
+        output.visit_finish();
 
-    (
-        $fnname:ident => $scenariofn:expr
-    ) => {
-        before!($fnname: "" => $scenariofn);
-    };
+        is_success
+    }
+
+    /// Whether the run was stopped by its time budget before every scenario ran.
+    fn timed_out(&self) -> bool {
+        self.out_of_time() && self.not_run.load(Ordering::SeqCst) > 0
+    }
 }
 
-pub struct CucumberBuilder<W: World, O: OutputVisitor> {
-    output: O,
-    features: Vec<PathBuf>,
-    setup: Option<fn() -> ()>,
-    before: Vec<fn(&Scenario) -> ()>,
-    after: Vec<fn(&Scenario) -> ()>,
-    steps: Steps<W>,
-    options: crate::cli::CliOptions,
+/// Where the runner keeps `file` from one run to the next, e.g. the failures
+/// of the last run for `--failed-first`.
+fn state_path(file: &str) -> PathBuf {
+    let target = std::env::var_os("CARGO_TARGET_DIR").unwrap_or_else(|| "target".into());
+    PathBuf::from(target).join("cucumber").join(file)
 }
 
-impl<W: World, O: OutputVisitor> CucumberBuilder<W, O> {
-    pub fn new(output: O) -> Self {
-        CucumberBuilder {
-            output,
-            features: vec![],
-            setup: None,
-            before: vec![],
-            after: vec![],
-            steps: Steps::default(),
-            options: crate::cli::CliOptions::default(),
-        }
-    }
+/// The order in which to start parallel jobs, given whether each one failed
+/// in the previous run with `--failed-first`, and how long it took before:
+/// failed ones first, then the longest ones, with unknown ones before others.
+fn schedule(count: usize, history: impl Fn(usize) -> (bool, Option<u64>)) -> Vec<usize> {
+    let mut schedule = (0..count).collect::<Vec<_>>();
+    schedule.sort_by_key(|&job| {
+        let (failed, duration) = history(job);
+        (!failed, Reverse(duration.unwrap_or(u64::MAX)))
+    });
+    schedule
+}
 
-    pub fn setup(&mut self, function: fn() -> ()) -> &mut Self {
-        self.setup = Some(function);
-        self
-    }
+/// How long scenarios in progress may run past the time budget.
+const TIME_BUDGET_GRACE: Duration = Duration::from_secs(30);
+
+/// The target reading a single feature from stdin.
+const STDIN_PATH: &str = "-";
+
+/// `text` with the `<placeholders>` of `header` replaced by their values in
+/// `row`, as in the steps of an outline.
+fn fill_placeholders(text: &str, header: &[String], row: &[String]) -> String {
+    let mut text = text.to_string();
+    for (k, v) in header.iter().zip(row.iter()) {
+        text = text.replace(&format!("<{}>", k), v);
+    }
+    text
+}
+
+/// The lines of the rows of an examples table, read from the `source` of its
+/// feature when there is one.
+fn example_lines(source: Option<&str>, examples: &gherkin::Examples) -> Vec<usize> {
+    let table = &examples.table;
+    let lines = source.map_or_else(Vec::new, |source| {
+        crate::table::row_lines(source, table.position.0)
+    });
+    (0..table.rows.len())
+        .map(|i| lines.get(i).copied().unwrap_or(table.position.0 + i + 1))
+        .collect()
+}
+
+/// Whether `line` is the line of `scenario`, of one of its steps or of one of
+/// its examples.
+fn scenario_has_line(source: Option<&str>, scenario: &gherkin::Scenario, line: usize) -> bool {
+    line == scenario.position.0
+        || scenario.steps.iter().any(|step| step.position.0 == line)
+        || match &scenario.examples {
+            Some(examples) => example_lines(source, examples).contains(&line),
+            None => false,
+        }
+}
+
+/// Splits entries into scenarios and whole outlines, which are to be kept
+/// together when reordering.
+fn group_entries(entries: Vec<Entry<'_>>) -> Vec<Vec<Entry<'_>>> {
+    let mut groups: Vec<Vec<Entry<'_>>> = vec![];
+    let mut in_outline = false;
+
+    for entry in entries {
+        match entry {
+            Entry::Outline(_) => {
+                in_outline = true;
+                groups.push(vec![entry]);
+            }
+            Entry::OutlineEnd(_) => {
+                in_outline = false;
+                groups.last_mut().unwrap().push(entry);
+            }
+            Entry::Scenario(..) if in_outline => groups.last_mut().unwrap().push(entry),
+            Entry::Scenario(..) | Entry::FilteredOut(_) => groups.push(vec![entry]),
+        }
+    }
+
+    groups
+}
+
+/// Shuffles scenarios, keeping the examples of an outline together but in a
+/// random order too.
+fn shuffle_entries<'a>(entries: Vec<Entry<'a>>, rng: &mut Rng) -> Vec<Entry<'a>> {
+    let mut groups = group_entries(entries);
+    for group in groups.iter_mut() {
+        if group.len() > 2 {
+            let last = group.len() - 1;
+            rng.shuffle(&mut group[1..last]);
+        }
+    }
+    rng.shuffle(&mut groups);
+
+    groups.into_iter().flatten().collect()
+}
+
+/// Sorts scenarios, and the examples of each outline, by line.
+fn sort_entries(entries: Vec<Entry<'_>>) -> Vec<Entry<'_>> {
+    let mut groups = group_entries(entries);
+    for group in groups.iter_mut() {
+        if group.len() > 2 {
+            let last = group.len() - 1;
+            group[1..last].sort_by_key(Entry::line);
+        }
+    }
+    groups.sort_by_key(|group| group[0].line());
+
+    groups.into_iter().flatten().collect()
+}
+
+/// Moves the scenarios for which `failed` is true first, and the outlines with
+/// such examples, which come first within their outline. The order is kept
+/// otherwise.
+fn failed_first_entries(entries: Vec<Entry<'_>>, failed: impl Fn(usize) -> bool) -> Vec<Entry<'_>> {
+    let is_failed = |entry: &Entry<'_>| entry.scenario().is_some() && failed(entry.line());
+    let mut groups = group_entries(entries);
+    for group in groups.iter_mut() {
+        if group.len() > 2 {
+            let last = group.len() - 1;
+            group[1..last].sort_by_key(|entry| !is_failed(entry));
+        }
+    }
+    groups.sort_by_key(|group| !group.iter().any(is_failed));
+
+    groups.into_iter().flatten().collect()
+}
+
+/// The ordering constraints of a scenario or outline, given by its
+/// `@order(N)` and `@depends-on(Name)` tags.
+#[derive(Default)]
+struct Constraints {
+    /// The name dependents refer to it by.
+    name: String,
+    /// Scenarios without an `@order` tag come at 0.
+    order: i64,
+    depends_on: Vec<String>,
+}
+
+impl Constraints {
+    fn of(scenario: &gherkin::Scenario) -> Constraints {
+        let mut constraints = Constraints {
+            name: constraint_name(&scenario.name),
+            ..Default::default()
+        };
+        for tag in scenario.tags.iter().flatten() {
+            if let Some(order) = cli::tag_argument(tag, "order") {
+                constraints.order = order.trim().parse().unwrap_or(0);
+            } else if let Some(name) = cli::tag_argument(tag, "depends-on") {
+                if !name.trim().is_empty() {
+                    constraints.depends_on.push(constraint_name(name));
+                }
+            }
+        }
+        constraints
+    }
+
+    fn is_empty(&self) -> bool {
+        self.order == 0 && self.depends_on.is_empty()
+    }
+}
+
+/// Scenario names are matched regardless of spaces and case, since tags
+/// cannot contain spaces: `@depends-on(SetupAccounts)` names "Setup accounts".
+fn constraint_name(name: &str) -> String {
+    name.split_whitespace().collect::<String>().to_lowercase()
+}
+
+/// Orders scenarios, and outlines as a whole, so that each comes after the
+/// ones its `@depends-on` tags name, then by increasing `@order`, keeping the
+/// order otherwise. Dependencies which are not about to run are ignored. If
+/// the dependencies form a cycle, `entries` are left as they were and the
+/// names of the scenarios which could not be ordered are returned.
+fn constrained_entries(entries: &mut Vec<Entry<'_>>) -> Result<(), Vec<String>> {
+    let groups = group_entries(std::mem::take(entries));
+    let constraints = groups
+        .iter()
+        .map(|group| match &group[0] {
+            Entry::FilteredOut(_) => Constraints::default(),
+            head => Constraints::of(head.source()),
+        })
+        .collect::<Vec<_>>();
+    let mut groups = groups.into_iter().map(Some).collect::<Vec<_>>();
+
+    let mut remaining = (0..groups.len()).collect::<Vec<_>>();
+    let mut order = vec![];
+    while !remaining.is_empty() {
+        let waiting = |i: usize| {
+            constraints[i].depends_on.iter().any(|name| {
+                remaining
+                    .iter()
+                    .any(|&j| j != i && constraints[j].name == *name)
+            })
+        };
+        let next = remaining
+            .iter()
+            .enumerate()
+            .filter(|&(_, &i)| !waiting(i))
+            .min_by_key(|&(_, &i)| (constraints[i].order, i))
+            .map(|(position, _)| position);
+
+        match next {
+            Some(position) => order.push(remaining.remove(position)),
+            None => {
+                let cycle = remaining
+                    .iter()
+                    .map(|&i| groups[i].as_ref().unwrap()[0].source().name.clone())
+                    .collect();
+                *entries = groups.into_iter().flatten().flatten().collect();
+                return Err(cycle);
+            }
+        }
+    }
+
+    *entries = order
+        .into_iter()
+        .flat_map(|i| groups[i].take().unwrap())
+        .collect();
+    Ok(())
+}
+
+/// For each scenario among `entries`, the earlier ones it depends on, by
+/// index among scenarios. Examples depend on what their outline depends on.
+fn dependencies(entries: &[Entry<'_>]) -> Vec<Vec<usize>> {
+    let mut outline = None;
+    let mut constraints = vec![];
+    for entry in entries {
+        match entry {
+            Entry::Outline(scenario) => outline = Some(*scenario),
+            Entry::OutlineEnd(_) => outline = None,
+            Entry::Scenario(scenario, _) => {
+                constraints.push(Constraints::of(outline.unwrap_or(scenario)))
+            }
+            Entry::FilteredOut(_) => {}
+        }
+    }
+
+    (0..constraints.len())
+        .map(|job| {
+            (0..job)
+                .filter(|&other| {
+                    constraints[job]
+                        .depends_on
+                        .contains(&constraints[other].name)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Runs each scenario `times` times in a row.
+fn repeat_entries(entries: Vec<Entry<'_>>, times: usize) -> Vec<Entry<'_>> {
+    entries
+        .into_iter()
+        .flat_map(|entry| match entry {
+            Entry::Scenario(..) => vec![entry; times],
+            entry => vec![entry],
+        })
+        .collect()
+}
+
+#[cfg(feature = "tokio-runtime")]
+fn default_executor() -> Arc<dyn Executor> {
+    Arc::new(TokioExecutor::new().expect("Failed to start the Tokio runtime"))
+}
+
+#[cfg(not(feature = "tokio-runtime"))]
+fn default_executor() -> Arc<dyn Executor> {
+    Arc::new(ThreadExecutor)
+}
+
+/// `text` with runs of whitespace made single spaces, curly quotes made
+/// straight, and no trailing punctuation.
+fn normalize(text: &str) -> String {
+    let text = text
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace(&['‘', '’', '‚', '‛'][..], "'")
+        .replace(&['“', '”', '„', '‟'][..], "\"");
+    text.trim_end_matches(|c: char| ".,;:!?".contains(c) || c.is_whitespace())
+        .to_string()
+}
+
+fn check_arity(pattern: &str, regex: &Regex, arity: usize) {
+    let captured = regex.captures_len() - 1;
+    if captured != arity {
+        panic!(
+            "`{}` captures {} value{}, but its step handler takes {}",
+            pattern,
+            captured,
+            if captured == 1 { "" } else { "s" },
+            arity
+        );
+    }
+}
+
+fn captures(regex: &HashableRegex, value: &str) -> Vec<String> {
+    regex
+        .0
+        .captures(value)
+        .unwrap()
+        .iter()
+        .map(|match_| {
+            match_
+                .map(|match_| match_.as_str().to_owned())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+/// The tag expression of a hook declared by `before!`, `around!` or `after!`.
+/// It is checked as the hook's `static` is built, so that a malformed one
+/// fails to compile, and parsed the first time the hook runs.
+#[doc(hidden)]
+pub struct TagRule {
+    source: &'static str,
+    expression: std::sync::OnceLock<TagExpression>,
+}
+
+impl TagRule {
+    pub const fn new(source: &'static str) -> TagRule {
+        if let Err(e) = tag_expression::check(source) {
+            panic!("{}", e);
+        }
+        TagRule {
+            source,
+            expression: std::sync::OnceLock::new(),
+        }
+    }
+
+    /// Whether the hook applies to `scenario`, by its tags and those of its
+    /// rule and feature.
+    pub fn applies(&self, scenario: &Scenario) -> bool {
+        let expression = self.expression.get_or_init(|| {
+            self.source
+                .parse()
+                .expect("the tag expression was checked by `TagRule::new`")
+        });
+        match context::scenario_tags() {
+            Some(tags) => expression.matches(&tags),
+            None => expression.matches(scenario.tags.as_deref().unwrap_or(&[])),
+        }
+    }
+}
+
+/// Declares a hook running before each scenario, or only before those
+/// matching a tag expression:
+/// `before!(launch_browser: "@browser and not @headless" => |scenario| { ... });`.
+#[macro_export]
+macro_rules! before {
+    (
+        $fnname:ident: $tagrule:tt => $scenariofn:expr
+    ) => {
+        fn $fnname(scenario: &$crate::Scenario) {
+            static TAG_RULE: $crate::TagRule = $crate::TagRule::new($tagrule);
+            let scenario_closure: fn(&$crate::Scenario) -> () = $scenariofn;
+
+            if TAG_RULE.applies(scenario) {
+                scenario_closure(scenario);
+            }
+        }
+    };
+
+    (
+        $fnname:ident => $scenariofn:expr
+    ) => {
+        before!($fnname: "" => $scenariofn);
+    };
+}
+
+/// Declares a hook given the rest of each scenario, or of those matching a
+/// tag expression, to run with `Proceed::proceed`:
+/// `around!(in_transaction: "@db" => |_scenario, proceed| { ... });`.
+#[macro_export]
+macro_rules! around {
+    (
+        $fnname:ident: $tagrule:tt => $scenariofn:expr
+    ) => {
+        fn $fnname(scenario: &$crate::Scenario, proceed: &mut $crate::Proceed<'_>) {
+            static TAG_RULE: $crate::TagRule = $crate::TagRule::new($tagrule);
+            let scenario_closure: fn(&$crate::Scenario, &mut $crate::Proceed<'_>) = $scenariofn;
+
+            if TAG_RULE.applies(scenario) {
+                scenario_closure(scenario, proceed);
+            } else {
+                proceed.proceed();
+            }
+        }
+    };
+
+    (
+        $fnname:ident => $scenariofn:expr
+    ) => {
+        around!($fnname: "" => $scenariofn);
+    };
+}
+
+/// Declares a hook running after each scenario, or only after those
+/// matching a tag expression, as `before!` does. Given a second argument, it
+/// is passed how the scenario went:
+/// `after!(screenshot => |_scenario, outcome| if outcome.is_failed() { ... });`.
+#[macro_export]
+macro_rules! after {
+    (
+        $fnname:ident: $tagrule:tt => |$scenario:tt, $outcome:tt| $body:expr
+    ) => {
+        before!($fnname: $tagrule => |scenario| {
+            let outcome = $crate::ScenarioOutcome::current()
+                .expect("after hooks run once the steps are over");
+            let outcome_closure: fn(&$crate::Scenario, &$crate::ScenarioOutcome) =
+                |$scenario, $outcome| $body;
+            outcome_closure(scenario, &outcome);
+        });
+    };
+
+    (
+        $fnname:ident: $tagrule:tt => $stepfn:expr
+    ) => {
+        before!($fnname: $tagrule => $stepfn);
+    };
+
+    (
+        $fnname:ident => |$scenario:tt, $outcome:tt| $body:expr
+    ) => {
+        after!($fnname: "" => |$scenario, $outcome| $body);
+    };
+
+    (
+        $fnname:ident => $scenariofn:expr
+    ) => {
+        before!($fnname: "" => $scenariofn);
+    };
+}
+
+/// Runs the after-run hooks when dropped, so that they run even if the run
+/// panics.
+struct AfterRun(Vec<fn()>);
+
+impl Drop for AfterRun {
+    fn drop(&mut self) {
+        for f in self.0.iter() {
+            f();
+        }
+        fixture::end_run();
+    }
+}
+
+pub struct CucumberBuilder<W: World, O: OutputVisitor> {
+    output: O,
+    features: Vec<PathBuf>,
+    setup: Option<fn() -> ()>,
+    before_run: Vec<fn()>,
+    after_run: Vec<fn()>,
+    before_feature: Vec<FeatureHookFn>,
+    after_feature: Vec<FeatureHookFn>,
+    feature_world: Option<FeatureWorldFn>,
+    services: Vec<Service>,
+    shared: Vec<Box<dyn Fn() + Send + Sync>>,
+    fixtures: Vec<Box<dyn Fn() + Send + Sync>>,
+    before: Vec<fn(&Scenario) -> ()>,
+    after: Vec<fn(&Scenario) -> ()>,
+    around: Vec<AroundFn>,
+    world_factories: Vec<(TagExpression, fn() -> W)>,
+    steps: Steps<W>,
+    formatters: Vec<FormatterSpec>,
+    serve: Option<String>,
+    capture_limit: Option<usize>,
+    metadata: BTreeMap<String, String>,
+    jobs: Option<usize>,
+    feature_jobs: Option<usize>,
+    executor: Option<Arc<dyn Executor>>,
+    random_order: bool,
+    seed: Option<u64>,
+    name: Option<Regex>,
+    strict: bool,
+    wip: bool,
+    resource_usage: bool,
+    unused_steps: bool,
+    backtraces: bool,
+    chain_panic_hook: bool,
+    isolate: bool,
+    shard: Option<crate::cli::Shard>,
+    repeat: Option<usize>,
+    max_failures: Option<usize>,
+    time_budget: Option<Duration>,
+    baseline: Option<PathBuf>,
+    regression_threshold: Option<f64>,
+    serial_tag: Option<String>,
+    quarantine_file: Option<String>,
+    quarantine_tag: Option<String>,
+    skip_tags: Option<Vec<String>>,
+    tag_timeouts: BTreeMap<String, Duration>,
+    tag_limits: BTreeMap<String, usize>,
+    pause_on_failure: bool,
+    world_debug: Option<fn(&W) -> String>,
+    normalize_step_text: bool,
+    sorted: bool,
+    failed_first: bool,
+    changed_since: Option<String>,
+    include: Vec<String>,
+    exclude: Vec<String>,
+    sources: BTreeMap<PathBuf, String>,
+    #[cfg(feature = "webhook")]
+    webhook: Option<String>,
+    options: crate::cli::CliOptions,
+}
+
+impl<W: World, O: OutputVisitor> CucumberBuilder<W, O> {
+    pub fn new(output: O) -> Self {
+        CucumberBuilder {
+            output,
+            features: vec![],
+            setup: None,
+            before_run: vec![],
+            after_run: vec![],
+            before_feature: vec![],
+            after_feature: vec![],
+            feature_world: None,
+            services: vec![],
+            shared: vec![],
+            fixtures: vec![],
+            before: vec![],
+            after: vec![],
+            around: vec![],
+            world_factories: vec![],
+            steps: Steps::default(),
+            formatters: vec![],
+            serve: None,
+            capture_limit: None,
+            metadata: BTreeMap::new(),
+            jobs: None,
+            feature_jobs: None,
+            executor: None,
+            random_order: false,
+            seed: None,
+            name: None,
+            strict: false,
+            wip: false,
+            resource_usage: false,
+            unused_steps: false,
+            backtraces: false,
+            chain_panic_hook: false,
+            isolate: false,
+            shard: None,
+            repeat: None,
+            max_failures: None,
+            time_budget: None,
+            baseline: None,
+            regression_threshold: None,
+            serial_tag: None,
+            quarantine_file: None,
+            quarantine_tag: None,
+            skip_tags: None,
+            tag_timeouts: BTreeMap::new(),
+            tag_limits: BTreeMap::new(),
+            pause_on_failure: false,
+            world_debug: None,
+            normalize_step_text: false,
+            sorted: false,
+            failed_first: false,
+            changed_since: None,
+            include: vec![],
+            exclude: vec![],
+            sources: BTreeMap::new(),
+            #[cfg(feature = "webhook")]
+            webhook: None,
+            options: crate::cli::CliOptions::default(),
+        }
+    }
+
+    pub fn setup(&mut self, function: fn() -> ()) -> &mut Self {
+        self.setup = Some(function);
+        self
+    }
+
+    /// Adds a function to run once before any feature, after `setup`, e.g. to
+    /// start a server or seed a database. Unlike `setup`, it does not run
+    /// again in the processes of isolated scenarios.
+    pub fn before_run(&mut self, function: fn()) -> &mut Self {
+        self.before_run.push(function);
+        self
+    }
+
+    /// Adds a function to run once after all features, even if the run
+    /// failed or panicked, e.g. to stop a server or publish artifacts.
+    pub fn after_run(&mut self, function: fn()) -> &mut Self {
+        self.after_run.push(function);
+        self
+    }
+
+    /// Adds a function to run once before the scenarios of each feature, e.g.
+    /// to load fixtures they share. If it panics, the scenarios of the feature
+    /// are skipped with its failure, and the run goes on with the next one.
+    pub fn before_feature(&mut self, function: fn(&Feature)) -> &mut Self {
+        self.before_feature.push(function);
+        self
+    }
+
+    /// Adds a function to run once after the scenarios of each feature, even
+    /// if its before feature hooks failed.
+    pub fn after_feature(&mut self, function: fn(&Feature)) -> &mut Self {
+        self.after_feature.push(function);
+        self
+    }
+
+    /// Adds an external process to start before the run, once the services
+    /// added before it are ready, and to stop after it. If one cannot be
+    /// started or made ready, the run fails without running any feature or
+    /// suite hook, and the report tells why along with what it printed.
+    /// Isolated scenarios use those of their parent.
+    pub fn service(&mut self, service: Service) -> &mut Self {
+        self.services.push(service);
+        self
+    }
+
+    /// Builds state once per feature with `init`, before its hooks run, and
+    /// shares it with its scenarios, which get it with
+    /// `cucumber::feature_world::<F>()`, e.g. one server per feature and a
+    /// fresh client per scenario. If `init` panics, the scenarios of the
+    /// feature are skipped as when a before feature hook fails.
+    pub fn feature_world<F: Send + Sync + 'static>(
+        &mut self,
+        init: fn(&Feature) -> F,
+    ) -> &mut Self {
+        self.feature_world = Some(Box::new(move |feature| Arc::new(init(feature))));
+        self
+    }
+
+    /// Computes read-only state once, before any feature, e.g. a configuration
+    /// or a connection pool, which steps, hooks and worlds then get with
+    /// `cucumber::shared::<T>()` instead of building it for each scenario.
+    /// Isolated scenarios compute it again in their own process.
+    pub fn shared<T: Send + Sync + 'static>(&mut self, init: fn() -> T) -> &mut Self {
+        self.shared.push(Box::new(move || shared::insert(init())));
+        self
+    }
+
+    /// Registers the constructor of the fixtures of type `T`, built the first
+    /// time `cucumber::fixture::<T>()` asks for one in `scope` and kept until
+    /// it ends, e.g. a database pool for the run or a logged in client for a
+    /// scenario. Isolated scenarios build theirs in their own process.
+    pub fn fixture<T: Send + Sync + 'static>(
+        &mut self,
+        scope: FixtureScope,
+        constructor: fn() -> T,
+    ) -> &mut Self {
+        self.fixtures
+            .push(Box::new(move || fixture::register(scope, constructor)));
+        self
+    }
 
     pub fn features(&mut self, features: Vec<PathBuf>) -> &mut Self {
         let mut features = features
@@ -639,63 +3245,952 @@ impl<W: World, O: OutputVisitor> CucumberBuilder<W, O> {
         self
     }
 
+    /// Runs the feature files matching a glob, e.g. `features/**/smoke_*.feature`,
+    /// instead of those given to [`features`](#method.features). Can be called
+    /// several times; `--feature` on the command line takes precedence.
+    pub fn include(&mut self, pattern: &str) -> &mut Self {
+        self.include.push(pattern.to_string());
+        self
+    }
+
+    /// Leaves out the feature files matching a glob, relative to the current
+    /// directory, e.g. `features/wip/**`. Can be called several times.
+    pub fn exclude(&mut self, pattern: &str) -> &mut Self {
+        self.exclude.push(pattern.to_string());
+        self
+    }
+
+    /// Runs a feature whose text is held in memory, e.g. embedded in the test
+    /// binary with `include_str!`, along with those found on disk. `path`
+    /// needs not exist: it names the feature in the output and reports, and
+    /// selects its scenarios as in `login.feature:42`.
+    pub fn feature_source(&mut self, path: &str, source: &str) -> &mut Self {
+        self.sources.insert(PathBuf::from(path), source.to_string());
+        self
+    }
+
     pub fn before(&mut self, functions: Vec<fn(&Scenario) -> ()>) -> &mut Self {
         self.before = functions;
         self
     }
 
-    pub fn add_before(&mut self, function: fn(&Scenario) -> ()) -> &mut Self {
-        self.before.push(function);
-        self
-    }
+    pub fn add_before(&mut self, function: fn(&Scenario) -> ()) -> &mut Self {
+        self.before.push(function);
+        self
+    }
+
+    pub fn after(&mut self, functions: Vec<fn(&Scenario) -> ()>) -> &mut Self {
+        self.after = functions;
+        self
+    }
+
+    pub fn add_after(&mut self, function: fn(&Scenario) -> ()) -> &mut Self {
+        self.after.push(function);
+        self
+    }
+
+    /// Sets the hooks wrapping each scenario, the first one outermost. Each
+    /// is given the rest of the scenario, which it runs with `Proceed::proceed`.
+    pub fn around(&mut self, functions: Vec<fn(&Scenario, &mut Proceed<'_>)>) -> &mut Self {
+        self.around = functions;
+        self
+    }
+
+    pub fn add_around(&mut self, function: fn(&Scenario, &mut Proceed<'_>)) -> &mut Self {
+        self.around.push(function);
+        self
+    }
+
+    pub fn steps(&mut self, steps: Steps<W>) -> &mut Self {
+        self.steps = steps;
+        self
+    }
+
+    /// Creates the worlds of the scenarios matching the tag expression `tags`
+    /// with `factory` instead of `Default::default`, e.g. backed by a real
+    /// database for `@postgres` scenarios and by memory for the others. The
+    /// first factory added which matches a scenario is used.
+    pub fn world_factory(&mut self, tags: &str, factory: fn() -> W) -> &mut Self {
+        let tags = tags
+            .parse()
+            .unwrap_or_else(|e| panic!("`{}` is not a valid tag expression: {}", tags, e));
+        self.world_factories.push((tags, factory));
+        self
+    }
+
+    /// Attaches a file-writing formatter given as `kind:path`, e.g. `junit:target/junit.xml`.
+    pub fn formatter(&mut self, spec: &str) -> &mut Self {
+        let spec = spec
+            .parse()
+            .unwrap_or_else(|e| panic!("`{}` is not a valid formatter: {}", spec, e));
+        self.formatters.push(spec);
+        self
+    }
+
+    /// Serves a live HTML report on `address` (e.g. `127.0.0.1:7878`) while running.
+    pub fn serve(&mut self, address: &str) -> &mut Self {
+        self.serve = Some(address.to_string());
+        self
+    }
+
+    /// Limits how many bytes of captured output per step are kept in file reports.
+    pub fn capture_limit(&mut self, bytes: usize) -> &mut Self {
+        self.capture_limit = Some(bytes);
+        self
+    }
+
+    /// Records a detail about the environment of the run, such as the git
+    /// revision or the CI job URL. It is shown in the terminal header and
+    /// included in reports. Values given with `--metadata` take precedence.
+    pub fn metadata(&mut self, key: &str, value: &str) -> &mut Self {
+        self.metadata.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Runs up to `jobs` scenarios of a feature at once, each with its own
+    /// world. Outputs still see the scenarios in file order. Step output is
+    /// not captured when running more than one job.
+    pub fn jobs(&mut self, jobs: usize) -> &mut Self {
+        assert!(jobs > 0, "at least one job is needed");
+        self.jobs = Some(jobs);
+        self
+    }
+
+    /// Runs up to `jobs` feature files at once. Combined with
+    /// [`jobs`](#method.jobs), up to `feature_jobs * jobs` scenarios may run
+    /// at the same time.
+    pub fn feature_jobs(&mut self, jobs: usize) -> &mut Self {
+        assert!(jobs > 0, "at least one job is needed");
+        self.feature_jobs = Some(jobs);
+        self
+    }
+
+    /// Runs feature files by path and scenarios by line, whatever order the
+    /// files were given in, so that reports of different runs are easy to
+    /// compare. Takes precedence over a random order.
+    pub fn sorted(&mut self) -> &mut Self {
+        self.sorted = true;
+        self
+    }
+
+    /// Records the CPU time of each scenario in reports, and with
+    /// [`isolate`](#method.isolate) its peak memory use too. Also enabled by
+    /// `--resource-usage`.
+    pub fn resource_usage(&mut self) -> &mut Self {
+        self.resource_usage = true;
+        self
+    }
+
+    /// Lists after the run the step definitions which no step of the
+    /// scenarios run matched, with their source file, to find step code
+    /// which can be deleted. Also enabled by `--unused-steps`.
+    pub fn unused_steps(&mut self) -> &mut Self {
+        self.unused_steps = true;
+        self
+    }
+
+    /// Only runs the scenarios tagged `@wip`, of those `--tag` selects,
+    /// expecting them to fail: the run fails if any of them passes, as it
+    /// should then lose its tag. Also enabled by `--wip`.
+    pub fn wip(&mut self) -> &mut Self {
+        self.wip = true;
+        self
+    }
+
+    /// Runs the scenarios which failed in the previous run, and their feature
+    /// files, before the others, for quick feedback on a fix. Failures are
+    /// recorded in `target/cucumber/last-failed.txt` after every run.
+    pub fn failed_first(&mut self) -> &mut Self {
+        self.failed_first = true;
+        self
+    }
+
+    /// Only runs the scenarios whose feature file, or the definition of one of
+    /// whose steps, changed since the git revision `rev`, e.g. `HEAD` for a
+    /// pre-commit hook. Step definitions are known to come from the files the
+    /// `steps!` macro was used in, or those given to `StepsBuilder::file`.
+    pub fn changed_since(&mut self, rev: &str) -> &mut Self {
+        self.changed_since = Some(rev.to_string());
+        self
+    }
+
+    /// Runs features and scenarios in a random order, to reveal scenarios
+    /// which depend on state left behind by others. The seed is shown in the
+    /// run header so the order can be reproduced with [`seed`](#method.seed).
+    pub fn random_order(&mut self) -> &mut Self {
+        self.random_order = true;
+        self
+    }
+
+    /// Runs in the random order given by `seed`.
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.random_order = true;
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Only runs the scenarios whose name matches `regex`; the others are
+    /// reported as filtered out. `--name` takes precedence.
+    pub fn name(&mut self, regex: &str) -> &mut Self {
+        let regex = Regex::new(regex)
+            .unwrap_or_else(|e| panic!("`{}` is not a valid regex: {}", regex, e));
+        self.name = Some(regex);
+        self
+    }
+
+    /// Normalizes the text of steps before matching it against definitions:
+    /// runs of whitespace become single spaces, curly quotes straight ones,
+    /// and trailing punctuation is dropped, so that text pasted from
+    /// documents still matches. Definitions are expected to be written in
+    /// that form.
+    pub fn normalize_step_text(&mut self) -> &mut Self {
+        self.normalize_step_text = true;
+        self
+    }
+
+    /// Makes steps without an implementation fail the run, so they cannot
+    /// go unnoticed as skipped. Also enabled by `--strict`.
+    pub fn strict(&mut self) -> &mut Self {
+        self.strict = true;
+        self
+    }
+
+    /// Records a backtrace with failed steps, shown in the output and reports,
+    /// even when `RUST_BACKTRACE` is not set. Also enabled by `--backtrace`.
+    pub fn backtraces(&mut self) -> &mut Self {
+        self.backtraces = true;
+        self
+    }
+
+    /// Keeps calling the panic hook set before the run, e.g. by
+    /// `color_eyre::install`, for panics in steps as well. They are otherwise
+    /// only recorded by the runner, to be reported with the failed step.
+    pub fn chain_panic_hook(&mut self) -> &mut Self {
+        self.chain_panic_hook = true;
+        self
+    }
+
+    /// Runs each scenario in a child process of its own, so that an abort or
+    /// a crash in native code fails that scenario only. The test binary is
+    /// re-run for every scenario, `setup` included.
+    pub fn isolate(&mut self) -> &mut Self {
+        self.isolate = true;
+        self
+    }
+
+    /// Only runs the `index`th of `count` parts of the suite, `index` starting
+    /// at 1, to split it across CI machines. `--shard` takes precedence.
+    pub fn shard(&mut self, index: usize, count: usize) -> &mut Self {
+        assert!(
+            index >= 1 && index <= count,
+            "shard {}/{} does not exist",
+            index,
+            count
+        );
+        self.shard = Some(crate::cli::Shard { index, count });
+        self
+    }
+
+    /// Runs each scenario `times` times, each with a fresh world, to tell
+    /// flaky scenarios apart. Pass rates are shown at the end of the run.
+    pub fn repeat(&mut self, times: usize) -> &mut Self {
+        assert!(times > 0, "scenarios must run at least once");
+        self.repeat = Some(times);
+        self
+    }
+
+    /// Stops starting scenarios once `failures` of them failed, rather than
+    /// letting a broken environment fail the rest of the suite slowly.
+    pub fn max_failures(&mut self, failures: usize) -> &mut Self {
+        assert!(failures > 0, "at least one failure must be allowed");
+        self.max_failures = Some(failures);
+        self
+    }
+
+    /// Stops starting scenarios once the run has lasted `budget`, reporting
+    /// the remaining ones as not run, so that a CI job stays within its slot.
+    pub fn time_budget(&mut self, budget: Duration) -> &mut Self {
+        self.time_budget = Some(budget);
+        self
+    }
+
+    /// Compares how long scenarios took with the JSON report of a previous
+    /// run at `path`, and lists those which got slower after the summary.
+    /// Also available as `--baseline`.
+    pub fn baseline(&mut self, path: &str) -> &mut Self {
+        self.baseline = Some(PathBuf::from(path));
+        self
+    }
+
+    /// Sets how much slower than in the baseline, in percent, scenarios may
+    /// get before being listed; 20 by default.
+    pub fn regression_threshold(&mut self, percent: f64) -> &mut Self {
+        assert!(percent >= 0.0, "regression_threshold requires a positive percentage");
+        self.regression_threshold = Some(percent);
+        self
+    }
+
+    /// Changes the tag of the scenarios which must not run alongside any
+    /// other, e.g. because they use a shared external resource. Defaults to
+    /// `serial`; the tag also applies to all scenarios of a feature or rule.
+    pub fn serial_tag(&mut self, tag: &str) -> &mut Self {
+        self.serial_tag = Some(tag.trim_start_matches('@').to_string());
+        self
+    }
+
+    /// Reads scenarios whose failures do not fail the run from the file at
+    /// `path`, listing `path[:line]` targets as the rerun formatter writes them.
+    /// They still run, and their failures are reported separately.
+    pub fn quarantine_file(&mut self, path: &str) -> &mut Self {
+        self.quarantine_file = Some(path.to_string());
+        self
+    }
+
+    /// Changes the tag of the scenarios whose failures do not fail the run.
+    /// Defaults to `quarantine`; the tag also applies to all scenarios of a
+    /// feature or rule.
+    pub fn quarantine_tag(&mut self, tag: &str) -> &mut Self {
+        self.quarantine_tag = Some(tag.trim_start_matches('@').to_string());
+        self
+    }
+
+    /// Changes the tags of the scenarios which are reported as skipped without
+    /// running, `skip` and `ignore` by default. A reason can be given as the
+    /// argument of the tag, as in `@skip(flaky-on-ci)`.
+    pub fn skip_tags(&mut self, tags: &[&str]) -> &mut Self {
+        self.skip_tags = Some(
+            tags.iter()
+                .map(|tag| tag.trim_start_matches('@').to_string())
+                .collect(),
+        );
+        self
+    }
+
+    /// Fails the scenarios tagged `tag`, or in a feature or rule with that
+    /// tag, which run for longer than `timeout`. Only isolated scenarios are
+    /// interrupted: with [`isolate`](#method.isolate), the process of one past
+    /// its timeout is killed right away. Otherwise steps cannot be
+    /// interrupted, so the scenario fails once its current step is done, a
+    /// step which never returns hanging the run; a warning says so.
+    pub fn tag_timeout(&mut self, tag: &str, timeout: Duration) -> &mut Self {
+        self.tag_timeouts
+            .insert(tag.trim_start_matches('@').to_string(), timeout);
+        self
+    }
+
+    /// Runs at most `max` scenarios tagged `tag`, or in a feature or rule with
+    /// that tag, at once, e.g. to share a database between parallel jobs.
+    pub fn tag_max_concurrent(&mut self, tag: &str, max: usize) -> &mut Self {
+        assert!(max > 0, "tag_max_concurrent requires a limit of at least 1");
+        self.tag_limits
+            .insert(tag.trim_start_matches('@').to_string(), max);
+        self
+    }
+
+    /// Drives asynchronous steps and world setup with `executor`, e.g. one
+    /// wrapping the async-std or smol runtime.
+    pub fn executor<E: Executor + 'static>(&mut self, executor: E) -> &mut Self {
+        self.executor = Some(Arc::new(executor));
+        self
+    }
+
+    /// Posts a JSON summary of the run to `url` once it finishes, e.g. a Slack
+    /// or Teams incoming webhook. File reports are mentioned by path.
+    #[cfg(feature = "webhook")]
+    pub fn webhook(&mut self, url: &str) -> &mut Self {
+        self.webhook = Some(url.to_string());
+        self
+    }
+
+    pub fn options(&mut self, options: crate::cli::CliOptions) -> &mut Self {
+        self.options = options;
+        self
+    }
+
+    /// Runs the features, returning the results rather than exiting, so that
+    /// callers can inspect them before deciding on an exit code.
+    pub fn run(mut self) -> RunSummary {
+        // A child process runs the single scenario it was started for, and
+        // reports it back to its parent only.
+        let child_report = match isolation::child() {
+            Some((target, report)) => {
+                self.options = crate::cli::CliOptions {
+                    targets: Some(vec![target]),
+                    suppress_output: true,
+                    // The parent decides whether to keep it.
+                    resource_usage: true,
+                    ..Default::default()
+                };
+                self.formatters.clear();
+                self.serve = None;
+                self.jobs = None;
+                self.feature_jobs = None;
+                self.random_order = false;
+                self.seed = None;
+                self.name = None;
+                self.isolate = false;
+                self.shard = None;
+                self.repeat = None;
+                self.max_failures = None;
+                self.time_budget = None;
+                self.baseline = None;
+                self.changed_since = None;
+                self.pause_on_failure = false;
+                self.include.clear();
+                self.exclude.clear();
+                #[cfg(feature = "webhook")]
+                {
+                    self.webhook = None;
+                }
+                Some(report)
+            }
+            None => None,
+        };
+
+        if let Some(path) = &self.options.export_steps {
+            if let Err(e) = export_definitions(&self.steps.definitions(), path) {
+                eprintln!("Could not write the step definitions to {}: {}", path.display(), e);
+                process::exit(1);
+            }
+        }
+        if self.options.list_steps || self.options.export_steps.is_some() {
+            if self.options.list_steps {
+                print_definitions(&self.steps.definitions());
+            }
+            return RunSummary {
+                success: true,
+                timed_out: false,
+                seed: None,
+                duration: Duration::from_secs(0),
+                report: report::Report::default(),
+                regressions: vec![],
+                unused_steps: vec![],
+            };
+        }
+
+        if self.options.include.is_empty() {
+            self.options.include = self.include.clone();
+        }
+        self.options.exclude.extend(self.exclude.iter().cloned());
+
+        if !self.options.include.is_empty() {
+            let (dirs, files): (Vec<_>, Vec<_>) = self
+                .options
+                .include
+                .iter()
+                .flat_map(|pattern| glob(pattern).expect("feature glob is invalid"))
+                .filter_map(Result::ok)
+                .map(|entry| entry.path().to_owned())
+                .partition(|path| path.is_dir());
+            self.features(dirs);
+            for path in files {
+                let path = path.canonicalize().unwrap_or(path);
+                if !self.features.contains(&path) {
+                    self.features.push(path);
+                }
+            }
+            self.features.sort();
+        }
+
+        for path in self.sources.keys() {
+            if !self.features.contains(path) {
+                self.features.push(path.clone());
+            }
+        }
+
+        if !self.options.exclude.is_empty() {
+            let mut excluded = GlobSetBuilder::new();
+            for pattern in &self.options.exclude {
+                excluded.add(Glob::new(pattern).expect("exclude glob is invalid"));
+            }
+            let excluded = excluded.build().expect("exclude glob is invalid");
+            let cwd = std::env::current_dir()
+                .and_then(|cwd| cwd.canonicalize())
+                .unwrap_or_default();
+            self.features.retain(|path| {
+                !excluded.is_match(path.strip_prefix(&cwd).unwrap_or(path))
+                    && !excluded.is_match(path)
+            });
+        }
+
+        let stdin = Path::new(STDIN_PATH);
+        if self.options.targets.iter().flatten().any(|t| t.path == stdin) {
+            let mut source = String::new();
+            std::io::stdin()
+                .read_to_string(&mut source)
+                .expect("could not read the feature from stdin");
+            self.sources.insert(stdin.to_path_buf(), source);
+        }
+
+        if let Some(targets) = self.options.targets.as_mut() {
+            // Paths are compared with the canonical ones of the features run.
+            for target in targets.iter_mut() {
+                if let Ok(path) = target.path.canonicalize() {
+                    target.path = path;
+                }
+            }
+            let (dirs, files): (Vec<_>, Vec<_>) = targets
+                .iter()
+                .map(|target| target.path.clone())
+                .partition(|path| path.is_dir());
+            self.features(dirs);
+            for path in files {
+                if !self.features.contains(&path) {
+                    self.features.push(path);
+                }
+            }
+        }
+
+        if let Some(setup) = self.setup {
+            setup();
+        }
+        for register in self.fixtures.iter() {
+            register();
+        }
+        for init in self.shared.iter() {
+            init();
+        }
+
+        if self.options.jobs.is_none() {
+            self.options.jobs = self.jobs;
+        }
+        if self.options.feature_jobs.is_none() {
+            self.options.feature_jobs = self.feature_jobs;
+        }
+        self.options.strict |= self.strict;
+        self.options.wip |= self.wip;
+        self.options.resource_usage |= self.resource_usage;
+        self.options.unused_steps |= self.unused_steps;
+        if self.options.wip {
+            // Work in progress is expected to fail, undefined steps included.
+            self.options.strict = true;
+        }
+        self.options.isolate |= self.isolate;
+        self.options.backtraces |= self.backtraces;
+        self.options.pause_on_failure |= self.pause_on_failure;
+        self.steps.world_debug = self.world_debug;
+        self.steps.around = std::mem::take(&mut self.around);
+        self.steps.world_factories = std::mem::take(&mut self.world_factories);
+        self.steps.before_feature = std::mem::take(&mut self.before_feature);
+        self.steps.after_feature = std::mem::take(&mut self.after_feature);
+        self.steps.feature_world = self.feature_world.take();
+        self.steps.normalize = self.normalize_step_text;
+        panic_trap::capture_backtraces(self.options.backtraces);
+        panic_trap::chain_hook(self.chain_panic_hook);
+        if self.options.filter.is_none() {
+            self.options.filter = self.name.take();
+        }
+        if let Some(executor) = self.executor.take() {
+            self.steps.set_executor(executor);
+        }
+
+        self.options.sorted |= self.sorted;
+        if self.options.sorted {
+            self.options.seed = None;
+        } else if self.options.seed.is_none() && (self.random_order || self.options.random_order) {
+            self.options.seed = Some(self.seed.unwrap_or_else(shuffle::random_seed));
+        }
+        if let Some(seed) = self.options.seed {
+            self.options
+                .metadata
+                .insert("seed".to_string(), seed.to_string());
+        }
+        if self.options.shard.is_none() {
+            self.options.shard = self.shard;
+        }
+        if self.options.repeat.is_none() {
+            self.options.repeat = self.repeat;
+        }
+        if self.options.max_failures.is_none() {
+            self.options.max_failures = self.max_failures;
+        }
+        if self.options.time_budget.is_none() {
+            self.options.time_budget = self.time_budget;
+        }
+        if self.options.baseline.is_none() {
+            self.options.baseline = self.baseline.take();
+        }
+        if self.options.regression_threshold.is_none() {
+            self.options.regression_threshold = self.regression_threshold;
+        }
+        if self.options.serial_tag.is_none() {
+            self.options.serial_tag = self.serial_tag.take();
+        }
+        if self.options.skip_tags.is_none() {
+            self.options.skip_tags = self.skip_tags.take();
+        }
+        for (tag, timeout) in self.tag_timeouts.iter() {
+            self.options
+                .tag_timeouts
+                .entry(tag.clone())
+                .or_insert(*timeout);
+        }
+        if !self.options.tag_timeouts.is_empty() && !self.options.isolate && !isolation::is_child()
+        {
+            eprintln!(
+                "Warning: only isolated scenarios are interrupted at their tag timeout; \
+                 without --isolate, they fail once their current step is done"
+            );
+        }
+        for (tag, max) in self.tag_limits.iter() {
+            self.options.tag_limits.entry(tag.clone()).or_insert(*max);
+        }
+        self.steps.slots = self
+            .options
+            .tag_limits
+            .iter()
+            .map(|(tag, &max)| (tag.clone(), Slots::new(max)))
+            .collect();
+        if self.options.quarantine_tag.is_none() {
+            self.options.quarantine_tag = self.quarantine_tag.take();
+        }
+        if self.options.quarantine.is_empty() {
+            if let Some(path) = self.quarantine_file.as_ref() {
+                self.options.quarantine =
+                    crate::cli::read_targets(path).expect("could not read the quarantine file");
+            }
+        }
+        for target in self.options.quarantine.iter_mut() {
+            if let Ok(path) = target.path.canonicalize() {
+                target.path = path;
+            }
+        }
+        if let Some(shard) = self.options.shard {
+            self.options
+                .metadata
+                .insert("shard".to_string(), shard.to_string());
+        }
+
+        for (key, value) in self.metadata.iter() {
+            self.options
+                .metadata
+                .entry(key.clone())
+                .or_insert_with(|| value.clone());
+        }
+
+        if self.options.changed_since.is_none() {
+            self.options.changed_since = self.changed_since.take();
+        }
+        if let Some(rev) = self.options.changed_since.as_ref() {
+            match git::changed_files(rev) {
+                Ok(files) => self.options.changed_files = Some(files),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    eprintln!("Could not list the files changed since {}; aborting.", rev);
+                    process::exit(1);
+                }
+            }
+        }
+
+        let durations_path = state_path("durations.json");
+        if self.options.jobs.unwrap_or(1) > 1 || self.options.feature_jobs.unwrap_or(1) > 1 {
+            self.steps.durations = Durations::load(&durations_path);
+        }
+
+        self.options.failed_first |= self.failed_first;
+        let last_failed = state_path("last-failed.txt");
+        if self.options.failed_first {
+            // A missing file means nothing failed, or nothing ran yet.
+            let mut failures =
+                crate::cli::read_targets(&last_failed.to_string_lossy()).unwrap_or_default();
+            for target in failures.iter_mut() {
+                if let Ok(path) = target.path.canonicalize() {
+                    target.path = path;
+                }
+            }
+            self.options.previous_failures = failures;
+        }
+
+        let (mut reports, mut messages) = crate::output::file_outputs(
+            self.formatters
+                .iter()
+                .chain(self.options.formatters.iter()),
+        );
+        messages.set_strict(self.options.strict);
+        messages.set_sources(self.sources.clone());
+        messages.set_step_definitions(self.steps.step_definitions(), self.steps.normalize);
+
+        if let Some(limit) = self.capture_limit {
+            reports.set_capture_limit(limit);
+        }
+
+        if let Some(path) = child_report.as_ref() {
+            let file = File::create(path)
+                .unwrap_or_else(|e| panic!("Could not open {} for writing: {}", path.display(), e));
+            reports.writer_mut().push(Box::new(JsonWriter::new(file)));
+            let progress = isolation::progress_path(path);
+            let file = File::create(&progress).unwrap_or_else(|e| {
+                panic!("Could not open {} for writing: {}", progress.display(), e)
+            });
+            reports
+                .writer_mut()
+                .push(Box::new(isolation::ProgressWriter::new(file)));
+            // The parent applies its own limit.
+            reports.set_capture_limit(usize::MAX);
+        }
+
+        if child_report.is_none() {
+            let file = last_failed
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| File::create(&last_failed));
+            match file {
+                Ok(file) => reports.writer_mut().push(Box::new(RerunWriter::new(file))),
+                Err(e) => eprintln!("Could not record failures in {}: {}", last_failed.display(), e),
+            }
+            reports
+                .writer_mut()
+                .push(Box::new(DurationsWriter::new(durations_path.clone())));
+        }
+
+        if let Some(address) = self.options.serve.as_ref().or(self.serve.as_ref()) {
+            let live = LiveHtmlWriter::bind(address.as_str())
+                .unwrap_or_else(|e| panic!("Could not serve live report on {}: {}", address, e));
+            reports.writer_mut().push(Box::new(live));
+        }
+
+        #[cfg(feature = "webhook")]
+        {
+            if let Some(url) = self.options.webhook.as_ref().or(self.webhook.as_ref()) {
+                let mut webhook = WebhookWriter::new(url);
+                for spec in self.formatters.iter().chain(self.options.formatters.iter()) {
+                    if let Destination::File(ref path) = spec.destination {
+                        webhook.add_link(&path.display().to_string());
+                    }
+                }
+                if let Some(address) = self.options.serve.as_ref().or(self.serve.as_ref()) {
+                    webhook.add_link(&format!("http://{}/", address));
+                }
+                reports.writer_mut().push(Box::new(webhook));
+            }
+        }
+
+        // Shared with the watchdog of the time budget, which finishes them
+        // itself if the run gets stuck.
+        let files = watchdog::Outputs::with((reports, messages));
+        let mut output = (self.output, files.clone());
+        let seed = self.options.seed;
+
+        // Suite hooks and services belong to the parent of isolated scenarios
+        // only.
+        if child_report.is_none() {
+            self.steps.services = services::Services::start(std::mem::take(&mut self.services));
+        }
+        let _after_run = if child_report.is_none() && !self.steps.services.failed() {
+            let after_run = AfterRun(self.after_run);
+            for f in self.before_run.iter() {
+                f();
+            }
+            Some(after_run)
+        } else {
+            None
+        };
+
+        let started = Instant::now();
+
+        // A missing baseline, as on a first run, only disables the comparison.
+        let baseline = self.options.baseline.clone().and_then(|path| {
+            match Baseline::load(&path) {
+                Ok(baseline) => Some((path, baseline)),
+                Err(e) => {
+                    eprintln!("Could not read the baseline {}: {}", path.display(), e);
+                    None
+                }
+            }
+        });
+        let threshold = self
+            .options
+            .regression_threshold
+            .unwrap_or(baseline::DEFAULT_THRESHOLD);
+
+        self.steps.deadline = self.options.time_budget.map(|budget| started + budget);
+        self.steps.sources = self.sources;
+        let unused_steps = self.options.unused_steps;
+        let after_run_hooks = _after_run.as_ref().map(|after_run| after_run.0.clone());
+        let (steps, options, features) = (&self.steps, &self.options, &self.features);
+        let (before, after) = (&self.before, &self.after);
+        let success = crossbeam_utils::thread::scope(|scope| {
+            let (done, finished) = mpsc::channel::<()>();
+            // Scenarios still running past the budget are given some time to
+            // finish; a hung one would otherwise keep the run going forever.
+            if let Some(budget) = options.time_budget {
+                let files = files.clone();
+                scope.spawn(move |_| {
+                    if let Err(RecvTimeoutError::Timeout) =
+                        finished.recv_timeout(budget + TIME_BUDGET_GRACE)
+                    {
+                        let not_run = steps.finish_stuck_run(features, options, &files);
+                        eprintln!(
+                            "Time budget exceeded while scenarios were still running, {} scenarios not run; exiting",
+                            not_run
+                        );
+                        for f in after_run_hooks.iter().flatten() {
+                            f();
+                        }
+                        steps.services.stop();
+                        process::exit(report::TIMED_OUT_EXIT_CODE);
+                    }
+                });
+            }
+            let success = steps.run_with(features.clone(), before, after, options, &mut output);
+            drop(done);
+            success
+        })
+        .expect("The time budget watchdog panicked");
+
+        let report = files.lock().0 .0.report().clone();
+        let regressions = match baseline {
+            Some((path, baseline)) => {
+                let regressions = baseline.regressions(&report, threshold);
+                if !regressions.is_empty() {
+                    baseline::print_regressions(&path, &regressions, threshold);
+                }
+                regressions
+            }
+            None => vec![],
+        };
+        let unused_steps = if unused_steps {
+            let unused = self.steps.unused_definitions();
+            print_unused(&unused);
+            unused
+        } else {
+            vec![]
+        };
+
+        RunSummary {
+            success,
+            timed_out: self.steps.timed_out(),
+            seed,
+            duration: started.elapsed(),
+            report,
+            regressions,
+            unused_steps,
+        }
+    }
+
+    pub fn command_line(mut self) -> RunSummary {
+        let options = make_app().unwrap();
+        self.options(options);
+        self.run()
+    }
+}
+
+impl<W: World + std::fmt::Debug, O: OutputVisitor> CucumberBuilder<W, O> {
+    /// When running serially, pauses after the first failed step of each
+    /// scenario with a prompt to print the world, re-run the step, continue
+    /// or abort the run. Meant for local debugging, as with `--pause-on-failure`,
+    /// which cannot print the world.
+    pub fn pause_on_failure(&mut self) -> &mut Self {
+        self.pause_on_failure = true;
+        self.world_debug = Some(debug_world::<W>);
+        self
+    }
+}
+
+/// Prints step definitions for `--list-steps`, one per line with their
+/// source file, followed by their description.
+fn print_definitions(definitions: &[StepDefinitionInfo]) {
+    for definition in definitions {
+        let mut line = format!("{:?} {}", definition.ty, definition.pattern);
+        if definition.is_async {
+            line.push_str(" (async)");
+        }
+        if let Some(original) = &definition.alias_of {
+            line.push_str(&format!(" (alias of {})", original));
+        }
+        if let Some(file) = definition.file {
+            line.push_str(&format!("  # {}", file));
+        }
+        println!("{}", line);
+        if let Some(doc) = &definition.doc {
+            for doc_line in doc.lines() {
+                println!("    {}", doc_line);
+            }
+        }
+    }
+}
+
+/// Prints the step definitions no step matched for `--unused-steps`.
+fn print_unused(definitions: &[StepDefinitionInfo]) {
+    if definitions.is_empty() {
+        println!("Every step definition matched a step.");
+        println!();
+        return;
+    }
+    println!("{} step definitions matched no step:", definitions.len());
+    for definition in definitions {
+        match definition.file {
+            Some(file) => println!("  {:?} {}  # {}", definition.ty, definition.pattern, file),
+            None => println!("  {:?} {}", definition.ty, definition.pattern),
+        }
+        if let Some(doc) = &definition.doc {
+            for doc_line in doc.lines() {
+                println!("      {}", doc_line);
+            }
+        }
+    }
+    println!();
+}
+
+/// Writes step definitions to `path` for `--export-steps`, as a JSON array
+/// of objects, for editors to complete and document steps.
+fn export_definitions(definitions: &[StepDefinitionInfo], path: &Path) -> std::io::Result<()> {
+    let definitions = definitions
+        .iter()
+        .map(|definition| {
+            let kind = match definition.kind {
+                PatternKind::Text => "text",
+                PatternKind::Regex => "regex",
+                PatternKind::Expression => "expression",
+            };
+            serde_json::json!({
+                "keyword": format!("{:?}", definition.ty),
+                "pattern": definition.pattern,
+                "kind": kind,
+                "async": definition.is_async,
+                "file": definition.file,
+                "doc": definition.doc,
+                "alias_of": definition.alias_of,
+            })
+        })
+        .collect::<Vec<_>>();
+    let json = serde_json::to_string_pretty(&definitions)?;
+    fs::write(path, json + "\n")
+}
+
+fn debug_world<W: std::fmt::Debug>(world: &W) -> String {
+    format!("{:#?}", world)
+}
 
-    pub fn after(&mut self, functions: Vec<fn(&Scenario) -> ()>) -> &mut Self {
-        self.after = functions;
+impl<W: World> CucumberBuilder<W, DefaultOutput> {
+    /// Shows scenario counts per tag after the summary.
+    pub fn tag_summary(&mut self, show: bool) -> &mut Self {
+        self.output.show_tag_summary(show);
         self
     }
 
-    pub fn add_after(&mut self, function: fn(&Scenario) -> ()) -> &mut Self {
-        self.after.push(function);
+    /// Shows a per-feature timing breakdown after the summary.
+    pub fn feature_timings(&mut self, show: bool) -> &mut Self {
+        self.output.show_feature_timings(show);
         self
     }
 
-    pub fn steps(&mut self, steps: Steps<W>) -> &mut Self {
-        self.steps = steps;
+    pub fn theme(&mut self, theme: Theme) -> &mut Self {
+        self.output.set_theme(theme);
         self
     }
 
-    pub fn options(&mut self, options: crate::cli::CliOptions) -> &mut Self {
-        self.options = options;
+    /// Prints the human-readable output to stderr, so that a formatter can
+    /// write to stdout (e.g. `message:-`) without the two being interleaved.
+    pub fn pretty_to_stderr(&mut self) -> &mut Self {
+        self.output.use_stderr();
         self
     }
 
-    pub fn run(mut self) -> bool {
-        if let Some(feature) = self.options.feature.as_ref() {
-            let features = glob(feature)
-                .expect("feature glob is invalid")
-                .filter_map(Result::ok)
-                .map(|entry| entry.path().to_owned())
-                .collect::<Vec<_>>();
-            self.features(features);
-        }
-
-        if let Some(setup) = self.setup {
-            setup();
-        }
-
-        self.steps.run(
-            self.features,
-            &self.before,
-            &self.after,
-            self.options,
-            &mut self.output,
-        )
-    }
-
-    pub fn command_line(mut self) -> bool {
-        let options = make_app().unwrap();
-        self.options(options);
-        self.run()
+    /// Lists the `count` slowest executed steps after the summary.
+    pub fn slowest_steps(&mut self, count: usize) -> &mut Self {
+        self.output.show_slowest_steps(count);
+        self
     }
 }
 
@@ -777,6 +4272,20 @@ macro_rules! cucumber {
         cucumber!(@finish; $featurepath; $worldtype; $vec; None; None; None);
     };
 
+    (
+        features: $featurepath:tt,
+        world: $worldtype:path
+        $(, $field:ident: $value:expr)* $(,)*
+    ) => {
+        // Without a list, the steps defined with the attributes are collected.
+        cucumber!(
+            features: $featurepath,
+            world: $worldtype,
+            steps: &[$crate::Steps::<$worldtype>::collected]
+            $(, $field: $value)*
+        );
+    };
+
     (
         @finish; $featurepath:tt; $worldtype:path; $vec:expr; $setupfn:expr; $beforefns:expr; $afterfns:expr
     ) => {
@@ -810,10 +4319,10 @@ macro_rules! cucumber {
                 instance
             };
 
-            let res = instance.command_line();
+            let summary = instance.command_line();
 
-            if !res {
-                std::process::exit(1);
+            if !summary.success {
+                std::process::exit(summary.exit_code());
             }
         }
     }
@@ -825,17 +4334,66 @@ macro_rules! typed_regex {
         $worldtype:path, ($($arg_type:ty),*) $body:expr
     ) => {
         |world: &mut $worldtype, matches, step| {
-            let body: fn(&mut $worldtype, $($arg_type,)* &$crate::Step) -> () = $body;
-            let mut matches = matches.into_iter().enumerate().skip(1);
+            let body: fn(&mut $worldtype, $($arg_type,)* &$crate::Step) -> _ = $body;
+            let mut index = 0;
 
-            body(
+            $crate::step_result(body(
                 world,
                 $({
-                    let (index, match_) = matches.next().unwrap();
-                    match_.parse::<$arg_type>().unwrap_or_else(|_| panic!("Failed to parse argument {} with value '{}' to type {}", index, match_, stringify!($arg_type)))
+                    index += 1;
+                    $crate::parse_capture::<$arg_type>(matches, index, stringify!($arg_type))
                 },)*
                 step
-            )
+            ))
+        }
+    };
+}
+
+/// Makes a `TypedStep` out of a handler taking the world and the values
+/// captured by the pattern of the step, parsed to the given types:
+/// `typed_step!(MyWorld, (u32, String) eat)` for `fn eat(&mut MyWorld, u32, String)`.
+#[macro_export]
+macro_rules! typed_step {
+    (@count) => { 0usize };
+
+    (@count $head:ty $(, $tail:ty)*) => {
+        1usize + $crate::typed_step!(@count $($tail),*)
+    };
+
+    (
+        $worldtype:path, ($($arg_type:ty),*) $body:expr
+    ) => {
+        $crate::TypedStep::<$worldtype>::new(
+            $crate::typed_step!(@count $($arg_type),*),
+            |world: &mut $worldtype, matches, _step| {
+                let body: fn(&mut $worldtype, $($arg_type),*) -> _ = $body;
+                let mut index = 0;
+
+                $crate::step_result(body(
+                    world,
+                    $({
+                        index += 1;
+                        $crate::parse_capture::<$arg_type>(matches, index, stringify!($arg_type))
+                    }),*
+                ))
+            },
+        )
+    };
+}
+
+/// Declares a `steps` function, as `steps!` does, out of the functions
+/// defined with the `#[given]`, `#[when]` and `#[then]` attributes:
+/// `step_definitions!(crate::MyWorld => [trying_out, other_steps::consider])`.
+#[macro_export]
+macro_rules! step_definitions {
+    (
+        $worldtype:path => [ $( $definition:path ),* $(,)* ]
+    ) => {
+        #[allow(missing_docs)]
+        pub fn steps() -> $crate::Steps<$worldtype> {
+            let mut tests: $crate::StepsBuilder::<$worldtype> = $crate::StepsBuilder::new();
+            $( tests.definition::<$definition>(); )*
+            tests.build()
         }
     };
 }
@@ -847,6 +4405,92 @@ macro_rules! skip {
     };
 }
 
+/// Marks the running step, and so the rest of its scenario, as pending:
+/// `pending!("waiting for the payments API")`. Pending steps are reported
+/// apart from failed and unimplemented ones, and only fail the run with
+/// `--strict`.
+#[macro_export]
+macro_rules! pending {
+    () => {
+        $crate::pending!("")
+    };
+    ($reason:expr) => {
+        $crate::mark_pending($reason)
+    };
+}
+
+/// What the panic of `pending!` starts with.
+const PENDING: &str = "cucumber step pending: ";
+
+/// Panics as `pending!` does. Typed as returning, so that a step made only
+/// of `pending!` returns `()` rather than `!`.
+#[doc(hidden)]
+pub fn mark_pending<R: std::fmt::Display>(reason: R) {
+    panic!("{}{}", PENDING, reason)
+}
+
+/// Checks a condition as `assert!` does, but lets the step go on when it
+/// fails: the step fails once it is over, reporting every failed soft
+/// assertion at once, e.g. to check all the fields of a response:
+/// `soft_assert!(response.ok, "the request failed: {}", response.error)`.
+#[macro_export]
+macro_rules! soft_assert {
+    ($cond:expr) => {
+        $crate::soft_assert!($cond, "assertion failed: {}", stringify!($cond))
+    };
+    ($cond:expr, $($arg:tt)+) => {
+        if !$cond {
+            $crate::soft_failure(
+                format!($($arg)+),
+                concat!(file!(), ":", line!(), ":", column!()),
+            );
+        }
+    };
+}
+
+/// Checks that two values are equal as `assert_eq!` does, but as a soft
+/// assertion, see `soft_assert!`.
+#[macro_export]
+macro_rules! soft_assert_eq {
+    ($left:expr, $right:expr) => {
+        $crate::soft_assert_eq!($left, $right, "")
+    };
+    ($left:expr, $right:expr, $($arg:tt)+) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                if !(*left == *right) {
+                    let reason = format!($($arg)+);
+                    $crate::soft_failure(
+                        format!(
+                            "assertion failed: `(left == right)`\n  left: `{:?}`,\n right: `{:?}`{}{}",
+                            left,
+                            right,
+                            if reason.is_empty() { "" } else { ": " },
+                            reason
+                        ),
+                        concat!(file!(), ":", line!(), ":", column!()),
+                    );
+                }
+            }
+        }
+    };
+}
+
+/// A line of a doc comment, without the space after `///`.
+#[doc(hidden)]
+pub fn doc_line(line: &str) -> &str {
+    match line.chars().next() {
+        Some(' ') => &line[1..],
+        _ => line,
+    }
+}
+
+/// Records a failed soft assertion. Outside of a step, panics right away.
+#[doc(hidden)]
+pub fn soft_failure(message: String, location: &str) {
+    panic_trap::record_soft_failure(message, location)
+}
+
 #[macro_export]
 macro_rules! steps {
     (
@@ -867,24 +4511,237 @@ macro_rules! steps {
         $crate::StepType::Then
     };
 
+    (
+        @result $worldtype:path, () $body:expr
+    ) => {
+        |world, step| {
+            let body: fn(&mut $worldtype, &$crate::Step) -> _ = $body;
+            $crate::step_result(body(world, step))
+        }
+    };
+
+    (
+        @result $worldtype:path, (&[String]) $body:expr
+    ) => {
+        |world, matches, step| {
+            let body: fn(&mut $worldtype, &[String], &$crate::Step) -> _ = $body;
+            $crate::step_result(body(world, matches, step))
+        }
+    };
+
+    (
+        @named $worldtype:path, $name:tt $body:expr
+    ) => {
+        |world, matches, step| {
+            let body: fn(&mut $worldtype, &$crate::Captures, &$crate::Step) -> _ = $body;
+            $crate::step_result(body(world, &$crate::Captures::new($name, matches), step))
+        }
+    };
+
     (
         @parse_matches $worldtype:path, ($($arg_type:ty),*) $body:expr
     ) => {
-        $crate::typed_regex!($worldtype, ($($arg_type),*) $body)
+        $crate::TypedStep::<$worldtype>::new(
+            $crate::typed_step!(@count $($arg_type),*),
+            $crate::typed_regex!($worldtype, ($($arg_type),*) $body),
+        )
+    };
+
+    (
+        @gather_steps, $worldtype:path, $tests:tt,
+    ) => {};
+
+    (
+        @requirement table
+    ) => {
+        $crate::Requirement::Table
+    };
+
+    (
+        @requirement docstring
+    ) => {
+        $crate::Requirement::Docstring
+    };
+
+    (
+        @gather_steps, $worldtype:path, $tests:tt,
+        #[doc = $doc:expr] $( $items:tt )*
+    ) => {
+        $tests.doc($crate::doc_line($doc));
+
+        steps!(@gather_steps, $worldtype, $tests, $( $items )*);
+    };
+
+    (
+        @gather_steps, $worldtype:path, $tests:tt,
+        requires $requirement:ident; $( $items:tt )*
+    ) => {
+        $tests.requires(steps!(@requirement $requirement));
+
+        steps!(@gather_steps, $worldtype, $tests, $( $items )*);
+    };
+
+    (
+        @gather_steps, $worldtype:path, $tests:tt,
+        regex_options $options:expr;
+    ) => {
+        $tests.regex_options($options);
+    };
+
+    (
+        @gather_steps, $worldtype:path, $tests:tt,
+        regex_options $options:expr; $( $items:tt )*
+    ) => {
+        $tests.regex_options($options);
+
+        steps!(@gather_steps, $worldtype, $tests, $( $items )*);
+    };
+
+    (
+        @gather_steps, $worldtype:path, $tests:tt,
+        $ty:ident alias $alias:tt => $name:tt;
+    ) => {
+        $tests.alias(steps!(@step_type $ty), $alias, $name);
+    };
+
+    (
+        @gather_steps, $worldtype:path, $tests:tt,
+        $ty:ident alias $alias:tt => $name:tt; $( $items:tt )*
+    ) => {
+        $tests.alias(steps!(@step_type $ty), $alias, $name);
+
+        steps!(@gather_steps, $worldtype, $tests, $( $items )*);
+    };
+
+    (
+        @gather_steps, $worldtype:path, $tests:tt,
+        $ty:ident async regex $name:tt |$world:pat, $matches:pat, $step:pat| $body:block;
+    ) => {
+        $tests.add_async_regex(steps!(@step_type $ty), $name, |world, matches, step| {
+            Box::pin(async move {
+                let $world = world;
+                let $matches = matches;
+                let $step = step;
+                $crate::step_result($body)
+            })
+        });
+    };
+
+    (
+        @gather_steps, $worldtype:path, $tests:tt,
+        $ty:ident async regex $name:tt |$world:pat, $matches:pat, $step:pat| $body:block; $( $items:tt )*
+    ) => {
+        $tests.add_async_regex(steps!(@step_type $ty), $name, |world, matches, step| {
+            Box::pin(async move {
+                let $world = world;
+                let $matches = matches;
+                let $step = step;
+                $crate::step_result($body)
+            })
+        });
+
+        steps!(@gather_steps, $worldtype, $tests, $( $items )*);
+    };
+
+    (
+        @gather_steps, $worldtype:path, $tests:tt,
+        $ty:ident async expression $name:tt |$world:pat, $matches:pat, $step:pat| $body:block;
+    ) => {
+        $tests.add_async_expression(steps!(@step_type $ty), $name, |world, matches, step| {
+            Box::pin(async move {
+                let $world = world;
+                let $matches = matches;
+                let $step = step;
+                $crate::step_result($body)
+            })
+        });
+    };
+
+    (
+        @gather_steps, $worldtype:path, $tests:tt,
+        $ty:ident async expression $name:tt |$world:pat, $matches:pat, $step:pat| $body:block; $( $items:tt )*
+    ) => {
+        $tests.add_async_expression(steps!(@step_type $ty), $name, |world, matches, step| {
+            Box::pin(async move {
+                let $world = world;
+                let $matches = matches;
+                let $step = step;
+                $crate::step_result($body)
+            })
+        });
+
+        steps!(@gather_steps, $worldtype, $tests, $( $items )*);
+    };
+
+    (
+        @gather_steps, $worldtype:path, $tests:tt,
+        $ty:ident async $name:tt |$world:pat, $step:pat| $body:block;
+    ) => {
+        $tests.add_async(steps!(@step_type $ty), $name, |world, step| {
+            Box::pin(async move {
+                let $world = world;
+                let $step = step;
+                $crate::step_result($body)
+            })
+        });
+    };
+
+    (
+        @gather_steps, $worldtype:path, $tests:tt,
+        $ty:ident async $name:tt |$world:pat, $step:pat| $body:block; $( $items:tt )*
+    ) => {
+        $tests.add_async(steps!(@step_type $ty), $name, |world, step| {
+            Box::pin(async move {
+                let $world = world;
+                let $step = step;
+                $crate::step_result($body)
+            })
+        });
+
+        steps!(@gather_steps, $worldtype, $tests, $( $items )*);
+    };
+
+    (
+        @gather_steps, $worldtype:path, $tests:tt,
+        $ty:ident async $name:tt $handler:path;
+    ) => {
+        $tests.add_async(steps!(@step_type $ty), $name, |world, step| {
+            Box::pin(async move { $crate::step_result($handler(world, step).await) })
+        });
+    };
+
+    (
+        @gather_steps, $worldtype:path, $tests:tt,
+        $ty:ident async $name:tt $handler:path; $( $items:tt )*
+    ) => {
+        $tests.add_async(steps!(@step_type $ty), $name, |world, step| {
+            Box::pin(async move { $crate::step_result($handler(world, step).await) })
+        });
+
+        steps!(@gather_steps, $worldtype, $tests, $( $items )*);
+    };
+
+    (
+        @gather_steps, $worldtype:path, $tests:tt,
+        $ty:ident regex $name:tt named $body:expr; $( $items:tt )*
+    ) => {
+        $tests.add_regex(steps!(@step_type $ty), $name, steps!(@named $worldtype, $name $body));
+
+        steps!(@gather_steps, $worldtype, $tests, $( $items )*);
     };
 
     (
         @gather_steps, $worldtype:path, $tests:tt,
         $ty:ident regex $name:tt $body:expr;
     ) => {
-        $tests.add_regex(steps!(@step_type $ty), $name, $body);
+        $tests.add_regex(steps!(@step_type $ty), $name, steps!(@result $worldtype, (&[String]) $body));
     };
 
     (
         @gather_steps, $worldtype:path, $tests:tt,
         $ty:ident regex $name:tt $body:expr; $( $items:tt )*
     ) => {
-        $tests.add_regex(steps!(@step_type $ty), $name, $body);
+        $tests.add_regex(steps!(@step_type $ty), $name, steps!(@result $worldtype, (&[String]) $body));
 
         steps!(@gather_steps, $worldtype, $tests, $( $items )*);
     };
@@ -893,28 +4750,62 @@ macro_rules! steps {
         @gather_steps, $worldtype:path, $tests:tt,
         $ty:ident regex $name:tt ($($arg_type:ty),*) $body:expr;
     ) => {
-        steps!(@gather_steps, $worldtype, $tests, $ty regex $name steps!(@parse_matches $worldtype, ($($arg_type),*) $body););
+        $tests.add_typed(steps!(@step_type $ty), $name, steps!(@parse_matches $worldtype, ($($arg_type),*) $body));
     };
 
     (
         @gather_steps, $worldtype:path, $tests:tt,
         $ty:ident regex $name:tt ($($arg_type:ty),*) $body:expr; $( $items:tt )*
     ) => {
-        steps!(@gather_steps, $worldtype, $tests, $ty regex $name steps!(@parse_matches $worldtype, ($($arg_type),*) $body); $( $items )*);
+        $tests.add_typed(steps!(@step_type $ty), $name, steps!(@parse_matches $worldtype, ($($arg_type),*) $body));
+
+        steps!(@gather_steps, $worldtype, $tests, $( $items )*);
+    };
+
+    (
+        @gather_steps, $worldtype:path, $tests:tt,
+        $ty:ident expression $name:tt $body:expr;
+    ) => {
+        $tests.add_expression(steps!(@step_type $ty), $name, steps!(@result $worldtype, (&[String]) $body));
+    };
+
+    (
+        @gather_steps, $worldtype:path, $tests:tt,
+        $ty:ident expression $name:tt $body:expr; $( $items:tt )*
+    ) => {
+        $tests.add_expression(steps!(@step_type $ty), $name, steps!(@result $worldtype, (&[String]) $body));
+
+        steps!(@gather_steps, $worldtype, $tests, $( $items )*);
+    };
+
+    (
+        @gather_steps, $worldtype:path, $tests:tt,
+        $ty:ident expression $name:tt ($($arg_type:ty),*) $body:expr;
+    ) => {
+        $tests.add_typed_expression(steps!(@step_type $ty), $name, steps!(@parse_matches $worldtype, ($($arg_type),*) $body));
+    };
+
+    (
+        @gather_steps, $worldtype:path, $tests:tt,
+        $ty:ident expression $name:tt ($($arg_type:ty),*) $body:expr; $( $items:tt )*
+    ) => {
+        $tests.add_typed_expression(steps!(@step_type $ty), $name, steps!(@parse_matches $worldtype, ($($arg_type),*) $body));
+
+        steps!(@gather_steps, $worldtype, $tests, $( $items )*);
     };
 
     (
         @gather_steps, $worldtype:path, $tests:tt,
         $ty:ident $name:tt $body:expr;
     ) => {
-        $tests.add_normal(steps!(@step_type $ty), $name, $body);
+        $tests.add_normal(steps!(@step_type $ty), $name, steps!(@result $worldtype, () $body));
     };
 
     (
         @gather_steps, $worldtype:path, $tests:tt,
         $ty:ident $name:tt $body:expr; $( $items:tt )*
     ) => {
-        $tests.add_normal(steps!(@step_type $ty), $name, $body);
+        $tests.add_normal(steps!(@step_type $ty), $name, steps!(@result $worldtype, () $body));
 
         steps!(@gather_steps, $worldtype, $tests, $( $items )*);
     };
@@ -925,8 +4816,21 @@ macro_rules! steps {
         #[allow(missing_docs)]
         pub fn steps() -> $crate::Steps<$worldtype> {
             let mut tests: $crate::StepsBuilder::<$worldtype> = $crate::StepsBuilder::new();
+            tests.file(file!());
             steps!(@gather_steps, $worldtype, tests, $( $items )*);
             tests.build()
         }
     };
+
+    (
+        $world:ident : $bound:path => { $( $items:tt )* }
+    ) => {
+        #[allow(missing_docs)]
+        pub fn steps<$world: $crate::World + $bound>() -> $crate::Steps<$world> {
+            let mut tests: $crate::StepsBuilder::<$world> = $crate::StepsBuilder::new();
+            tests.file(file!());
+            steps!(@gather_steps, $world, tests, $( $items )*);
+            tests.build()
+        }
+    };
 }
@@ -0,0 +1,74 @@
+use std::io::{self, stdout, Write};
+
+use crate::output::structured::{ReportOutput, ReportWriter};
+use crate::report::Report;
+
+/// Writes one CSV row per scenario: feature, scenario, tags, status,
+/// duration in seconds and the failing step, if any.
+pub struct CsvWriter {
+    writer: Box<dyn Write + Send>,
+}
+
+impl CsvWriter {
+    pub fn new<Wr: Write + Send + 'static>(writer: Wr) -> CsvWriter {
+        CsvWriter {
+            writer: Box::new(writer),
+        }
+    }
+}
+
+impl Default for CsvWriter {
+    fn default() -> CsvWriter {
+        CsvWriter::new(stdout())
+    }
+}
+
+impl ReportWriter for CsvWriter {
+    fn write_report(&mut self, report: &Report) -> io::Result<()> {
+        let w = &mut self.writer;
+        writeln!(w, "feature,scenario,tags,status,duration,failing_step")?;
+
+        for feature in &report.features {
+            for scenario in &feature.scenarios {
+                let failing_step = scenario
+                    .failed_step()
+                    .map(|step| {
+                        format!(
+                            "{} {} ({}:{})",
+                            step.keyword, step.text, feature.path, step.line
+                        )
+                    })
+                    .unwrap_or_default();
+
+                writeln!(
+                    w,
+                    "{},{},{},{},{:.3},{}",
+                    quote(&feature.name),
+                    quote(&scenario.name),
+                    quote(&scenario.tags.join(" ")),
+                    scenario.status.as_str(),
+                    scenario.duration().as_secs_f64(),
+                    quote(&failing_step),
+                )?;
+            }
+        }
+
+        w.flush()
+    }
+}
+
+fn quote(field: &str) -> String {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+pub type CsvOutput = ReportOutput<CsvWriter>;
+
+impl ReportOutput<CsvWriter> {
+    pub fn from_writer<Wr: Write + Send + 'static>(writer: Wr) -> CsvOutput {
+        ReportOutput::with_writer(CsvWriter::new(writer))
+    }
+}
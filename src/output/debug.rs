@@ -1,13 +1,32 @@
 use std;
+use std::collections::BTreeMap;
 use std::path::Path;
 
 use gherkin;
 
 use crate::OutputVisitor;
-use crate::TestResult;
+use crate::{Captured, TestResult, Timing};
 
 pub struct DebugOutput;
 
+fn position(position: (usize, usize)) -> String {
+    format!("{}:{}", position.0, position.1)
+}
+
+fn tags(tags: &Option<Vec<String>>) -> String {
+    match tags {
+        Some(tags) if !tags.is_empty() => format!(" tags={:?}", tags),
+        _ => String::new(),
+    }
+}
+
+fn print_table(table: &gherkin::Table) {
+    println!("  table @{} {:?}", position(table.position), table.header);
+    for row in &table.rows {
+        println!("        {:?}", row);
+    }
+}
+
 impl OutputVisitor for DebugOutput {
     fn new() -> Self
     where
@@ -16,12 +35,25 @@ impl OutputVisitor for DebugOutput {
         DebugOutput
     }
 
-    fn visit_start(&mut self) {
+    fn visit_start(&mut self, metadata: &BTreeMap<String, String>) {
         println!("visit_start");
+        for (key, value) in metadata {
+            println!("  metadata {}={}", key, value);
+        }
+    }
+
+    fn visit_services(&mut self, services: &[crate::report::Service]) {
+        println!("visit_services {:?}", services);
     }
 
     fn visit_feature(&mut self, feature: &gherkin::Feature, path: &Path) {
-        println!("visit_feature {} {}", feature.name, path.display());
+        println!(
+            "visit_feature {} {}:{}{}",
+            feature.name,
+            path.display(),
+            position(feature.position),
+            tags(&feature.tags)
+        );
     }
 
     fn visit_feature_end(&mut self, feature: &gherkin::Feature) {
@@ -32,16 +64,81 @@ impl OutputVisitor for DebugOutput {
         println!("visit_feature_error {} {}", path.display(), error);
     }
 
+    fn visit_feature_hook_failed(
+        &mut self,
+        feature: &gherkin::Feature,
+        hook: crate::report::Hook,
+        failure: &crate::report::Failure,
+    ) {
+        println!(
+            "visit_feature_hook_failed {} {:?} {:?}",
+            feature.name, hook, failure
+        );
+    }
+
+    fn visit_dependency_cycle(
+        &mut self,
+        feature: &gherkin::Feature,
+        rule: Option<&gherkin::Rule>,
+        scenarios: &[String],
+    ) {
+        println!(
+            "visit_dependency_cycle {} {:?} {:?}",
+            feature.name,
+            rule.map(|r| &r.name),
+            scenarios
+        );
+    }
+
     fn visit_rule(&mut self, rule: &gherkin::Rule) {
-        println!("visit_rule {}", rule.name);
+        println!(
+            "visit_rule {} @{}{}",
+            rule.name,
+            position(rule.position),
+            tags(&rule.tags)
+        );
     }
 
     fn visit_rule_end(&mut self, rule: &gherkin::Rule) {
         println!("visit_rule_end {}", rule.name);
     }
 
+    fn visit_scenario_outline(&mut self, _rule: Option<&gherkin::Rule>, outline: &crate::Scenario) {
+        println!(
+            "visit_scenario_outline {} @{}{}",
+            outline.name,
+            position(outline.position),
+            tags(&outline.tags)
+        );
+        if let Some(ref examples) = outline.examples {
+            print_table(&examples.table);
+        }
+    }
+
+    fn visit_scenario_outline_end(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        outline: &crate::Scenario,
+    ) {
+        println!("visit_scenario_outline_end {}", outline.name);
+    }
+
+    fn visit_scenario_example(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &crate::Scenario,
+        values: &[(String, String)],
+    ) {
+        println!("visit_scenario_example {:?}", values);
+    }
+
     fn visit_scenario(&mut self, _rule: Option<&gherkin::Rule>, scenario: &crate::Scenario) {
-        println!("visit_scenario {}", scenario.name);
+        println!(
+            "visit_scenario {} @{}{}",
+            scenario.name,
+            position(scenario.position),
+            tags(&scenario.tags)
+        );
     }
 
     fn visit_scenario_end(&mut self, _rule: Option<&gherkin::Rule>, scenario: &crate::Scenario) {
@@ -56,13 +153,100 @@ impl OutputVisitor for DebugOutput {
         println!("visit_scenario_skipped {}", scenario.name);
     }
 
+    fn visit_scenario_filtered_out(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        scenario: &crate::Scenario,
+    ) {
+        println!("visit_scenario_filtered_out {}", scenario.name);
+    }
+
+    fn visit_scenario_not_run(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        scenario: &crate::Scenario,
+    ) {
+        println!("visit_scenario_not_run {}", scenario.name);
+    }
+
+    fn visit_scenario_ignored(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        scenario: &crate::Scenario,
+        reason: &str,
+    ) {
+        println!("visit_scenario_ignored {} ({})", scenario.name, reason);
+    }
+
+    fn visit_scenario_quarantined(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        scenario: &crate::Scenario,
+    ) {
+        println!("visit_scenario_quarantined {}", scenario.name);
+    }
+
+    fn visit_scenario_resources(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        scenario: &crate::Scenario,
+        usage: &crate::report::ResourceUsage,
+    ) {
+        println!("visit_scenario_resources {} {:?}", scenario.name, usage);
+    }
+
+    fn visit_scenario_xfail(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        scenario: &crate::Scenario,
+        passed: bool,
+    ) {
+        println!("visit_scenario_xfail {} passed={}", scenario.name, passed);
+    }
+
+    fn visit_scenario_teardown_failed(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        scenario: &crate::Scenario,
+        failure: &crate::report::Failure,
+    ) {
+        println!(
+            "visit_scenario_teardown_failed {} {:?}",
+            scenario.name, failure
+        );
+    }
+
+    fn visit_scenario_hook_failed(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        scenario: &crate::Scenario,
+        hook: crate::report::Hook,
+        failure: &crate::report::Failure,
+    ) {
+        println!(
+            "visit_scenario_hook_failed {} {:?} {:?}",
+            scenario.name, hook, failure
+        );
+    }
+
     fn visit_step(
         &mut self,
         _rule: Option<&gherkin::Rule>,
         _scenario: &crate::Scenario,
         step: &crate::Step,
     ) {
-        println!("visit_step {} {}", step.raw_type, step.value);
+        println!(
+            "visit_step {} {} @{}",
+            step.raw_type,
+            step.value,
+            position(step.position)
+        );
+        if let Some(ref table) = step.table {
+            print_table(table);
+        }
+        if let Some(ref docstring) = step.docstring {
+            println!("  docstring {:?}", docstring);
+        }
     }
 
     fn visit_step_result(
@@ -71,10 +255,12 @@ impl OutputVisitor for DebugOutput {
         _scenario: &crate::Scenario,
         step: &crate::Step,
         result: &TestResult,
+        timing: &Timing,
+        _captured: &Captured,
     ) {
         println!(
-            "visit_step_result {} {} - {:?}",
-            step.raw_type, step.value, result
+            "visit_step_result {} {} - {:?} ({:?})",
+            step.raw_type, step.value, result, timing.duration
         );
     }
 
@@ -1,27 +1,92 @@
 use std;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::default::Default;
 use std::env;
 use std::io::Write;
 use std::path::Path;
+use std::time::Duration;
 
 use gherkin;
 use pathdiff::diff_paths;
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
 use textwrap;
 
+use crate::table_diff;
 use crate::OutputVisitor;
-use crate::TestResult;
+use crate::{Captured, TestResult, Timing};
 
+#[derive(Clone, Copy)]
 enum ScenarioResult {
     Pass,
     Fail,
     Skip,
 }
 
+/// The colors used by [`DefaultOutput`](struct.DefaultOutput.html).
+#[derive(Debug, Clone)]
+pub struct Theme {
+    /// The run header and summary headings.
+    pub title: Color,
+    /// Feature, rule and scenario names, and summary text.
+    pub text: Color,
+    /// The `path:line:column` comments on the right.
+    pub comment: Color,
+    pub tag: Color,
+    /// Table borders and docstring delimiters.
+    pub table: Color,
+    pub passed: Color,
+    pub failed: Color,
+    pub skipped: Color,
+    pub unimplemented: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme {
+            title: Color::Green,
+            text: Color::White,
+            comment: Color::White,
+            tag: Color::Cyan,
+            table: Color::Magenta,
+            passed: Color::Green,
+            failed: Color::Red,
+            skipped: Color::Cyan,
+            unimplemented: Color::Yellow,
+        }
+    }
+}
+
+struct FeatureTiming {
+    path: String,
+    scenario_count: u32,
+    duration: Duration,
+}
+
+struct StepTiming {
+    text: String,
+    location: String,
+    duration: Duration,
+}
+
+struct PassRate {
+    name: String,
+    location: String,
+    passed: u32,
+    runs: u32,
+}
+
 pub struct DefaultOutput {
-    stdout: StandardStream,
+    out: StandardStream,
+    theme: Theme,
+    show_tag_summary: bool,
+    show_feature_timings: bool,
+    feature_timings: Vec<FeatureTiming>,
+    slowest_step_count: usize,
+    step_timings: Vec<StepTiming>,
     cur_feature: String,
+    in_outline: bool,
+    /// The values of the example the next scenario is run with.
+    example: Vec<(String, String)>,
     feature_count: u32,
     feature_error_count: u32,
     rule_count: u32,
@@ -29,13 +94,30 @@ pub struct DefaultOutput {
     step_count: u32,
     skipped_count: u32,
     fail_count: u32,
+    filtered_out_count: u32,
+    not_run_count: u32,
+    quarantined: Vec<(String, String)>,
+    unexpected_passes: Vec<(String, String)>,
+    run_result: ScenarioResult,
+    /// How each run of a scenario went, as opposed to `scenarios` which
+    /// holds one result per scenario however many times it ran.
+    scenario_runs: Vec<ScenarioResult>,
+    pass_rates: Vec<PassRate>,
 }
 
 impl Default for DefaultOutput {
     fn default() -> DefaultOutput {
         DefaultOutput {
-            stdout: StandardStream::stdout(ColorChoice::Auto),
+            out: StandardStream::stdout(ColorChoice::Auto),
+            theme: Theme::default(),
+            show_tag_summary: false,
+            show_feature_timings: false,
+            feature_timings: vec![],
+            slowest_step_count: 0,
+            step_timings: vec![],
             cur_feature: "".to_string(),
+            in_outline: false,
+            example: vec![],
             feature_count: 0,
             feature_error_count: 0,
             rule_count: 0,
@@ -43,6 +125,13 @@ impl Default for DefaultOutput {
             step_count: 0,
             skipped_count: 0,
             fail_count: 0,
+            filtered_out_count: 0,
+            not_run_count: 0,
+            quarantined: vec![],
+            unexpected_passes: vec![],
+            run_result: ScenarioResult::Pass,
+            scenario_runs: vec![],
+            pass_rates: vec![],
         }
     }
 }
@@ -67,64 +156,222 @@ fn wrap_with_comment(s: &str, c: &str, indent: &str) -> String {
 }
 
 impl DefaultOutput {
+    pub fn set_theme(&mut self, theme: Theme) -> &mut Self {
+        self.theme = theme;
+        self
+    }
+
+    /// Writes to stderr instead of stdout, leaving stdout free for a
+    /// machine-readable formatter.
+    pub fn use_stderr(&mut self) -> &mut Self {
+        self.out = StandardStream::stderr(ColorChoice::Auto);
+        self
+    }
+
+    /// Prints scenario counts per tag after the summary.
+    pub fn show_tag_summary(&mut self, show: bool) -> &mut Self {
+        self.show_tag_summary = show;
+        self
+    }
+
+    /// Prints each feature's scenario count and total duration after the
+    /// summary, slowest first.
+    pub fn show_feature_timings(&mut self, show: bool) -> &mut Self {
+        self.show_feature_timings = show;
+        self
+    }
+
+    /// Prints the `count` slowest executed steps after the summary. Zero
+    /// disables the report.
+    pub fn show_slowest_steps(&mut self, count: usize) -> &mut Self {
+        self.slowest_step_count = count;
+        self
+    }
+
     fn set_color(&mut self, c: Color, b: bool) {
-        self.stdout
+        self.out
             .set_color(ColorSpec::new().set_fg(Some(c)).set_bold(b))
             .unwrap();
     }
 
     fn write(&mut self, s: &str, c: Color, bold: bool) {
-        self.stdout
+        self.out
             .set_color(ColorSpec::new().set_fg(Some(c)).set_bold(bold))
             .unwrap();
-        write!(&mut self.stdout, "{}", s).unwrap();
-        self.stdout
+        write!(&mut self.out, "{}", s).unwrap();
+        self.out
             .set_color(ColorSpec::new().set_fg(None).set_bold(false))
             .unwrap();
     }
 
     fn writeln(&mut self, s: &str, c: Color, bold: bool) {
-        self.stdout
+        self.out
             .set_color(ColorSpec::new().set_fg(Some(c)).set_bold(bold))
             .unwrap();
-        writeln!(&mut self.stdout, "{}", s).unwrap();
-        self.stdout
+        writeln!(&mut self.out, "{}", s).unwrap();
+        self.out
             .set_color(ColorSpec::new().set_fg(None).set_bold(false))
             .unwrap();
     }
 
     fn writeln_cmt(&mut self, s: &str, cmt: &str, indent: &str, c: Color, bold: bool) {
-        self.stdout
+        self.out
             .set_color(ColorSpec::new().set_fg(Some(c)).set_bold(bold))
             .unwrap();
-        write!(&mut self.stdout, "{}", wrap_with_comment(s, cmt, indent)).unwrap();
-        self.stdout
-            .set_color(ColorSpec::new().set_fg(Some(Color::White)).set_bold(false))
-            .unwrap();
-        writeln!(&mut self.stdout, " {}", cmt).unwrap();
-        self.stdout
-            .set_color(ColorSpec::new().set_fg(None))
+        write!(&mut self.out, "{}", wrap_with_comment(s, cmt, indent)).unwrap();
+        self.out
+            .set_color(
+                ColorSpec::new()
+                    .set_fg(Some(self.theme.comment))
+                    .set_bold(false),
+            )
             .unwrap();
+        writeln!(&mut self.out, " {}", cmt).unwrap();
+        self.out.set_color(ColorSpec::new().set_fg(None)).unwrap();
+    }
+
+    fn print(&mut self, s: &str) {
+        write!(&mut self.out, "{}", s).unwrap();
     }
 
     fn println(&mut self, s: &str) {
-        writeln!(&mut self.stdout, "{}", s).unwrap();
+        writeln!(&mut self.out, "{}", s).unwrap();
     }
 
     fn red(&mut self, s: &str) {
-        self.writeln(s, Color::Red, false);
+        self.writeln(s, self.theme.failed, false);
+    }
+
+    /// Prints a failure outside of the steps, such as that of a hook, under
+    /// `title`.
+    fn print_failure(&mut self, title: &str, failure: &crate::report::Failure) {
+        self.writeln_cmt(
+            &format!(
+                "{:—<1$}",
+                title,
+                textwrap::termwidth()
+                    .saturating_sub(failure.location.chars().count())
+                    .saturating_sub(7),
+            ),
+            &failure.location,
+            "———— ",
+            self.theme.failed,
+            true,
+        );
+        self.print_payload(&failure.message);
+        if let Some(backtrace) = &failure.backtrace {
+            self.writeln(
+                &format!("{:—<1$}", "———— Backtrace: ", textwrap::termwidth()),
+                self.theme.failed,
+                true,
+            );
+            self.red(textwrap::indent(backtrace, "  ").trim_end());
+        }
+        self.writeln(
+            &format!("{:—<1$}", "", textwrap::termwidth()),
+            self.theme.failed,
+            true,
+        );
+    }
+
+    /// Prints the message of a failure, but for the rows of a table diff,
+    /// which are printed unwrapped with the cells at fault in color.
+    fn print_payload(&mut self, payload: &str) {
+        let mut text = vec![];
+        for line in payload.lines() {
+            if !table_diff::is_diff_row(line) {
+                text.push(line);
+                continue;
+            }
+            if !text.is_empty() {
+                self.print_filled(&text.join("\n"));
+                text.clear();
+            }
+            self.print_diff_row(line);
+        }
+        self.print_filled(&text.join("\n"));
+    }
+
+    fn print_filled(&mut self, text: &str) {
+        if !text.is_empty() {
+            self.red(
+                &textwrap::indent(&textwrap::fill(text, textwrap::termwidth() - 4), "  ")
+                    .trim_end(),
+            );
+        }
+    }
+
+    fn print_diff_row(&mut self, line: &str) {
+        let (mark, cells) = line.split_at(1);
+        let color = match mark {
+            "-" => self.theme.failed,
+            "+" => self.theme.passed,
+            _ => self.theme.text,
+        };
+        self.print("  ");
+        self.write(mark, color, true);
+        let cells = &cells[2..cells.len() - 1];
+        self.write(" |", self.theme.table, false);
+        for cell in cells.split('|') {
+            match cell.find(" → ") {
+                Some(i) if mark == "~" => {
+                    self.write(&cell[..i], self.theme.failed, true);
+                    self.print(" → ");
+                    self.write(&cell[i + " → ".len()..], self.theme.passed, true);
+                }
+                _ if mark == "-" || mark == "+" => self.write(cell, color, false),
+                _ => self.print(cell),
+            }
+            self.write("|", self.theme.table, false);
+        }
+        self.println("");
+    }
+
+    fn print_captured(&mut self, name: &str, captured: &[u8]) {
+        if captured.is_empty() {
+            return;
+        }
+
+        self.writeln(
+            &format!(
+                "{:—<1$}",
+                format!("———— Captured {}: ", name),
+                textwrap::termwidth()
+            ),
+            self.theme.failed,
+            true,
+        );
+        self.red(
+            &textwrap::indent(
+                &textwrap::fill(
+                    &String::from_utf8_lossy(captured),
+                    textwrap::termwidth() - 4,
+                ),
+                "  ",
+            )
+            .trim_end(),
+        );
     }
 
     fn bold_white(&mut self, s: &str) {
-        self.writeln(s, Color::Green, true);
+        self.writeln(s, self.theme.title, true);
     }
 
     fn bold_white_comment(&mut self, s: &str, c: &str, indent: &str) {
-        self.writeln_cmt(s, c, indent, Color::White, true);
+        self.writeln_cmt(s, c, indent, self.theme.text, true);
+    }
+
+    fn indent(&self, rule: Option<&gherkin::Rule>, depth: usize) -> String {
+        let depth = depth + rule.map_or(0, |_| 1) + if self.in_outline { 1 } else { 0 };
+        " ".repeat(depth)
     }
 
     fn relpath(&self, target: &Path) -> std::path::PathBuf {
-        let target = target.canonicalize().expect("invalid target path");
+        // Features held in memory have no file to resolve.
+        let target = match target.canonicalize() {
+            Ok(target) => target,
+            Err(_) => return target.to_path_buf(),
+        };
         diff_paths(
             &target,
             &env::current_dir().expect("invalid current directory"),
@@ -169,20 +416,20 @@ impl DefaultOutput {
                 })
                 .collect();
 
-            print!("{}", indent);
-            let border_color = Color::Magenta;
+            self.print(indent);
+            let border_color = self.theme.table;
             self.write("|", border_color, true);
             for field in formatted_header_fields {
-                self.write(&field, Color::White, true);
+                self.write(&field, self.theme.text, true);
                 self.write("|", border_color, true);
             }
             self.println("");
 
             for row in formatted_row_fields {
-                print!("{}", indent);
+                self.print(indent);
                 self.write("|", border_color, false);
                 for field in row {
-                    print!("{}", field);
+                    self.print(&field);
                     self.write("|", border_color, false);
                 }
                 self.println("");
@@ -190,121 +437,304 @@ impl DefaultOutput {
         };
 
         if let Some(ref docstring) = &step.docstring {
-            self.writeln(&format!("{}\"\"\"", indent), Color::Magenta, true);
-            println!("{}", textwrap::indent(docstring, indent).trim_end());
-            self.writeln(&format!("{}\"\"\"", indent), Color::Magenta, true);
+            self.writeln(&format!("{}\"\"\"", indent), self.theme.table, true);
+            self.println(textwrap::indent(docstring, indent).trim_end());
+            self.writeln(&format!("{}\"\"\"", indent), self.theme.table, true);
         }
     }
 
     fn print_finish(&mut self) -> Result<(), std::io::Error> {
-        self.set_color(Color::White, true);
+        self.set_color(self.theme.text, true);
 
         // Do feature count
-        write!(&mut self.stdout, "{} features", &self.feature_count)?;
+        write!(&mut self.out, "{} features", &self.feature_count)?;
         if self.feature_error_count > 0 {
-            write!(&mut self.stdout, " (")?;
-            self.set_color(Color::Red, true);
-            write!(&mut self.stdout, "{} errored", self.feature_error_count)?;
-            self.set_color(Color::White, true);
-            write!(&mut self.stdout, ")")?;
+            write!(&mut self.out, " (")?;
+            self.set_color(self.theme.failed, true);
+            write!(&mut self.out, "{} errored", self.feature_error_count)?;
+            self.set_color(self.theme.text, true);
+            write!(&mut self.out, ")")?;
         }
 
         // Do rule count
         if self.rule_count > 0 {
-            write!(&mut self.stdout, ", {} rules", &self.rule_count)?;
+            write!(&mut self.out, ", {} rules", &self.rule_count)?;
         }
 
         self.println("");
 
-        // Do scenario count
-        let scenario_passed_count = self
-            .scenarios
-            .values()
+        // Do scenario count. Repeated scenarios are counted once per run, as
+        // their steps are.
+        let repeated = self.pass_rates.iter().any(|r| r.runs > 1);
+        let results = if repeated {
+            self.scenario_runs.clone()
+        } else {
+            self.scenarios.values().copied().collect()
+        };
+        let scenario_passed_count = results
+            .iter()
             .filter(|v| match v {
                 ScenarioResult::Pass => true,
                 _ => false,
             })
             .count();
-        let scenario_fail_count = self
-            .scenarios
-            .values()
+        let scenario_fail_count = results
+            .iter()
             .filter(|v| match v {
                 ScenarioResult::Fail => true,
                 _ => false,
             })
             .count();
-        let scenario_skipped_count = self
-            .scenarios
-            .values()
+        let scenario_skipped_count = results
+            .iter()
             .filter(|v| match v {
                 ScenarioResult::Skip => true,
                 _ => false,
             })
             .count();
+        let (scenarios, steps) = if repeated {
+            ("scenario runs", "step runs")
+        } else {
+            ("scenarios", "steps")
+        };
 
-        write!(&mut self.stdout, "{} scenarios (", &self.scenarios.len())?;
+        write!(&mut self.out, "{} {} (", results.len(), scenarios)?;
 
         if scenario_fail_count > 0 {
-            self.set_color(Color::Red, true);
-            write!(&mut self.stdout, "{} failed", scenario_fail_count)?;
-            self.set_color(Color::White, true);
+            self.set_color(self.theme.failed, true);
+            write!(&mut self.out, "{} failed", scenario_fail_count)?;
+            self.set_color(self.theme.text, true);
         }
 
         if scenario_skipped_count > 0 {
             if scenario_fail_count > 0 {
-                write!(&mut self.stdout, ", ")?;
+                write!(&mut self.out, ", ")?;
             }
-            self.set_color(Color::Cyan, true);
-            write!(&mut self.stdout, "{} skipped", scenario_skipped_count)?;
-            self.set_color(Color::White, true);
+            self.set_color(self.theme.skipped, true);
+            write!(&mut self.out, "{} skipped", scenario_skipped_count)?;
+            self.set_color(self.theme.text, true);
         }
 
         if scenario_fail_count > 0 || scenario_skipped_count > 0 {
-            write!(&mut self.stdout, ", ")?;
+            write!(&mut self.out, ", ")?;
         }
 
-        self.set_color(Color::Green, true);
-        write!(&mut self.stdout, "{} passed", scenario_passed_count)?;
-        self.set_color(Color::White, true);
+        self.set_color(self.theme.passed, true);
+        write!(&mut self.out, "{} passed", scenario_passed_count)?;
+        self.set_color(self.theme.text, true);
 
-        write!(&mut self.stdout, ")")?;
+        write!(&mut self.out, ")")?;
+
+        if self.filtered_out_count > 0 {
+            write!(&mut self.out, ", {} filtered out", self.filtered_out_count)?;
+        }
+        if self.not_run_count > 0 {
+            write!(&mut self.out, ", {} not run", self.not_run_count)?;
+        }
 
         self.println("");
 
         // Do steps
         let passed_count = self.step_count - self.skipped_count - self.fail_count;
 
-        write!(&mut self.stdout, "{} steps (", &self.step_count)?;
+        write!(&mut self.out, "{} {} (", &self.step_count, steps)?;
 
         if self.fail_count > 0 {
-            self.set_color(Color::Red, true);
-            write!(&mut self.stdout, "{} failed", self.fail_count)?;
-            self.set_color(Color::White, true);
+            self.set_color(self.theme.failed, true);
+            write!(&mut self.out, "{} failed", self.fail_count)?;
+            self.set_color(self.theme.text, true);
         }
 
         if self.skipped_count > 0 {
             if self.fail_count > 0 {
-                write!(&mut self.stdout, ", ")?;
+                write!(&mut self.out, ", ")?;
             }
-            self.set_color(Color::Cyan, true);
-            write!(&mut self.stdout, "{} skipped", self.skipped_count)?;
-            self.set_color(Color::White, true);
+            self.set_color(self.theme.skipped, true);
+            write!(&mut self.out, "{} skipped", self.skipped_count)?;
+            self.set_color(self.theme.text, true);
         }
 
         if self.fail_count > 0 || self.skipped_count > 0 {
-            write!(&mut self.stdout, ", ")?;
+            write!(&mut self.out, ", ")?;
         }
 
-        self.set_color(Color::Green, true);
-        write!(&mut self.stdout, "{} passed", passed_count)?;
-        self.set_color(Color::White, true);
-        write!(&mut self.stdout, ")")?;
+        self.set_color(self.theme.passed, true);
+        write!(&mut self.out, "{} passed", passed_count)?;
+        self.set_color(self.theme.text, true);
+        write!(&mut self.out, ")")?;
         self.println("");
 
-        self.stdout
+        self.out
             .set_color(ColorSpec::new().set_fg(None).set_bold(false))?;
         self.println("");
 
+        if !self.unexpected_passes.is_empty() {
+            self.print_unexpected_passes()?;
+        }
+
+        if self.show_tag_summary {
+            self.print_tag_summary()?;
+        }
+
+        if self.show_feature_timings {
+            self.print_feature_timings()?;
+        }
+
+        if self.slowest_step_count > 0 {
+            self.print_slowest_steps()?;
+        }
+
+        if self.pass_rates.iter().any(|r| r.runs > 1) {
+            self.print_pass_rates()?;
+        }
+
+        if !self.quarantined.is_empty() {
+            self.print_quarantined()?;
+        }
+
+        Ok(())
+    }
+
+    fn print_unexpected_passes(&mut self) -> Result<(), std::io::Error> {
+        self.writeln(
+            &format!(
+                "{} scenarios expected to fail passed:",
+                self.unexpected_passes.len()
+            ),
+            self.theme.unimplemented,
+            true,
+        );
+        let lines = self
+            .unexpected_passes
+            .iter()
+            .map(|(name, location)| format!("  {}  {}", name, location))
+            .collect::<Vec<_>>();
+        for line in lines {
+            self.writeln(&line, self.theme.unimplemented, true);
+        }
+        self.println("");
+
+        Ok(())
+    }
+
+    fn print_quarantined(&mut self) -> Result<(), std::io::Error> {
+        self.bold_white("Quarantined failures:");
+        let lines = self
+            .quarantined
+            .iter()
+            .map(|(name, location)| format!("  {}  {}", name, location))
+            .collect::<Vec<_>>();
+        for line in lines {
+            self.writeln(&line, self.theme.failed, false);
+        }
+        self.println("");
+
+        Ok(())
+    }
+
+    fn print_pass_rates(&mut self) -> Result<(), std::io::Error> {
+        self.bold_white("Pass rates:");
+        let lines = self
+            .pass_rates
+            .iter()
+            .map(|rate| {
+                let color = if rate.passed == rate.runs {
+                    self.theme.passed
+                } else {
+                    self.theme.failed
+                };
+                let line = format!(
+                    "  {:>4}/{:<4} {}  {}",
+                    rate.passed, rate.runs, rate.name, rate.location
+                );
+                (line, color)
+            })
+            .collect::<Vec<_>>();
+        for (line, color) in lines {
+            self.writeln(&line, color, false);
+        }
+        self.println("");
+
+        Ok(())
+    }
+
+    fn print_slowest_steps(&mut self) -> Result<(), std::io::Error> {
+        self.step_timings
+            .sort_by_key(|timing| std::cmp::Reverse(timing.duration));
+        self.step_timings.truncate(self.slowest_step_count);
+
+        self.bold_white(&format!("Slowest {} steps:", self.step_timings.len()));
+        for timing in &self.step_timings {
+            writeln!(
+                &mut self.out,
+                "  {:>10.3}s  {}  {}",
+                timing.duration.as_secs_f64(),
+                timing.text,
+                timing.location
+            )?;
+        }
+        self.println("");
+
+        Ok(())
+    }
+
+    fn print_tag_summary(&mut self) -> Result<(), std::io::Error> {
+        // (failed, skipped, passed) per tag
+        let mut counts: BTreeMap<&str, (u32, u32, u32)> = BTreeMap::new();
+        for (scenario, result) in &self.scenarios {
+            for tag in scenario.tags.iter().flatten() {
+                let count = counts.entry(crate::cli::tag_name(tag)).or_insert((0, 0, 0));
+                match result {
+                    ScenarioResult::Fail => count.0 += 1,
+                    ScenarioResult::Skip => count.1 += 1,
+                    ScenarioResult::Pass => count.2 += 1,
+                }
+            }
+        }
+
+        let counts = counts
+            .into_iter()
+            .map(|(tag, count)| (tag.to_string(), count))
+            .collect::<Vec<_>>();
+
+        self.bold_white("Tags:");
+        for (tag, (failed, skipped, passed)) in counts {
+            write!(
+                &mut self.out,
+                "  @{} {} scenarios (",
+                tag,
+                failed + skipped + passed
+            )?;
+            if failed > 0 {
+                self.write(&format!("{} failed, ", failed), self.theme.failed, true);
+            }
+            if skipped > 0 {
+                self.write(&format!("{} skipped, ", skipped), self.theme.skipped, true);
+            }
+            self.write(&format!("{} passed", passed), self.theme.passed, true);
+            self.println(")");
+        }
+        self.println("");
+
+        Ok(())
+    }
+
+    fn print_feature_timings(&mut self) -> Result<(), std::io::Error> {
+        self.feature_timings
+            .sort_by_key(|timing| std::cmp::Reverse(timing.duration));
+
+        self.bold_white("Feature timings:");
+        for timing in &self.feature_timings {
+            writeln!(
+                &mut self.out,
+                "  {:>10.3}s  {:>4} scenarios  {}",
+                timing.duration.as_secs_f64(),
+                timing.scenario_count,
+                timing.path
+            )?;
+        }
+        self.println("");
+
         Ok(())
     }
 }
@@ -319,13 +749,48 @@ fn error_position(error: &gherkin::Error) -> (usize, usize) {
     }
 }
 
+/// The last lines a failed service printed, which usually tell why.
+fn log_tail(service: &crate::report::Service) -> String {
+    const LINES: usize = 20;
+    let mut tail = String::new();
+    for (stream, log) in &[("stdout", &service.stdout), ("stderr", &service.stderr)] {
+        let lines = log.lines().collect::<Vec<_>>();
+        if lines.is_empty() {
+            continue;
+        }
+        tail.push_str(&format!("\n{}:", stream));
+        for line in &lines[lines.len().saturating_sub(LINES)..] {
+            tail.push_str(&format!("\n  {}", line));
+        }
+    }
+    tail
+}
+
 impl OutputVisitor for DefaultOutput {
     fn new() -> Self {
         Default::default()
     }
 
-    fn visit_start(&mut self) {
-        self.bold_white(&format!("[Cucumber v{}]\n", env!("CARGO_PKG_VERSION")))
+    fn visit_start(&mut self, metadata: &BTreeMap<String, String>) {
+        self.bold_white(&format!("[Cucumber v{}]", env!("CARGO_PKG_VERSION")));
+        for (key, value) in metadata {
+            self.writeln(&format!("  {}: {}", key, value), self.theme.comment, false);
+        }
+        self.println("");
+    }
+
+    fn visit_services(&mut self, services: &[crate::report::Service]) {
+        for service in services {
+            if let Some(error) = &service.error {
+                let failure = crate::report::Failure {
+                    message: format!("{}{}", error, log_tail(service)),
+                    location: String::new(),
+                    backtrace: None,
+                };
+                self.print_failure(&format!("! Service {} failed: ", service.name), &failure);
+                self.run_result = ScenarioResult::Fail;
+            }
+        }
     }
 
     fn visit_feature(&mut self, feature: &gherkin::Feature, path: &Path) {
@@ -336,7 +801,34 @@ impl OutputVisitor for DefaultOutput {
             &self.cur_feature, feature.position.0, feature.position.1
         );
         self.bold_white_comment(msg, cmt, "");
-        println!();
+
+        self.feature_timings.push(FeatureTiming {
+            path: self.cur_feature.clone(),
+            scenario_count: 0,
+            duration: Duration::from_secs(0),
+        });
+
+        if let Some(ref tags) = &feature.tags {
+            if !tags.is_empty() {
+                let tags = tags
+                    .iter()
+                    .map(|tag| format!("@{}", crate::cli::tag_name(tag)))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                self.writeln(&format!("  {}", tags), self.theme.tag, false);
+            }
+        }
+
+        if let Some(ref description) = &feature.description {
+            let description = textwrap::fill(description.trim(), textwrap::termwidth() - 2);
+            self.writeln(
+                &textwrap::indent(&description, "  ").trim_end(),
+                self.theme.text,
+                false,
+            );
+        }
+
+        self.println("");
 
         self.feature_count += 1;
     }
@@ -356,7 +848,7 @@ impl OutputVisitor for DefaultOutput {
             ),
             &loc,
             "———— ",
-            Color::Red,
+            self.theme.failed,
             true,
         );
 
@@ -370,13 +862,43 @@ impl OutputVisitor for DefaultOutput {
 
         self.writeln(
             &format!("{:—<1$}\n", "", textwrap::termwidth()),
-            Color::Red,
+            self.theme.failed,
             true,
         );
 
         self.feature_error_count += 1;
     }
 
+    fn visit_feature_hook_failed(
+        &mut self,
+        _feature: &gherkin::Feature,
+        hook: crate::report::Hook,
+        failure: &crate::report::Failure,
+    ) {
+        let title = match hook {
+            crate::report::Hook::Before => "! Before feature hook failed: ",
+            crate::report::Hook::After => "! After feature hook failed: ",
+        };
+        self.print_failure(title, failure);
+        self.run_result = ScenarioResult::Fail;
+    }
+
+    fn visit_dependency_cycle(
+        &mut self,
+        feature: &gherkin::Feature,
+        rule: Option<&gherkin::Rule>,
+        scenarios: &[String],
+    ) {
+        let line = rule.map_or(feature.position.0, |rule| rule.position.0);
+        let failure = crate::report::Failure {
+            message: format!("could not order: {}", scenarios.join(", ")),
+            location: format!("{}:{}", self.cur_feature, line),
+            backtrace: None,
+        };
+        self.print_failure("! The @depends-on tags form a cycle: ", &failure);
+        self.run_result = ScenarioResult::Fail;
+    }
+
     fn visit_rule(&mut self, rule: &gherkin::Rule) {
         let cmt = &format!(
             "{}:{}:{}",
@@ -389,13 +911,61 @@ impl OutputVisitor for DefaultOutput {
         self.rule_count += 1;
     }
 
+    fn visit_scenario_outline(
+        &mut self,
+        rule: Option<&gherkin::Rule>,
+        outline: &gherkin::Scenario,
+    ) {
+        let cmt = &format!(
+            "{}:{}:{}",
+            &self.cur_feature, outline.position.0, outline.position.1
+        );
+        let indent = self.indent(rule, 1);
+        self.bold_white_comment(
+            &format!("Scenario Outline: {}", &outline.name),
+            cmt,
+            &indent,
+        );
+        self.in_outline = true;
+    }
+
+    fn visit_scenario_outline_end(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _outline: &gherkin::Scenario,
+    ) {
+        self.in_outline = false;
+        self.println("");
+    }
+
+    fn visit_scenario_example(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+        values: &[(String, String)],
+    ) {
+        self.example = values.to_vec();
+    }
+
     fn visit_scenario(&mut self, rule: Option<&gherkin::Rule>, scenario: &gherkin::Scenario) {
         let cmt = &format!(
             "{}:{}:{}",
             &self.cur_feature, scenario.position.0, scenario.position.1
         );
-        let indent = if rule.is_some() { "  " } else { " " };
-        self.bold_white_comment(&format!("Scenario: {}", &scenario.name), cmt, indent);
+        let indent = self.indent(rule, 1);
+        let example = std::mem::take(&mut self.example);
+        let msg = if self.in_outline && !example.is_empty() {
+            let values = example
+                .iter()
+                .map(|(_, value)| value.as_str())
+                .collect::<Vec<_>>()
+                .join(" | ");
+            format!("Example: | {} |", values)
+        } else {
+            format!("Scenario: {}", &scenario.name)
+        };
+        self.bold_white_comment(&msg, cmt, &indent);
+        self.run_result = ScenarioResult::Pass;
     }
 
     fn visit_scenario_skipped(
@@ -407,6 +977,90 @@ impl OutputVisitor for DefaultOutput {
             self.scenarios
                 .insert(scenario.clone(), ScenarioResult::Skip);
         }
+        if let ScenarioResult::Pass = self.run_result {
+            self.run_result = ScenarioResult::Skip;
+        }
+    }
+
+    fn visit_scenario_filtered_out(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+    ) {
+        self.filtered_out_count += 1;
+    }
+
+    fn visit_scenario_not_run(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+    ) {
+        self.not_run_count += 1;
+    }
+
+    fn visit_scenario_ignored(
+        &mut self,
+        rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+        reason: &str,
+    ) {
+        let indent = self.indent(rule, 2);
+        self.writeln(
+            &format!("{}Skipped: {}", indent, reason),
+            self.theme.skipped,
+            false,
+        );
+    }
+
+    fn visit_scenario_quarantined(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+    ) {
+        let location = format!("{}:{}", self.cur_feature, scenario.position.0);
+        self.quarantined.push((scenario.name.clone(), location));
+    }
+
+    fn visit_scenario_xfail(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+        passed: bool,
+    ) {
+        if passed {
+            let location = format!("{}:{}", self.cur_feature, scenario.position.0);
+            self.unexpected_passes
+                .push((scenario.name.clone(), location));
+        }
+    }
+
+    fn visit_scenario_teardown_failed(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+        failure: &crate::report::Failure,
+    ) {
+        self.print_failure("! Teardown failed: ", failure);
+        self.scenarios
+            .insert(scenario.clone(), ScenarioResult::Fail);
+        self.run_result = ScenarioResult::Fail;
+    }
+
+    fn visit_scenario_hook_failed(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+        hook: crate::report::Hook,
+        failure: &crate::report::Failure,
+    ) {
+        let title = match hook {
+            crate::report::Hook::Before => "! Before hook failed: ",
+            crate::report::Hook::After => "! After hook failed: ",
+        };
+        self.print_failure(title, failure);
+        self.scenarios
+            .insert(scenario.clone(), ScenarioResult::Fail);
+        self.run_result = ScenarioResult::Fail;
     }
 
     fn visit_scenario_end(&mut self, _rule: Option<&gherkin::Rule>, scenario: &gherkin::Scenario) {
@@ -414,7 +1068,33 @@ impl OutputVisitor for DefaultOutput {
             self.scenarios
                 .insert(scenario.clone(), ScenarioResult::Pass);
         }
-        self.println("");
+
+        let location = format!("{}:{}", self.cur_feature, scenario.position.0);
+        let index = match self.pass_rates.iter().position(|r| r.location == location) {
+            Some(index) => index,
+            None => {
+                self.pass_rates.push(PassRate {
+                    name: scenario.name.clone(),
+                    location,
+                    passed: 0,
+                    runs: 0,
+                });
+                self.pass_rates.len() - 1
+            }
+        };
+        self.pass_rates[index].runs += 1;
+        if let ScenarioResult::Pass = self.run_result {
+            self.pass_rates[index].passed += 1;
+        }
+        let result = std::mem::replace(&mut self.run_result, ScenarioResult::Pass);
+        self.scenario_runs.push(result);
+
+        if let Some(timing) = self.feature_timings.last_mut() {
+            timing.scenario_count += 1;
+        }
+        if !self.in_outline {
+            self.println("");
+        }
     }
 
     fn visit_step(
@@ -432,21 +1112,37 @@ impl OutputVisitor for DefaultOutput {
         scenario: &gherkin::Scenario,
         step: &gherkin::Step,
         result: &TestResult,
+        timing: &Timing,
+        _captured: &Captured,
     ) {
+        if let Some(feature_timing) = self.feature_timings.last_mut() {
+            feature_timing.duration += timing.duration;
+        }
+
         let cmt = &format!(
             "{}:{}:{}",
             &self.cur_feature, step.position.0, step.position.1
         );
         let msg = &step.to_string();
-        let indent = if rule.is_some() { "   " } else { "  " };
+        let indent = &self.indent(rule, 2);
+
+        if self.slowest_step_count > 0 {
+            if let TestResult::Pass | TestResult::Fail(..) = result {
+                self.step_timings.push(StepTiming {
+                    text: msg.clone(),
+                    location: cmt.clone(),
+                    duration: timing.duration,
+                });
+            }
+        }
 
         match result {
             TestResult::Pass => {
-                self.writeln_cmt(&format!("✔ {}", msg), cmt, indent, Color::Green, false);
+                self.writeln_cmt(&format!("✔ {}", msg), cmt, indent, self.theme.passed, false);
                 self.print_step_extras(step);
             }
             TestResult::Fail(panic_info, captured_stdout, captured_stderr) => {
-                self.writeln_cmt(&format!("✘ {}", msg), cmt, indent, Color::Red, false);
+                self.writeln_cmt(&format!("✘ {}", msg), cmt, indent, self.theme.failed, false);
                 self.print_step_extras(step);
                 self.writeln_cmt(
                     &format!(
@@ -458,74 +1154,126 @@ impl OutputVisitor for DefaultOutput {
                     ),
                     &panic_info.location,
                     "———— ",
-                    Color::Red,
+                    self.theme.failed,
                     true,
                 );
-                self.red(
-                    &textwrap::indent(
-                        &textwrap::fill(&panic_info.payload, textwrap::termwidth() - 4),
-                        "  ",
-                    )
-                    .trim_end(),
-                );
+                self.print_payload(&panic_info.payload);
 
-                if !captured_stdout.is_empty() {
+                if let Some(backtrace) = &panic_info.backtrace {
                     self.writeln(
-                        &format!("{:—<1$}", "———— Captured stdout: ", textwrap::termwidth()),
-                        Color::Red,
+                        &format!("{:—<1$}", "———— Backtrace: ", textwrap::termwidth()),
+                        self.theme.failed,
                         true,
                     );
-                    self.red(
-                        &textwrap::indent(
-                            &textwrap::fill(
-                                &String::from_utf8_lossy(captured_stderr),
-                                textwrap::termwidth() - 4,
-                            ),
-                            "  ",
-                        )
-                        .trim_end(),
-                    );
+                    // Frames are kept one per line rather than filled.
+                    self.red(textwrap::indent(backtrace, "  ").trim_end());
                 }
+                self.print_captured("stdout", captured_stdout);
+                self.print_captured("stderr", captured_stderr);
 
-                if !captured_stderr.is_empty() {
-                    self.writeln(
-                        &format!("{:—<1$}", "———— Captured stderr: ", textwrap::termwidth()),
-                        Color::Red,
-                        true,
-                    );
-                    self.red(
-                        &textwrap::indent(
-                            &textwrap::fill(
-                                &String::from_utf8_lossy(captured_stderr),
-                                textwrap::termwidth() - 4,
-                            ),
-                            "  ",
-                        )
-                        .trim_end(),
-                    );
-                }
+                self.writeln(
+                    &format!("{:—<1$}", "", textwrap::termwidth()),
+                    self.theme.failed,
+                    true,
+                );
+
+                self.fail_count += 1;
+                self.scenarios
+                    .insert(scenario.clone(), ScenarioResult::Fail);
+                self.run_result = ScenarioResult::Fail;
+            }
+            TestResult::Crashed(status, captured_stdout, captured_stderr) => {
+                self.writeln_cmt(&format!("✘ {}", msg), cmt, indent, self.theme.failed, false);
+                self.print_step_extras(step);
+                self.writeln(
+                    &format!(
+                        "{:—<1$}",
+                        format!("! Scenario process crashed ({}) ", status),
+                        textwrap::termwidth()
+                    ),
+                    self.theme.failed,
+                    true,
+                );
+                self.print_captured("stdout", captured_stdout);
+                self.print_captured("stderr", captured_stderr);
+                self.writeln(
+                    &format!("{:—<1$}", "", textwrap::termwidth()),
+                    self.theme.failed,
+                    true,
+                );
 
+                self.fail_count += 1;
+                self.scenarios
+                    .insert(scenario.clone(), ScenarioResult::Fail);
+                self.run_result = ScenarioResult::Fail;
+            }
+            TestResult::Ambiguous(matches) => {
+                self.writeln_cmt(&format!("✘ {}", msg), cmt, indent, self.theme.failed, false);
+                self.print_step_extras(step);
+                self.writeln(
+                    &format!(
+                        "{:—<1$}",
+                        format!("! Step matches {} definitions ", matches.len()),
+                        textwrap::termwidth()
+                    ),
+                    self.theme.failed,
+                    true,
+                );
+                for definition in matches {
+                    self.red(&format!("  {}", definition));
+                }
                 self.writeln(
                     &format!("{:—<1$}", "", textwrap::termwidth()),
-                    Color::Red,
+                    self.theme.failed,
                     true,
                 );
 
                 self.fail_count += 1;
                 self.scenarios
                     .insert(scenario.clone(), ScenarioResult::Fail);
+                self.run_result = ScenarioResult::Fail;
             }
             TestResult::Skipped => {
-                self.writeln_cmt(&format!("- {}", msg), cmt, indent, Color::Cyan, false);
+                self.writeln_cmt(
+                    &format!("- {}", msg),
+                    cmt,
+                    indent,
+                    self.theme.skipped,
+                    false,
+                );
                 self.print_step_extras(step);
                 self.skipped_count += 1;
             }
             TestResult::Unimplemented => {
-                self.writeln_cmt(&format!("- {}", msg), cmt, indent, Color::Cyan, false);
+                self.writeln_cmt(
+                    &format!("- {}", msg),
+                    cmt,
+                    indent,
+                    self.theme.skipped,
+                    false,
+                );
                 self.print_step_extras(step);
-                self.write(&format!("{}  ⚡ ", indent), Color::Yellow, false);
+                self.write(&format!("{}  ⚡ ", indent), self.theme.unimplemented, false);
                 self.println("Not yet implemented (skipped)");
 
+                self.skipped_count += 1;
+            }
+            TestResult::Pending(reason) => {
+                self.writeln_cmt(
+                    &format!("- {}", msg),
+                    cmt,
+                    indent,
+                    self.theme.skipped,
+                    false,
+                );
+                self.print_step_extras(step);
+                self.write(&format!("{}  ⏸ ", indent), self.theme.unimplemented, false);
+                if reason.is_empty() {
+                    self.println("Pending (skipped)");
+                } else {
+                    self.println(&format!("Pending: {} (skipped)", reason));
+                }
+
                 self.skipped_count += 1;
             }
         };
@@ -0,0 +1,39 @@
+use std::io::{self, stdout, Write};
+
+use crate::output::structured::{ReportOutput, ReportWriter};
+use crate::report::Report;
+
+/// Writes a [`Report`](../report/struct.Report.html) as a single JSON document.
+pub struct JsonWriter {
+    writer: Box<dyn Write + Send>,
+}
+
+impl JsonWriter {
+    pub fn new<Wr: Write + Send + 'static>(writer: Wr) -> JsonWriter {
+        JsonWriter {
+            writer: Box::new(writer),
+        }
+    }
+}
+
+impl Default for JsonWriter {
+    fn default() -> JsonWriter {
+        JsonWriter::new(stdout())
+    }
+}
+
+impl ReportWriter for JsonWriter {
+    fn write_report(&mut self, report: &Report) -> io::Result<()> {
+        serde_json::to_writer_pretty(&mut self.writer, report)?;
+        writeln!(&mut self.writer)?;
+        self.writer.flush()
+    }
+}
+
+pub type JsonOutput = ReportOutput<JsonWriter>;
+
+impl ReportOutput<JsonWriter> {
+    pub fn from_writer<Wr: Write + Send + 'static>(writer: Wr) -> JsonOutput {
+        ReportOutput::with_writer(JsonWriter::new(writer))
+    }
+}
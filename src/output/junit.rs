@@ -0,0 +1,282 @@
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, stdout, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::output::escape;
+use crate::output::structured::{ReportOutput, ReportWriter};
+use crate::report::{self, Report, Status};
+
+enum Target {
+    Single(Box<dyn Write + Send>),
+    PerFeature(PathBuf),
+}
+
+/// Writes a [`Report`](../report/struct.Report.html) as JUnit XML, with one
+/// `<testsuite>` per feature and one `<testcase>` per scenario.
+pub struct JUnitWriter {
+    target: Target,
+}
+
+impl JUnitWriter {
+    pub fn new<Wr: Write + Send + 'static>(writer: Wr) -> JUnitWriter {
+        JUnitWriter {
+            target: Target::Single(Box::new(writer)),
+        }
+    }
+
+    /// Writes one `<feature>.xml` file per feature into `dir` instead of a
+    /// single document, for CI systems which ingest reports in parallel.
+    pub fn per_feature<P: AsRef<Path>>(dir: P) -> JUnitWriter {
+        JUnitWriter {
+            target: Target::PerFeature(dir.as_ref().to_path_buf()),
+        }
+    }
+}
+
+impl Default for JUnitWriter {
+    fn default() -> JUnitWriter {
+        JUnitWriter::new(stdout())
+    }
+}
+
+impl ReportWriter for JUnitWriter {
+    fn write_report(&mut self, report: &Report) -> io::Result<()> {
+        match &mut self.target {
+            Target::Single(w) => {
+                let features = report.features.iter().collect::<Vec<_>>();
+                write_testsuites(w, report, &features)?;
+                w.flush()
+            }
+            Target::PerFeature(dir) => {
+                fs::create_dir_all(&dir)?;
+                let mut names = HashSet::new();
+                for feature in &report.features {
+                    let path = dir.join(file_name(feature, &mut names));
+                    let mut w = BufWriter::new(File::create(path)?);
+                    write_testsuites(&mut w, report, &[feature])?;
+                    w.flush()?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Picks `<feature file stem>.xml`, adding a number when two feature files
+/// in different directories share a name.
+fn file_name(feature: &report::Feature, taken: &mut HashSet<String>) -> String {
+    let stem = Path::new(&feature.path)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "feature".to_string());
+    let stem = stem
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+
+    let mut name = stem.clone();
+    let mut i = 1;
+    while !taken.insert(name.clone()) {
+        i += 1;
+        name = format!("{}-{}", stem, i);
+    }
+    format!("{}.xml", name)
+}
+
+fn write_testsuites(
+    w: &mut dyn Write,
+    report: &Report,
+    features: &[&report::Feature],
+) -> io::Result<()> {
+    let scenarios = features.iter().flat_map(|f| f.scenarios.iter());
+
+    writeln!(w, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        w,
+        r#"<testsuites name="cucumber" tests="{}" failures="{}" time="{}">"#,
+        scenarios.clone().count(),
+        scenarios.filter(|s| s.status.is_failure()).count(),
+        seconds(features.iter().map(|f| f.duration()).sum()),
+    )?;
+
+    for feature in features {
+        write_testsuite(w, feature, report)?;
+    }
+
+    writeln!(w, "</testsuites>")
+}
+
+fn write_testsuite(
+    w: &mut dyn Write,
+    feature: &report::Feature,
+    report: &Report,
+) -> io::Result<()> {
+    let count = |status| {
+        feature
+            .scenarios
+            .iter()
+            .filter(|s| s.status == status)
+            .count()
+    };
+    let cycle = !feature.dependency_cycle.is_empty();
+
+    writeln!(
+        w,
+        r#"  <testsuite name="{}" tests="{}" failures="{}" errors="{}" skipped="{}" time="{}">"#,
+        escape(&feature.name),
+        feature.scenarios.len(),
+        count(Status::Failed) + count(Status::Ambiguous),
+        count(Status::Crashed)
+            + if feature.error.is_some() { 1 } else { 0 }
+            + if cycle { 1 } else { 0 },
+        count(Status::Skipped) + count(Status::Unimplemented) + count(Status::Pending),
+        seconds(feature.duration()),
+    )?;
+
+    if !report.metadata.is_empty() {
+        writeln!(w, "    <properties>")?;
+        for (key, value) in &report.metadata {
+            writeln!(
+                w,
+                r#"      <property name="{}" value="{}"/>"#,
+                escape(key),
+                escape(value),
+            )?;
+        }
+        writeln!(w, "    </properties>")?;
+    }
+
+    if let Some(ref error) = feature.error {
+        writeln!(
+            w,
+            r#"    <testcase classname="{}" name="{}" time="0"><error message="Parsing feature failed">{}</error></testcase>"#,
+            escape(&feature.path),
+            escape(&feature.path),
+            escape(error),
+        )?;
+    }
+
+    if cycle {
+        writeln!(
+            w,
+            r#"    <testcase classname="{}" name="{}" time="0"><error message="The @depends-on tags form a cycle">{}</error></testcase>"#,
+            escape(&feature.path),
+            escape(&feature.path),
+            escape(&feature.dependency_cycle.join(", ")),
+        )?;
+    }
+
+    for scenario in &feature.scenarios {
+        write!(
+            w,
+            r#"    <testcase classname="{}" name="{}" time="{}">"#,
+            escape(&feature.name),
+            escape(&scenario.name),
+            seconds(scenario.duration()),
+        )?;
+
+        match scenario.status {
+            Status::Failed | Status::Ambiguous => {
+                if let Some(step) = scenario.failed_step() {
+                    let failure = step.failure.as_ref();
+                    writeln!(w)?;
+                    writeln!(
+                        w,
+                        r#"      <failure message="{}" type="{}">{} {} ({}:{})"#,
+                        escape(failure.map(|f| f.message.as_str()).unwrap_or("")),
+                        if scenario.status == Status::Ambiguous {
+                            "ambiguous"
+                        } else {
+                            "panic"
+                        },
+                        escape(&step.keyword),
+                        escape(&step.text),
+                        escape(&feature.path),
+                        step.line,
+                    )?;
+                    if let Some(failure) = failure {
+                        writeln!(w, "{}", escape(&failure.location))?;
+                    }
+                    writeln!(w, "      </failure>")?;
+                    write!(w, "    ")?;
+                } else if let Some(hook) = scenario.hook_failures.first() {
+                    writeln!(w)?;
+                    writeln!(
+                        w,
+                        r#"      <failure message="{}" type="{}-hook">{}"#,
+                        escape(&hook.failure.message),
+                        hook.hook.as_str(),
+                        escape(&hook.failure.location),
+                    )?;
+                    writeln!(w, "      </failure>")?;
+                    write!(w, "    ")?;
+                } else if let Some(failure) = &scenario.teardown_failure {
+                    writeln!(w)?;
+                    writeln!(
+                        w,
+                        r#"      <failure message="{}" type="teardown">{}"#,
+                        escape(&failure.message),
+                        escape(&failure.location),
+                    )?;
+                    writeln!(w, "      </failure>")?;
+                    write!(w, "    ")?;
+                }
+            }
+            Status::Crashed => {
+                if let Some(step) = scenario.failed_step() {
+                    writeln!(w)?;
+                    writeln!(
+                        w,
+                        r#"      <error message="{}" type="crash">{} {} ({}:{})</error>"#,
+                        escape(
+                            step.failure
+                                .as_ref()
+                                .map(|f| f.message.as_str())
+                                .unwrap_or("")
+                        ),
+                        escape(&step.keyword),
+                        escape(&step.text),
+                        escape(&feature.path),
+                        step.line,
+                    )?;
+                    write!(w, "    ")?;
+                }
+            }
+            Status::Skipped | Status::Unimplemented | Status::Pending => write!(w, "<skipped/>")?,
+            Status::Passed => {}
+        }
+
+        let stdout = scenario.stdout();
+        if !stdout.is_empty() {
+            write!(w, "<system-out>{}</system-out>", escape(&stdout))?;
+        }
+        let stderr = scenario.stderr();
+        if !stderr.is_empty() {
+            write!(w, "<system-err>{}</system-err>", escape(&stderr))?;
+        }
+
+        writeln!(w, "</testcase>")?;
+    }
+
+    writeln!(w, "  </testsuite>")
+}
+
+fn seconds(duration: Duration) -> String {
+    format!("{:.3}", duration.as_secs_f64())
+}
+
+pub type JUnitOutput = ReportOutput<JUnitWriter>;
+
+impl ReportOutput<JUnitWriter> {
+    pub fn from_writer<Wr: Write + Send + 'static>(writer: Wr) -> JUnitOutput {
+        ReportOutput::with_writer(JUnitWriter::new(writer))
+    }
+}
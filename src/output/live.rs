@@ -0,0 +1,148 @@
+use std::fmt::Write as _;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::output::escape;
+use crate::output::structured::ReportWriter;
+use crate::report::{Report, Status};
+
+/// Serves an auto-refreshing HTML page describing the run so far.
+///
+/// The page is updated every time a scenario finishes and stops refreshing
+/// once the run is over. The server lives on a background thread until the
+/// process exits.
+pub struct LiveHtmlWriter {
+    page: Arc<Mutex<String>>,
+}
+
+impl LiveHtmlWriter {
+    pub fn bind<A: ToSocketAddrs>(address: A) -> io::Result<LiveHtmlWriter> {
+        let listener = TcpListener::bind(address)?;
+        let page = Arc::new(Mutex::new(render(&Report::default(), false)));
+
+        eprintln!("Serving live report on http://{}/", listener.local_addr()?);
+
+        thread::spawn({
+            let page = page.clone();
+            move || {
+                for stream in listener.incoming() {
+                    let mut stream = match stream {
+                        Ok(v) => v,
+                        Err(_) => continue,
+                    };
+                    // The request itself does not matter, every path gets the page.
+                    let mut buf = [0; 1024];
+                    let _ = stream.read(&mut buf);
+
+                    let body = page.lock().expect("Live page mutex poisoned").clone();
+                    let _ = write!(
+                        stream,
+                        "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    );
+                }
+            }
+        });
+
+        Ok(LiveHtmlWriter { page })
+    }
+
+    fn set_page(&self, report: &Report, finished: bool) {
+        *self.page.lock().expect("Live page mutex poisoned") = render(report, finished);
+    }
+}
+
+impl ReportWriter for LiveHtmlWriter {
+    fn write_report(&mut self, report: &Report) -> io::Result<()> {
+        self.set_page(report, true);
+        Ok(())
+    }
+
+    fn update(&mut self, report: &Report) -> io::Result<()> {
+        self.set_page(report, false);
+        Ok(())
+    }
+}
+
+fn status_color(status: Status) -> &'static str {
+    match status {
+        Status::Passed => "#2e7d32",
+        Status::Failed | Status::Ambiguous => "#c62828",
+        Status::Crashed => "#6a1b9a",
+        Status::Skipped | Status::Unimplemented | Status::Pending => "#00838f",
+    }
+}
+
+fn render(report: &Report, finished: bool) -> String {
+    let mut html = String::new();
+    let refresh = if finished {
+        ""
+    } else {
+        r#"<meta http-equiv="refresh" content="2">"#
+    };
+
+    let _ = write!(
+        html,
+        r#"<!DOCTYPE html><html><head><meta charset="utf-8">{}<title>Cucumber</title></head><body style="font-family: sans-serif">"#,
+        refresh
+    );
+    let _ = write!(
+        html,
+        "<h1>Cucumber v{} &mdash; {}</h1>",
+        escape(&report.cucumber_version),
+        if finished { "finished" } else { "running" }
+    );
+
+    if !report.metadata.is_empty() {
+        html.push_str("<dl>");
+        for (key, value) in &report.metadata {
+            let _ = write!(html, "<dt>{}</dt><dd>{}</dd>", escape(key), escape(value));
+        }
+        html.push_str("</dl>");
+    }
+
+    for feature in &report.features {
+        let _ = write!(
+            html,
+            "<h2>Feature: {} <small>{}</small></h2>",
+            escape(&feature.name),
+            escape(&feature.path)
+        );
+        if let Some(ref error) = feature.error {
+            let _ = write!(
+                html,
+                r#"<pre style="color: {}">{}</pre>"#,
+                status_color(Status::Failed),
+                escape(error)
+            );
+        }
+
+        html.push_str("<ul>");
+        for scenario in &feature.scenarios {
+            let _ = write!(
+                html,
+                r#"<li style="color: {}">{} &mdash; {:?} ({:.3}s)"#,
+                status_color(scenario.status),
+                escape(&scenario.name),
+                scenario.status,
+                scenario.duration().as_secs_f64()
+            );
+            if let Some(failure) = scenario.failed_step().and_then(|s| s.failure.as_ref()) {
+                let _ = write!(
+                    html,
+                    "<pre>{}\n{}</pre>",
+                    escape(&failure.location),
+                    escape(&failure.message)
+                );
+            }
+            html.push_str("</li>");
+        }
+        html.push_str("</ul>");
+    }
+
+    html.push_str("</body></html>");
+    html
+}
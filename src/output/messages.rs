@@ -0,0 +1,1476 @@
+//! A subset of the [cucumber messages](https://github.com/cucumber/messages)
+//! protocol, written either as NDJSON or as length-delimited protobuf.
+//!
+//! Each message is built once as a list of `(field number, JSON name, value)`
+//! triples, so both encodings always carry the same data.
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use gherkin;
+use regex::Regex;
+use serde_json::{Map, Value};
+
+use crate::OutputVisitor;
+use crate::{Captured, PatternKind, StepDefinitionInfo, TestResult, Timing};
+
+const PROTOCOL_VERSION: &str = "13.0.0";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessagesFormat {
+    /// One JSON envelope per line.
+    Ndjson,
+    /// Each protobuf-encoded envelope prefixed by its varint length.
+    Protobuf,
+}
+
+enum Field {
+    Str(String),
+    Int(i64),
+    Bool(bool),
+    Enum(i32, &'static str),
+    Msg(Message),
+    List(Vec<Message>),
+    Strs(Vec<String>),
+}
+
+#[derive(Default)]
+struct Message(Vec<(u32, &'static str, Field)>);
+
+impl Message {
+    fn new() -> Message {
+        Message::default()
+    }
+
+    fn field(mut self, number: u32, name: &'static str, value: Field) -> Message {
+        self.0.push((number, name, value));
+        self
+    }
+
+    fn str(self, number: u32, name: &'static str, value: &str) -> Message {
+        self.field(number, name, Field::Str(value.to_string()))
+    }
+
+    fn int(self, number: u32, name: &'static str, value: i64) -> Message {
+        self.field(number, name, Field::Int(value))
+    }
+
+    fn msg(self, number: u32, name: &'static str, value: Message) -> Message {
+        self.field(number, name, Field::Msg(value))
+    }
+
+    fn list(self, number: u32, name: &'static str, value: Vec<Message>) -> Message {
+        self.field(number, name, Field::List(value))
+    }
+
+    fn to_json(&self) -> Value {
+        let mut map = Map::new();
+        for (_, name, value) in &self.0 {
+            let value = match value {
+                Field::Str(s) => Value::from(s.as_str()),
+                Field::Int(i) => Value::from(*i),
+                Field::Bool(b) => Value::from(*b),
+                Field::Enum(_, name) => Value::from(*name),
+                Field::Msg(m) => m.to_json(),
+                Field::List(ms) => Value::Array(ms.iter().map(Message::to_json).collect()),
+                Field::Strs(ss) => Value::from(ss.clone()),
+            };
+            map.insert((*name).to_string(), value);
+        }
+        Value::Object(map)
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        for (number, _, value) in &self.0 {
+            match value {
+                Field::Str(s) => encode_bytes(buf, *number, s.as_bytes()),
+                Field::Int(i) => encode_varint_field(buf, *number, *i as u64),
+                Field::Bool(b) => encode_varint_field(buf, *number, *b as u64),
+                Field::Enum(i, _) => encode_varint_field(buf, *number, *i as u64),
+                Field::Msg(m) => encode_bytes(buf, *number, &m.encoded()),
+                Field::List(ms) => {
+                    for m in ms {
+                        encode_bytes(buf, *number, &m.encoded());
+                    }
+                }
+                Field::Strs(ss) => {
+                    for s in ss {
+                        encode_bytes(buf, *number, s.as_bytes());
+                    }
+                }
+            }
+        }
+    }
+
+    fn encoded(&self) -> Vec<u8> {
+        let mut buf = vec![];
+        self.encode(&mut buf);
+        buf
+    }
+}
+
+fn encode_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn encode_varint_field(buf: &mut Vec<u8>, number: u32, value: u64) {
+    encode_varint(buf, u64::from(number) << 3);
+    encode_varint(buf, value);
+}
+
+fn encode_bytes(buf: &mut Vec<u8>, number: u32, bytes: &[u8]) {
+    encode_varint(buf, (u64::from(number) << 3) | 2);
+    encode_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+fn timestamp(time: SystemTime) -> Message {
+    duration(time.duration_since(UNIX_EPOCH).unwrap_or_default())
+}
+
+fn duration(d: Duration) -> Message {
+    Message::new()
+        .field(1, "seconds", Field::Int(d.as_secs() as i64))
+        .field(2, "nanos", Field::Int(i64::from(d.subsec_nanos())))
+}
+
+fn status(result: &TestResult) -> Field {
+    match result {
+        TestResult::Pass => Field::Enum(1, "PASSED"),
+        TestResult::Skipped => Field::Enum(2, "SKIPPED"),
+        TestResult::Pending(_) => Field::Enum(3, "PENDING"),
+        TestResult::Unimplemented => Field::Enum(4, "UNDEFINED"),
+        TestResult::Ambiguous(_) => Field::Enum(5, "AMBIGUOUS"),
+        TestResult::Fail(..) | TestResult::Crashed(..) => Field::Enum(6, "FAILED"),
+    }
+}
+
+/// The columns of the cells of a table row, from 1, after the `|` before
+/// each of them.
+fn cell_columns(text: &str) -> Vec<usize> {
+    let mut columns = vec![];
+    let mut escaped = false;
+    let mut cell = None;
+    for (i, c) in text.chars().enumerate() {
+        match c {
+            '|' if !escaped => {
+                columns.extend(cell.take());
+                cell = Some(i + 2);
+            }
+            ' ' | '\t' if cell == Some(i + 1) => cell = Some(i + 2),
+            _ => {}
+        }
+        escaped = c == '\\' && !escaped;
+    }
+    columns
+}
+
+/// A step definition, with the regular expression matching its steps when
+/// they are not matched by its text.
+pub(crate) struct StepDefinition {
+    pub(crate) info: StepDefinitionInfo,
+    pub(crate) regex: Option<Regex>,
+}
+
+impl StepDefinition {
+    fn matches(&self, ty: gherkin::StepType, text: &str) -> bool {
+        self.info.ty == ty
+            && match &self.regex {
+                Some(regex) => regex.is_match(text),
+                None => self.info.pattern == text,
+            }
+    }
+}
+
+/// Streams cucumber messages envelopes to any number of sinks while the run
+/// progresses.
+pub struct MessagesOutput {
+    sinks: Vec<(MessagesFormat, Box<dyn Write + Send>)>,
+    strict: bool,
+    normalize: bool,
+    sources: BTreeMap<PathBuf, String>,
+    definitions: Vec<(String, StepDefinition)>,
+    next_id: usize,
+    uri: String,
+    /// The lines of the source of the feature being run.
+    lines: Vec<String>,
+    background: Vec<gherkin::Step>,
+    /// The ids of the nodes of the feature being run by their line, and the
+    /// names and ids of their tags.
+    ast_ids: HashMap<usize, String>,
+    tag_ids: HashMap<usize, Vec<(String, String)>>,
+    /// The outline and examples of each row of examples, by line.
+    outlines: HashMap<usize, (usize, usize)>,
+    feature_line: usize,
+    test_case_started_id: String,
+    test_step_ids: Vec<String>,
+    step_index: usize,
+    /// Whether the scenario being run has failed so far.
+    scenario_failed: bool,
+    success: bool,
+}
+
+impl MessagesOutput {
+    pub fn with_sinks(sinks: Vec<(MessagesFormat, Box<dyn Write + Send>)>) -> MessagesOutput {
+        MessagesOutput {
+            sinks,
+            strict: false,
+            normalize: false,
+            sources: BTreeMap::new(),
+            definitions: vec![],
+            next_id: 0,
+            uri: String::new(),
+            lines: vec![],
+            background: vec![],
+            ast_ids: HashMap::new(),
+            tag_ids: HashMap::new(),
+            outlines: HashMap::new(),
+            feature_line: 0,
+            test_case_started_id: String::new(),
+            test_step_ids: vec![],
+            step_index: 0,
+            scenario_failed: false,
+            success: true,
+        }
+    }
+
+    pub fn add_sink<Wr: Write + Send + 'static>(&mut self, format: MessagesFormat, writer: Wr) {
+        self.sinks.push((format, Box::new(writer)));
+    }
+
+    /// Undefined and pending steps fail the run, as with `--strict`.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Sources of features which are not read from their path.
+    pub(crate) fn set_sources(&mut self, sources: BTreeMap<PathBuf, String>) {
+        self.sources = sources;
+    }
+
+    /// The definitions steps are matched against, with their text normalized
+    /// first if `normalize` is set.
+    pub(crate) fn set_step_definitions(
+        &mut self,
+        definitions: Vec<StepDefinition>,
+        normalize: bool,
+    ) {
+        self.definitions = definitions
+            .into_iter()
+            .enumerate()
+            .map(|(i, definition)| (format!("step-definition-{}", i), definition))
+            .collect();
+        self.normalize = normalize;
+    }
+
+    fn next_id(&mut self) -> String {
+        let id = self.next_id.to_string();
+        self.next_id += 1;
+        id
+    }
+
+    /// The id of the node at `line`, given to it when first asked for.
+    fn ast_id(&mut self, line: usize) -> String {
+        if let Some(id) = self.ast_ids.get(&line) {
+            return id.clone();
+        }
+        let id = self.next_id();
+        self.ast_ids.insert(line, id.clone());
+        id
+    }
+
+    fn line(&self, line: usize) -> &str {
+        line.checked_sub(1)
+            .and_then(|i| self.lines.get(i))
+            .map_or("", String::as_str)
+    }
+
+    /// The location of what starts `line`, at its first column which is not
+    /// blank.
+    fn location(&self, line: usize) -> Message {
+        let text = self.line(line);
+        let column = text.chars().take_while(|c| c.is_whitespace()).count() + 1;
+        Message::new()
+            .int(1, "line", line as i64)
+            .int(2, "column", column as i64)
+    }
+
+    /// The first line from `line` on which starts with `keyword`: the parser
+    /// places some nodes at the end of what comes before them.
+    fn find_line(&self, line: usize, keywords: &[&str]) -> usize {
+        (line..=self.lines.len())
+            .find(|&l| {
+                let text = self.line(l).trim_start();
+                keywords.iter().any(|keyword| text.starts_with(keyword))
+            })
+            .unwrap_or(line)
+    }
+
+    /// The keyword starting `line`, such as `Scenario Outline`, or `default`
+    /// when it cannot be read.
+    fn keyword(&self, line: usize, default: &str) -> String {
+        let text = self.line(line).trim_start();
+        match text.find(':') {
+            Some(end) => text[..end].to_string(),
+            None => default.to_string(),
+        }
+    }
+
+    fn tags(&mut self, line: usize, tags: &Option<Vec<String>>) -> Vec<Message> {
+        let mut messages = vec![];
+        for tag in tags.iter().flatten() {
+            let name = format!("@{}", crate::cli::tag_name(tag));
+            // Tags are on the lines above what they tag, comments aside.
+            let found = (1..line)
+                .rev()
+                .map(|l| (l, self.line(l)))
+                .take_while(|(_, text)| {
+                    let text = text.trim_start();
+                    text.is_empty() || text.starts_with('@') || text.starts_with('#')
+                })
+                .find_map(|(l, text)| text.find(&name).map(|i| (l, text[..i].chars().count())));
+            let (tag_line, column) = found.unwrap_or((line, 0));
+            let id = self.next_id();
+            self.tag_ids
+                .entry(line)
+                .or_default()
+                .push((name.clone(), id.clone()));
+            let location =
+                Message::new()
+                    .int(1, "line", tag_line as i64)
+                    .int(2, "column", column as i64 + 1);
+            messages.push(
+                Message::new()
+                    .msg(1, "location", location)
+                    .str(2, "name", &name)
+                    .str(3, "id", &id),
+            );
+        }
+        messages
+    }
+
+    /// The rows of the table found from `line` on, with their cells.
+    fn table_rows(&mut self, line: usize, rows: &[&Vec<String>]) -> Vec<Message> {
+        let source = self.lines.join("\n");
+        let lines = crate::table::table_lines(&source, line);
+        rows.iter()
+            .enumerate()
+            .map(|(i, row)| {
+                let line = lines.get(i).copied().unwrap_or(line + i);
+                let columns = cell_columns(self.line(line));
+                let cells = row
+                    .iter()
+                    .enumerate()
+                    .map(|(j, value)| {
+                        let location = Message::new().int(1, "line", line as i64).int(
+                            2,
+                            "column",
+                            columns.get(j).copied().unwrap_or(1) as i64,
+                        );
+                        Message::new()
+                            .msg(1, "location", location)
+                            .str(2, "value", value)
+                    })
+                    .collect();
+                Message::new()
+                    .msg(1, "location", self.location(line))
+                    .list(2, "cells", cells)
+                    .str(3, "id", &self.ast_id(line))
+            })
+            .collect()
+    }
+
+    fn step(&mut self, step: &gherkin::Step) -> Message {
+        let line = step.position.0;
+        let mut message = Message::new()
+            .msg(1, "location", self.location(line))
+            .str(2, "keyword", &format!("{} ", step.raw_type))
+            .str(3, "text", &step.value);
+        if let Some(docstring) = &step.docstring {
+            let delimiter = if self.line(line + 1).trim_start().starts_with("```") {
+                "```"
+            } else {
+                "\"\"\""
+            };
+            let docstring = Message::new()
+                .msg(1, "location", self.location(line + 1))
+                .str(3, "content", docstring)
+                .str(4, "delimiter", delimiter);
+            message = message.msg(5, "docString", docstring);
+        }
+        if let Some(table) = &step.table {
+            let rows = std::iter::once(&table.header)
+                .chain(&table.rows)
+                .collect::<Vec<_>>();
+            let rows = self.table_rows(table.position.0, &rows);
+            let data_table = Message::new()
+                .msg(1, "location", self.location(table.position.0))
+                .list(2, "rows", rows);
+            message = message.msg(6, "dataTable", data_table);
+        }
+        message.str(7, "id", &self.ast_id(line))
+    }
+
+    fn background(&mut self, line: usize, steps: &[gherkin::Step]) -> Message {
+        let steps = steps.iter().map(|step| self.step(step)).collect();
+        Message::new()
+            .msg(1, "location", self.location(line))
+            .str(2, "keyword", &self.keyword(line, "Background"))
+            .str(3, "name", "")
+            .str(4, "description", "")
+            .list(5, "steps", steps)
+            .str(6, "id", &self.ast_id(line))
+    }
+
+    fn scenario(&mut self, scenario: &gherkin::Scenario) -> Message {
+        let line = scenario.position.0;
+        let tags = self.tags(line, &scenario.tags);
+        // Steps of the background of a rule were added to the scenario.
+        let steps = scenario
+            .steps
+            .iter()
+            .filter(|step| step.position.0 > line)
+            .map(|step| self.step(step))
+            .collect();
+        let examples = scenario
+            .examples
+            .iter()
+            .map(|examples| {
+                let examples_line = self.find_line(examples.position.0, &["Examples", "Scenarios"]);
+                let tags = self.tags(examples_line, &examples.tags);
+                let table = &examples.table;
+                let rows = std::iter::once(&table.header)
+                    .chain(&table.rows)
+                    .collect::<Vec<_>>();
+                let mut rows = self.table_rows(examples_line, &rows).into_iter();
+                let header = rows.next().unwrap_or_default();
+                let body = rows.collect::<Vec<_>>();
+                let source = self.lines.join("\n");
+                for row_line in crate::table::row_lines(&source, examples_line) {
+                    self.outlines.insert(row_line, (line, examples_line));
+                }
+                Message::new()
+                    .msg(1, "location", self.location(examples_line))
+                    .list(2, "tags", tags)
+                    .str(3, "keyword", &self.keyword(examples_line, "Examples"))
+                    .str(4, "name", "")
+                    .str(5, "description", "")
+                    .msg(6, "tableHeader", header)
+                    .list(7, "tableBody", body)
+                    .str(8, "id", &self.ast_id(examples_line))
+            })
+            .collect();
+        let keyword = if scenario.examples.is_some() {
+            "Scenario Outline"
+        } else {
+            "Scenario"
+        };
+        Message::new()
+            .msg(1, "location", self.location(line))
+            .list(2, "tags", tags)
+            .str(3, "keyword", &self.keyword(line, keyword))
+            .str(4, "name", &scenario.name)
+            .str(5, "description", "")
+            .list(6, "steps", steps)
+            .list(7, "examples", examples)
+            .str(8, "id", &self.ast_id(line))
+    }
+
+    fn rule(&mut self, rule: &gherkin::Rule) -> Message {
+        let line = rule.position.0;
+        let mut children = vec![];
+        // The background of a rule is only found in the steps it added to its
+        // scenarios, before their own.
+        if let Some(first) = rule.scenarios.first() {
+            let steps = first
+                .steps
+                .iter()
+                .filter(|step| step.position.0 < first.position.0)
+                .cloned()
+                .collect::<Vec<_>>();
+            if !steps.is_empty() {
+                let background_line = self.find_line(line, &["Background"]);
+                let background = self.background(background_line, &steps);
+                children.push(Message::new().msg(1, "background", background));
+            }
+        }
+        for scenario in &rule.scenarios {
+            let scenario = self.scenario(scenario);
+            children.push(Message::new().msg(2, "scenario", scenario));
+        }
+        Message::new()
+            .msg(1, "location", self.location(line))
+            .str(2, "keyword", &self.keyword(line, "Rule"))
+            .str(3, "name", &rule.name)
+            .str(4, "description", "")
+            .list(5, "children", children)
+            .str(6, "id", &self.ast_id(line))
+    }
+
+    fn gherkin_document(&mut self, feature: &gherkin::Feature) -> Message {
+        let line = feature.position.0;
+        let tags = self.tags(line, &feature.tags);
+        let mut children = vec![];
+        if let Some(background) = &feature.background {
+            let background_line = self.find_line(background.position.0, &["Background"]);
+            let background = self.background(background_line, &background.steps);
+            children.push(Message::new().msg(2, "background", background));
+        }
+        for scenario in &feature.scenarios {
+            let scenario = self.scenario(scenario);
+            children.push(Message::new().msg(3, "scenario", scenario));
+        }
+        for rule in &feature.rules {
+            let rule = self.rule(rule);
+            children.push(Message::new().msg(1, "rule", rule));
+        }
+        let feature = Message::new()
+            .msg(1, "location", self.location(line))
+            .list(2, "tags", tags)
+            .str(3, "language", "en")
+            .str(4, "keyword", &self.keyword(line, "Feature"))
+            .str(5, "name", &feature.name)
+            .str(
+                6,
+                "description",
+                feature.description.as_deref().unwrap_or_default(),
+            )
+            .list(7, "children", children);
+        Message::new()
+            .str(1, "uri", &self.uri)
+            .msg(2, "feature", feature)
+    }
+
+    /// The tags of a pickle: those of its feature, rule, scenario and
+    /// examples. Rules have no tags in the document to refer to.
+    fn pickle_tags(&self, rule: Option<&gherkin::Rule>, lines: &[usize]) -> Vec<Message> {
+        let tagged = |line: &usize| self.tag_ids.get(line).into_iter().flatten();
+        let feature = tagged(&self.feature_line).map(|(name, id)| (name.clone(), Some(id)));
+        let rule = rule
+            .and_then(|rule| rule.tags.as_ref())
+            .into_iter()
+            .flatten()
+            .map(|tag| (format!("@{}", crate::cli::tag_name(tag)), None));
+        let own = lines
+            .iter()
+            .flat_map(tagged)
+            .map(|(name, id)| (name.clone(), Some(id)));
+        feature
+            .chain(rule)
+            .chain(own)
+            .map(|(name, id)| {
+                let tag = Message::new().str(1, "name", &name);
+                match id {
+                    Some(id) => tag.str(2, "astNodeId", id),
+                    None => tag,
+                }
+            })
+            .collect()
+    }
+
+    /// The id of the pickle step of `step` and the step, run with the
+    /// example `row` if any.
+    fn pickle_step(&mut self, step: &gherkin::Step, row: Option<&str>) -> (String, Message) {
+        let mut message = Message::new().str(1, "text", &step.value);
+        if let Some(docstring) = &step.docstring {
+            let docstring = Message::new().str(2, "content", docstring);
+            message = message.msg(2, "argument", Message::new().msg(1, "docString", docstring));
+        } else if let Some(table) = &step.table {
+            let rows = std::iter::once(&table.header)
+                .chain(&table.rows)
+                .map(|row| {
+                    let cells = row
+                        .iter()
+                        .map(|value| Message::new().str(1, "value", value))
+                        .collect();
+                    Message::new().list(1, "cells", cells)
+                })
+                .collect();
+            let table = Message::new().list(1, "rows", rows);
+            message = message.msg(2, "argument", Message::new().msg(2, "dataTable", table));
+        }
+        let id = self.next_id();
+        let ast_node_ids = self
+            .ast_ids
+            .get(&step.position.0)
+            .map(String::as_str)
+            .into_iter()
+            .chain(row)
+            .map(str::to_string)
+            .collect();
+        let message = message
+            .str(3, "id", &id)
+            .field(4, "astNodeIds", Field::Strs(ast_node_ids));
+        (id, message)
+    }
+
+    /// The ids of the definitions `step` matches.
+    fn definition_ids(&self, step: &gherkin::Step) -> Vec<String> {
+        let text = if self.normalize {
+            Cow::Owned(crate::normalize(&step.value))
+        } else {
+            Cow::Borrowed(&step.value)
+        };
+        self.definitions
+            .iter()
+            .filter(|(_, definition)| definition.matches(step.ty, &text))
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+
+    fn emit(&mut self, number: u32, name: &'static str, message: Message) {
+        if self.sinks.is_empty() {
+            return;
+        }
+
+        let envelope = Message::new().msg(number, name, message);
+        for (format, writer) in self.sinks.iter_mut() {
+            let result = match format {
+                MessagesFormat::Ndjson => writeln!(writer, "{}", envelope.to_json()),
+                MessagesFormat::Protobuf => {
+                    let mut buf = vec![];
+                    let body = envelope.encoded();
+                    encode_varint(&mut buf, body.len() as u64);
+                    buf.extend_from_slice(&body);
+                    writer.write_all(&buf)
+                }
+            };
+            result.expect("failed to write message");
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        for (_, writer) in self.sinks.iter_mut() {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl OutputVisitor for MessagesOutput {
+    fn new() -> Self {
+        MessagesOutput::with_sinks(vec![(MessagesFormat::Ndjson, Box::new(io::stdout()))])
+    }
+
+    fn visit_start(&mut self, _metadata: &BTreeMap<String, String>) {
+        let implementation = Message::new().str(1, "name", "cucumber-rust").str(
+            2,
+            "version",
+            env!("CARGO_PKG_VERSION"),
+        );
+        let meta = Message::new()
+            .str(1, "protocolVersion", PROTOCOL_VERSION)
+            .msg(2, "implementation", implementation);
+        self.emit(17, "meta", meta);
+
+        let definitions = self
+            .definitions
+            .iter()
+            .map(|(id, definition)| {
+                let pattern_type = match definition.info.kind {
+                    PatternKind::Regex => Field::Enum(1, "REGULAR_EXPRESSION"),
+                    PatternKind::Text | PatternKind::Expression => {
+                        Field::Enum(0, "CUCUMBER_EXPRESSION")
+                    }
+                };
+                let pattern = Message::new()
+                    .str(1, "source", &definition.info.pattern)
+                    .field(2, "type", pattern_type);
+                let mut message = Message::new().str(1, "id", id).msg(2, "pattern", pattern);
+                if let Some(file) = definition.info.file {
+                    message = message.msg(3, "sourceReference", Message::new().str(1, "uri", file));
+                }
+                message
+            })
+            .collect::<Vec<_>>();
+        for definition in definitions {
+            self.emit(4, "stepDefinition", definition);
+        }
+
+        let started = Message::new().msg(1, "timestamp", timestamp(SystemTime::now()));
+        self.emit(9, "testRunStarted", started);
+    }
+
+    fn visit_services(&mut self, services: &[crate::report::Service]) {
+        if services.iter().any(|service| service.error.is_some()) {
+            self.success = false;
+        }
+    }
+
+    fn visit_feature(&mut self, feature: &gherkin::Feature, path: &Path) {
+        self.uri = path.display().to_string();
+        let source = match self.sources.get(path) {
+            Some(source) => Some(source.clone()),
+            None => fs::read_to_string(path).ok(),
+        };
+        self.lines = source
+            .iter()
+            .flat_map(|source| source.lines())
+            .map(str::to_string)
+            .collect();
+        self.background = feature
+            .background
+            .as_ref()
+            .map(|bg| bg.steps.clone())
+            .unwrap_or_default();
+        self.ast_ids.clear();
+        self.tag_ids.clear();
+        self.outlines.clear();
+
+        if let Some(source) = source {
+            let message = Message::new()
+                .str(1, "uri", &self.uri)
+                .str(2, "data", &source)
+                .str(3, "mediaType", "text/x.cucumber.gherkin+plain");
+            self.emit(1, "source", message);
+        }
+        let document = self.gherkin_document(feature);
+        self.emit(2, "gherkinDocument", document);
+
+        self.feature_line = feature.position.0;
+    }
+
+    fn visit_feature_end(&mut self, _feature: &gherkin::Feature) {}
+
+    fn visit_feature_error(&mut self, path: &Path, error: &gherkin::Error) {
+        let source = Message::new().str(1, "uri", &path.display().to_string());
+        let parse_error =
+            Message::new()
+                .msg(1, "source", source)
+                .str(2, "message", &error.to_string());
+        self.emit(16, "parseError", parse_error);
+        self.success = false;
+    }
+
+    fn visit_feature_hook_failed(
+        &mut self,
+        _feature: &gherkin::Feature,
+        _hook: crate::report::Hook,
+        _failure: &crate::report::Failure,
+    ) {
+        self.success = false;
+    }
+
+    fn visit_dependency_cycle(
+        &mut self,
+        _feature: &gherkin::Feature,
+        _rule: Option<&gherkin::Rule>,
+        scenarios: &[String],
+    ) {
+        let message = format!(
+            "The @depends-on tags form a cycle, could not order: {}",
+            scenarios.join(", ")
+        );
+        let source = Message::new().str(1, "uri", &self.uri);
+        let parse_error = Message::new()
+            .msg(1, "source", source)
+            .str(2, "message", &message);
+        self.emit(16, "parseError", parse_error);
+        self.success = false;
+    }
+
+    fn visit_rule(&mut self, _rule: &gherkin::Rule) {}
+
+    fn visit_rule_end(&mut self, _rule: &gherkin::Rule) {}
+
+    fn visit_scenario(&mut self, rule: Option<&gherkin::Rule>, scenario: &gherkin::Scenario) {
+        self.step_index = 0;
+        self.scenario_failed = false;
+
+        // Examples run as scenarios of their own, at the line of their row.
+        let line = scenario.position.0;
+        let outline = self.outlines.get(&line).copied();
+        let row = outline.and_then(|_| self.ast_ids.get(&line).cloned());
+        let scenario_id = match outline {
+            Some((outline, _)) => self.ast_ids.get(&outline).cloned(),
+            None => self.ast_ids.get(&line).cloned(),
+        };
+
+        let background = self.background.clone();
+        let steps = background
+            .iter()
+            .chain(scenario.steps.iter())
+            .map(|step| (self.pickle_step(step, row.as_deref()), step))
+            .collect::<Vec<_>>();
+        let tags = match outline {
+            Some((outline, examples)) => self.pickle_tags(rule, &[outline, examples]),
+            None => self.pickle_tags(rule, &[line]),
+        };
+        let id = self.next_id();
+        let ast_node_ids = scenario_id.into_iter().chain(row).collect();
+
+        let mut test_steps = vec![];
+        self.test_step_ids.clear();
+        for ((pickle_step_id, _), step) in &steps {
+            let test_step_id = self.next_id();
+            test_steps.push(
+                Message::new()
+                    .str(1, "id", &test_step_id)
+                    .str(2, "pickleStepId", pickle_step_id)
+                    .field(
+                        3,
+                        "stepDefinitionIds",
+                        Field::Strs(self.definition_ids(step)),
+                    ),
+            );
+            self.test_step_ids.push(test_step_id);
+        }
+
+        let pickle = Message::new()
+            .str(1, "id", &id)
+            .str(2, "uri", &self.uri)
+            .str(3, "name", &scenario.name)
+            .str(4, "language", "en")
+            .list(
+                5,
+                "steps",
+                steps.into_iter().map(|((_, step), _)| step).collect(),
+            )
+            .list(6, "tags", tags)
+            .field(7, "astNodeIds", Field::Strs(ast_node_ids));
+        self.emit(3, "pickle", pickle);
+
+        let test_case_id = self.next_id();
+        let test_case = Message::new()
+            .str(1, "id", &test_case_id)
+            .str(2, "pickleId", &id)
+            .list(3, "testSteps", test_steps);
+        self.emit(7, "testCase", test_case);
+
+        self.test_case_started_id = self.next_id();
+        let started = Message::new()
+            .msg(1, "timestamp", timestamp(SystemTime::now()))
+            .int(3, "attempt", 0)
+            .str(4, "testCaseId", &test_case_id)
+            .str(5, "id", &self.test_case_started_id);
+        self.emit(10, "testCaseStarted", started);
+    }
+
+    fn visit_scenario_end(&mut self, _rule: Option<&gherkin::Rule>, _scenario: &gherkin::Scenario) {
+        if self.scenario_failed {
+            self.success = false;
+        }
+
+        let finished = Message::new()
+            .msg(1, "timestamp", timestamp(SystemTime::now()))
+            .str(3, "testCaseStartedId", &self.test_case_started_id);
+        self.emit(14, "testCaseFinished", finished);
+    }
+
+    fn visit_scenario_skipped(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+    ) {
+    }
+
+    fn visit_scenario_not_run(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+    ) {
+        self.success = false;
+    }
+
+    fn visit_scenario_quarantined(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+    ) {
+        self.scenario_failed = false;
+    }
+
+    fn visit_scenario_xfail(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+        passed: bool,
+    ) {
+        // Expected failures which pass fail the run, as with `--wip`.
+        self.scenario_failed = passed;
+    }
+
+    fn visit_scenario_teardown_failed(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+        _failure: &crate::report::Failure,
+    ) {
+        self.scenario_failed = true;
+    }
+
+    fn visit_scenario_hook_failed(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+        _hook: crate::report::Hook,
+        _failure: &crate::report::Failure,
+    ) {
+        self.scenario_failed = true;
+    }
+
+    fn visit_step(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+        _step: &gherkin::Step,
+    ) {
+    }
+
+    fn visit_step_resolved<'a, W: crate::World>(
+        &mut self,
+        _step: &gherkin::Step,
+        _test: &crate::TestCaseType<'a, W>,
+    ) {
+    }
+
+    fn visit_step_result(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+        _step: &gherkin::Step,
+        result: &TestResult,
+        timing: &Timing,
+        _captured: &Captured,
+    ) {
+        let mut step_result = Message::new().field(1, "status", status(result));
+        let message = match result {
+            TestResult::Fail(panic_info, _, _) => {
+                Some(format!("{}\n{}", panic_info.payload, panic_info.location))
+            }
+            TestResult::Crashed(exit_status, _, _) => {
+                Some(format!("Scenario process crashed ({})", exit_status))
+            }
+            TestResult::Ambiguous(matches) => Some(format!(
+                "Step matches {} definitions:\n{}",
+                matches.len(),
+                matches.join("\n")
+            )),
+            _ => None,
+        };
+        if let Some(message) = message {
+            step_result = step_result.str(2, "message", &message);
+            self.scenario_failed = true;
+        }
+        if let TestResult::Unimplemented | TestResult::Pending(_) = result {
+            self.scenario_failed |= self.strict;
+        }
+        step_result = step_result.msg(3, "duration", duration(timing.duration));
+
+        let test_step_id = self
+            .test_step_ids
+            .get(self.step_index)
+            .cloned()
+            .unwrap_or_default();
+        let started = Message::new()
+            .msg(1, "timestamp", timestamp(timing.started))
+            .str(2, "testStepId", &test_step_id)
+            .str(3, "testCaseStartedId", &self.test_case_started_id);
+        self.emit(11, "testStepStarted", started);
+
+        let finished = Message::new()
+            .msg(1, "testStepResult", step_result)
+            .msg(2, "timestamp", timestamp(timing.started + timing.duration))
+            .str(3, "testStepId", &test_step_id)
+            .str(4, "testCaseStartedId", &self.test_case_started_id);
+        self.emit(13, "testStepFinished", finished);
+        self.step_index += 1;
+    }
+
+    fn visit_finish(&mut self) {
+        let finished = Message::new()
+            .field(1, "success", Field::Bool(self.success))
+            .msg(2, "timestamp", timestamp(SystemTime::now()));
+        self.emit(15, "testRunFinished", finished);
+        self.flush().expect("failed to flush messages");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::{Arc, Mutex};
+
+    const SOURCE: &str = "\
+@smoke
+Feature: Eating
+
+  Background:
+    Given a cucumber
+
+  @outline
+  Scenario Outline: eating
+    When I eat <count>
+      | count | left |
+      | <count> | 0 |
+
+    @few
+    Examples:
+      | count |
+      # Only one
+      | 1     |
+
+  Rule: Leftovers
+    Background:
+      Given a plate
+
+    Scenario: nothing left
+      Then the plate says
+        \"\"\"
+        empty
+        \"\"\"
+";
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[derive(Clone, Copy)]
+    enum Kind {
+        Str,
+        Int,
+        Bool,
+        Enum(&'static [&'static str]),
+        Msg(&'static str),
+        List(&'static str),
+        Strs,
+    }
+
+    use Kind::*;
+
+    const STATUS: &[&str] = &[
+        "UNKNOWN",
+        "PASSED",
+        "SKIPPED",
+        "PENDING",
+        "UNDEFINED",
+        "AMBIGUOUS",
+        "FAILED",
+    ];
+    const PATTERN_TYPE: &[&str] = &["CUCUMBER_EXPRESSION", "REGULAR_EXPRESSION"];
+
+    type Fields = &'static [(u32, &'static str, Kind)];
+
+    /// The messages written, as `messages.proto` of version 13 numbers their
+    /// fields.
+    #[rustfmt::skip]
+    const SCHEMA: &[(&str, Fields)] = &[
+        ("Envelope", &[
+            (1, "source", Msg("Source")),
+            (2, "gherkinDocument", Msg("GherkinDocument")),
+            (3, "pickle", Msg("Pickle")),
+            (4, "stepDefinition", Msg("StepDefinition")),
+            (7, "testCase", Msg("TestCase")),
+            (9, "testRunStarted", Msg("TestRunStarted")),
+            (10, "testCaseStarted", Msg("TestCaseStarted")),
+            (11, "testStepStarted", Msg("TestStepStarted")),
+            (13, "testStepFinished", Msg("TestStepFinished")),
+            (14, "testCaseFinished", Msg("TestCaseFinished")),
+            (15, "testRunFinished", Msg("TestRunFinished")),
+            (16, "parseError", Msg("ParseError")),
+            (17, "meta", Msg("Meta")),
+        ]),
+        ("Meta", &[(1, "protocolVersion", Str), (2, "implementation", Msg("Product"))]),
+        ("Product", &[(1, "name", Str), (2, "version", Str)]),
+        ("Timestamp", &[(1, "seconds", Int), (2, "nanos", Int)]),
+        ("Duration", &[(1, "seconds", Int), (2, "nanos", Int)]),
+        ("Location", &[(1, "line", Int), (2, "column", Int)]),
+        ("Source", &[(1, "uri", Str), (2, "data", Str), (3, "mediaType", Str)]),
+        ("SourceReference", &[(1, "uri", Str)]),
+        ("ParseError", &[(1, "source", Msg("SourceReference")), (2, "message", Str)]),
+        ("StepDefinition", &[
+            (1, "id", Str),
+            (2, "pattern", Msg("StepDefinitionPattern")),
+            (3, "sourceReference", Msg("SourceReference")),
+        ]),
+        ("StepDefinitionPattern", &[(1, "source", Str), (2, "type", Enum(PATTERN_TYPE))]),
+        ("GherkinDocument", &[(1, "uri", Str), (2, "feature", Msg("Feature"))]),
+        ("Feature", &[
+            (1, "location", Msg("Location")),
+            (2, "tags", List("Tag")),
+            (3, "language", Str),
+            (4, "keyword", Str),
+            (5, "name", Str),
+            (6, "description", Str),
+            (7, "children", List("FeatureChild")),
+        ]),
+        ("FeatureChild", &[
+            (1, "rule", Msg("Rule")),
+            (2, "background", Msg("Background")),
+            (3, "scenario", Msg("Scenario")),
+        ]),
+        ("Rule", &[
+            (1, "location", Msg("Location")),
+            (2, "keyword", Str),
+            (3, "name", Str),
+            (4, "description", Str),
+            (5, "children", List("RuleChild")),
+            (6, "id", Str),
+        ]),
+        ("RuleChild", &[(1, "background", Msg("Background")), (2, "scenario", Msg("Scenario"))]),
+        ("Background", &[
+            (1, "location", Msg("Location")),
+            (2, "keyword", Str),
+            (3, "name", Str),
+            (4, "description", Str),
+            (5, "steps", List("Step")),
+            (6, "id", Str),
+        ]),
+        ("Scenario", &[
+            (1, "location", Msg("Location")),
+            (2, "tags", List("Tag")),
+            (3, "keyword", Str),
+            (4, "name", Str),
+            (5, "description", Str),
+            (6, "steps", List("Step")),
+            (7, "examples", List("Examples")),
+            (8, "id", Str),
+        ]),
+        ("Examples", &[
+            (1, "location", Msg("Location")),
+            (2, "tags", List("Tag")),
+            (3, "keyword", Str),
+            (4, "name", Str),
+            (5, "description", Str),
+            (6, "tableHeader", Msg("TableRow")),
+            (7, "tableBody", List("TableRow")),
+            (8, "id", Str),
+        ]),
+        ("Tag", &[(1, "location", Msg("Location")), (2, "name", Str), (3, "id", Str)]),
+        ("Step", &[
+            (1, "location", Msg("Location")),
+            (2, "keyword", Str),
+            (3, "text", Str),
+            (5, "docString", Msg("DocString")),
+            (6, "dataTable", Msg("DataTable")),
+            (7, "id", Str),
+        ]),
+        ("DocString", &[
+            (1, "location", Msg("Location")),
+            (2, "mediaType", Str),
+            (3, "content", Str),
+            (4, "delimiter", Str),
+        ]),
+        ("DataTable", &[(1, "location", Msg("Location")), (2, "rows", List("TableRow"))]),
+        ("TableRow", &[(1, "location", Msg("Location")), (2, "cells", List("TableCell")), (3, "id", Str)]),
+        ("TableCell", &[(1, "location", Msg("Location")), (2, "value", Str)]),
+        ("Pickle", &[
+            (1, "id", Str),
+            (2, "uri", Str),
+            (3, "name", Str),
+            (4, "language", Str),
+            (5, "steps", List("PickleStep")),
+            (6, "tags", List("PickleTag")),
+            (7, "astNodeIds", Strs),
+        ]),
+        ("PickleStep", &[
+            (1, "text", Str),
+            (2, "argument", Msg("PickleStepArgument")),
+            (3, "id", Str),
+            (4, "astNodeIds", Strs),
+        ]),
+        ("PickleStepArgument", &[
+            (1, "docString", Msg("PickleDocString")),
+            (2, "dataTable", Msg("PickleTable")),
+        ]),
+        ("PickleDocString", &[(1, "mediaType", Str), (2, "content", Str)]),
+        ("PickleTable", &[(1, "rows", List("PickleTableRow"))]),
+        ("PickleTableRow", &[(1, "cells", List("PickleTableCell"))]),
+        ("PickleTableCell", &[(1, "value", Str)]),
+        ("PickleTag", &[(1, "name", Str), (2, "astNodeId", Str)]),
+        ("TestCase", &[(1, "id", Str), (2, "pickleId", Str), (3, "testSteps", List("TestStep"))]),
+        ("TestStep", &[(1, "id", Str), (2, "pickleStepId", Str), (3, "stepDefinitionIds", Strs)]),
+        ("TestRunStarted", &[(1, "timestamp", Msg("Timestamp"))]),
+        ("TestCaseStarted", &[
+            (1, "timestamp", Msg("Timestamp")),
+            (3, "attempt", Int),
+            (4, "testCaseId", Str),
+            (5, "id", Str),
+        ]),
+        ("TestStepStarted", &[
+            (1, "timestamp", Msg("Timestamp")),
+            (2, "testStepId", Str),
+            (3, "testCaseStartedId", Str),
+        ]),
+        ("TestStepFinished", &[
+            (1, "testStepResult", Msg("TestStepResult")),
+            (2, "timestamp", Msg("Timestamp")),
+            (3, "testStepId", Str),
+            (4, "testCaseStartedId", Str),
+        ]),
+        ("TestStepResult", &[
+            (1, "status", Enum(STATUS)),
+            (2, "message", Str),
+            (3, "duration", Msg("Duration")),
+        ]),
+        ("TestCaseFinished", &[(1, "timestamp", Msg("Timestamp")), (3, "testCaseStartedId", Str)]),
+        ("TestRunFinished", &[
+            (1, "success", Bool),
+            (2, "timestamp", Msg("Timestamp")),
+            (3, "message", Str),
+        ]),
+    ];
+
+    fn read_varint(bytes: &[u8], at: &mut usize) -> u64 {
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            let byte = bytes[*at];
+            *at += 1;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte < 0x80 {
+                return value;
+            }
+            shift += 7;
+        }
+    }
+
+    /// Decodes a message of type `name` as the JSON it is written as.
+    fn decode(name: &str, bytes: &[u8]) -> Value {
+        let fields = SCHEMA
+            .iter()
+            .find(|(message, _)| *message == name)
+            .unwrap_or_else(|| panic!("no message {} in the schema", name))
+            .1;
+        let mut json = Map::new();
+        let mut at = 0;
+        while at < bytes.len() {
+            let key = read_varint(bytes, &mut at);
+            let number = (key >> 3) as u32;
+            let &(_, field, kind) = fields
+                .iter()
+                .find(|(n, _, _)| *n == number)
+                .unwrap_or_else(|| panic!("{} has no field {}", name, number));
+            let value = if key & 7 == 2 {
+                let len = read_varint(bytes, &mut at) as usize;
+                let value = &bytes[at..at + len];
+                at += len;
+                match kind {
+                    Str | Strs => Value::from(String::from_utf8(value.to_vec()).unwrap()),
+                    Msg(message) | List(message) => decode(message, value),
+                    _ => panic!("{}.{} is not length-delimited", name, field),
+                }
+            } else {
+                let value = read_varint(bytes, &mut at);
+                match kind {
+                    Int => Value::from(value as i64),
+                    Bool => Value::from(value != 0),
+                    Enum(names) => Value::from(names[value as usize]),
+                    _ => panic!("{}.{} is not a varint", name, field),
+                }
+            };
+            match kind {
+                List(_) | Strs => json
+                    .entry(field)
+                    .or_insert_with(|| Value::Array(vec![]))
+                    .as_array_mut()
+                    .unwrap()
+                    .push(value),
+                _ => {
+                    json.insert(field.to_string(), value);
+                }
+            }
+        }
+        Value::Object(json)
+    }
+
+    /// Drops the empty lists protobuf has no way to tell from absent ones.
+    fn without_empty_lists(value: Value) -> Value {
+        match value {
+            Value::Object(fields) => Value::Object(
+                fields
+                    .into_iter()
+                    .filter(|(_, value)| !matches!(value, Value::Array(list) if list.is_empty()))
+                    .map(|(key, value)| (key, without_empty_lists(value)))
+                    .collect(),
+            ),
+            Value::Array(values) => {
+                Value::Array(values.into_iter().map(without_empty_lists).collect())
+            }
+            value => value,
+        }
+    }
+
+    #[test]
+    fn protobuf_decodes_to_the_ndjson() {
+        let ndjson = Buffer::default();
+        let protobuf = Buffer::default();
+        let mut output = MessagesOutput::with_sinks(vec![]);
+        output.add_sink(MessagesFormat::Ndjson, ndjson.clone());
+        output.add_sink(MessagesFormat::Protobuf, protobuf.clone());
+        let path = Path::new("features/eating.feature");
+        output.set_sources(
+            vec![(path.to_path_buf(), SOURCE.to_string())]
+                .into_iter()
+                .collect(),
+        );
+        output.set_strict(true);
+
+        let feature = crate::rule_background::parse_feature(SOURCE).unwrap();
+        let outline = &feature.scenarios[0];
+        let example = gherkin::Scenario {
+            examples: None,
+            position: (17, 7),
+            ..outline.clone()
+        };
+        let rule = &feature.rules[0];
+        let timing = Timing {
+            started: SystemTime::now(),
+            duration: Duration::from_millis(3),
+        };
+
+        output.visit_start(&BTreeMap::new());
+        output.visit_feature(&feature, path);
+        for &(rule, scenario, ref result) in &[
+            (None, &example, TestResult::Pass),
+            (Some(rule), &rule.scenarios[0], TestResult::Unimplemented),
+        ] {
+            output.visit_scenario(rule, scenario);
+            let steps = feature.background.iter().flat_map(|bg| &bg.steps);
+            for step in steps.chain(&scenario.steps) {
+                output.visit_step_result(
+                    rule,
+                    scenario,
+                    step,
+                    result,
+                    &timing,
+                    &Captured::default(),
+                );
+            }
+            output.visit_scenario_end(rule, scenario);
+        }
+        output.visit_finish();
+
+        let ndjson = String::from_utf8(ndjson.0.lock().unwrap().clone()).unwrap();
+        let protobuf = protobuf.0.lock().unwrap().clone();
+        let mut at = 0;
+        let mut envelopes = vec![];
+        while at < protobuf.len() {
+            let len = read_varint(&protobuf, &mut at) as usize;
+            envelopes.push(decode("Envelope", &protobuf[at..at + len]));
+            at += len;
+        }
+        let lines = ndjson
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            envelopes,
+            lines
+                .iter()
+                .cloned()
+                .map(without_empty_lists)
+                .collect::<Vec<_>>()
+        );
+
+        let kinds = lines
+            .iter()
+            .map(|envelope| {
+                envelope
+                    .as_object()
+                    .unwrap()
+                    .keys()
+                    .next()
+                    .unwrap()
+                    .as_str()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            &kinds[..8],
+            &[
+                "meta",
+                "testRunStarted",
+                "source",
+                "gherkinDocument",
+                "pickle",
+                "testCase",
+                "testCaseStarted",
+                "testStepStarted"
+            ]
+        );
+        // Undefined steps fail a strict run.
+        assert_eq!(lines.last().unwrap()["testRunFinished"]["success"], false);
+    }
+
+    #[test]
+    fn test_steps_refer_to_the_pickle_and_the_document() {
+        let ndjson = Buffer::default();
+        let mut output = MessagesOutput::with_sinks(vec![]);
+        output.add_sink(MessagesFormat::Ndjson, ndjson.clone());
+        let path = Path::new("features/eating.feature");
+        output.set_sources(
+            vec![(path.to_path_buf(), SOURCE.to_string())]
+                .into_iter()
+                .collect(),
+        );
+
+        let feature = crate::rule_background::parse_feature(SOURCE).unwrap();
+        let example = gherkin::Scenario {
+            examples: None,
+            position: (17, 7),
+            ..feature.scenarios[0].clone()
+        };
+        output.visit_feature(&feature, path);
+        output.visit_scenario(None, &example);
+        output.visit_finish();
+
+        let ndjson = String::from_utf8(ndjson.0.lock().unwrap().clone()).unwrap();
+        let lines = ndjson
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        let find = |kind: &str| {
+            lines
+                .iter()
+                .find_map(|envelope| envelope.get(kind))
+                .unwrap()
+                .clone()
+        };
+        let document = find("gherkinDocument");
+        let pickle = find("pickle");
+        let test_case = find("testCase");
+        let started = find("testCaseStarted");
+
+        let outline = &document["feature"]["children"][1]["scenario"];
+        let row = &outline["examples"][0]["tableBody"][0];
+        assert_eq!(row["location"]["line"], 17);
+        assert_eq!(
+            pickle["astNodeIds"],
+            serde_json::json!([outline["id"], row["id"]])
+        );
+        assert_eq!(test_case["pickleId"], pickle["id"]);
+        assert_eq!(started["testCaseId"], test_case["id"]);
+        let pickle_steps = pickle["steps"].as_array().unwrap();
+        let test_steps = test_case["testSteps"].as_array().unwrap();
+        assert_eq!(pickle_steps.len(), 2);
+        for (pickle_step, test_step) in pickle_steps.iter().zip(test_steps) {
+            assert_eq!(test_step["pickleStepId"], pickle_step["id"]);
+            assert_ne!(test_step["id"], pickle_step["id"]);
+        }
+        let tags = pickle["tags"].as_array().unwrap();
+        let names = tags
+            .iter()
+            .map(|tag| tag["name"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["@smoke", "@outline", "@few"]);
+    }
+}
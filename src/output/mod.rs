@@ -1,22 +1,90 @@
+pub mod csv;
 pub mod debug;
 pub mod default;
+pub mod json;
+pub mod junit;
+pub mod live;
+pub mod messages;
+pub mod rerun;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod structured;
+pub mod stubs;
+#[cfg(feature = "webhook")]
+pub mod webhook;
 
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 use gherkin;
 
-use crate::TestResult;
+use crate::output::csv::CsvWriter;
+use crate::output::json::JsonWriter;
+use crate::output::junit::JUnitWriter;
+use crate::output::messages::{MessagesFormat, MessagesOutput};
+use crate::output::rerun::RerunWriter;
+#[cfg(feature = "sqlite")]
+use crate::output::sqlite::SqliteWriter;
+use crate::output::structured::{ReportOutput, ReportWriter};
+use crate::output::stubs::StubsWriter;
+use crate::{Captured, TestResult, Timing};
 
 pub trait OutputVisitor {
     fn new() -> Self
     where
         Self: Sized;
-    fn visit_start(&mut self);
+    fn visit_start(&mut self, metadata: &BTreeMap<String, String>);
+    /// Called before `visit_finish` with the external services of the run,
+    /// or right after `visit_start` when one of them failed to start, in
+    /// which case no feature runs.
+    fn visit_services(&mut self, _services: &[crate::report::Service]) {}
     fn visit_feature(&mut self, feature: &gherkin::Feature, path: &Path);
     fn visit_feature_end(&mut self, feature: &gherkin::Feature);
     fn visit_feature_error(&mut self, path: &Path, error: &gherkin::Error);
+    fn visit_feature_hook_failed(
+        &mut self,
+        _feature: &gherkin::Feature,
+        _hook: crate::report::Hook,
+        _failure: &crate::report::Failure,
+    ) {
+    }
+    /// Called instead of running the scenarios of `feature`, or of `rule`,
+    /// when their `@depends-on` tags form a cycle, with the names of those
+    /// which could not be ordered. Each scenario is then visited as not run.
+    fn visit_dependency_cycle(
+        &mut self,
+        _feature: &gherkin::Feature,
+        _rule: Option<&gherkin::Rule>,
+        _scenarios: &[String],
+    ) {
+    }
     fn visit_rule(&mut self, rule: &gherkin::Rule);
     fn visit_rule_end(&mut self, rule: &gherkin::Rule);
+    fn visit_scenario_outline(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _outline: &gherkin::Scenario,
+    ) {
+    }
+    fn visit_scenario_outline_end(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _outline: &gherkin::Scenario,
+    ) {
+    }
+    /// Called before `visit_scenario` for a scenario expanded from an
+    /// outline, with the header and values of its example row.
+    fn visit_scenario_example(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+        _values: &[(String, String)],
+    ) {
+    }
     fn visit_scenario(&mut self, rule: Option<&gherkin::Rule>, scenario: &gherkin::Scenario);
     fn visit_scenario_end(&mut self, rule: Option<&gherkin::Rule>, scenario: &gherkin::Scenario);
     fn visit_scenario_skipped(
@@ -24,6 +92,62 @@ pub trait OutputVisitor {
         rule: Option<&gherkin::Rule>,
         scenario: &gherkin::Scenario,
     );
+    /// Called instead of running a scenario, or a whole outline, which does
+    /// not match the `--name` filter.
+    fn visit_scenario_filtered_out(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+    ) {
+    }
+    fn visit_scenario_not_run(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+    ) {
+    }
+    fn visit_scenario_ignored(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+        _reason: &str,
+    ) {
+    }
+    fn visit_scenario_quarantined(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+    ) {
+    }
+    fn visit_scenario_resources(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+        _usage: &crate::report::ResourceUsage,
+    ) {
+    }
+    fn visit_scenario_xfail(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+        _passed: bool,
+    ) {
+    }
+    fn visit_scenario_teardown_failed(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+        _failure: &crate::report::Failure,
+    ) {
+    }
+    fn visit_scenario_hook_failed(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+        _hook: crate::report::Hook,
+        _failure: &crate::report::Failure,
+    ) {
+    }
     fn visit_step(
         &mut self,
         rule: Option<&gherkin::Rule>,
@@ -41,6 +165,408 @@ pub trait OutputVisitor {
         scenario: &gherkin::Scenario,
         step: &gherkin::Step,
         result: &TestResult,
+        timing: &Timing,
+        captured: &Captured,
     );
     fn visit_finish(&mut self);
 }
+
+/// Feeds every event to both outputs, the first one first.
+impl<A: OutputVisitor, B: OutputVisitor> OutputVisitor for (A, B) {
+    fn new() -> Self {
+        (A::new(), B::new())
+    }
+
+    fn visit_start(&mut self, metadata: &BTreeMap<String, String>) {
+        self.0.visit_start(metadata);
+        self.1.visit_start(metadata);
+    }
+
+    fn visit_services(&mut self, services: &[crate::report::Service]) {
+        self.0.visit_services(services);
+        self.1.visit_services(services);
+    }
+
+    fn visit_feature(&mut self, feature: &gherkin::Feature, path: &Path) {
+        self.0.visit_feature(feature, path);
+        self.1.visit_feature(feature, path);
+    }
+
+    fn visit_feature_end(&mut self, feature: &gherkin::Feature) {
+        self.0.visit_feature_end(feature);
+        self.1.visit_feature_end(feature);
+    }
+
+    fn visit_feature_error(&mut self, path: &Path, error: &gherkin::Error) {
+        self.0.visit_feature_error(path, error);
+        self.1.visit_feature_error(path, error);
+    }
+
+    fn visit_feature_hook_failed(
+        &mut self,
+        feature: &gherkin::Feature,
+        hook: crate::report::Hook,
+        failure: &crate::report::Failure,
+    ) {
+        self.0.visit_feature_hook_failed(feature, hook, failure);
+        self.1.visit_feature_hook_failed(feature, hook, failure);
+    }
+
+    fn visit_dependency_cycle(
+        &mut self,
+        feature: &gherkin::Feature,
+        rule: Option<&gherkin::Rule>,
+        scenarios: &[String],
+    ) {
+        self.0.visit_dependency_cycle(feature, rule, scenarios);
+        self.1.visit_dependency_cycle(feature, rule, scenarios);
+    }
+
+    fn visit_rule(&mut self, rule: &gherkin::Rule) {
+        self.0.visit_rule(rule);
+        self.1.visit_rule(rule);
+    }
+
+    fn visit_rule_end(&mut self, rule: &gherkin::Rule) {
+        self.0.visit_rule_end(rule);
+        self.1.visit_rule_end(rule);
+    }
+
+    fn visit_scenario_outline(
+        &mut self,
+        rule: Option<&gherkin::Rule>,
+        outline: &gherkin::Scenario,
+    ) {
+        self.0.visit_scenario_outline(rule, outline);
+        self.1.visit_scenario_outline(rule, outline);
+    }
+
+    fn visit_scenario_outline_end(
+        &mut self,
+        rule: Option<&gherkin::Rule>,
+        outline: &gherkin::Scenario,
+    ) {
+        self.0.visit_scenario_outline_end(rule, outline);
+        self.1.visit_scenario_outline_end(rule, outline);
+    }
+
+    fn visit_scenario_example(
+        &mut self,
+        rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+        values: &[(String, String)],
+    ) {
+        self.0.visit_scenario_example(rule, scenario, values);
+        self.1.visit_scenario_example(rule, scenario, values);
+    }
+
+    fn visit_scenario(&mut self, rule: Option<&gherkin::Rule>, scenario: &gherkin::Scenario) {
+        self.0.visit_scenario(rule, scenario);
+        self.1.visit_scenario(rule, scenario);
+    }
+
+    fn visit_scenario_end(&mut self, rule: Option<&gherkin::Rule>, scenario: &gherkin::Scenario) {
+        self.0.visit_scenario_end(rule, scenario);
+        self.1.visit_scenario_end(rule, scenario);
+    }
+
+    fn visit_scenario_skipped(
+        &mut self,
+        rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+    ) {
+        self.0.visit_scenario_skipped(rule, scenario);
+        self.1.visit_scenario_skipped(rule, scenario);
+    }
+
+    fn visit_scenario_filtered_out(
+        &mut self,
+        rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+    ) {
+        self.0.visit_scenario_filtered_out(rule, scenario);
+        self.1.visit_scenario_filtered_out(rule, scenario);
+    }
+
+    fn visit_scenario_not_run(
+        &mut self,
+        rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+    ) {
+        self.0.visit_scenario_not_run(rule, scenario);
+        self.1.visit_scenario_not_run(rule, scenario);
+    }
+
+    fn visit_scenario_ignored(
+        &mut self,
+        rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+        reason: &str,
+    ) {
+        self.0.visit_scenario_ignored(rule, scenario, reason);
+        self.1.visit_scenario_ignored(rule, scenario, reason);
+    }
+
+    fn visit_scenario_quarantined(
+        &mut self,
+        rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+    ) {
+        self.0.visit_scenario_quarantined(rule, scenario);
+        self.1.visit_scenario_quarantined(rule, scenario);
+    }
+
+    fn visit_scenario_resources(
+        &mut self,
+        rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+        usage: &crate::report::ResourceUsage,
+    ) {
+        self.0.visit_scenario_resources(rule, scenario, usage);
+        self.1.visit_scenario_resources(rule, scenario, usage);
+    }
+
+    fn visit_scenario_xfail(
+        &mut self,
+        rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+        passed: bool,
+    ) {
+        self.0.visit_scenario_xfail(rule, scenario, passed);
+        self.1.visit_scenario_xfail(rule, scenario, passed);
+    }
+
+    fn visit_scenario_teardown_failed(
+        &mut self,
+        rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+        failure: &crate::report::Failure,
+    ) {
+        self.0
+            .visit_scenario_teardown_failed(rule, scenario, failure);
+        self.1
+            .visit_scenario_teardown_failed(rule, scenario, failure);
+    }
+
+    fn visit_scenario_hook_failed(
+        &mut self,
+        rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+        hook: crate::report::Hook,
+        failure: &crate::report::Failure,
+    ) {
+        self.0
+            .visit_scenario_hook_failed(rule, scenario, hook, failure);
+        self.1
+            .visit_scenario_hook_failed(rule, scenario, hook, failure);
+    }
+
+    fn visit_step(
+        &mut self,
+        rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+        step: &gherkin::Step,
+    ) {
+        self.0.visit_step(rule, scenario, step);
+        self.1.visit_step(rule, scenario, step);
+    }
+
+    fn visit_step_resolved<'a, W: crate::World>(
+        &mut self,
+        step: &gherkin::Step,
+        test: &crate::TestCaseType<'a, W>,
+    ) {
+        self.0.visit_step_resolved(step, test);
+        self.1.visit_step_resolved(step, test);
+    }
+
+    fn visit_step_result(
+        &mut self,
+        rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+        step: &gherkin::Step,
+        result: &TestResult,
+        timing: &Timing,
+        captured: &Captured,
+    ) {
+        self.0
+            .visit_step_result(rule, scenario, step, result, timing, captured);
+        self.1
+            .visit_step_result(rule, scenario, step, result, timing, captured);
+    }
+
+    fn visit_finish(&mut self) {
+        self.0.visit_finish();
+        self.1.visit_finish();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormatterKind {
+    Json,
+    JUnit,
+    /// JUnit XML, one file per feature in the destination directory.
+    JUnitDir,
+    /// One row per scenario.
+    Csv,
+    /// Cucumber messages as NDJSON.
+    Message,
+    /// Cucumber messages as length-delimited protobuf.
+    Protobuf,
+    /// The failed scenarios as `path:line` targets, for `@rerun.txt`.
+    Rerun,
+    /// Pending stubs of the undefined steps, as a Rust module.
+    Stubs,
+    /// Appends results to a SQLite database.
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+}
+
+/// Where a formatter writes to: `-` or `stdout`, `stderr`, or any other path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Destination {
+    Stdout,
+    Stderr,
+    File(PathBuf),
+}
+
+impl fmt::Display for Destination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Destination::Stdout => write!(f, "stdout"),
+            Destination::Stderr => write!(f, "stderr"),
+            Destination::File(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+impl From<&str> for Destination {
+    fn from(s: &str) -> Destination {
+        match s {
+            "-" | "stdout" => Destination::Stdout,
+            "stderr" => Destination::Stderr,
+            path => Destination::File(PathBuf::from(path)),
+        }
+    }
+}
+
+/// A formatter and its destination, written as `kind:destination`,
+/// e.g. `junit:target/junit.xml`, `json:target/report.json` or `message:-`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatterSpec {
+    pub kind: FormatterKind,
+    pub destination: Destination,
+}
+
+impl FormatterSpec {
+    /// Opens the destination, creating the file and its parent directories
+    /// if needed.
+    pub fn create(&self) -> io::Result<Box<dyn Write + Send>> {
+        Ok(match &self.destination {
+            Destination::Stdout => Box::new(io::stdout()),
+            Destination::Stderr => Box::new(io::stderr()),
+            Destination::File(path) => {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                Box::new(File::create(path)?)
+            }
+        })
+    }
+}
+
+/// The outputs writing to the files configured by formatter specs.
+pub(crate) type FileOutputs = (
+    ReportOutput<Vec<Box<dyn ReportWriter + Send>>>,
+    MessagesOutput,
+);
+
+pub(crate) fn file_outputs<'a>(specs: impl Iterator<Item = &'a FormatterSpec>) -> FileOutputs {
+    let mut writers: Vec<Box<dyn ReportWriter + Send>> = vec![];
+    let mut messages = MessagesOutput::with_sinks(vec![]);
+
+    for spec in specs {
+        if let (FormatterKind::JUnitDir, Destination::File(dir)) = (spec.kind, &spec.destination) {
+            writers.push(Box::new(JUnitWriter::per_feature(dir)));
+            continue;
+        }
+
+        // The database is appended to, so it must not be truncated like other files.
+        #[cfg(feature = "sqlite")]
+        {
+            if let (FormatterKind::Sqlite, Destination::File(path)) = (spec.kind, &spec.destination)
+            {
+                writers.push(Box::new(SqliteWriter::new(path)));
+                continue;
+            }
+        }
+
+        let file = spec
+            .create()
+            .unwrap_or_else(|e| panic!("Could not open {} for writing: {}", spec.destination, e));
+
+        match spec.kind {
+            FormatterKind::Json => writers.push(Box::new(JsonWriter::new(file))),
+            FormatterKind::JUnit => writers.push(Box::new(JUnitWriter::new(file))),
+            FormatterKind::JUnitDir => panic!(
+                "The junit-dir formatter needs a directory, not {}",
+                spec.destination
+            ),
+            FormatterKind::Csv => writers.push(Box::new(CsvWriter::new(file))),
+            FormatterKind::Message => messages.add_sink(MessagesFormat::Ndjson, file),
+            FormatterKind::Protobuf => messages.add_sink(MessagesFormat::Protobuf, file),
+            FormatterKind::Rerun => writers.push(Box::new(RerunWriter::new(file))),
+            FormatterKind::Stubs => writers.push(Box::new(StubsWriter::new(file))),
+            #[cfg(feature = "sqlite")]
+            FormatterKind::Sqlite => panic!(
+                "The sqlite formatter needs a file path, not {}",
+                spec.destination
+            ),
+        }
+    }
+
+    (ReportOutput::with_writer(writers), messages)
+}
+
+impl FromStr for FormatterSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<FormatterSpec, String> {
+        let mut chunks = s.splitn(2, ':');
+        let kind = match chunks.next() {
+            Some("json") => FormatterKind::Json,
+            Some("junit") => FormatterKind::JUnit,
+            Some("junit-dir") => FormatterKind::JUnitDir,
+            Some("csv") => FormatterKind::Csv,
+            Some("message") => FormatterKind::Message,
+            Some("protobuf") => FormatterKind::Protobuf,
+            Some("rerun") => FormatterKind::Rerun,
+            Some("stubs") => FormatterKind::Stubs,
+            #[cfg(feature = "sqlite")]
+            Some("sqlite") => FormatterKind::Sqlite,
+            _ => return Err(format!("unknown formatter in `{}`", s)),
+        };
+        let destination = match chunks.next() {
+            Some(path) if !path.is_empty() => Destination::from(path),
+            _ => return Err(format!("missing output path in `{}`", s)),
+        };
+
+        Ok(FormatterSpec { kind, destination })
+    }
+}
+
+/// Escapes text for inclusion in XML or HTML.
+pub(crate) fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,59 @@
+use std::env;
+use std::io::{self, stdout, Write};
+use std::path::Path;
+
+use crate::output::structured::{ReportOutput, ReportWriter};
+use crate::report::Report;
+
+/// Writes the failed scenarios as `path:line:line` targets, one feature per
+/// line, to be run again with `@rerun.txt`.
+pub struct RerunWriter {
+    writer: Box<dyn Write + Send>,
+}
+
+impl RerunWriter {
+    pub fn new<Wr: Write + Send + 'static>(writer: Wr) -> RerunWriter {
+        RerunWriter {
+            writer: Box::new(writer),
+        }
+    }
+}
+
+impl Default for RerunWriter {
+    fn default() -> RerunWriter {
+        RerunWriter::new(stdout())
+    }
+}
+
+impl ReportWriter for RerunWriter {
+    fn write_report(&mut self, report: &Report) -> io::Result<()> {
+        let cwd = env::current_dir()?;
+        let w = &mut self.writer;
+
+        for feature in &report.features {
+            let lines = feature
+                .scenarios
+                .iter()
+                .filter(|s| s.status.is_failure())
+                .map(|s| format!(":{}", s.line))
+                .collect::<String>();
+            if lines.is_empty() {
+                continue;
+            }
+
+            let path = Path::new(&feature.path);
+            let path = path.strip_prefix(&cwd).unwrap_or(path);
+            writeln!(w, "{}{}", path.display(), lines)?;
+        }
+
+        w.flush()
+    }
+}
+
+pub type RerunOutput = ReportOutput<RerunWriter>;
+
+impl ReportOutput<RerunWriter> {
+    pub fn from_writer<Wr: Write + Send + 'static>(writer: Wr) -> RerunOutput {
+        ReportOutput::with_writer(RerunWriter::new(writer))
+    }
+}
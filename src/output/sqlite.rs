@@ -0,0 +1,116 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use rusqlite::{params, Connection};
+
+use crate::output::structured::ReportWriter;
+use crate::report::{self, Report};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY,
+    finished_ms INTEGER NOT NULL,
+    cucumber_version TEXT NOT NULL,
+    schema_version INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS scenarios (
+    id INTEGER PRIMARY KEY,
+    run_id INTEGER NOT NULL REFERENCES runs(id),
+    feature TEXT NOT NULL,
+    path TEXT NOT NULL,
+    rule TEXT,
+    name TEXT NOT NULL,
+    line INTEGER NOT NULL,
+    tags TEXT NOT NULL,
+    status TEXT NOT NULL,
+    duration_ns INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS steps (
+    id INTEGER PRIMARY KEY,
+    scenario_id INTEGER NOT NULL REFERENCES scenarios(id),
+    keyword TEXT NOT NULL,
+    text TEXT NOT NULL,
+    line INTEGER NOT NULL,
+    status TEXT NOT NULL,
+    started_ms INTEGER NOT NULL,
+    duration_ns INTEGER NOT NULL,
+    failure TEXT
+);
+";
+
+/// Appends each run, with its scenarios and steps, to a SQLite database so
+/// results can be queried across many runs.
+pub struct SqliteWriter {
+    path: PathBuf,
+}
+
+impl SqliteWriter {
+    pub fn new<P: AsRef<Path>>(path: P) -> SqliteWriter {
+        SqliteWriter {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    fn append(&self, report: &Report) -> rusqlite::Result<()> {
+        let mut conn = Connection::open(&self.path)?;
+        conn.execute_batch(SCHEMA)?;
+
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO runs (finished_ms, cucumber_version, schema_version) VALUES (?1, ?2, ?3)",
+            params![
+                report::millis_since_epoch(SystemTime::now()) as i64,
+                report.cucumber_version,
+                report.schema_version
+            ],
+        )?;
+        let run_id = tx.last_insert_rowid();
+
+        for feature in &report.features {
+            for scenario in &feature.scenarios {
+                tx.execute(
+                    "INSERT INTO scenarios (run_id, feature, path, rule, name, line, tags, status, duration_ns)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                    params![
+                        run_id,
+                        feature.name,
+                        feature.path,
+                        scenario.rule,
+                        scenario.name,
+                        scenario.line as i64,
+                        scenario.tags.join(" "),
+                        scenario.status.as_str(),
+                        report::nanos(scenario.duration()) as i64
+                    ],
+                )?;
+                let scenario_id = tx.last_insert_rowid();
+
+                for step in &scenario.steps {
+                    tx.execute(
+                        "INSERT INTO steps (scenario_id, keyword, text, line, status, started_ms, duration_ns, failure)
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                        params![
+                            scenario_id,
+                            step.keyword,
+                            step.text,
+                            step.line as i64,
+                            step.status.as_str(),
+                            step.started_ms as i64,
+                            step.duration_ns as i64,
+                            step.failure.as_ref().map(|f| f.message.clone())
+                        ],
+                    )?;
+                }
+            }
+        }
+
+        tx.commit()
+    }
+}
+
+impl ReportWriter for SqliteWriter {
+    fn write_report(&mut self, report: &Report) -> io::Result<()> {
+        self.append(report).map_err(io::Error::other)
+    }
+}
@@ -0,0 +1,402 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+
+use gherkin;
+
+use crate::report::{self, Report, Status};
+use crate::OutputVisitor;
+use crate::{Captured, TestResult, Timing};
+
+/// Something that can persist a finished [`Report`](../report/struct.Report.html).
+pub trait ReportWriter {
+    fn write_report(&mut self, report: &Report) -> io::Result<()>;
+
+    /// Called with the report so far each time a scenario finishes.
+    fn update(&mut self, _report: &Report) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Called with each step as soon as it finishes.
+    fn step_finished(&mut self, _step: &report::Step) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<R: ReportWriter + ?Sized> ReportWriter for Box<R> {
+    fn write_report(&mut self, report: &Report) -> io::Result<()> {
+        (**self).write_report(report)
+    }
+
+    fn update(&mut self, report: &Report) -> io::Result<()> {
+        (**self).update(report)
+    }
+
+    fn step_finished(&mut self, step: &report::Step) -> io::Result<()> {
+        (**self).step_finished(step)
+    }
+}
+
+impl<R: ReportWriter> ReportWriter for Vec<R> {
+    fn write_report(&mut self, report: &Report) -> io::Result<()> {
+        for writer in self.iter_mut() {
+            writer.write_report(report)?;
+        }
+        Ok(())
+    }
+
+    fn update(&mut self, report: &Report) -> io::Result<()> {
+        for writer in self.iter_mut() {
+            writer.update(report)?;
+        }
+        Ok(())
+    }
+
+    fn step_finished(&mut self, step: &report::Step) -> io::Result<()> {
+        for writer in self.iter_mut() {
+            writer.step_finished(step)?;
+        }
+        Ok(())
+    }
+}
+
+/// How many bytes of captured stdout or stderr are kept per step by default.
+pub const DEFAULT_CAPTURE_LIMIT: usize = 64 * 1024;
+
+/// Collects the events of a run into a [`Report`](../report/struct.Report.html)
+/// and hands it to a [`ReportWriter`](trait.ReportWriter.html) when the run finishes.
+pub struct ReportOutput<R: ReportWriter> {
+    writer: R,
+    report: Report,
+    capture_limit: usize,
+    /// Whether the last scenario visited has not ended yet.
+    running: bool,
+    /// The values of the example the next scenario is run with.
+    example: Vec<(String, String)>,
+}
+
+impl<R: ReportWriter> ReportOutput<R> {
+    pub fn with_writer(writer: R) -> ReportOutput<R> {
+        ReportOutput {
+            writer,
+            report: Report::default(),
+            capture_limit: DEFAULT_CAPTURE_LIMIT,
+            running: false,
+            example: vec![],
+        }
+    }
+
+    /// Sets how many bytes of captured stdout and stderr are kept per step.
+    /// Anything beyond is dropped and replaced with a note.
+    pub fn set_capture_limit(&mut self, limit: usize) {
+        self.capture_limit = limit;
+    }
+
+    pub fn report(&self) -> &Report {
+        &self.report
+    }
+
+    pub fn writer_mut(&mut self) -> &mut R {
+        &mut self.writer
+    }
+
+    fn cur_feature(&mut self) -> &mut report::Feature {
+        self.report
+            .features
+            .last_mut()
+            .expect("scenario visited outside of a feature")
+    }
+
+    fn cur_scenario(&mut self) -> &mut report::Scenario {
+        self.cur_feature()
+            .scenarios
+            .last_mut()
+            .expect("step visited outside of a scenario")
+    }
+}
+
+impl<R: ReportWriter + Default> OutputVisitor for ReportOutput<R> {
+    fn new() -> Self {
+        ReportOutput::with_writer(R::default())
+    }
+
+    fn visit_start(&mut self, metadata: &BTreeMap<String, String>) {
+        self.report = Report {
+            metadata: metadata.clone(),
+            ..Report::default()
+        };
+    }
+
+    fn visit_services(&mut self, services: &[report::Service]) {
+        self.report.services = services.to_vec();
+    }
+
+    fn visit_feature(&mut self, feature: &gherkin::Feature, path: &Path) {
+        self.report.features.push(report::Feature {
+            name: feature.name.clone(),
+            path: path.display().to_string(),
+            line: feature.position.0,
+            tags: report::tags(&feature.tags),
+            description: feature.description.clone(),
+            error: None,
+            scenarios: vec![],
+            filtered_out: vec![],
+            not_run: vec![],
+            dependency_cycle: vec![],
+            hook_failures: vec![],
+        });
+    }
+
+    fn visit_feature_end(&mut self, _feature: &gherkin::Feature) {}
+
+    fn visit_feature_error(&mut self, path: &Path, error: &gherkin::Error) {
+        self.report.features.push(report::Feature {
+            path: path.display().to_string(),
+            error: Some(error.to_string()),
+            ..Default::default()
+        });
+    }
+
+    fn visit_feature_hook_failed(
+        &mut self,
+        _feature: &gherkin::Feature,
+        hook: report::Hook,
+        failure: &report::Failure,
+    ) {
+        self.cur_feature().hook_failures.push(report::HookFailure {
+            hook,
+            failure: failure.clone(),
+        });
+    }
+
+    fn visit_dependency_cycle(
+        &mut self,
+        _feature: &gherkin::Feature,
+        _rule: Option<&gherkin::Rule>,
+        scenarios: &[String],
+    ) {
+        self.cur_feature()
+            .dependency_cycle
+            .extend_from_slice(scenarios);
+    }
+
+    fn visit_rule(&mut self, _rule: &gherkin::Rule) {}
+
+    fn visit_rule_end(&mut self, _rule: &gherkin::Rule) {}
+
+    fn visit_scenario_example(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+        values: &[(String, String)],
+    ) {
+        self.example = values.to_vec();
+    }
+
+    fn visit_scenario(&mut self, rule: Option<&gherkin::Rule>, scenario: &gherkin::Scenario) {
+        let scenario = report::Scenario {
+            name: scenario.name.clone(),
+            line: scenario.position.0,
+            rule: rule.map(|r| r.name.clone()),
+            tags: report::tags(&scenario.tags),
+            examples: std::mem::take(&mut self.example),
+            status: Status::Passed,
+            skip_reason: None,
+            resources: None,
+            quarantined: false,
+            xfail: false,
+            teardown_failure: None,
+            hook_failures: vec![],
+            steps: vec![],
+        };
+        self.cur_feature().scenarios.push(scenario);
+        self.running = true;
+    }
+
+    fn visit_scenario_end(&mut self, _rule: Option<&gherkin::Rule>, _scenario: &gherkin::Scenario) {
+        self.running = false;
+        self.writer
+            .update(&self.report)
+            .expect("failed to update report");
+    }
+
+    fn visit_scenario_skipped(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+    ) {
+    }
+
+    fn visit_scenario_filtered_out(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+    ) {
+        self.cur_feature().filtered_out.push(scenario.name.clone());
+    }
+
+    fn visit_scenario_not_run(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+    ) {
+        // One stopped while running, by the watchdog of the time budget, is
+        // only reported as not run.
+        if self.running {
+            self.cur_feature().scenarios.pop();
+            self.running = false;
+        }
+        self.cur_feature().not_run.push(scenario.name.clone());
+    }
+
+    fn visit_scenario_ignored(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+        reason: &str,
+    ) {
+        self.cur_scenario().skip_reason = Some(reason.to_string());
+    }
+
+    fn visit_scenario_quarantined(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+    ) {
+        self.cur_scenario().quarantined = true;
+    }
+
+    fn visit_scenario_resources(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+        usage: &report::ResourceUsage,
+    ) {
+        self.cur_scenario().resources = Some(usage.clone());
+    }
+
+    fn visit_scenario_xfail(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+        _passed: bool,
+    ) {
+        self.cur_scenario().xfail = true;
+    }
+
+    fn visit_scenario_teardown_failed(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+        failure: &report::Failure,
+    ) {
+        let scenario = self.cur_scenario();
+        scenario.status = scenario.status.merge(report::Status::Failed);
+        scenario.teardown_failure = Some(failure.clone());
+    }
+
+    fn visit_scenario_hook_failed(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+        hook: report::Hook,
+        failure: &report::Failure,
+    ) {
+        let scenario = self.cur_scenario();
+        scenario.status = scenario.status.merge(report::Status::Failed);
+        scenario.hook_failures.push(report::HookFailure {
+            hook,
+            failure: failure.clone(),
+        });
+    }
+
+    fn visit_step(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+        _step: &gherkin::Step,
+    ) {
+    }
+
+    fn visit_step_resolved<'a, W: crate::World>(
+        &mut self,
+        _step: &gherkin::Step,
+        _test: &crate::TestCaseType<'a, W>,
+    ) {
+    }
+
+    fn visit_step_result(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+        step: &gherkin::Step,
+        result: &TestResult,
+        timing: &Timing,
+        captured: &Captured,
+    ) {
+        let status = Status::from(result);
+        let failure = match result {
+            TestResult::Fail(panic_info, _, _) => Some(report::Failure {
+                message: panic_info.payload.clone(),
+                location: panic_info.location.clone(),
+                backtrace: panic_info.backtrace.clone(),
+            }),
+            TestResult::Crashed(exit_status, _, _) => Some(report::Failure {
+                message: format!("Scenario process crashed ({})", exit_status),
+                location: format!("{}:{}", self.cur_feature().path, step.position.0),
+                backtrace: None,
+            }),
+            TestResult::Pending(reason) => Some(report::Failure {
+                message: reason.clone(),
+                location: format!("{}:{}", self.cur_feature().path, step.position.0),
+                backtrace: None,
+            }),
+            TestResult::Ambiguous(matches) => Some(report::Failure {
+                message: matches.join("\n"),
+                location: format!("{}:{}", self.cur_feature().path, step.position.0),
+                backtrace: None,
+            }),
+            _ => None,
+        };
+        let stdout = truncated(&captured.stdout, self.capture_limit);
+        let stderr = truncated(&captured.stderr, self.capture_limit);
+
+        let step = report::Step {
+            keyword: step.raw_type.clone(),
+            text: step.value.clone(),
+            line: step.position.0,
+            status,
+            started_ms: report::millis_since_epoch(timing.started),
+            duration_ns: report::nanos(timing.duration),
+            failure,
+            stdout,
+            stderr,
+        };
+        self.writer
+            .step_finished(&step)
+            .expect("failed to record step");
+        let scenario = self.cur_scenario();
+        scenario.status = scenario.status.merge(status);
+        scenario.steps.push(step);
+    }
+
+    fn visit_finish(&mut self) {
+        self.writer
+            .write_report(&self.report)
+            .expect("failed to write report");
+    }
+}
+
+fn truncated(bytes: &[u8], limit: usize) -> Option<String> {
+    if bytes.is_empty() {
+        return None;
+    }
+
+    if bytes.len() <= limit {
+        return Some(String::from_utf8_lossy(bytes).into_owned());
+    }
+
+    let mut s = String::from_utf8_lossy(&bytes[..limit]).into_owned();
+    s.push_str(&format!("\n[{} bytes truncated]", bytes.len() - limit));
+    Some(s)
+}
@@ -0,0 +1,209 @@
+use std::collections::BTreeSet;
+use std::env;
+use std::io::{self, stdout, Write};
+use std::path::Path;
+
+use crate::output::structured::{ReportOutput, ReportWriter};
+use crate::report::{Report, Status};
+
+/// Writes a Rust module of pending stubs for the steps which had no
+/// definition, grouped by the feature they were first found in, to be added
+/// to the steps of the run as `undefined_stubs::steps::<MyWorld>`.
+///
+/// Numbers and quoted strings in the text of a step become the parameters of
+/// a Cucumber Expression, so that similar steps share a stub.
+pub struct StubsWriter {
+    writer: Box<dyn Write + Send>,
+}
+
+impl StubsWriter {
+    pub fn new<Wr: Write + Send + 'static>(writer: Wr) -> StubsWriter {
+        StubsWriter {
+            writer: Box::new(writer),
+        }
+    }
+}
+
+impl Default for StubsWriter {
+    fn default() -> StubsWriter {
+        StubsWriter::new(stdout())
+    }
+}
+
+impl ReportWriter for StubsWriter {
+    fn write_report(&mut self, report: &Report) -> io::Result<()> {
+        let cwd = env::current_dir()?;
+        let mut seen = BTreeSet::new();
+        let mut groups = vec![];
+
+        for feature in &report.features {
+            let mut stubs = vec![];
+            for scenario in &feature.scenarios {
+                let mut ty = "given";
+                for step in &scenario.steps {
+                    // And and But continue the type of the step before them.
+                    match step.keyword.trim().to_lowercase().as_str() {
+                        "given" => ty = "given",
+                        "when" => ty = "when",
+                        "then" => ty = "then",
+                        _ => {}
+                    }
+                    if step.status != Status::Unimplemented {
+                        continue;
+                    }
+                    let stub = Stub::new(ty, &step.text);
+                    if seen.insert((stub.ty, stub.pattern.clone())) {
+                        stubs.push(stub);
+                    }
+                }
+            }
+            if !stubs.is_empty() {
+                let path = Path::new(&feature.path);
+                let path = path.strip_prefix(&cwd).unwrap_or(path);
+                groups.push((path.display().to_string(), stubs));
+            }
+        }
+
+        let w = &mut self.writer;
+        writeln!(w, "// Stubs for the steps which had no definition.")?;
+        writeln!(
+            w,
+            "// Add `steps::<MyWorld>` of this module to the steps of the run, then fill them in."
+        )?;
+        writeln!(w)?;
+        writeln!(w, "use cucumber_rust::steps;")?;
+        writeln!(w)?;
+        writeln!(w, "steps!(W: cucumber_rust::World => {{")?;
+        for (i, (path, stubs)) in groups.iter().enumerate() {
+            if i > 0 {
+                writeln!(w)?;
+            }
+            writeln!(w, "    // {}", path)?;
+            for stub in stubs {
+                stub.write(w)?;
+            }
+        }
+        writeln!(w, "}});")?;
+
+        w.flush()
+    }
+}
+
+/// The definition of an undefined step.
+struct Stub {
+    ty: &'static str,
+    pattern: String,
+    /// The Rust types of the parameters, when the pattern is an expression.
+    parameters: Vec<&'static str>,
+}
+
+impl Stub {
+    fn new(ty: &'static str, text: &str) -> Stub {
+        let mut pattern = String::new();
+        let mut parameters = vec![];
+        let chars = text.chars().collect::<Vec<_>>();
+        let mut i = 0;
+
+        while i < chars.len() {
+            let c = chars[i];
+            let at_word_start = i == 0 || !chars[i - 1].is_alphanumeric();
+
+            if c == '"' {
+                if let Some(end) = chars[i + 1..].iter().position(|&c| c == '"') {
+                    pattern.push_str("{string}");
+                    parameters.push("String");
+                    i += end + 2;
+                    continue;
+                }
+            } else if at_word_start && number_starts(&chars[i..]) {
+                let mut end = i + 1;
+                while end < chars.len() && chars[end].is_ascii_digit() {
+                    end += 1;
+                }
+                let mut is_float = false;
+                if end + 1 < chars.len() && chars[end] == '.' && chars[end + 1].is_ascii_digit() {
+                    is_float = true;
+                    end += 1;
+                    while end < chars.len() && chars[end].is_ascii_digit() {
+                        end += 1;
+                    }
+                }
+                if end == chars.len() || !chars[end].is_alphanumeric() {
+                    if is_float {
+                        pattern.push_str("{float}");
+                        parameters.push("f64");
+                    } else {
+                        pattern.push_str("{int}");
+                        parameters.push("i64");
+                    }
+                    i = end;
+                    continue;
+                }
+            }
+
+            match c {
+                '(' | ')' | '{' | '}' | '/' | '\\' => {
+                    pattern.push('\\');
+                    pattern.push(c);
+                }
+                c => pattern.push(c),
+            }
+            i += 1;
+        }
+
+        if parameters.is_empty() {
+            pattern = text.to_string();
+        }
+        Stub {
+            ty,
+            pattern,
+            parameters,
+        }
+    }
+
+    fn write(&self, w: &mut dyn Write) -> io::Result<()> {
+        if self.parameters.is_empty() {
+            writeln!(w, "    {} {:?} |_world, _step| {{", self.ty, self.pattern)?;
+        } else {
+            let names = (1..=self.parameters.len())
+                .map(|i| format!("_arg{}, ", i))
+                .collect::<String>();
+            writeln!(
+                w,
+                "    {} expression {} ({}) |_world, {}_step| {{",
+                self.ty,
+                literal(&self.pattern),
+                self.parameters.join(", "),
+                names
+            )?;
+        }
+        writeln!(w, "        cucumber_rust::pending!()")?;
+        writeln!(w, "    }};")
+    }
+}
+
+/// A string literal of `s`, raw when that spares escaping its backslashes.
+fn literal(s: &str) -> String {
+    if s.contains('\\') && !s.contains('"') {
+        format!("r\"{}\"", s)
+    } else {
+        format!("{:?}", s)
+    }
+}
+
+/// Whether `chars` start with a number, possibly negative.
+fn number_starts(chars: &[char]) -> bool {
+    match chars {
+        [c, ..] if c.is_ascii_digit() => true,
+        ['-', c, ..] => c.is_ascii_digit(),
+        _ => false,
+    }
+}
+
+pub type StubsOutput = ReportOutput<StubsWriter>;
+
+impl ReportOutput<StubsWriter> {
+    pub fn from_writer<Wr: Write + Send + 'static>(writer: Wr) -> StubsOutput {
+        ReportOutput::with_writer(StubsWriter::new(writer))
+    }
+}
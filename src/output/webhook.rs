@@ -0,0 +1,158 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::output::structured::ReportWriter;
+use crate::report::{Report, Status};
+
+/// How many failures are spelled out in the notification text.
+const MAX_HIGHLIGHTS: usize = 10;
+
+#[derive(Serialize)]
+struct Summary {
+    /// A human readable message, as understood by Slack and Teams webhooks.
+    text: String,
+    success: bool,
+    passed: usize,
+    failed: usize,
+    skipped: usize,
+    unimplemented: usize,
+    duration_secs: f64,
+    failures: Vec<FailureSummary>,
+    reports: Vec<String>,
+    metadata: BTreeMap<String, String>,
+}
+
+#[derive(Serialize)]
+struct FailureSummary {
+    feature: String,
+    path: String,
+    scenario: String,
+    step: String,
+    message: String,
+}
+
+/// Posts a JSON summary of the run to a URL once it finishes.
+///
+/// Delivery problems are reported on stderr but never fail the run.
+pub struct WebhookWriter {
+    url: String,
+    links: Vec<String>,
+}
+
+impl WebhookWriter {
+    pub fn new(url: &str) -> WebhookWriter {
+        WebhookWriter {
+            url: url.to_string(),
+            links: vec![],
+        }
+    }
+
+    /// Mentions a detailed report, as a link or a path, in the notification.
+    pub fn add_link(&mut self, link: &str) {
+        self.links.push(link.to_string());
+    }
+
+    fn summary(&self, report: &Report) -> Summary {
+        let scenarios = report.features.iter().flat_map(|f| f.scenarios.iter());
+        let count = |status| scenarios.clone().filter(|s| s.status == status).count();
+
+        let mut failures = vec![];
+        for feature in &report.features {
+            if let Some(ref error) = feature.error {
+                failures.push(FailureSummary {
+                    feature: feature.name.clone(),
+                    path: feature.path.clone(),
+                    scenario: String::new(),
+                    step: String::new(),
+                    message: error.clone(),
+                });
+            }
+
+            for scenario in &feature.scenarios {
+                if let Some(step) = scenario.failed_step() {
+                    failures.push(FailureSummary {
+                        feature: feature.name.clone(),
+                        path: format!("{}:{}", feature.path, step.line),
+                        scenario: scenario.name.clone(),
+                        step: format!("{} {}", step.keyword, step.text),
+                        message: step
+                            .failure
+                            .as_ref()
+                            .map(|f| f.message.clone())
+                            .unwrap_or_default(),
+                    });
+                }
+            }
+        }
+
+        let duration: Duration = report.features.iter().map(|f| f.duration()).sum();
+        let failed = count(Status::Failed) + count(Status::Crashed) + count(Status::Ambiguous);
+        let success = failures.is_empty();
+
+        let mut text = format!(
+            "Cucumber {}: {} scenarios, {} passed, {} failed ({:.1}s)",
+            if success { "passed" } else { "failed" },
+            scenarios.clone().count(),
+            count(Status::Passed),
+            failed,
+            duration.as_secs_f64()
+        );
+        for failure in failures.iter().take(MAX_HIGHLIGHTS) {
+            text.push_str(&format!(
+                "\n- {} / {}: {} ({})",
+                failure.feature, failure.scenario, failure.step, failure.path
+            ));
+        }
+        if failures.len() > MAX_HIGHLIGHTS {
+            text.push_str(&format!(
+                "\n... and {} more",
+                failures.len() - MAX_HIGHLIGHTS
+            ));
+        }
+        for link in &self.links {
+            text.push_str(&format!("\nReport: {}", link));
+        }
+
+        Summary {
+            text,
+            success,
+            passed: count(Status::Passed),
+            failed,
+            skipped: count(Status::Skipped),
+            unimplemented: count(Status::Unimplemented),
+            duration_secs: duration.as_secs_f64(),
+            failures,
+            reports: self.links.clone(),
+            metadata: report.metadata.clone(),
+        }
+    }
+
+    fn post(&self, report: &Report) -> Result<(), attohttpc::Error> {
+        let response = attohttpc::post(&self.url)
+            .connect_timeout(Duration::from_secs(10))
+            .read_timeout(Duration::from_secs(30))
+            .json(&self.summary(report))?
+            .send()?;
+
+        if !response.is_success() {
+            eprintln!(
+                "Webhook {} answered with status {}",
+                self.url,
+                response.status()
+            );
+        }
+        Ok(())
+    }
+}
+
+impl ReportWriter for WebhookWriter {
+    fn write_report(&mut self, report: &Report) -> io::Result<()> {
+        if let Err(e) = self.post(report) {
+            eprintln!("Could not notify webhook {}: {}", self.url, e);
+        }
+        Ok(())
+    }
+}
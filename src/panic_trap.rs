@@ -1,33 +1,149 @@
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::env;
 use std::io::Read;
 use std::ops::Deref;
 use std::panic;
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Once;
 
+use backtrace::Backtrace;
 use shh::{stderr, stdout};
 
 #[derive(Debug, Clone)]
 pub struct PanicDetails {
     pub payload: String,
     pub location: String,
+    /// Set when backtraces are captured, see [`capture_backtraces`](fn.capture_backtraces.html).
+    pub backtrace: Option<String>,
+}
+
+fn payload_string(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else if let Some(s) = payload.downcast_ref::<&str>() {
+        s.deref().to_owned()
+    } else {
+        "Opaque panic payload".to_owned()
+    }
 }
 
 impl PanicDetails {
     fn from_panic_info(info: &panic::PanicInfo) -> PanicDetails {
-        let payload = if let Some(s) = info.payload().downcast_ref::<String>() {
-            s.clone()
-        } else if let Some(s) = info.payload().downcast_ref::<&str>() {
-            s.deref().to_owned()
-        } else {
-            "Opaque panic payload".to_owned()
-        };
+        let payload = payload_string(info.payload());
 
         let location = info
             .location()
             .map(|loc| format!("{}:{}:{}", loc.file(), loc.line(), loc.column()))
             .unwrap_or_else(|| "Unknown panic location".to_owned());
 
-        PanicDetails { payload, location }
+        let backtrace = if capturing_backtraces() {
+            Some(format!("{:?}", Backtrace::new()))
+        } else {
+            None
+        };
+
+        PanicDetails {
+            payload,
+            location,
+            backtrace,
+        }
+    }
+}
+
+static CAPTURE_BACKTRACES: AtomicBool = AtomicBool::new(false);
+
+/// Records a backtrace along with failed steps when `force` is set or when
+/// `RUST_BACKTRACE` asks for them, as the default panic hook does.
+pub(crate) fn capture_backtraces(force: bool) {
+    let enabled = match env::var("RUST_BACKTRACE") {
+        Ok(value) => value != "0",
+        Err(_) => false,
+    };
+    CAPTURE_BACKTRACES.store(force || enabled, Ordering::SeqCst);
+}
+
+pub(crate) fn capturing_backtraces() -> bool {
+    CAPTURE_BACKTRACES.load(Ordering::SeqCst)
+}
+
+static CHAIN_HOOK: AtomicBool = AtomicBool::new(false);
+
+/// Also passes trapped panics on to the hook which was in place before the
+/// runner's, e.g. one installed by `color-eyre`, once their details are recorded.
+pub(crate) fn chain_hook(chain: bool) {
+    CHAIN_HOOK.store(chain, Ordering::SeqCst);
+}
+
+thread_local! {
+    static TRAPPING: Cell<bool> = const { Cell::new(false) };
+    static LAST_PANIC: RefCell<Option<PanicDetails>> = const { RefCell::new(None) };
+    static RETURNED_ERROR: RefCell<Option<String>> = const { RefCell::new(None) };
+    static SOFT_FAILURES: RefCell<Vec<PanicDetails>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Records the error a step returned, for the trap it runs in to fail as if
+/// it had panicked.
+pub(crate) fn record_error(message: String) {
+    RETURNED_ERROR.with(|returned| *returned.borrow_mut() = Some(message));
+}
+
+/// Records a failed soft assertion at `location`, for the trap it runs in to
+/// fail once the step is over. Outside of a trap, panics right away.
+pub(crate) fn record_soft_failure(message: String, location: &str) {
+    if !TRAPPING.with(Cell::get) {
+        panic!("{} at {}", message, location);
+    }
+    let backtrace = if capturing_backtraces() {
+        Some(format!("{:?}", Backtrace::new()))
+    } else {
+        None
+    };
+    let failure = PanicDetails {
+        payload: message,
+        location: location.to_owned(),
+        backtrace,
+    };
+    SOFT_FAILURES.with(|failures| failures.borrow_mut().push(failure));
+}
+
+/// The details of a step failing with every one of `failures`, in the order
+/// they happened, located at the first.
+fn combine(mut failures: Vec<PanicDetails>) -> PanicDetails {
+    if failures.len() == 1 {
+        return failures.remove(0);
+    }
+    let mut payload = format!("{} assertions failed:", failures.len());
+    for failure in &failures {
+        payload.push_str(&format!(
+            "\n  {}: {}",
+            failure.location,
+            failure.payload.replace('\n', "\n    ")
+        ));
     }
+    let first = failures.remove(0);
+    PanicDetails { payload, ..first }
+}
+
+static INSTALL_HOOK: Once = Once::new();
+
+/// Installs, once, a panic hook recording the details of panics raised inside
+/// a trap on the panicking thread, so traps can run on several threads at once.
+/// Any other panic goes to the hook which was in place before, as trapped
+/// ones do too when [`chain_hook`](fn.chain_hook.html) is set.
+fn install_hook() {
+    INSTALL_HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let trapping = TRAPPING.with(Cell::get);
+            if trapping {
+                LAST_PANIC.with(|last| *last.borrow_mut() = Some(PanicDetails::from_panic_info(info)));
+            }
+            if !trapping || CHAIN_HOOK.load(Ordering::SeqCst) {
+                previous(info);
+            }
+        }));
+    });
 }
 
 pub struct PanicTrap<T> {
@@ -58,29 +174,45 @@ impl<T> PanicTrap<T> {
     }
 
     fn run_loudly<F: FnOnce() -> T>(f: F) -> PanicTrap<T> {
-        let last_panic = Arc::new(Mutex::new(None));
-
-        panic::set_hook({
-            let last_panic = last_panic.clone();
-
-            Box::new(move |info| {
-                *last_panic.lock().expect("Last panic mutex poisoned") =
-                    Some(PanicDetails::from_panic_info(info));
-            })
-        });
+        install_hook();
 
+        RETURNED_ERROR.with(|returned| returned.borrow_mut().take());
+        SOFT_FAILURES.with(|failures| failures.borrow_mut().clear());
+        TRAPPING.with(|trapping| trapping.set(true));
         let result = panic::catch_unwind(panic::AssertUnwindSafe(f));
-
-        let _ = panic::take_hook();
+        TRAPPING.with(|trapping| trapping.set(false));
+
+        let result = result.map_err(|payload| {
+            // The hook may have been replaced since it was installed, in
+            // which case only the payload is known.
+            LAST_PANIC
+                .with(|last| last.borrow_mut().take())
+                .unwrap_or_else(|| PanicDetails {
+                    payload: payload_string(&*payload),
+                    location: "Unknown panic location".to_owned(),
+                    backtrace: None,
+                })
+        });
+        let result = match RETURNED_ERROR.with(|returned| returned.borrow_mut().take()) {
+            Some(message) if result.is_ok() => Err(PanicDetails {
+                payload: message,
+                location: "Returned by the step".to_owned(),
+                backtrace: None,
+            }),
+            _ => result,
+        };
+        // Soft assertions which failed fail the step too, along with
+        // whatever stopped it.
+        let mut failures = SOFT_FAILURES.with(|failures| failures.replace(Vec::new()));
+        let result = if failures.is_empty() {
+            result
+        } else {
+            failures.extend(result.err());
+            Err(combine(failures))
+        };
 
         PanicTrap {
-            result: result.map_err(|_| {
-                last_panic
-                    .lock()
-                    .expect("Last panic mutex poisoned")
-                    .take()
-                    .expect("Panic occurred but no panic details were set")
-            }),
+            result,
             stdout: Vec::new(),
             stderr: Vec::new(),
         }
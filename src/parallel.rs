@@ -0,0 +1,473 @@
+//! Running scenarios or whole features on several threads while keeping
+//! outputs coherent.
+//!
+//! Workers run each scenario or feature against a
+//! [`Recorder`](struct.Recorder.html) instead of the real output. The recorded
+//! events are then replayed on the calling thread, in the order they would
+//! have happened serially.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::{Condvar, Mutex, PoisonError};
+
+use crossbeam_utils::thread;
+
+use crate::report::{Failure, Hook, ResourceUsage};
+use crate::{Captured, OutputVisitor, Steps, TestResult, Timing, World};
+
+enum Event {
+    Feature(gherkin::Feature, PathBuf),
+    FeatureEnd,
+    FeatureHookFailed(Hook, Failure),
+    DependencyCycle(Vec<String>),
+    Rule(gherkin::Rule),
+    RuleEnd,
+    Outline(gherkin::Scenario),
+    OutlineEnd,
+    ScenarioExample(gherkin::Scenario, Vec<(String, String)>),
+    Scenario(gherkin::Scenario),
+    ScenarioEnd,
+    ScenarioSkipped,
+    ScenarioFilteredOut(gherkin::Scenario),
+    ScenarioNotRun(gherkin::Scenario),
+    ScenarioIgnored(String),
+    ScenarioQuarantined,
+    ScenarioResources(ResourceUsage),
+    ScenarioXfail(bool),
+    ScenarioTeardownFailed(Failure),
+    ScenarioHookFailed(Hook, Failure),
+    Step(gherkin::Step),
+    StepResolved(gherkin::Step),
+    StepResult(gherkin::Step, TestResult, Timing, Captured),
+}
+
+/// Records the events of a scenario or a feature so they can be replayed later.
+#[derive(Default)]
+pub(crate) struct Recorder {
+    events: Vec<Event>,
+}
+
+impl Recorder {
+    /// Replays the recorded events. Scenarios recorded on their own are
+    /// attributed to `rule`.
+    pub(crate) fn replay<W: World>(
+        self,
+        steps: &Steps<W>,
+        rule: Option<&gherkin::Rule>,
+        output: &mut impl OutputVisitor,
+    ) {
+        let mut feature = None;
+        let mut recorded_rule = None;
+        let mut outline = None;
+        let mut scenario = None;
+
+        for event in self.events {
+            let rule = recorded_rule.as_ref().or(rule);
+            match event {
+                Event::Feature(f, path) => {
+                    output.visit_feature(&f, &path);
+                    feature = Some(f);
+                }
+                Event::FeatureEnd => output.visit_feature_end(feature.as_ref().unwrap()),
+                Event::FeatureHookFailed(hook, failure) => {
+                    output.visit_feature_hook_failed(feature.as_ref().unwrap(), hook, &failure)
+                }
+                Event::DependencyCycle(scenarios) => {
+                    output.visit_dependency_cycle(feature.as_ref().unwrap(), rule, &scenarios)
+                }
+                Event::Rule(r) => {
+                    output.visit_rule(&r);
+                    recorded_rule = Some(r);
+                }
+                Event::RuleEnd => {
+                    if let Some(r) = recorded_rule.take() {
+                        output.visit_rule_end(&r);
+                    }
+                }
+                Event::Outline(o) => {
+                    output.visit_scenario_outline(rule, &o);
+                    outline = Some(o);
+                }
+                Event::OutlineEnd => {
+                    output.visit_scenario_outline_end(rule, outline.as_ref().unwrap())
+                }
+                Event::ScenarioExample(s, values) => {
+                    output.visit_scenario_example(rule, &s, &values)
+                }
+                Event::Scenario(s) => {
+                    output.visit_scenario(rule, &s);
+                    scenario = Some(s);
+                }
+                Event::ScenarioEnd => output.visit_scenario_end(rule, scenario.as_ref().unwrap()),
+                Event::ScenarioSkipped => {
+                    output.visit_scenario_skipped(rule, scenario.as_ref().unwrap())
+                }
+                Event::ScenarioFilteredOut(s) => output.visit_scenario_filtered_out(rule, &s),
+                Event::ScenarioNotRun(s) => output.visit_scenario_not_run(rule, &s),
+                Event::ScenarioIgnored(reason) => {
+                    output.visit_scenario_ignored(rule, scenario.as_ref().unwrap(), &reason)
+                }
+                Event::ScenarioQuarantined => {
+                    output.visit_scenario_quarantined(rule, scenario.as_ref().unwrap())
+                }
+                Event::ScenarioResources(usage) => {
+                    output.visit_scenario_resources(rule, scenario.as_ref().unwrap(), &usage)
+                }
+                Event::ScenarioXfail(passed) => {
+                    output.visit_scenario_xfail(rule, scenario.as_ref().unwrap(), passed)
+                }
+                Event::ScenarioTeardownFailed(failure) => output.visit_scenario_teardown_failed(
+                    rule,
+                    scenario.as_ref().unwrap(),
+                    &failure,
+                ),
+                Event::ScenarioHookFailed(hook, failure) => output.visit_scenario_hook_failed(
+                    rule,
+                    scenario.as_ref().unwrap(),
+                    hook,
+                    &failure,
+                ),
+                Event::Step(step) => output.visit_step(rule, scenario.as_ref().unwrap(), &step),
+                Event::StepResolved(step) => {
+                    if let Some(test) = steps.test_type(&step) {
+                        output.visit_step_resolved(&step, &test);
+                    }
+                }
+                Event::StepResult(step, result, timing, captured) => output.visit_step_result(
+                    rule,
+                    scenario.as_ref().unwrap(),
+                    &step,
+                    &result,
+                    &timing,
+                    &captured,
+                ),
+            }
+        }
+    }
+}
+
+impl OutputVisitor for Recorder {
+    fn new() -> Self {
+        Recorder::default()
+    }
+
+    fn visit_start(&mut self, _metadata: &BTreeMap<String, String>) {}
+
+    fn visit_feature(&mut self, feature: &gherkin::Feature, path: &Path) {
+        self.events
+            .push(Event::Feature(feature.clone(), path.to_path_buf()));
+    }
+
+    fn visit_feature_end(&mut self, _feature: &gherkin::Feature) {
+        self.events.push(Event::FeatureEnd);
+    }
+
+    // Workers parse their feature themselves and hand parse errors back directly.
+    fn visit_feature_error(&mut self, _path: &Path, _error: &gherkin::Error) {}
+
+    fn visit_feature_hook_failed(
+        &mut self,
+        _feature: &gherkin::Feature,
+        hook: Hook,
+        failure: &Failure,
+    ) {
+        self.events.push(Event::FeatureHookFailed(hook, failure.clone()));
+    }
+
+    fn visit_dependency_cycle(
+        &mut self,
+        _feature: &gherkin::Feature,
+        _rule: Option<&gherkin::Rule>,
+        scenarios: &[String],
+    ) {
+        self.events.push(Event::DependencyCycle(scenarios.to_vec()));
+    }
+
+    fn visit_rule(&mut self, rule: &gherkin::Rule) {
+        self.events.push(Event::Rule(rule.clone()));
+    }
+
+    fn visit_rule_end(&mut self, _rule: &gherkin::Rule) {
+        self.events.push(Event::RuleEnd);
+    }
+
+    fn visit_scenario_outline(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        outline: &gherkin::Scenario,
+    ) {
+        self.events.push(Event::Outline(outline.clone()));
+    }
+
+    fn visit_scenario_outline_end(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _outline: &gherkin::Scenario,
+    ) {
+        self.events.push(Event::OutlineEnd);
+    }
+
+    fn visit_scenario_example(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+        values: &[(String, String)],
+    ) {
+        self.events
+            .push(Event::ScenarioExample(scenario.clone(), values.to_vec()));
+    }
+
+    fn visit_scenario(&mut self, _rule: Option<&gherkin::Rule>, scenario: &gherkin::Scenario) {
+        self.events.push(Event::Scenario(scenario.clone()));
+    }
+
+    fn visit_scenario_end(&mut self, _rule: Option<&gherkin::Rule>, _scenario: &gherkin::Scenario) {
+        self.events.push(Event::ScenarioEnd);
+    }
+
+    fn visit_scenario_skipped(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+    ) {
+        self.events.push(Event::ScenarioSkipped);
+    }
+
+    fn visit_scenario_filtered_out(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+    ) {
+        self.events
+            .push(Event::ScenarioFilteredOut(scenario.clone()));
+    }
+
+    fn visit_scenario_not_run(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+    ) {
+        self.events.push(Event::ScenarioNotRun(scenario.clone()));
+    }
+
+    fn visit_scenario_ignored(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+        reason: &str,
+    ) {
+        self.events.push(Event::ScenarioIgnored(reason.to_string()));
+    }
+
+    fn visit_scenario_quarantined(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+    ) {
+        self.events.push(Event::ScenarioQuarantined);
+    }
+
+    fn visit_scenario_resources(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+        usage: &ResourceUsage,
+    ) {
+        self.events.push(Event::ScenarioResources(usage.clone()));
+    }
+
+    fn visit_scenario_xfail(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+        passed: bool,
+    ) {
+        self.events.push(Event::ScenarioXfail(passed));
+    }
+
+    fn visit_scenario_teardown_failed(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+        failure: &Failure,
+    ) {
+        self.events.push(Event::ScenarioTeardownFailed(failure.clone()));
+    }
+
+    fn visit_scenario_hook_failed(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+        hook: Hook,
+        failure: &Failure,
+    ) {
+        self.events.push(Event::ScenarioHookFailed(hook, failure.clone()));
+    }
+
+    fn visit_step(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+        step: &gherkin::Step,
+    ) {
+        self.events.push(Event::Step(step.clone()));
+    }
+
+    fn visit_step_resolved<'a, W: World>(
+        &mut self,
+        step: &gherkin::Step,
+        _test: &crate::TestCaseType<'a, W>,
+    ) {
+        self.events.push(Event::StepResolved(step.clone()));
+    }
+
+    fn visit_step_result(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+        step: &gherkin::Step,
+        result: &TestResult,
+        timing: &Timing,
+        captured: &Captured,
+    ) {
+        self.events.push(Event::StepResult(
+            step.clone(),
+            result.clone(),
+            *timing,
+            captured.clone(),
+        ));
+    }
+
+    fn visit_finish(&mut self) {}
+}
+
+/// Runs jobs `0..count` on up to `threads` threads, starting them in the order
+/// of `schedule`, which lists every job once, and hands each result to
+/// `consume` on the calling thread, in job order, as soon as it and all the
+/// jobs before it are done.
+pub(crate) fn run_scheduled<T, R, C>(schedule: &[usize], threads: usize, run: R, mut consume: C)
+where
+    T: Send,
+    R: Fn(usize) -> T + Sync,
+    C: FnMut(usize, T),
+{
+    let count = schedule.len();
+    let next = AtomicUsize::new(0);
+    let (tx, rx) = mpsc::channel();
+
+    thread::scope(|s| {
+        for _ in 0..threads.min(count) {
+            let tx = tx.clone();
+            let next = &next;
+            let run = &run;
+            s.spawn(move |_| {
+                while let Some(&job) = schedule.get(next.fetch_add(1, Ordering::SeqCst)) {
+                    if tx.send((job, run(job))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(tx);
+
+        let mut done = BTreeMap::new();
+        let mut expected = 0;
+        for (job, result) in rx {
+            done.insert(job, result);
+            while let Some(result) = done.remove(&expected) {
+                consume(expected, result);
+                expected += 1;
+            }
+        }
+    })
+    .expect("A worker thread panicked");
+}
+
+/// Which jobs finished, so that jobs can wait for the ones they depend on.
+/// Dependencies must be started before their dependents, or workers could
+/// all end up waiting.
+pub(crate) struct Finished {
+    done: Mutex<Vec<bool>>,
+    changed: Condvar,
+}
+
+impl Finished {
+    pub(crate) fn new(count: usize) -> Finished {
+        Finished {
+            done: Mutex::new(vec![false; count]),
+            changed: Condvar::new(),
+        }
+    }
+
+    /// Waits for the jobs `job` depends on to finish, then returns a guard
+    /// marking `job` finished when dropped, even if it panics.
+    pub(crate) fn start(&self, job: usize, dependencies: &[usize]) -> Running<'_> {
+        let mut done = self.done.lock().unwrap_or_else(PoisonError::into_inner);
+        while dependencies.iter().any(|&d| !done[d]) {
+            done = self
+                .changed
+                .wait(done)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        Running {
+            finished: self,
+            job,
+        }
+    }
+}
+
+pub(crate) struct Running<'a> {
+    finished: &'a Finished,
+    job: usize,
+}
+
+impl<'a> Drop for Running<'a> {
+    fn drop(&mut self) {
+        let mut done = self
+            .finished
+            .done
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        done[self.job] = true;
+        self.finished.changed.notify_all();
+    }
+}
+
+/// Limits how many scenarios with a tag run at once.
+pub(crate) struct Slots {
+    free: Mutex<usize>,
+    released: Condvar,
+}
+
+impl Slots {
+    pub(crate) fn new(count: usize) -> Slots {
+        Slots {
+            free: Mutex::new(count),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Waits for a free slot, which is released when the guard is dropped.
+    pub(crate) fn take(&self) -> Slot<'_> {
+        let mut free = self.free.lock().unwrap_or_else(PoisonError::into_inner);
+        while *free == 0 {
+            free = self
+                .released
+                .wait(free)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        *free -= 1;
+        Slot(self)
+    }
+}
+
+pub(crate) struct Slot<'a>(&'a Slots);
+
+impl<'a> Drop for Slot<'a> {
+    fn drop(&mut self) {
+        *self.0.free.lock().unwrap_or_else(PoisonError::into_inner) += 1;
+        self.0.released.notify_one();
+    }
+}
@@ -0,0 +1,55 @@
+//! The prompt of `--pause-on-failure`, shown after a failed step while the
+//! world of its scenario is still alive, to inspect it before moving on.
+
+use std::io::{self, BufRead, Write};
+
+/// What to do once the prompt is left.
+pub(crate) enum Resume {
+    Continue,
+    Abort,
+}
+
+/// Asks what to do about the failure of `step` until told to continue or
+/// abort. `debug` prints the world, when it can be; `rerun` runs the step
+/// again on it, returning the panic message if it fails.
+pub(crate) fn prompt<W>(
+    step: &gherkin::Step,
+    world: &mut W,
+    debug: Option<fn(&W) -> String>,
+    mut rerun: impl FnMut(&mut W) -> Result<(), String>,
+) -> Resume {
+    let stdin = io::stdin();
+    eprintln!();
+    eprintln!(
+        "Paused after the failure of \"{} {}\".",
+        step.raw_type, step.value
+    );
+
+    loop {
+        eprint!("[p]rint world, [r]e-run step, [c]ontinue, [a]bort? ");
+        let _ = io::stderr().flush();
+
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) | Err(_) => return Resume::Continue,
+            Ok(_) => {}
+        }
+
+        match line.trim() {
+            "p" | "print" => match debug {
+                Some(debug) => eprintln!("{}", debug(world)),
+                None => eprintln!(
+                    "The world can only be printed when pausing with \
+                     CucumberBuilder::pause_on_failure, which requires it to implement Debug."
+                ),
+            },
+            "r" | "rerun" => match rerun(world) {
+                Ok(()) => eprintln!("The step passed."),
+                Err(message) => eprintln!("The step failed again: {}", message),
+            },
+            "" | "c" | "continue" => return Resume::Continue,
+            "a" | "abort" => return Resume::Abort,
+            other => eprintln!("Unknown command: {}", other),
+        }
+    }
+}
@@ -0,0 +1,171 @@
+//! Options of the regular expressions of steps, applied to their pattern
+//! when it is compiled.
+
+/// How the regular expressions of steps match, as set with
+/// `StepsBuilder::regex_options`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegexOptions {
+    /// Whether letters match whatever their case.
+    pub case_insensitive: bool,
+    /// Whether the whole text of steps must match, as if the regular
+    /// expression started with `^` and ended with `$`, rather than any part
+    /// of it.
+    pub anchored: bool,
+    /// Whether `\d`, `\s` and `\w` match any Unicode character of their kind,
+    /// rather than only ASCII ones.
+    pub unicode: bool,
+}
+
+impl Default for RegexOptions {
+    fn default() -> RegexOptions {
+        RegexOptions {
+            case_insensitive: false,
+            anchored: false,
+            unicode: true,
+        }
+    }
+}
+
+impl RegexOptions {
+    /// `regex` rewritten to match as set, but for the case, which is a flag
+    /// of the compiled regular expression.
+    pub(crate) fn pattern(&self, regex: &str) -> String {
+        let regex = if self.unicode {
+            regex.to_string()
+        } else {
+            ascii_classes(regex)
+        };
+        if self.anchored {
+            format!("^(?:{})$", regex)
+        } else {
+            regex
+        }
+    }
+}
+
+/// Replaces the `\d`, `\s` and `\w` classes, and their negations outside of
+/// brackets, with their ASCII counterparts. Turning Unicode off altogether
+/// would also make `.` match any byte, which a `Regex` on text rejects.
+fn ascii_classes(regex: &str) -> String {
+    let mut pattern = String::with_capacity(regex.len());
+    // How deep in brackets the next character is, and whether it is the
+    // first of its brackets, where `]` is taken literally.
+    let mut depth = 0;
+    let mut first = false;
+    let mut chars = regex.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let was_first = first;
+        first = false;
+        match c {
+            '\\' => {
+                let escaped = match chars.next() {
+                    Some(escaped) => escaped,
+                    None => {
+                        pattern.push(c);
+                        break;
+                    }
+                };
+                let class = match escaped {
+                    'd' | 'D' => "0-9",
+                    's' | 'S' => r"\t\n\x0B\x0C\r\x20",
+                    'w' | 'W' => "0-9A-Za-z_",
+                    _ => "",
+                };
+                let negated = escaped.is_uppercase();
+                if class.is_empty() || (depth > 0 && negated) {
+                    pattern.push(c);
+                    pattern.push(escaped);
+                } else if depth > 0 {
+                    pattern.push_str(class);
+                } else if negated {
+                    pattern.push_str(&format!("[^{}]", class));
+                } else {
+                    pattern.push_str(&format!("[{}]", class));
+                }
+            }
+            '[' => {
+                depth += 1;
+                first = true;
+                pattern.push(c);
+                if chars.peek() == Some(&'^') {
+                    pattern.push(chars.next().unwrap());
+                }
+            }
+            ']' if depth > 0 && !was_first => {
+                depth -= 1;
+                pattern.push(c);
+            }
+            c => pattern.push(c),
+        }
+    }
+
+    pattern
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+
+    use super::*;
+
+    fn ascii() -> RegexOptions {
+        RegexOptions {
+            unicode: false,
+            ..RegexOptions::default()
+        }
+    }
+
+    #[test]
+    fn ascii_classes_outside_brackets() {
+        assert_eq!(ascii_classes(r"\d+"), "[0-9]+");
+        assert_eq!(ascii_classes(r"\D"), "[^0-9]");
+        assert_eq!(ascii_classes(r"\w\W"), "[0-9A-Za-z_][^0-9A-Za-z_]");
+        assert_eq!(ascii_classes(r"\s"), r"[\t\n\x0B\x0C\r\x20]");
+    }
+
+    #[test]
+    fn ascii_classes_inside_brackets() {
+        assert_eq!(ascii_classes(r"[\d.]"), "[0-9.]");
+        assert_eq!(ascii_classes(r"[^\w-]"), "[^0-9A-Za-z_-]");
+        // A negated class cannot be spelled out inside brackets.
+        assert_eq!(ascii_classes(r"[\D]"), r"[\D]");
+        assert_eq!(ascii_classes(r"[a-z[\d]]\d"), "[a-z[0-9]][0-9]");
+    }
+
+    #[test]
+    fn ascii_classes_leading_bracket() {
+        // `]` first in its brackets is literal, so the class goes on.
+        assert_eq!(ascii_classes(r"[]\d]\d"), "[]0-9][0-9]");
+        assert_eq!(ascii_classes(r"[^]\d]\d"), "[^]0-9][0-9]");
+    }
+
+    #[test]
+    fn ascii_classes_keep_escapes() {
+        assert_eq!(ascii_classes(r"\.\[\d\]"), r"\.\[[0-9]\]");
+        assert_eq!(ascii_classes(r"\\d"), r"\\d");
+        assert_eq!(ascii_classes(r"\bx\b"), r"\bx\b");
+        assert_eq!(ascii_classes(r"ends with \"), r"ends with \");
+    }
+
+    #[test]
+    fn ascii_digits_only() {
+        let regex = Regex::new(&ascii().pattern(r"^\d+$")).unwrap();
+        assert!(regex.is_match("42"));
+        assert!(!regex.is_match("٤٢"));
+        let regex = Regex::new(&RegexOptions::default().pattern(r"^\d+$")).unwrap();
+        assert!(regex.is_match("٤٢"));
+    }
+
+    #[test]
+    fn anchored() {
+        let options = RegexOptions {
+            anchored: true,
+            ..RegexOptions::default()
+        };
+        assert_eq!(options.pattern("a|b"), "^(?:a|b)$");
+        let regex = Regex::new(&options.pattern("a|b")).unwrap();
+        assert!(regex.is_match("b"));
+        assert!(!regex.is_match("ab"));
+    }
+}
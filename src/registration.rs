@@ -0,0 +1,40 @@
+//! Steps defined with the `#[given]`, `#[when]` and `#[then]` attributes,
+//! collected at link time from every module and crate so that they need not
+//! be listed.
+
+use std::any::{Any, TypeId};
+
+use crate::{StepDefinition, StepsBuilder, World};
+
+/// A step definition submitted by the attributes, for any world.
+#[doc(hidden)]
+pub struct Registration {
+    world: TypeId,
+    register: fn(&mut dyn Any),
+}
+
+impl Registration {
+    pub fn new<W: World + 'static, D: StepDefinition<W>>() -> Registration {
+        Registration {
+            world: TypeId::of::<W>(),
+            register: register::<W, D>,
+        }
+    }
+}
+
+inventory::collect!(Registration);
+
+fn register<W: World + 'static, D: StepDefinition<W>>(steps: &mut dyn Any) {
+    if let Some(steps) = steps.downcast_mut::<StepsBuilder<W>>() {
+        D::register(steps);
+    }
+}
+
+/// Adds the steps defined with the attributes for worlds of type `W`.
+pub(crate) fn register_all<W: World + 'static>(steps: &mut StepsBuilder<W>) {
+    for registration in inventory::iter::<Registration> {
+        if registration.world == TypeId::of::<W>() {
+            (registration.register)(steps);
+        }
+    }
+}
@@ -0,0 +1,435 @@
+//! Types describing the machine-readable reports written by the structured
+//! outputs, such as [`JsonOutput`](../type.JsonOutput.html).
+//!
+//! Every report carries a `schema_version`. It is bumped whenever a field is
+//! removed or changes meaning; adding new optional fields does not bump it.
+//! Consumers should check it before relying on the rest of the document.
+
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::panic_trap::PanicDetails;
+use crate::{StepDefinitionInfo, TestResult};
+
+/// The exit code of a run stopped by its time budget.
+pub(crate) const TIMED_OUT_EXIT_CODE: i32 = 124;
+
+/// The version of the report schema produced by this release.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// A complete run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Report {
+    /// Always [`SCHEMA_VERSION`](constant.SCHEMA_VERSION.html) when written by this crate.
+    pub schema_version: u32,
+    /// The version of `cucumber_rust` which produced the report.
+    pub cucumber_version: String,
+    /// Free-form details about the environment of the run, such as the git
+    /// revision or the CI job URL.
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+    /// The external services started for the run, with what they printed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub services: Vec<Service>,
+    pub features: Vec<Feature>,
+}
+
+impl Default for Report {
+    fn default() -> Report {
+        Report {
+            schema_version: SCHEMA_VERSION,
+            cucumber_version: env!("CARGO_PKG_VERSION").to_string(),
+            metadata: BTreeMap::new(),
+            services: vec![],
+            features: vec![],
+        }
+    }
+}
+
+/// The outcome of a run, as returned by
+/// [`CucumberBuilder::run`](../struct.CucumberBuilder.html#method.run).
+#[derive(Debug, Clone)]
+pub struct RunSummary {
+    /// Whether every scenario passed and every feature file could be parsed.
+    pub success: bool,
+    /// Whether the time budget ran out before every scenario could run.
+    pub timed_out: bool,
+    /// The seed of the random order, if scenarios ran in one.
+    pub seed: Option<u64>,
+    pub duration: Duration,
+    /// The results of every feature, scenario and step.
+    pub report: Report,
+    /// The scenarios which got slower than in the `--baseline` report.
+    pub regressions: Vec<Regression>,
+    /// The step definitions no step matched, with `--unused-steps`.
+    pub unused_steps: Vec<StepDefinitionInfo>,
+}
+
+impl RunSummary {
+    /// The process exit code matching the outcome: 0 on success, 124 if the
+    /// time budget ran out, as with `timeout`, and 1 otherwise.
+    pub fn exit_code(&self) -> i32 {
+        if self.success {
+            0
+        } else if self.timed_out {
+            TIMED_OUT_EXIT_CODE
+        } else {
+            1
+        }
+    }
+
+    pub fn scenarios(&self) -> impl Iterator<Item = &Scenario> {
+        self.report.features.iter().flat_map(|f| f.scenarios.iter())
+    }
+
+    /// How many scenarios ended with `status`.
+    pub fn count(&self, status: Status) -> usize {
+        self.scenarios().filter(|s| s.status == status).count()
+    }
+
+    /// The scenarios which failed or crashed.
+    pub fn failed_scenarios(&self) -> impl Iterator<Item = &Scenario> {
+        self.scenarios().filter(|s| s.status.is_failure())
+    }
+
+    /// The scenarios expected to fail which passed.
+    pub fn unexpected_passes(&self) -> impl Iterator<Item = &Scenario> {
+        self.scenarios()
+            .filter(|s| s.xfail && s.status == Status::Passed)
+    }
+
+    /// How many runs of each scenario passed, out of how many, for runs
+    /// repeating scenarios. Scenarios are listed in the order they first ran.
+    pub fn pass_rates(&self) -> Vec<PassRate> {
+        let mut rates: Vec<PassRate> = vec![];
+
+        for feature in &self.report.features {
+            for scenario in &feature.scenarios {
+                let index = match rates
+                    .iter()
+                    .position(|r| r.path == feature.path && r.line == scenario.line)
+                {
+                    Some(index) => index,
+                    None => {
+                        rates.push(PassRate {
+                            path: feature.path.clone(),
+                            line: scenario.line,
+                            name: scenario.name.clone(),
+                            passed: 0,
+                            runs: 0,
+                        });
+                        rates.len() - 1
+                    }
+                };
+                rates[index].runs += 1;
+                if scenario.status == Status::Passed {
+                    rates[index].passed += 1;
+                }
+            }
+        }
+
+        rates
+    }
+}
+
+/// A scenario which took longer than in a baseline report.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Regression {
+    pub path: String,
+    pub line: usize,
+    pub name: String,
+    pub baseline: Duration,
+    pub duration: Duration,
+}
+
+impl Regression {
+    /// How much longer the scenario took, in percent of the baseline.
+    pub fn slowdown_percent(&self) -> f64 {
+        let baseline = self.baseline.as_secs_f64();
+        if baseline == 0.0 {
+            return f64::INFINITY;
+        }
+        (self.duration.as_secs_f64() - baseline) / baseline * 100.0
+    }
+}
+
+/// The outcome of the repeated runs of a scenario.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PassRate {
+    pub path: String,
+    pub line: usize,
+    pub name: String,
+    pub passed: usize,
+    pub runs: usize,
+}
+
+/// A feature file, or a file that failed to parse.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Feature {
+    pub name: String,
+    pub path: String,
+    pub line: usize,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Set when the file could not be parsed; `scenarios` is then empty.
+    #[serde(default)]
+    pub error: Option<String>,
+    pub scenarios: Vec<Scenario>,
+    /// Names of the scenarios and outlines left out by the `--name` filter.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub filtered_out: Vec<String>,
+    /// Names of the scenarios left out because the run was stopped early.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub not_run: Vec<String>,
+    /// Names of the scenarios whose `@depends-on` tags form a cycle, which
+    /// are also in `not_run`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dependency_cycle: Vec<String>,
+    /// The before and after feature hooks which failed, in the order they ran.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hook_failures: Vec<HookFailure>,
+}
+
+/// A scenario, or a single example of a scenario outline.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Scenario {
+    pub name: String,
+    pub line: usize,
+    /// The name of the rule containing this scenario, if any.
+    #[serde(default)]
+    pub rule: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// For outline examples, the header and values of the example row.
+    #[serde(default)]
+    pub examples: Vec<(String, String)>,
+    pub status: Status,
+    /// Why the scenario was skipped without running, e.g. because of an
+    /// `@skip` tag.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skip_reason: Option<String>,
+    /// What the scenario used, when recorded with `--resource-usage`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resources: Option<ResourceUsage>,
+    /// Set when the scenario failed but is quarantined, so that its failure
+    /// did not fail the run.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub quarantined: bool,
+    /// Set when the scenario is expected to fail: a failure did not fail the
+    /// run, and a `passed` status is unexpected.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub xfail: bool,
+    /// Set when tearing the world down failed, after the steps ran.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub teardown_failure: Option<Failure>,
+    /// The before and after hooks which failed, in the order they ran.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hook_failures: Vec<HookFailure>,
+    pub steps: Vec<Step>,
+}
+
+/// The resources used by a scenario.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    /// CPU time, user and system, in milliseconds, if known.
+    #[serde(default)]
+    pub cpu_ms: Option<u64>,
+    /// Peak resident memory, in kilobytes; only known for isolated scenarios.
+    #[serde(default)]
+    pub peak_rss_kb: Option<u64>,
+}
+
+/// A single executed (or skipped) step.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Step {
+    /// The keyword as written in the feature file, e.g. `And`.
+    pub keyword: String,
+    pub text: String,
+    pub line: usize,
+    pub status: Status,
+    /// Milliseconds since the Unix epoch at which the step started.
+    pub started_ms: u64,
+    /// Time spent running the step, in nanoseconds.
+    pub duration_ns: u64,
+    #[serde(default)]
+    pub failure: Option<Failure>,
+    /// Captured standard output, if any, possibly truncated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stdout: Option<String>,
+    /// Captured standard error, if any, possibly truncated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stderr: Option<String>,
+}
+
+/// An external service started for the run, as added with
+/// [`CucumberBuilder::service`](../struct.CucumberBuilder.html#method.service).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Service {
+    pub name: String,
+    /// Why it could not be started or made ready, or why it stopped early.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The end of what it printed, at most the last 64 KiB of each stream.
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// A failed hook of a scenario or a feature.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HookFailure {
+    pub hook: Hook,
+    pub failure: Failure,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Hook {
+    Before,
+    After,
+}
+
+impl Hook {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Hook::Before => "before",
+            Hook::After => "after",
+        }
+    }
+}
+
+/// Details of a failed step, hook or teardown.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Failure {
+    pub message: String,
+    /// The `file:line:column` of the panic.
+    pub location: String,
+    /// Set when backtraces are captured.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backtrace: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Passed,
+    Failed,
+    Skipped,
+    Unimplemented,
+    /// The process running the scenario died; see
+    /// [`CucumberBuilder::isolate`](../struct.CucumberBuilder.html#method.isolate).
+    Crashed,
+    /// The step matches more than one definition.
+    Ambiguous,
+    /// The step called `pending!`.
+    Pending,
+}
+
+impl From<PanicDetails> for Failure {
+    fn from(panic_info: PanicDetails) -> Failure {
+        Failure {
+            message: panic_info.payload,
+            location: panic_info.location,
+            backtrace: panic_info.backtrace,
+        }
+    }
+}
+
+impl Status {
+    /// Whether this status fails a scenario.
+    pub fn is_failure(self) -> bool {
+        self == Status::Failed || self == Status::Crashed || self == Status::Ambiguous
+    }
+
+    /// The name used for this status in serialized reports.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Status::Passed => "passed",
+            Status::Failed => "failed",
+            Status::Skipped => "skipped",
+            Status::Unimplemented => "unimplemented",
+            Status::Crashed => "crashed",
+            Status::Ambiguous => "ambiguous",
+            Status::Pending => "pending",
+        }
+    }
+
+    /// Combines the status of a scenario so far with the status of its next step.
+    pub fn merge(self, step: Status) -> Status {
+        match (self, step) {
+            (Status::Crashed, _) | (_, Status::Crashed) => Status::Crashed,
+            (Status::Failed, _) | (_, Status::Failed) => Status::Failed,
+            (Status::Ambiguous, _) | (_, Status::Ambiguous) => Status::Ambiguous,
+            (Status::Passed, other) => other,
+            (this, _) => this,
+        }
+    }
+}
+
+impl<'a> From<&'a TestResult> for Status {
+    fn from(result: &'a TestResult) -> Status {
+        match result {
+            TestResult::Pass => Status::Passed,
+            TestResult::Fail(..) => Status::Failed,
+            TestResult::Skipped => Status::Skipped,
+            TestResult::Unimplemented => Status::Unimplemented,
+            TestResult::Crashed(..) => Status::Crashed,
+            TestResult::Ambiguous(_) => Status::Ambiguous,
+            TestResult::Pending(_) => Status::Pending,
+        }
+    }
+}
+
+impl Feature {
+    pub fn duration(&self) -> Duration {
+        self.scenarios.iter().map(Scenario::duration).sum()
+    }
+}
+
+impl Scenario {
+    pub fn duration(&self) -> Duration {
+        self.steps.iter().map(Step::duration).sum()
+    }
+
+    /// The captured stdout of all steps, in order.
+    pub fn stdout(&self) -> String {
+        self.steps.iter().filter_map(|s| s.stdout.as_ref()).cloned().collect()
+    }
+
+    /// The captured stderr of all steps, in order.
+    pub fn stderr(&self) -> String {
+        self.steps.iter().filter_map(|s| s.stderr.as_ref()).cloned().collect()
+    }
+
+    /// The first failed step, if any.
+    pub fn failed_step(&self) -> Option<&Step> {
+        self.steps.iter().find(|s| s.status.is_failure())
+    }
+}
+
+impl Step {
+    pub fn duration(&self) -> Duration {
+        Duration::from_nanos(self.duration_ns)
+    }
+}
+
+pub(crate) fn millis_since_epoch(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+pub(crate) fn nanos(duration: Duration) -> u64 {
+    duration.as_nanos() as u64
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+pub(crate) fn tags(tags: &Option<Vec<String>>) -> Vec<String> {
+    tags.clone().unwrap_or_default()
+}
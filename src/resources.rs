@@ -0,0 +1,103 @@
+//! Sampling the CPU time and memory used by scenarios, for `--resource-usage`.
+//!
+//! CPU time is that of the thread running the scenario, where the OS tells it
+//! apart, or of the whole process in a child running a single scenario. Peak
+//! memory is only known per process, so it is only sampled in such children.
+
+use std::time::Duration;
+
+use crate::report::ResourceUsage;
+
+/// What a scenario used so far, to be compared with a later sample.
+pub(crate) struct Sample {
+    cpu: Option<Duration>,
+    whole_process: bool,
+}
+
+impl Sample {
+    /// Samples the calling thread, or the whole process when it runs a
+    /// single scenario.
+    pub(crate) fn now(whole_process: bool) -> Sample {
+        Sample {
+            cpu: cpu_time(whole_process),
+            whole_process,
+        }
+    }
+
+    /// What was used since this sample was taken, if anything is known.
+    pub(crate) fn usage(&self) -> Option<ResourceUsage> {
+        let cpu = match (self.cpu, cpu_time(self.whole_process)) {
+            (Some(before), Some(after)) => Some(after.checked_sub(before).unwrap_or_default()),
+            _ => None,
+        };
+        let peak_rss_kb = if self.whole_process {
+            peak_rss_kb()
+        } else {
+            None
+        };
+        if cpu.is_none() && peak_rss_kb.is_none() {
+            return None;
+        }
+
+        Some(ResourceUsage {
+            cpu_ms: cpu.map(|cpu| cpu.as_millis() as u64),
+            peak_rss_kb,
+        })
+    }
+}
+
+#[cfg(unix)]
+fn rusage(who: libc::c_int) -> Option<libc::rusage> {
+    let mut usage = unsafe { std::mem::zeroed::<libc::rusage>() };
+    if unsafe { libc::getrusage(who, &mut usage) } == 0 {
+        Some(usage)
+    } else {
+        None
+    }
+}
+
+#[cfg(unix)]
+fn total_cpu(usage: &libc::rusage) -> Duration {
+    let micros = |time: libc::timeval| time.tv_sec as u64 * 1_000_000 + time.tv_usec as u64;
+    Duration::from_micros(micros(usage.ru_utime) + micros(usage.ru_stime))
+}
+
+#[cfg(target_os = "linux")]
+fn cpu_time(whole_process: bool) -> Option<Duration> {
+    let who = if whole_process {
+        libc::RUSAGE_SELF
+    } else {
+        libc::RUSAGE_THREAD
+    };
+    rusage(who).map(|usage| total_cpu(&usage))
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn cpu_time(whole_process: bool) -> Option<Duration> {
+    if whole_process {
+        rusage(libc::RUSAGE_SELF).map(|usage| total_cpu(&usage))
+    } else {
+        None
+    }
+}
+
+#[cfg(not(unix))]
+fn cpu_time(_whole_process: bool) -> Option<Duration> {
+    None
+}
+
+#[cfg(unix)]
+fn peak_rss_kb() -> Option<u64> {
+    let max_rss = rusage(libc::RUSAGE_SELF)?.ru_maxrss as u64;
+    // macOS counts in bytes, other systems in kilobytes.
+    if cfg!(target_os = "macos") {
+        Some(max_rss / 1024)
+    } else {
+        Some(max_rss)
+    }
+}
+
+#[cfg(not(unix))]
+fn peak_rss_kb() -> Option<u64> {
+    None
+}
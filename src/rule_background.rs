@@ -0,0 +1,250 @@
+//! Backgrounds of rules, which the Gherkin parser does not read: they are
+//! taken out of the source before it is parsed, and their steps added at the
+//! start of each scenario of their rule, after those of the feature
+//! background. Their descriptions are dropped, as the parser reads none in
+//! backgrounds.
+//!
+//! Only English keywords are looked for, as the parser reads no others:
+//! `# language:` is not honoured.
+
+use std::convert::TryFrom;
+
+use gherkin::Feature;
+
+/// Parses the feature in `source`, backgrounds of rules included.
+pub(crate) fn parse_feature(source: &str) -> Result<Feature, gherkin::Error> {
+    let (kept, backgrounds) = take_backgrounds(source);
+    if backgrounds.is_empty() {
+        return Feature::try_from(source);
+    }
+    let mut feature = Feature::try_from(kept.as_str())?;
+    for (rule, text) in backgrounds {
+        let steps = Feature::try_from(text.as_str())?
+            .background
+            .map(|background| background.steps)
+            .unwrap_or_default();
+        if let Some(rule) = feature.rules.get_mut(rule) {
+            for scenario in rule.scenarios.iter_mut() {
+                scenario.steps.splice(0..0, steps.iter().cloned());
+            }
+        }
+    }
+    Ok(feature)
+}
+
+/// `source` without the backgrounds of rules, blank lines in their place so
+/// that positions are kept, and each background as a feature of its own with
+/// the rule it belongs to, counting from 0.
+fn take_backgrounds(source: &str) -> (String, Vec<(usize, String)>) {
+    let mut kept = String::with_capacity(source.len());
+    let mut backgrounds = vec![];
+    let mut rules = 0;
+    let mut fence = None;
+    let mut background: Option<String> = None;
+    // Whether the steps of the background are yet to come.
+    let mut in_description = false;
+
+    for (i, line) in source.lines().enumerate() {
+        let trimmed = line.trim_start();
+        let mut description = false;
+        match fence {
+            // Docstrings may hold anything, keywords included.
+            Some(end) => {
+                if trimmed.starts_with(end) {
+                    fence = None;
+                }
+            }
+            None if trimmed.starts_with("\"\"\"") => fence = Some("\"\"\""),
+            None if trimmed.starts_with("```") => fence = Some("```"),
+            None => {
+                if starts_block(trimmed) {
+                    if let Some(text) = background.take() {
+                        backgrounds.push((rules - 1, text));
+                    }
+                }
+                if trimmed.starts_with("Rule:") {
+                    rules += 1;
+                } else if rules > 0 && trimmed.starts_with("Background:") {
+                    // Padded to the line of the background, for its steps to
+                    // keep their position.
+                    background = Some(format!("Feature: Rule background{}", "\n".repeat(i)));
+                    in_description = true;
+                } else if background.is_some() && in_description {
+                    // The description runs up to the first step.
+                    in_description = !is_step(trimmed);
+                    description = in_description && !trimmed.starts_with('#');
+                }
+            }
+        }
+
+        match background.as_mut() {
+            Some(text) => {
+                // The parser takes neither descriptions nor blank lines before
+                // the steps of a background, but takes comments.
+                text.push_str(if description { "#" } else { line });
+                text.push('\n');
+                kept.push('\n');
+            }
+            None => {
+                kept.push_str(line);
+                kept.push('\n');
+            }
+        }
+    }
+    if let Some(text) = background {
+        backgrounds.push((rules - 1, text));
+    }
+    (kept, backgrounds)
+}
+
+/// Whether `line`, without its indentation, starts a block ending the
+/// background before it. Keywords are matched with their colon, so that a
+/// line of a description such as `Scenarios below share it` is not one.
+fn starts_block(line: &str) -> bool {
+    [
+        "Scenario:",
+        "Scenario Outline:",
+        "Scenario Template:",
+        "Example:",
+        "Examples:",
+        "Scenarios:",
+        "Rule:",
+        "Background:",
+        "@",
+    ]
+    .iter()
+    .any(|keyword| line.starts_with(keyword))
+}
+
+/// Whether `line`, without its indentation, is a step.
+fn is_step(line: &str) -> bool {
+    ["Given ", "When ", "Then ", "And ", "But ", "* "]
+        .iter()
+        .any(|keyword| line.starts_with(keyword))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"Feature: Accounts
+  Background:
+    Given a bank
+
+  Rule: Overdrafts are refused
+    Background:
+      Given an account with 10 EUR
+      And a note
+        """
+        Scenario: not a scenario
+        Rule: not a rule
+        """
+
+    Scenario: Withdrawing too much
+      When I withdraw 20 EUR
+
+  Rule: Deposits are accepted
+
+    Scenario: Depositing
+      When I deposit 20 EUR
+"#;
+
+    fn steps(scenario: &gherkin::Scenario) -> Vec<(&str, usize)> {
+        scenario
+            .steps
+            .iter()
+            .map(|step| (step.value.as_str(), step.position.0))
+            .collect()
+    }
+
+    #[test]
+    fn adds_the_steps_of_the_background_of_a_rule() {
+        let feature = parse_feature(SOURCE).unwrap();
+
+        assert_eq!(feature.rules.len(), 2);
+        assert_eq!(feature.rules[0].scenarios.len(), 1);
+        assert_eq!(
+            steps(&feature.rules[0].scenarios[0]),
+            vec![
+                ("an account with 10 EUR", 7),
+                ("a note", 8),
+                ("I withdraw 20 EUR", 15)
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_the_feature_background() {
+        let feature = parse_feature(SOURCE).unwrap();
+
+        let background = feature.background.unwrap();
+        assert_eq!(background.steps.len(), 1);
+        assert_eq!(background.steps[0].value, "a bank");
+        assert_eq!(background.steps[0].position.0, 3);
+    }
+
+    #[test]
+    fn keeps_docstrings_in_backgrounds() {
+        let feature = parse_feature(SOURCE).unwrap();
+
+        let note = &feature.rules[0].scenarios[0].steps[1];
+        assert_eq!(
+            note.docstring.as_deref().map(str::trim),
+            Some("Scenario: not a scenario\nRule: not a rule")
+        );
+    }
+
+    #[test]
+    fn leaves_rules_without_a_background_alone() {
+        let feature = parse_feature(SOURCE).unwrap();
+
+        assert_eq!(feature.rules[1].scenarios.len(), 1);
+        assert_eq!(
+            steps(&feature.rules[1].scenarios[0]),
+            vec![("I deposit 20 EUR", 20)]
+        );
+    }
+
+    #[test]
+    fn reads_descriptions_starting_with_keywords_as_part_of_the_background() {
+        let source = r#"Feature: Accounts
+  Background:
+    Given a bank
+
+  Rule: Overdrafts are refused
+    Background:
+      Scenarios below share this account.
+      Examples of overdrafts follow.
+      Given an account with 10 EUR
+
+    Scenario: Withdrawing too much
+      When I withdraw 20 EUR
+"#;
+
+        let feature = parse_feature(source).unwrap();
+        assert_eq!(feature.rules[0].scenarios.len(), 1);
+        assert_eq!(
+            steps(&feature.rules[0].scenarios[0]),
+            vec![("an account with 10 EUR", 9), ("I withdraw 20 EUR", 12)]
+        );
+    }
+
+    #[test]
+    fn starts_blocks_at_whole_keywords() {
+        assert!(starts_block("Scenario: One"));
+        assert!(starts_block("Scenario Outline: Many"));
+        assert!(starts_block("Example: One"));
+        assert!(starts_block("@slow"));
+        assert!(!starts_block("Scenarios below share this account."));
+        assert!(!starts_block("Examples of overdrafts follow."));
+        assert!(!starts_block("Rules apply."));
+    }
+
+    #[test]
+    fn parses_features_without_rule_backgrounds_as_they_are() {
+        let source = "Feature: Plain\n\n  Scenario: One\n    Given a step\n";
+
+        let feature = parse_feature(source).unwrap();
+        assert_eq!(steps(&feature.scenarios[0]), vec![("a step", 4)]);
+    }
+}
@@ -0,0 +1,270 @@
+//! External processes the run depends on, such as a database or a container,
+//! started before it, waited for until ready and stopped after it, with what
+//! they print kept for the report.
+
+use std::io::Read;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::report;
+
+/// How much of each stream of a service is kept, from its end.
+const LOG_LIMIT: usize = 64 * 1024;
+/// How long a service is given to exit once asked to, before it is killed.
+#[cfg(unix)]
+const STOP_GRACE: Duration = Duration::from_secs(5);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// An external process to start before the run, given to
+/// `CucumberBuilder::service`.
+///
+/// ```ignore
+/// let mut command = Command::new("docker");
+/// command.args(&["run", "--rm", "-p", "5432:5432", "postgres:12"]);
+/// builder.service(Service {
+///     ready: Some(Readiness::Tcp("localhost:5432".to_string())),
+///     ..Service::new("postgres", command)
+/// });
+/// ```
+pub struct Service {
+    pub name: String,
+    pub command: Command,
+    /// How to tell it is ready; when `None`, as soon as it started.
+    pub ready: Option<Readiness>,
+    /// How long it is given to get ready. Defaults to 30 seconds.
+    pub timeout: Duration,
+}
+
+impl Service {
+    pub fn new(name: &str, command: Command) -> Service {
+        Service {
+            name: name.to_string(),
+            command,
+            ready: None,
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// How to tell a service is ready.
+pub enum Readiness {
+    /// It accepts TCP connections at an address, e.g. `localhost:5432`.
+    Tcp(String),
+    /// It printed a text, on its standard output or error.
+    Output(String),
+    /// A function returns `true`, e.g. after a successful health request.
+    Check(fn() -> bool),
+}
+
+/// What a service printed so far on one stream.
+#[derive(Clone, Default)]
+struct Log(Arc<Mutex<Vec<u8>>>);
+
+impl Log {
+    /// Keeps what `pipe` prints, until it is closed.
+    fn read<R: Read + Send + 'static>(&self, pipe: Option<R>) {
+        let log = self.clone();
+        let mut pipe = match pipe {
+            Some(pipe) => pipe,
+            None => return,
+        };
+        thread::spawn(move || {
+            let mut buffer = [0; 4096];
+            while let Ok(read) = pipe.read(&mut buffer) {
+                if read == 0 {
+                    break;
+                }
+                let mut log = log.0.lock().unwrap();
+                log.extend_from_slice(&buffer[..read]);
+                // Trimmed well past the limit, not to shift it on every read.
+                if log.len() > 2 * LOG_LIMIT {
+                    let excess = log.len() - LOG_LIMIT;
+                    log.drain(..excess);
+                }
+            }
+        });
+    }
+
+    fn contains(&self, text: &str) -> bool {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).contains(text)
+    }
+
+    fn text(&self) -> String {
+        let log = self.0.lock().unwrap();
+        let start = log.len().saturating_sub(LOG_LIMIT);
+        String::from_utf8_lossy(&log[start..]).into_owned()
+    }
+}
+
+struct Running {
+    name: String,
+    child: Mutex<Option<Child>>,
+    stdout: Log,
+    stderr: Log,
+    /// Why it could not be started or made ready.
+    error: Option<String>,
+}
+
+/// The services of the run, stopped in the reverse order they started in
+/// when dropped.
+#[derive(Default)]
+pub(crate) struct Services {
+    running: Vec<Running>,
+}
+
+impl Services {
+    /// Starts `services` in order, each once the previous one is ready, and
+    /// stops at the first one which fails.
+    pub(crate) fn start(services: Vec<Service>) -> Services {
+        let mut started = Services::default();
+        for service in services {
+            let running = Running::start(service);
+            let failed = running.error.is_some();
+            started.running.push(running);
+            if failed {
+                break;
+            }
+        }
+        started
+    }
+
+    /// Whether a service could not be started or made ready.
+    pub(crate) fn failed(&self) -> bool {
+        self.running.iter().any(|service| service.error.is_some())
+    }
+
+    /// Stops the services in the reverse order they started in, as when
+    /// dropped, for the process to exit right after.
+    pub(crate) fn stop(&self) {
+        for service in self.running.iter().rev() {
+            if let Some(child) = service.child.lock().unwrap().take() {
+                stop(child);
+            }
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.running.is_empty()
+    }
+
+    /// The services for the report, with what they printed so far. One which
+    /// already exited is reported as failed.
+    pub(crate) fn reports(&self) -> Vec<report::Service> {
+        self.running
+            .iter()
+            .map(|service| {
+                let exited = match service.child.lock().unwrap().as_mut() {
+                    Some(child) => child.try_wait().ok().and_then(|status| status),
+                    None => None,
+                };
+                let error = service.error.clone().or_else(|| {
+                    exited.map(|status| format!("it exited during the run ({})", status))
+                });
+                report::Service {
+                    name: service.name.clone(),
+                    error,
+                    stdout: service.stdout.text(),
+                    stderr: service.stderr.text(),
+                }
+            })
+            .collect()
+    }
+}
+
+impl Drop for Services {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl Running {
+    fn start(service: Service) -> Running {
+        let Service {
+            name,
+            mut command,
+            ready,
+            timeout,
+        } = service;
+        let mut running = Running {
+            name,
+            child: Mutex::new(None),
+            stdout: Log::default(),
+            stderr: Log::default(),
+            error: None,
+        };
+
+        let mut child = match command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) => {
+                running.error = Some(format!("it could not be started: {}", e));
+                return running;
+            }
+        };
+        running.stdout.read(child.stdout.take());
+        running.stderr.read(child.stderr.take());
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Ok(Some(status)) = child.try_wait() {
+                running.error = Some(format!("it exited before it was ready ({})", status));
+                break;
+            }
+            if running.is_ready(ready.as_ref()) {
+                break;
+            }
+            if Instant::now() >= deadline {
+                running.error = Some(format!("it was not ready after {:?}", timeout));
+                break;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+        running.child = Mutex::new(Some(child));
+        running
+    }
+
+    fn is_ready(&self, ready: Option<&Readiness>) -> bool {
+        match ready {
+            None => true,
+            Some(Readiness::Tcp(address)) => match address.to_socket_addrs() {
+                Ok(mut addresses) => addresses
+                    .any(|address| TcpStream::connect_timeout(&address, POLL_INTERVAL).is_ok()),
+                Err(_) => false,
+            },
+            Some(Readiness::Output(text)) => {
+                self.stdout.contains(text) || self.stderr.contains(text)
+            }
+            Some(Readiness::Check(check)) => check(),
+        }
+    }
+}
+
+/// Asks `child` to exit, and kills it if it is still running after a grace
+/// period, or right away where it cannot be asked.
+fn stop(mut child: Child) {
+    if let Ok(Some(_)) = child.try_wait() {
+        return;
+    }
+    #[cfg(unix)]
+    {
+        unsafe {
+            libc::kill(child.id() as libc::pid_t, libc::SIGTERM);
+        }
+        let deadline = Instant::now() + STOP_GRACE;
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = child.try_wait() {
+                return;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+}
@@ -0,0 +1,43 @@
+//! Read-only state computed once per run, such as configuration or a
+//! connection pool, and shared by every scenario instead of being rebuilt
+//! by each world.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use lazy_static::lazy_static;
+
+lazy_static! {
+    static ref SHARED: RwLock<HashMap<TypeId, Arc<dyn Any + Send + Sync>>> =
+        RwLock::new(HashMap::new());
+}
+
+/// Keeps `value` for the rest of the run, replacing any of the same type.
+pub(crate) fn insert<T: Send + Sync + 'static>(value: T) {
+    SHARED
+        .write()
+        .unwrap()
+        .insert(TypeId::of::<T>(), Arc::new(value));
+}
+
+/// The state of type `T` given to `CucumberBuilder::shared`, from anywhere
+/// in the run: a step, a hook or `Default::default` of the world.
+///
+/// # Panics
+///
+/// If no state of type `T` was given.
+pub fn shared<T: Send + Sync + 'static>() -> Arc<T> {
+    try_shared().unwrap_or_else(|| {
+        panic!(
+            "no shared state of type `{}`; add it with `CucumberBuilder::shared`",
+            std::any::type_name::<T>()
+        )
+    })
+}
+
+/// The state of type `T` given to `CucumberBuilder::shared`, if any.
+pub fn try_shared<T: Send + Sync + 'static>() -> Option<Arc<T>> {
+    let value = SHARED.read().unwrap().get(&TypeId::of::<T>())?.clone();
+    value.downcast().ok()
+}
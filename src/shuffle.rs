@@ -0,0 +1,53 @@
+//! Reproducible shuffling for the random scenario order, and the stable hash
+//! which assigns scenarios to CI shards.
+//!
+//! The generator is implemented here rather than taken from a crate so that
+//! a seed keeps producing the same order across releases.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A SplitMix64 generator.
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    /// Creates a generator for the list identified by `salt`, so that every
+    /// list gets its own sequence no matter in which order lists are shuffled.
+    pub(crate) fn new(seed: u64, salt: &str) -> Rng {
+        Rng(seed ^ hash(salt))
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Fisher-Yates shuffle.
+    pub(crate) fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = (self.next_u64() % (i as u64 + 1)) as usize;
+            items.swap(i, j);
+        }
+    }
+}
+
+/// FNV-1a, a hash which is stable across releases and platforms.
+pub(crate) fn hash(s: &str) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    for byte in s.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+/// A seed for runs which did not ask for a specific one.
+pub(crate) fn random_seed() -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    // Keep seeds short enough to be typed back in.
+    (now.as_secs() ^ u64::from(now.subsec_nanos())) % 1_000_000
+}
@@ -0,0 +1,491 @@
+//! Reading data tables into typed values with serde, instead of indexing
+//! `rows[i][j]` by hand.
+//!
+//! Each row is deserialized as a map from the cells of the header to its own
+//! cells, so a struct's fields are named by the header. Cells are parsed to
+//! numbers and booleans as needed, an empty cell is `None` for an `Option`,
+//! and unit enum variants are matched by name.
+//!
+//! Vertical tables of two columns, one key and one value per row, have no
+//! header: their first row is a pair like the others.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use gherkin::Table;
+use serde::de::{
+    self, Deserialize, DeserializeOwned, DeserializeSeed, IntoDeserializer, MapAccess, SeqAccess,
+    Visitor,
+};
+use serde::Serialize;
+
+use crate::table_diff::{TableDiff, TableMatch};
+
+/// Why a data table could not be read, and where.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableError {
+    /// The data row at fault, from 1, not counting the header.
+    pub row: Option<usize>,
+    /// The line of the row in the feature file, or of the table when no
+    /// row is at fault.
+    pub line: Option<usize>,
+    /// The header of the column at fault.
+    pub column: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for TableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.row, self.line) {
+            (Some(row), Some(line)) => write!(f, "row {} (line {}), ", row, line)?,
+            (Some(row), None) => write!(f, "row {}, ", row)?,
+            (None, Some(line)) => write!(f, "line {}, ", line)?,
+            (None, None) => {}
+        }
+        if let Some(column) = &self.column {
+            write!(f, "column `{}`: ", column)?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for TableError {}
+
+impl de::Error for TableError {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        TableError {
+            row: None,
+            line: None,
+            column: None,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl TableError {
+    fn in_column(mut self, column: &str) -> Self {
+        if self.column.is_none() {
+            self.column = Some(column.to_string());
+        }
+        self
+    }
+}
+
+/// Typed access to data tables.
+pub trait TableExt {
+    /// Deserializes every row after the header into a `T`, usually a struct
+    /// whose fields are named by the header.
+    fn rows_as<'a, T: Deserialize<'a>>(&'a self) -> Result<Vec<T>, TableError>;
+
+    /// The values of a vertical table by their keys.
+    fn to_map(&self) -> Result<BTreeMap<String, String>, TableError>;
+
+    /// Parses the value of `key` in a vertical table.
+    fn get<T>(&self, key: &str) -> Result<T, TableError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display;
+
+    /// Deserializes a vertical table into a `T`, usually a struct whose
+    /// fields are named by the keys.
+    fn fields_as<T: DeserializeOwned>(&self) -> Result<T, TableError>;
+
+    /// Compares the rows after the header with `actual`, serialized with
+    /// serde, telling how they differ if they do.
+    fn compare<T: Serialize>(&self, actual: &[T], options: TableMatch) -> Result<(), TableDiff>;
+
+    /// Checks that the rows after the header match `actual`, as `compare`
+    /// does.
+    ///
+    /// # Panics
+    ///
+    /// If they do not, with the table marking the rows and cells at fault.
+    fn assert_rows<T: Serialize>(&self, actual: &[T], options: TableMatch) {
+        if let Err(diff) = self.compare(actual, options) {
+            panic!("{}", diff);
+        }
+    }
+}
+
+impl TableExt for Table {
+    fn rows_as<'a, T: Deserialize<'a>>(&'a self) -> Result<Vec<T>, TableError> {
+        self.rows
+            .iter()
+            .enumerate()
+            .map(|(i, row)| {
+                T::deserialize(RowDeserializer {
+                    header: &self.header,
+                    row,
+                })
+                .map_err(|mut e| {
+                    e.row = Some(i + 1);
+                    e.line = Some(self.position.0 + i + 1);
+                    e
+                })
+            })
+            .collect()
+    }
+
+    fn to_map(&self) -> Result<BTreeMap<String, String>, TableError> {
+        let mut map = BTreeMap::new();
+        for (line, key, value) in pairs(self)? {
+            if map.insert(key.to_string(), value.to_string()).is_some() {
+                return Err(TableError {
+                    line: Some(line),
+                    ..de::Error::custom(format!("`{}` is given twice", key))
+                });
+            }
+        }
+        Ok(map)
+    }
+
+    fn get<T>(&self, key: &str) -> Result<T, TableError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        let (line, _, value) = pairs(self)?
+            .into_iter()
+            .find(|(_, k, _)| *k == key)
+            .ok_or_else(|| TableError {
+                line: Some(self.position.0),
+                ..de::Error::custom(format!("no row for `{}`", key))
+            })?;
+        CellDeserializer(value).parse().map_err(|e| TableError {
+            line: Some(line),
+            ..e.in_column(key)
+        })
+    }
+
+    fn fields_as<T: DeserializeOwned>(&self) -> Result<T, TableError> {
+        let pairs = pairs(self)?;
+        let keys = pairs
+            .iter()
+            .map(|(_, key, _)| key.to_string())
+            .collect::<Vec<_>>();
+        let values = pairs
+            .iter()
+            .map(|(_, _, value)| value.to_string())
+            .collect::<Vec<_>>();
+        T::deserialize(RowDeserializer {
+            header: &keys,
+            row: &values,
+        })
+        .map_err(|e| {
+            // Tell the line of the value at fault, when there is one.
+            let line = e.column.as_ref().and_then(|column| {
+                pairs
+                    .iter()
+                    .find(|(_, key, _)| key == column)
+                    .map(|(line, _, _)| *line)
+            });
+            TableError {
+                line: line.or(Some(self.position.0)),
+                ..e
+            }
+        })
+    }
+
+    fn compare<T: Serialize>(&self, actual: &[T], options: TableMatch) -> Result<(), TableDiff> {
+        let diff = TableDiff::new(self, actual, options);
+        if diff.is_empty() {
+            Ok(())
+        } else {
+            Err(diff)
+        }
+    }
+}
+
+/// Deserializes `values` into a `T` whose fields are named by `keys`, as
+/// rows are.
+pub(crate) fn deserialize_pairs<T: DeserializeOwned>(
+    keys: &[String],
+    values: &[String],
+) -> Result<T, TableError> {
+    T::deserialize(RowDeserializer {
+        header: keys,
+        row: values,
+    })
+}
+
+/// The lines of the table found from `line` of `source` on, header first.
+/// The parser keeps no position for rows, and comment lines may come between
+/// them.
+pub(crate) fn table_lines(source: &str, line: usize) -> Vec<usize> {
+    let lines = source
+        .lines()
+        .enumerate()
+        .skip(line.saturating_sub(1))
+        .map(|(i, text)| (i + 1, text.trim_start()))
+        .skip_while(|(_, text)| !text.starts_with('|'));
+
+    let mut rows = vec![];
+    for (line, text) in lines {
+        if text.starts_with('|') {
+            rows.push(line);
+        } else if !text.is_empty() && !text.starts_with('#') {
+            break;
+        }
+    }
+    rows
+}
+
+/// The lines of the rows of the table found from `line` of `source` on,
+/// header excluded.
+pub(crate) fn row_lines(source: &str, line: usize) -> Vec<usize> {
+    table_lines(source, line).into_iter().skip(1).collect()
+}
+
+/// The rows of a vertical table, header included, as their line, key and
+/// value.
+fn pairs(table: &Table) -> Result<Vec<(usize, &str, &str)>, TableError> {
+    std::iter::once(&table.header)
+        .chain(&table.rows)
+        .enumerate()
+        .map(|(i, row)| {
+            let line = table.position.0 + i;
+            match row.as_slice() {
+                [key, value] => Ok((line, key.as_str(), value.as_str())),
+                _ => Err(TableError {
+                    line: Some(line),
+                    ..de::Error::custom(format!(
+                        "expected a key and a value, found {} cells",
+                        row.len()
+                    ))
+                }),
+            }
+        })
+        .collect()
+}
+
+/// A row, as a map from the header to its cells, or as a sequence of cells.
+struct RowDeserializer<'a> {
+    header: &'a [String],
+    row: &'a [String],
+}
+
+impl<'de> de::Deserializer<'de> for RowDeserializer<'de> {
+    type Error = TableError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TableError> {
+        if self.header.len() != self.row.len() {
+            return Err(de::Error::custom(format!(
+                "expected {} cells as in the header, found {}",
+                self.header.len(),
+                self.row.len()
+            )));
+        }
+        visitor.visit_map(Cells {
+            header: self.header,
+            row: self.row,
+            index: 0,
+        })
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TableError> {
+        visitor.visit_seq(Cells {
+            header: self.header,
+            row: self.row,
+            index: 0,
+        })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, TableError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, TableError> {
+        self.deserialize_seq(visitor)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct map struct enum
+        identifier ignored_any
+    }
+}
+
+/// The cells of a row, with the header they are under.
+struct Cells<'a> {
+    header: &'a [String],
+    row: &'a [String],
+    index: usize,
+}
+
+impl<'a> Cells<'a> {
+    fn column(&self, index: usize) -> &'a str {
+        self.header.get(index).map(String::as_str).unwrap_or("")
+    }
+}
+
+impl<'de> MapAccess<'de> for Cells<'de> {
+    type Error = TableError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, TableError> {
+        match self.header.get(self.index) {
+            Some(column) => seed
+                .deserialize(column.as_str().into_deserializer())
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, TableError> {
+        let column = self.column(self.index);
+        let value = &self.row[self.index];
+        self.index += 1;
+        seed.deserialize(CellDeserializer(value))
+            .map_err(|e| e.in_column(column))
+    }
+}
+
+impl<'de> SeqAccess<'de> for Cells<'de> {
+    type Error = TableError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, TableError> {
+        let value = match self.row.get(self.index) {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        let column = self.column(self.index);
+        self.index += 1;
+        seed.deserialize(CellDeserializer(value))
+            .map(Some)
+            .map_err(|e| e.in_column(column))
+    }
+}
+
+/// A single cell, parsed to whatever type is asked for.
+struct CellDeserializer<'a>(&'a str);
+
+impl<'a> CellDeserializer<'a> {
+    fn parse<T>(&self) -> Result<T, TableError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        self.0
+            .trim()
+            .parse()
+            .map_err(|e| de::Error::custom(format!("invalid value `{}`: {}", self.0, e)))
+    }
+}
+
+macro_rules! parse_cell {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TableError> {
+                visitor.$visit(self.parse()?)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for CellDeserializer<'de> {
+    type Error = TableError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TableError> {
+        visitor.visit_borrowed_str(self.0)
+    }
+
+    parse_cell! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TableError> {
+        if self.0.trim().is_empty() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, TableError> {
+        visitor.visit_enum(self.0.trim().into_deserializer())
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, TableError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        i128 u128 str string bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "\
+Feature: Tables
+
+  Scenario Outline: eating
+    Given there are <start> cucumbers
+
+    Examples:
+      | start |
+      | 12    |
+      # Not eaten yet
+      | 20    |
+
+      | 5     |
+
+  Scenario: next
+";
+
+    #[test]
+    fn row_lines_skip_comments_and_blank_lines() {
+        assert_eq!(row_lines(SOURCE, 6), vec![8, 10, 12]);
+    }
+
+    #[test]
+    fn row_lines_start_at_the_header() {
+        assert_eq!(row_lines(SOURCE, 7), vec![8, 10, 12]);
+    }
+
+    #[test]
+    fn table_lines_include_the_header() {
+        assert_eq!(table_lines(SOURCE, 6), vec![7, 8, 10, 12]);
+    }
+}
@@ -0,0 +1,508 @@
+//! Comparing a data table of expected values with actual data, row by row
+//! and cell by cell, for `Then` steps checking a list of results.
+//!
+//! The actual rows are serialized with serde, so they can be structs, whose
+//! fields are named by the header, maps, or tuples and sequences, taken by
+//! position. Their values are compared as the text of the cells, numbers and
+//! booleans being written as in the table.
+
+use std::fmt;
+
+use gherkin::Table;
+use serde::Serialize;
+use serde_json::Value;
+
+/// How rows are matched, as given to `TableExt::compare`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TableMatch {
+    /// Whether the rows may come in any order.
+    pub unordered: bool,
+    /// Whether there may be actual rows which the table does not list.
+    pub subset: bool,
+}
+
+/// A row of a `TableDiff`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiffRow {
+    /// A row of the table matched by an actual row.
+    Same(Vec<String>),
+    /// A row of the table matched by no actual row.
+    Missing(Vec<String>),
+    /// An actual row which the table does not list.
+    Unexpected(Vec<String>),
+    /// A row of the table and the actual row closest to it, as pairs of the
+    /// expected and actual value of each cell.
+    Changed(Vec<(String, String)>),
+}
+
+/// How actual rows differ from a table, shown as the table with the rows
+/// and cells which differ marked.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableDiff {
+    pub header: Vec<String>,
+    pub rows: Vec<DiffRow>,
+}
+
+/// The text of a cell which an actual row does not have.
+const MISSING: &str = "(missing)";
+
+impl TableDiff {
+    /// Compares the rows of `table` after its header with `actual`.
+    ///
+    /// # Panics
+    ///
+    /// If a row of `actual` cannot be serialized, e.g. a map whose keys are
+    /// not strings.
+    pub(crate) fn new<T: Serialize>(table: &Table, actual: &[T], options: TableMatch) -> TableDiff {
+        let actual = actual
+            .iter()
+            .map(|row| cells(&table.header, row))
+            .collect::<Vec<_>>();
+        let expected = &table.rows;
+
+        let mut rows = vec![];
+        if options.unordered {
+            let all = (0..actual.len()).collect::<Vec<_>>();
+            let mut used = vec![false; actual.len()];
+            let mut unmatched = vec![];
+            for (i, row) in expected.iter().enumerate() {
+                match all.iter().find(|&&j| !used[j] && same(row, &actual[j])) {
+                    Some(&j) => used[j] = true,
+                    None => unmatched.push(i),
+                }
+            }
+            let leftover = all.into_iter().filter(|&j| !used[j]).collect::<Vec<_>>();
+            let mut changes = pair(expected, &actual, &unmatched, &leftover, options);
+            for (i, row) in expected.iter().enumerate() {
+                if unmatched.contains(&i) {
+                    rows.push(changes.remove(0));
+                } else {
+                    rows.push(DiffRow::Same(row.clone()));
+                }
+            }
+            rows.extend(changes);
+        } else {
+            // The rows in the same order on both sides are the longest
+            // common subsequence; those in between differ.
+            let (mut i, mut j) = (0, 0);
+            for (next_i, next_j) in common_rows(expected, &actual)
+                .into_iter()
+                .chain(Some((expected.len(), actual.len())))
+            {
+                let unmatched = (i..next_i).collect::<Vec<_>>();
+                let leftover = (j..next_j).collect::<Vec<_>>();
+                rows.extend(pair(expected, &actual, &unmatched, &leftover, options));
+                if next_i < expected.len() {
+                    rows.push(DiffRow::Same(expected[next_i].clone()));
+                }
+                i = next_i + 1;
+                j = next_j + 1;
+            }
+        }
+
+        TableDiff {
+            header: table.header.clone(),
+            rows,
+        }
+    }
+
+    /// Whether every row is the same.
+    pub fn is_empty(&self) -> bool {
+        self.counts() == (0, 0, 0)
+    }
+
+    /// The numbers of changed, missing and unexpected rows.
+    fn counts(&self) -> (usize, usize, usize) {
+        let (mut changed, mut missing, mut unexpected) = (0, 0, 0);
+        for row in &self.rows {
+            match row {
+                DiffRow::Same(_) => {}
+                DiffRow::Changed(_) => changed += 1,
+                DiffRow::Missing(_) => missing += 1,
+                DiffRow::Unexpected(_) => unexpected += 1,
+            }
+        }
+        (changed, missing, unexpected)
+    }
+}
+
+/// The text of the cells of `row` under each column of `header`.
+fn cells<T: Serialize>(header: &[String], row: &T) -> Vec<Option<String>> {
+    let value = serde_json::to_value(row)
+        .unwrap_or_else(|e| panic!("a row could not be serialized to compare it: {}", e));
+    match value {
+        Value::Object(mut fields) => header
+            .iter()
+            .map(|column| fields.remove(column).map(text))
+            .collect(),
+        Value::Array(values) => {
+            let mut values = values.into_iter().map(text);
+            header.iter().map(|_| values.next()).collect()
+        }
+        value => {
+            let mut values = Some(text(value)).into_iter();
+            header.iter().map(|_| values.next()).collect()
+        }
+    }
+}
+
+fn text(value: Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s,
+        value => value.to_string(),
+    }
+}
+
+fn same(expected: &[String], actual: &[Option<String>]) -> bool {
+    expected.len() == actual.len()
+        && expected
+            .iter()
+            .zip(actual)
+            .all(|(e, a)| a.as_ref() == Some(e))
+}
+
+/// The indices of the rows of the longest common subsequence of `expected`
+/// and `actual`.
+fn common_rows(expected: &[Vec<String>], actual: &[Vec<Option<String>>]) -> Vec<(usize, usize)> {
+    let (n, m) = (expected.len(), actual.len());
+    // lengths[i][j] is the length of the subsequence of the rows from i and j.
+    let mut lengths = vec![vec![0; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[i][j] = if same(&expected[i], &actual[j]) {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut common = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if same(&expected[i], &actual[j]) {
+            common.push((i, j));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    common
+}
+
+/// The rows of the table in `unmatched`, each changed into the actual row
+/// of `leftover` sharing the most cells with it, if any, or missing, in
+/// order, followed by the actual rows left unexpected unless `subset` is set.
+fn pair(
+    expected: &[Vec<String>],
+    actual: &[Vec<Option<String>>],
+    unmatched: &[usize],
+    leftover: &[usize],
+    options: TableMatch,
+) -> Vec<DiffRow> {
+    let mut used = vec![false; leftover.len()];
+    let mut rows = vec![];
+    for &i in unmatched {
+        let shared = |j: usize| {
+            expected[i]
+                .iter()
+                .zip(&actual[j])
+                .filter(|(e, a)| a.as_ref() == Some(e))
+                .count()
+        };
+        let closest = (0..leftover.len())
+            .filter(|&k| !used[k])
+            .map(|k| (k, shared(leftover[k])))
+            .filter(|&(_, shared)| shared > 0)
+            .fold(
+                None,
+                |best: Option<(usize, usize)>, (k, shared)| match best {
+                    Some((_, most)) if most >= shared => best,
+                    _ => Some((k, shared)),
+                },
+            );
+        rows.push(match closest {
+            Some((k, _)) => {
+                used[k] = true;
+                let cells = expected[i]
+                    .iter()
+                    .zip(&actual[leftover[k]])
+                    .map(|(e, a)| (e.clone(), a.clone().unwrap_or_else(|| MISSING.to_string())))
+                    .collect();
+                DiffRow::Changed(cells)
+            }
+            None => DiffRow::Missing(expected[i].clone()),
+        });
+    }
+    if !options.subset {
+        for (k, &j) in leftover.iter().enumerate() {
+            if !used[k] {
+                let cells = actual[j]
+                    .iter()
+                    .map(|a| a.clone().unwrap_or_else(|| MISSING.to_string()))
+                    .collect();
+                rows.push(DiffRow::Unexpected(cells));
+            }
+        }
+    }
+    rows
+}
+
+/// Writes the summary of the differences, then the table with a mark before
+/// each row: `~` for a changed row, whose cells which differ are written
+/// `expected → actual`, `-` for a missing row and `+` for an unexpected one.
+impl fmt::Display for TableDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (changed, missing, unexpected) = self.counts();
+        let counts = [
+            (changed, "different"),
+            (missing, "missing"),
+            (unexpected, "unexpected"),
+        ];
+        let summary = counts
+            .iter()
+            .filter(|(n, _)| *n > 0)
+            .map(|(n, what)| format!("{} row{} {}", n, if *n == 1 { "" } else { "s" }, what))
+            .collect::<Vec<_>>();
+        writeln!(f, "The table does not match ({}):", summary.join(", "))?;
+
+        let lines = self
+            .rows
+            .iter()
+            .map(|row| match row {
+                DiffRow::Same(cells) => (' ', cells.clone()),
+                DiffRow::Missing(cells) => ('-', cells.clone()),
+                DiffRow::Unexpected(cells) => ('+', cells.clone()),
+                DiffRow::Changed(cells) => (
+                    '~',
+                    cells
+                        .iter()
+                        .map(|(e, a)| {
+                            if e == a {
+                                e.clone()
+                            } else {
+                                format!("{} → {}", e, a)
+                            }
+                        })
+                        .collect(),
+                ),
+            })
+            .collect::<Vec<_>>();
+        let mut widths = self
+            .header
+            .iter()
+            .map(|h| h.chars().count())
+            .collect::<Vec<_>>();
+        for (_, cells) in &lines {
+            for (width, cell) in widths.iter_mut().zip(cells) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        let row = |mark: char, cells: &[String]| {
+            let cells = cells
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| {
+                    format!(" {}{} |", cell, " ".repeat(width - cell.chars().count()))
+                })
+                .collect::<String>();
+            format!("{} |{}", mark, cells)
+        };
+        write!(f, "{}", row(' ', &self.header))?;
+        for (mark, cells) in &lines {
+            write!(f, "\n{}", row(*mark, cells))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for TableDiff {}
+
+/// Whether `line` of a failure message is a row written by `TableDiff`.
+pub(crate) fn is_diff_row(line: &str) -> bool {
+    let mut chars = line.chars();
+    match chars.next() {
+        Some(' ') | Some('~') | Some('-') | Some('+') => {
+            line.len() > 3 && chars.as_str().starts_with(" |") && line.ends_with('|')
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde::Serialize;
+
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Fruit {
+        name: &'static str,
+        count: u32,
+    }
+
+    fn table(rows: &[&[&str]]) -> Table {
+        let rows = rows
+            .iter()
+            .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+            .collect::<Vec<Vec<String>>>();
+        Table {
+            header: rows[0].clone(),
+            rows: rows[1..].to_vec(),
+            position: (0, 0),
+        }
+    }
+
+    fn strings(cells: &[&str]) -> Vec<String> {
+        cells.iter().map(|cell| cell.to_string()).collect()
+    }
+
+    fn fruits() -> Table {
+        table(&[&["name", "count"], &["apple", "1"], &["pear", "2"]])
+    }
+
+    #[test]
+    fn same_structs() {
+        let actual = [
+            Fruit {
+                name: "apple",
+                count: 1,
+            },
+            Fruit {
+                name: "pear",
+                count: 2,
+            },
+        ];
+        let diff = TableDiff::new(&fruits(), &actual, TableMatch::default());
+        assert!(diff.is_empty());
+    }
+
+    #[test]
+    fn rows_of_maps_tuples_and_values() {
+        let mut apple = BTreeMap::new();
+        apple.insert("count", "1");
+        apple.insert("name", "apple");
+        apple.insert("color", "red");
+        let diff = TableDiff::new(
+            &table(&[&["name", "count"], &["apple", "1"]]),
+            &[apple],
+            TableMatch::default(),
+        );
+        assert!(diff.is_empty());
+
+        let diff = TableDiff::new(
+            &fruits(),
+            &[("apple", 1), ("pear", 2)],
+            TableMatch::default(),
+        );
+        assert!(diff.is_empty());
+
+        let diff = TableDiff::new(
+            &table(&[&["ok"], &["true"], &["false"]]),
+            &[true, false],
+            TableMatch::default(),
+        );
+        assert!(diff.is_empty());
+    }
+
+    #[test]
+    fn changed_cell() {
+        let diff = TableDiff::new(
+            &fruits(),
+            &[("apple", 1), ("pear", 3)],
+            TableMatch::default(),
+        );
+        assert_eq!(
+            diff.rows,
+            vec![
+                DiffRow::Same(strings(&["apple", "1"])),
+                DiffRow::Changed(vec![
+                    ("pear".to_string(), "pear".to_string()),
+                    ("2".to_string(), "3".to_string()),
+                ]),
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_and_unexpected_rows() {
+        let diff = TableDiff::new(
+            &fruits(),
+            &[("plum", 5), ("apple", 1)],
+            TableMatch::default(),
+        );
+        assert_eq!(
+            diff.rows,
+            vec![
+                DiffRow::Unexpected(strings(&["plum", "5"])),
+                DiffRow::Same(strings(&["apple", "1"])),
+                DiffRow::Missing(strings(&["pear", "2"])),
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_cells() {
+        let diff = TableDiff::new(
+            &table(&[&["name", "count"], &["apple", "1"]]),
+            &[("apple",)],
+            TableMatch::default(),
+        );
+        assert_eq!(
+            diff.rows,
+            vec![DiffRow::Changed(vec![
+                ("apple".to_string(), "apple".to_string()),
+                ("1".to_string(), MISSING.to_string()),
+            ])]
+        );
+    }
+
+    #[test]
+    fn order_matters_unless_unordered() {
+        let actual = [("pear", 2), ("apple", 1)];
+        assert!(!TableDiff::new(&fruits(), &actual, TableMatch::default()).is_empty());
+
+        let unordered = TableMatch {
+            unordered: true,
+            ..TableMatch::default()
+        };
+        assert!(TableDiff::new(&fruits(), &actual, unordered).is_empty());
+    }
+
+    #[test]
+    fn subset_allows_unlisted_rows() {
+        let actual = [("apple", 1), ("plum", 5), ("pear", 2)];
+        assert!(!TableDiff::new(&fruits(), &actual, TableMatch::default()).is_empty());
+
+        let subset = TableMatch {
+            subset: true,
+            ..TableMatch::default()
+        };
+        assert!(TableDiff::new(&fruits(), &actual, subset).is_empty());
+    }
+
+    #[test]
+    fn display() {
+        let diff = TableDiff::new(
+            &fruits(),
+            &[("apple", 1), ("pear", 3), ("plum", 5)],
+            TableMatch::default(),
+        );
+        let expected = [
+            "The table does not match (1 row different, 1 row unexpected):",
+            "  | name  | count |",
+            "  | apple | 1     |",
+            "~ | pear  | 2 → 3 |",
+            "+ | plum  | 5     |",
+        ];
+        assert_eq!(diff.to_string(), expected.join("\n"));
+        assert!(diff.to_string().lines().skip(1).all(is_diff_row));
+    }
+}
@@ -0,0 +1,379 @@
+//! Tag expressions, such as `@browser and not (@slow or @wip)`, telling
+//! which scenarios a hook applies to by their tags.
+//!
+//! `not` binds tighter than `and`, which binds tighter than `or`. Tags may be
+//! written with or without their `@`, and tags following each other with no
+//! operator in between must all be there, as in `@browser @admin`.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::cli::tag_name;
+
+/// A parsed tag expression, the empty one matching any scenario.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TagExpression {
+    Any,
+    Tag(String),
+    Not(Box<TagExpression>),
+    And(Box<TagExpression>, Box<TagExpression>),
+    Or(Box<TagExpression>, Box<TagExpression>),
+}
+
+impl TagExpression {
+    /// Whether scenarios with `tags`, given with or without their `@`, match.
+    pub fn matches<S: AsRef<str>>(&self, tags: &[S]) -> bool {
+        match self {
+            TagExpression::Any => true,
+            TagExpression::Tag(tag) => tags.iter().any(|t| tag_name(t.as_ref()) == tag),
+            TagExpression::Not(e) => !e.matches(tags),
+            TagExpression::And(a, b) => a.matches(tags) && b.matches(tags),
+            TagExpression::Or(a, b) => a.matches(tags) || b.matches(tags),
+        }
+    }
+}
+
+impl FromStr for TagExpression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<TagExpression, String> {
+        let tokens = tokens(s);
+        if tokens.is_empty() {
+            return Ok(TagExpression::Any);
+        }
+        let mut parser = Parser { tokens, next: 0 };
+        let expression = parser.or()?;
+        match parser.peek() {
+            None => Ok(expression),
+            Some(token) => Err(format!("unexpected `{}` in `{}`", token, s)),
+        }
+    }
+}
+
+impl fmt::Display for TagExpression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TagExpression::Any => Ok(()),
+            TagExpression::Tag(tag) => write!(f, "@{}", tag),
+            TagExpression::Not(e) => write!(f, "not ({})", e),
+            TagExpression::And(a, b) => write!(f, "({} and {})", a, b),
+            TagExpression::Or(a, b) => write!(f, "({} or {})", a, b),
+        }
+    }
+}
+
+/// Splits `s` at whitespace and around parentheses.
+fn tokens(s: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    for word in s.split_whitespace() {
+        let mut token = String::new();
+        for c in word.chars() {
+            if c == '(' || c == ')' {
+                if !token.is_empty() {
+                    tokens.push(token.clone());
+                    token.clear();
+                }
+                tokens.push(c.to_string());
+            } else {
+                token.push(c);
+            }
+        }
+        if !token.is_empty() {
+            tokens.push(token);
+        }
+    }
+    tokens
+}
+
+struct Parser {
+    tokens: Vec<String>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.next).map(String::as_str)
+    }
+
+    fn or(&mut self) -> Result<TagExpression, String> {
+        let mut expression = self.and()?;
+        while self.peek() == Some("or") {
+            self.next += 1;
+            expression = TagExpression::Or(Box::new(expression), Box::new(self.and()?));
+        }
+        Ok(expression)
+    }
+
+    fn and(&mut self) -> Result<TagExpression, String> {
+        let mut expression = self.not()?;
+        loop {
+            match self.peek() {
+                Some("and") => self.next += 1,
+                // Tags next to each other must all be there.
+                Some(token) if token != "or" && token != ")" => {}
+                _ => return Ok(expression),
+            }
+            expression = TagExpression::And(Box::new(expression), Box::new(self.not()?));
+        }
+    }
+
+    fn not(&mut self) -> Result<TagExpression, String> {
+        let token = match self.peek() {
+            Some(token) => token.to_string(),
+            None => return Err("the tag expression ends early".to_string()),
+        };
+        self.next += 1;
+        match token.as_str() {
+            "not" => Ok(TagExpression::Not(Box::new(self.not()?))),
+            "(" => {
+                let expression = self.or()?;
+                if self.peek() != Some(")") {
+                    return Err("unclosed `(` in the tag expression".to_string());
+                }
+                self.next += 1;
+                Ok(expression)
+            }
+            "and" | "or" | ")" => Err(format!("unexpected `{}` in the tag expression", token)),
+            tag => Ok(TagExpression::Tag(tag.trim_start_matches('@').to_string())),
+        }
+    }
+}
+
+/// A token as `check` reads it.
+#[derive(Clone, Copy)]
+enum Token {
+    End,
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Tag,
+}
+
+/// Whether `bytes[start..end]` is `word`.
+const fn is_word(bytes: &[u8], start: usize, end: usize, word: &[u8]) -> bool {
+    if end - start != word.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < word.len() {
+        if bytes[start + i] != word[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// The token at `at`, and where the next one starts, as `tokens` splits them.
+const fn token(bytes: &[u8], mut at: usize) -> (Token, usize) {
+    while at < bytes.len() && bytes[at].is_ascii_whitespace() {
+        at += 1;
+    }
+    if at == bytes.len() {
+        return (Token::End, at);
+    }
+    match bytes[at] {
+        b'(' => return (Token::Open, at + 1),
+        b')' => return (Token::Close, at + 1),
+        _ => {}
+    }
+    let start = at;
+    while at < bytes.len()
+        && !bytes[at].is_ascii_whitespace()
+        && bytes[at] != b'('
+        && bytes[at] != b')'
+    {
+        at += 1;
+    }
+    let token = if is_word(bytes, start, at, b"and") {
+        Token::And
+    } else if is_word(bytes, start, at, b"or") {
+        Token::Or
+    } else if is_word(bytes, start, at, b"not") {
+        Token::Not
+    } else {
+        Token::Tag
+    };
+    (token, at)
+}
+
+/// Checks `s` as `from_str` parses it, in a `const` for the hook macros to
+/// reject a malformed expression where it is written.
+pub(crate) const fn check(s: &str) -> Result<(), &'static str> {
+    let bytes = s.as_bytes();
+    if let (Token::End, _) = token(bytes, 0) {
+        return Ok(());
+    }
+    match check_or(bytes, 0) {
+        Ok(at) => match token(bytes, at) {
+            (Token::End, _) => Ok(()),
+            // Anything else would have been read as part of the expression.
+            _ => Err("unexpected `)` in the tag expression"),
+        },
+        Err(e) => Err(e),
+    }
+}
+
+const fn check_or(bytes: &[u8], at: usize) -> Result<usize, &'static str> {
+    let mut at = match check_and(bytes, at) {
+        Ok(at) => at,
+        Err(e) => return Err(e),
+    };
+    while let (Token::Or, next) = token(bytes, at) {
+        at = match check_and(bytes, next) {
+            Ok(at) => at,
+            Err(e) => return Err(e),
+        };
+    }
+    Ok(at)
+}
+
+const fn check_and(bytes: &[u8], at: usize) -> Result<usize, &'static str> {
+    let mut at = match check_not(bytes, at) {
+        Ok(at) => at,
+        Err(e) => return Err(e),
+    };
+    loop {
+        let next = match token(bytes, at) {
+            (Token::And, next) => next,
+            (Token::Or, _) | (Token::Close, _) | (Token::End, _) => return Ok(at),
+            _ => at,
+        };
+        at = match check_not(bytes, next) {
+            Ok(at) => at,
+            Err(e) => return Err(e),
+        };
+    }
+}
+
+const fn check_not(bytes: &[u8], at: usize) -> Result<usize, &'static str> {
+    match token(bytes, at) {
+        (Token::End, _) => Err("the tag expression ends early"),
+        (Token::Not, next) => check_not(bytes, next),
+        (Token::Open, next) => match check_or(bytes, next) {
+            Ok(at) => match token(bytes, at) {
+                (Token::Close, next) => Ok(next),
+                _ => Err("unclosed `(` in the tag expression"),
+            },
+            Err(e) => Err(e),
+        },
+        (Token::And, _) => Err("unexpected `and` in the tag expression"),
+        (Token::Or, _) => Err("unexpected `or` in the tag expression"),
+        (Token::Close, _) => Err("unexpected `)` in the tag expression"),
+        (Token::Tag, next) => Ok(next),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> TagExpression {
+        s.parse().unwrap_or_else(|e| panic!("{}: {}", s, e))
+    }
+
+    #[test]
+    fn empty_matches_anything() {
+        assert_eq!(parse(""), TagExpression::Any);
+        assert_eq!(parse("  \t "), TagExpression::Any);
+        assert!(parse("").matches::<&str>(&[]));
+        assert!(parse("").matches(&["@slow"]));
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(
+            parse("@a or @b and not @c").to_string(),
+            "(@a or (@b and not (@c)))"
+        );
+        assert_eq!(
+            parse("not @a and @b or @c").to_string(),
+            "((not (@a) and @b) or @c)"
+        );
+        assert_eq!(
+            parse("(@a or @b) and @c").to_string(),
+            "((@a or @b) and @c)"
+        );
+        assert_eq!(parse("not not @a").to_string(), "not (not (@a))");
+    }
+
+    #[test]
+    fn implicit_and() {
+        assert_eq!(parse("@a @b").to_string(), "(@a and @b)");
+        assert_eq!(parse("@a @b or @c").to_string(), "((@a and @b) or @c)");
+        assert_eq!(parse("@a not @b").to_string(), "(@a and not (@b))");
+        assert!(parse("@browser @admin").matches(&["@admin", "@browser"]));
+        assert!(!parse("@browser @admin").matches(&["@browser"]));
+    }
+
+    #[test]
+    fn tags_with_or_without_at() {
+        assert_eq!(parse("slow"), parse("@slow"));
+        assert!(parse("@slow").matches(&["slow"]));
+        assert!(parse("slow").matches(&["@slow"]));
+    }
+
+    #[test]
+    fn parentheses_without_spaces() {
+        assert_eq!(
+            parse("not(@a or @b) and (@c)").to_string(),
+            "(not ((@a or @b)) and @c)"
+        );
+    }
+
+    #[test]
+    fn matches() {
+        let expression = parse("@browser and not (@slow or @wip)");
+        assert!(expression.matches(&["@browser"]));
+        assert!(expression.matches(&["@browser", "@db"]));
+        assert!(!expression.matches(&["@browser", "@slow"]));
+        assert!(!expression.matches(&["@wip", "@browser"]));
+        assert!(!expression.matches::<&str>(&[]));
+    }
+
+    #[test]
+    fn rejects_malformed_expressions() {
+        let errors = [
+            ("@a and", "the tag expression ends early"),
+            ("not", "the tag expression ends early"),
+            ("(@a or @b", "unclosed `(` in the tag expression"),
+            ("@a or or @b", "unexpected `or` in the tag expression"),
+            ("and @a", "unexpected `and` in the tag expression"),
+            ("()", "unexpected `)` in the tag expression"),
+            ("@a)", "unexpected `)` in `@a)`"),
+        ];
+        for (s, error) in errors.iter() {
+            assert_eq!(
+                s.parse::<TagExpression>().err().as_deref(),
+                Some(*error),
+                "{}",
+                s
+            );
+            assert_eq!(
+                check(s).err(),
+                Some(error.replace("in `@a)`", "in the tag expression").as_str()),
+                "{}",
+                s
+            );
+        }
+    }
+
+    #[test]
+    fn check_accepts_what_parses() {
+        for s in [
+            "",
+            "@a",
+            "@a or @b and not @c",
+            "not(@a or @b) and (@c)",
+            "@a @b or @c",
+            "not not @a",
+        ]
+        .iter()
+        {
+            parse(s);
+            assert_eq!(check(s), Ok(()), "{}", s);
+        }
+    }
+}
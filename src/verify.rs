@@ -0,0 +1,548 @@
+//! Checking from a build script that every step of the features has a
+//! definition, so that a step whose definition was renamed or removed is
+//! caught when the tests are built rather than when they run:
+//!
+//! ```ignore
+//! // build.rs
+//! fn main() {
+//!     cucumber_rust::verify::Verify::new().deny();
+//! }
+//! ```
+//!
+//! Definitions are found by reading the Rust sources rather than running
+//! them: the patterns given to `steps!`, to the `given`, `when` and `then`
+//! attributes and to the methods of `StepsBuilder`, as string literals. A
+//! pattern built at run time cannot be seen, and neither can the options of
+//! regular expressions, which are taken as the defaults.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use gherkin::{Feature, Scenario, Step, StepType};
+use globwalk::GlobWalkerBuilder;
+use proc_macro2::{Delimiter, TokenStream, TokenTree};
+use regex::Regex;
+
+use crate::expression::Expression;
+
+/// Compares the steps of feature files with the step definitions of Rust
+/// sources, by default those of `features` and of `src` and `tests`.
+#[derive(Debug, Clone, Default)]
+pub struct Verify {
+    features: Vec<PathBuf>,
+    sources: Vec<PathBuf>,
+    normalize: bool,
+}
+
+/// A step of a feature file which no definition matches.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UndefinedStep {
+    pub path: PathBuf,
+    pub line: usize,
+    pub ty: StepType,
+    pub text: String,
+}
+
+impl fmt::Display for UndefinedStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: no {:?} step matches `{}`",
+            self.path.display(),
+            self.line,
+            self.ty,
+            self.text
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Text,
+    Regex,
+    Expression,
+}
+
+#[derive(Debug, Clone)]
+struct Definition {
+    ty: StepType,
+    kind: Kind,
+    pattern: String,
+}
+
+/// What the sources define: the steps, their aliases as (type, alias,
+/// original), and the parameter types of Cucumber Expressions.
+#[derive(Debug, Default)]
+struct Found {
+    definitions: Vec<Definition>,
+    aliases: Vec<(StepType, String, String)>,
+    parameter_types: BTreeMap<String, String>,
+}
+
+impl Verify {
+    pub fn new() -> Verify {
+        Verify::default()
+    }
+
+    /// Adds a directory of feature files, instead of `features`.
+    pub fn features<P: Into<PathBuf>>(&mut self, path: P) -> &mut Self {
+        self.features.push(path.into());
+        self
+    }
+
+    /// Adds a directory of Rust sources defining steps, instead of `src` and
+    /// `tests`.
+    pub fn sources<P: Into<PathBuf>>(&mut self, path: P) -> &mut Self {
+        self.sources.push(path.into());
+        self
+    }
+
+    /// Normalizes the text of steps before matching them, as
+    /// `CucumberBuilder::normalize_step_text` does.
+    pub fn normalize_step_text(&mut self) -> &mut Self {
+        self.normalize = true;
+        self
+    }
+
+    /// The steps of the features which no definition of the sources matches.
+    pub fn undefined_steps(&self) -> io::Result<Vec<UndefinedStep>> {
+        let mut found = Found::default();
+        for path in files(&self.source_dirs(), "*.rs")? {
+            let source = fs::read_to_string(&path)?;
+            let tokens = TokenStream::from_str(&source).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}: {:?}", path.display(), e),
+                )
+            })?;
+            found.scan(tokens);
+        }
+        let matchers = found.matchers()?;
+
+        let mut undefined = vec![];
+        for path in files(&self.feature_dirs(), "*.feature")? {
+            let source = fs::read_to_string(&path)?;
+            let feature = crate::rule_background::parse_feature(&source).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}: {}", path.display(), e),
+                )
+            })?;
+            for step in feature_steps(&feature) {
+                let text = if self.normalize {
+                    crate::normalize(&step.value)
+                } else {
+                    step.value.clone()
+                };
+                if matchers.iter().any(|m| m.matches(step.ty, &text)) {
+                    continue;
+                }
+                let step = UndefinedStep {
+                    path: path.clone(),
+                    line: step.position.0,
+                    ty: step.ty,
+                    text: step.value,
+                };
+                if !undefined.contains(&step) {
+                    undefined.push(step);
+                }
+            }
+        }
+        Ok(undefined)
+    }
+
+    /// Prints a warning of Cargo for each undefined step, and tells Cargo to
+    /// check again when the features or the sources change.
+    ///
+    /// # Panics
+    ///
+    /// If the features or the sources cannot be read.
+    pub fn warn(&self) {
+        for step in self.undefined() {
+            println!("cargo:warning={}", step);
+        }
+    }
+
+    /// Fails the build when a step is undefined, as `warn` otherwise does.
+    ///
+    /// # Panics
+    ///
+    /// If a step is undefined, or if the features or the sources cannot be
+    /// read.
+    pub fn deny(&self) {
+        let undefined = self.undefined();
+        if !undefined.is_empty() {
+            let steps = undefined
+                .iter()
+                .map(|step| format!("  {}\n", step))
+                .collect::<String>();
+            panic!(
+                "{} step{} of the features ha{} no definition:\n{}",
+                undefined.len(),
+                if undefined.len() == 1 { "" } else { "s" },
+                if undefined.len() == 1 { "s" } else { "ve" },
+                steps
+            );
+        }
+    }
+
+    fn undefined(&self) -> Vec<UndefinedStep> {
+        for dir in self.feature_dirs().iter().chain(&self.source_dirs()) {
+            println!("cargo:rerun-if-changed={}", dir.display());
+        }
+        self.undefined_steps()
+            .unwrap_or_else(|e| panic!("could not verify the steps: {}", e))
+    }
+
+    fn feature_dirs(&self) -> Vec<PathBuf> {
+        if self.features.is_empty() {
+            vec![PathBuf::from("features")]
+        } else {
+            self.features.clone()
+        }
+    }
+
+    fn source_dirs(&self) -> Vec<PathBuf> {
+        if self.sources.is_empty() {
+            vec![PathBuf::from("src"), PathBuf::from("tests")]
+        } else {
+            self.sources.clone()
+        }
+    }
+}
+
+/// The files of `dirs` matching `pattern`, sorted, skipping the
+/// directories which do not exist.
+fn files(dirs: &[PathBuf], pattern: &str) -> io::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    for dir in dirs.iter().filter(|dir| dir.exists()) {
+        let walker = GlobWalkerBuilder::new(dir, pattern)
+            .build()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+        files.extend(
+            walker
+                .filter_map(Result::ok)
+                .filter(|entry| entry.file_type().is_file())
+                .map(|entry| entry.path().to_path_buf()),
+        );
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// The steps of every scenario of `feature`, background ones included, with
+/// those of outlines once per example row.
+fn feature_steps(feature: &Feature) -> Vec<Step> {
+    let background = feature.background.as_ref().map(|bg| &bg.steps[..]);
+    let scenarios = feature
+        .scenarios
+        .iter()
+        .chain(feature.rules.iter().flat_map(|rule| &rule.scenarios));
+
+    let mut steps = background.unwrap_or(&[]).to_vec();
+    for scenario in scenarios {
+        steps.extend(scenario_steps(scenario));
+    }
+    steps
+}
+
+fn scenario_steps(scenario: &Scenario) -> Vec<Step> {
+    let examples = match scenario.examples {
+        Some(ref examples) => examples,
+        None => return scenario.steps.clone(),
+    };
+    let mut steps = vec![];
+    for row in &examples.table.rows {
+        for step in &scenario.steps {
+            let mut step = step.clone();
+            for (k, v) in examples.table.header.iter().zip(row) {
+                step.value = step.value.replace(&format!("<{}>", k), v);
+            }
+            steps.push(step);
+        }
+    }
+    steps
+}
+
+enum Matcher {
+    Text(StepType, String),
+    Regex(StepType, Regex),
+}
+
+impl Matcher {
+    fn matches(&self, ty: StepType, text: &str) -> bool {
+        match self {
+            Matcher::Text(t, pattern) => *t == ty && pattern == text,
+            Matcher::Regex(t, regex) => *t == ty && regex.is_match(text),
+        }
+    }
+}
+
+impl Found {
+    /// Finds the definitions in `tokens` and the groups within them.
+    fn scan(&mut self, tokens: TokenStream) {
+        let tokens = tokens.into_iter().collect::<Vec<_>>();
+        for (i, token) in tokens.iter().enumerate() {
+            let ident = match token {
+                TokenTree::Group(group) => {
+                    self.scan(group.stream());
+                    continue;
+                }
+                TokenTree::Ident(ident) => ident.to_string(),
+                _ => continue,
+            };
+            let rest = &tokens[i + 1..];
+            match arguments(rest) {
+                // `given("...", ...)`, `#[given(regex = "...")]`,
+                // `.given_regex("...", ...)`, `.alias(StepType::Given, "...",
+                // "...")` or `.parameter_type("...", "...")`
+                Some(args) => self.scan_call(&ident, &args),
+                // `given regex "..." ...;` or `given alias "..." => "...";`
+                // in `steps!`
+                None => self.scan_item(&ident, rest),
+            }
+        }
+    }
+
+    fn scan_call(&mut self, name: &str, args: &[Vec<TokenTree>]) {
+        let literals = args
+            .iter()
+            .map(|arg| match &arg[..] {
+                [TokenTree::Literal(literal)] => string(&literal.to_string()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        if name == "alias" {
+            let ty = args
+                .first()
+                .and_then(|arg| arg.iter().rev().find_map(step_type));
+            if let (Some(ty), Some(Some(alias)), Some(Some(original))) =
+                (ty, literals.get(1), literals.get(2))
+            {
+                self.aliases.push((ty, alias.clone(), original.clone()));
+            }
+            return;
+        }
+        if name == "parameter_type" {
+            if let (Some(Some(name)), Some(Some(regex))) = (literals.first(), literals.get(1)) {
+                self.parameter_types.insert(name.clone(), regex.clone());
+            }
+            return;
+        }
+
+        let (ty, kind) = match step_method(name) {
+            Some(method) => method,
+            None => return,
+        };
+        if let Some(Some(pattern)) = literals.first() {
+            self.define(ty, kind, pattern);
+            return;
+        }
+        if kind != Kind::Text {
+            return;
+        }
+        // The named arguments of the attributes
+        for arg in args {
+            if let [TokenTree::Ident(key), TokenTree::Punct(eq), TokenTree::Literal(literal)] =
+                &arg[..]
+            {
+                let kind = match key.to_string().as_str() {
+                    "regex" => Kind::Regex,
+                    "expression" => Kind::Expression,
+                    _ => continue,
+                };
+                if let (Some(pattern), '=') = (string(&literal.to_string()), eq.as_char()) {
+                    self.define(ty, kind, &pattern);
+                }
+            }
+        }
+    }
+
+    fn scan_item(&mut self, name: &str, rest: &[TokenTree]) {
+        let ty = match step_method(name) {
+            Some((ty, Kind::Text)) => ty,
+            _ => return,
+        };
+        let mut kind = Kind::Text;
+        let mut rest = rest;
+        while let Some(TokenTree::Ident(ident)) = rest.first() {
+            match ident.to_string().as_str() {
+                "async" => {}
+                "regex" => kind = Kind::Regex,
+                "expression" => kind = Kind::Expression,
+                "alias" => {
+                    if let [TokenTree::Literal(alias), TokenTree::Punct(eq), TokenTree::Punct(gt), TokenTree::Literal(original), ..] =
+                        &rest[1..]
+                    {
+                        let alias = string(&alias.to_string());
+                        let original = string(&original.to_string());
+                        if let (Some(alias), Some(original), '=', '>') =
+                            (alias, original, eq.as_char(), gt.as_char())
+                        {
+                            self.aliases.push((ty, alias, original));
+                        }
+                    }
+                    return;
+                }
+                _ => return,
+            }
+            rest = &rest[1..];
+        }
+        if let Some(TokenTree::Literal(literal)) = rest.first() {
+            if let Some(pattern) = string(&literal.to_string()) {
+                self.define(ty, kind, &pattern);
+            }
+        }
+    }
+
+    fn define(&mut self, ty: StepType, kind: Kind, pattern: &str) {
+        self.definitions.push(Definition {
+            ty,
+            kind,
+            pattern: pattern.to_string(),
+        });
+    }
+
+    /// Compiles the definitions, with their aliases matched as the original.
+    fn matchers(mut self) -> io::Result<Vec<Matcher>> {
+        for (ty, alias, original) in &self.aliases {
+            let kind = self
+                .definitions
+                .iter()
+                .find(|d| d.ty == *ty && d.pattern == *original)
+                .map_or(Kind::Text, |d| d.kind);
+            self.definitions.push(Definition {
+                ty: *ty,
+                kind,
+                pattern: alias.clone(),
+            });
+        }
+
+        let invalid = |pattern: &str, e: String| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("`{}` is not a valid step pattern: {}", pattern, e),
+            )
+        };
+        let mut matchers = vec![];
+        for d in &self.definitions {
+            matchers.push(match d.kind {
+                Kind::Text => Matcher::Text(d.ty, d.pattern.clone()),
+                Kind::Regex => Matcher::Regex(
+                    d.ty,
+                    Regex::new(&d.pattern).map_err(|e| invalid(&d.pattern, e.to_string()))?,
+                ),
+                Kind::Expression => {
+                    let expression = Expression::new(&d.pattern, &self.parameter_types)
+                        .map_err(|e| invalid(&d.pattern, e))?;
+                    Matcher::Regex(d.ty, expression.regex().clone())
+                }
+            });
+        }
+        Ok(matchers)
+    }
+}
+
+/// The type and the kind of pattern of the steps defined by the method,
+/// attribute or `steps!` item `name`.
+fn step_method(name: &str) -> Option<(StepType, Kind)> {
+    let (ty, suffix) = match name.find('_') {
+        Some(i) => name.split_at(i),
+        None => (name, ""),
+    };
+    let ty = match ty {
+        "given" => StepType::Given,
+        "when" => StepType::When,
+        "then" => StepType::Then,
+        _ => return None,
+    };
+    let kind = match suffix {
+        "" | "_async" => Kind::Text,
+        "_regex" | "_regex_async" | "_typed" => Kind::Regex,
+        "_expression" => Kind::Expression,
+        _ => return None,
+    };
+    Some((ty, kind))
+}
+
+fn step_type(token: &TokenTree) -> Option<StepType> {
+    match token.to_string().as_str() {
+        "Given" => Some(StepType::Given),
+        "When" => Some(StepType::When),
+        "Then" => Some(StepType::Then),
+        _ => None,
+    }
+}
+
+/// The arguments in the parentheses starting `tokens`, split at commas.
+fn arguments(tokens: &[TokenTree]) -> Option<Vec<Vec<TokenTree>>> {
+    let group = match tokens.first() {
+        Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Parenthesis => group,
+        _ => return None,
+    };
+    let mut args = vec![vec![]];
+    for token in group.stream() {
+        match token {
+            TokenTree::Punct(ref punct) if punct.as_char() == ',' => args.push(vec![]),
+            token => args.last_mut().unwrap().push(token),
+        }
+    }
+    Some(args)
+}
+
+/// The value of a string literal, raw or not, as written in the sources.
+fn string(literal: &str) -> Option<String> {
+    let raw = literal.starts_with('r');
+    let body = literal.trim_start_matches('r').trim_matches('#');
+    if body.len() < 2 || !body.starts_with('"') || !body.ends_with('"') {
+        return None;
+    }
+    let body = &body[1..body.len() - 1];
+    if raw {
+        return Some(body.to_string());
+    }
+
+    let mut value = String::new();
+    let mut chars = body.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => value.push('\n'),
+            'r' => value.push('\r'),
+            't' => value.push('\t'),
+            '0' => value.push('\0'),
+            'x' => {
+                let code = chars.by_ref().take(2).collect::<String>();
+                value.push(u8::from_str_radix(&code, 16).ok()? as char);
+            }
+            'u' => {
+                let code = chars
+                    .by_ref()
+                    .skip(1)
+                    .take_while(|&c| c != '}')
+                    .collect::<String>();
+                value.push(std::char::from_u32(u32::from_str_radix(&code, 16).ok()?)?);
+            }
+            // A line continuation, skipping the whitespace starting the next
+            // line
+            '\n' => {
+                while let Some(c) = chars.peek() {
+                    if !c.is_whitespace() {
+                        break;
+                    }
+                    chars.next();
+                }
+            }
+            c => value.push(c),
+        }
+    }
+    Some(value)
+}
@@ -0,0 +1,434 @@
+//! Finishing the reports of a run whose scenarios are still running long
+//! after its time budget ran out. A hung step would keep the run going
+//! forever, and exiting right away would leave the reports unwritten.
+//!
+//! The file outputs are shared with a watchdog thread. When the run is stuck,
+//! it reports every scenario the outputs were not told about as not run,
+//! the ones still running included, and finishes the outputs before the
+//! process exits.
+
+use std::collections::{BTreeMap, HashSet};
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use crate::output::FileOutputs;
+use crate::{cli, report, Captured, OutputVisitor, Steps, TestResult, Timing, World};
+
+/// An output shared with the watchdog, locked for each event.
+pub(crate) struct Shared<O>(Arc<Mutex<O>>);
+
+impl<O> Shared<O> {
+    pub(crate) fn lock(&self) -> MutexGuard<'_, O> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<O> Clone for Shared<O> {
+    fn clone(&self) -> Self {
+        Shared(self.0.clone())
+    }
+}
+
+/// The file outputs, along with what they were told about so far.
+pub(crate) type Outputs = Shared<(FileOutputs, Progress)>;
+
+impl Outputs {
+    pub(crate) fn with(outputs: FileOutputs) -> Outputs {
+        Shared(Arc::new(Mutex::new((outputs, Progress::default()))))
+    }
+}
+
+/// Which features and scenarios an output was told about, so that the
+/// others can be reported as not run.
+#[derive(Default)]
+pub(crate) struct Progress {
+    /// The feature visited and not ended yet, with its path.
+    feature: Option<(gherkin::Feature, PathBuf)>,
+    /// The scenario visited and not ended yet, with its rule.
+    scenario: Option<(Option<gherkin::Rule>, gherkin::Scenario)>,
+    /// The feature files visited, including those which could not be parsed.
+    features: HashSet<PathBuf>,
+    /// The scenarios reported, by feature file and line.
+    reported: HashSet<(PathBuf, usize)>,
+}
+
+impl Progress {
+    /// Records `scenario` of the current feature as reported, and returns
+    /// whether it was not already.
+    fn report(&mut self, scenario: &gherkin::Scenario) -> bool {
+        let path = match &self.feature {
+            Some((_, path)) => path.clone(),
+            None => return false,
+        };
+        self.reported.insert((path, scenario.position.0))
+    }
+}
+
+impl OutputVisitor for Progress {
+    fn new() -> Self {
+        Progress::default()
+    }
+
+    fn visit_start(&mut self, _metadata: &BTreeMap<String, String>) {}
+
+    fn visit_feature(&mut self, feature: &gherkin::Feature, path: &Path) {
+        self.features.insert(path.to_path_buf());
+        self.feature = Some((feature.clone(), path.to_path_buf()));
+    }
+
+    fn visit_feature_end(&mut self, _feature: &gherkin::Feature) {
+        self.feature = None;
+    }
+
+    fn visit_feature_error(&mut self, path: &Path, _error: &gherkin::Error) {
+        self.features.insert(path.to_path_buf());
+    }
+
+    fn visit_rule(&mut self, _rule: &gherkin::Rule) {}
+
+    fn visit_rule_end(&mut self, _rule: &gherkin::Rule) {}
+
+    fn visit_scenario(&mut self, rule: Option<&gherkin::Rule>, scenario: &gherkin::Scenario) {
+        self.scenario = Some((rule.cloned(), scenario.clone()));
+    }
+
+    fn visit_scenario_end(&mut self, _rule: Option<&gherkin::Rule>, scenario: &gherkin::Scenario) {
+        self.scenario = None;
+        self.report(scenario);
+    }
+
+    fn visit_scenario_skipped(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+    ) {
+    }
+
+    fn visit_scenario_filtered_out(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+    ) {
+        self.report(scenario);
+    }
+
+    fn visit_scenario_not_run(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+    ) {
+        self.report(scenario);
+    }
+
+    fn visit_step(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+        _step: &gherkin::Step,
+    ) {
+    }
+
+    fn visit_step_resolved<'a, W: World>(
+        &mut self,
+        _step: &gherkin::Step,
+        _test: &crate::TestCaseType<'a, W>,
+    ) {
+    }
+
+    fn visit_step_result(
+        &mut self,
+        _rule: Option<&gherkin::Rule>,
+        _scenario: &gherkin::Scenario,
+        _step: &gherkin::Step,
+        _result: &TestResult,
+        _timing: &Timing,
+        _captured: &Captured,
+    ) {
+    }
+
+    fn visit_finish(&mut self) {}
+}
+
+impl<W: World> Steps<W> {
+    /// Reports the scenarios selected in `feature_files` which `outputs`
+    /// were not told about as not run, the one still running included, and
+    /// finishes `outputs`. Returns how many scenarios were not run.
+    ///
+    /// Rules are left out: the file outputs only need them on scenarios.
+    pub(crate) fn finish_stuck_run(
+        &self,
+        feature_files: &[PathBuf],
+        options: &cli::CliOptions,
+        outputs: &Outputs,
+    ) -> usize {
+        let mut guard = outputs.lock();
+        let (outputs, progress) = &mut *guard;
+        let mut not_run = 0;
+
+        if let Some((rule, scenario)) = progress.scenario.take() {
+            progress.report(&scenario);
+            outputs.visit_scenario_not_run(rule.as_ref(), &scenario);
+            outputs.visit_scenario_end(rule.as_ref(), &scenario);
+            not_run += 1;
+        }
+        if let Some((feature, path)) = progress.feature.take() {
+            not_run += self.report_not_run(&feature, &path, options, progress, outputs);
+            outputs.visit_feature_end(&feature);
+        }
+        for path in feature_files {
+            if progress.features.contains(path) {
+                continue;
+            }
+            match self.read_feature(path) {
+                Ok(feature) => {
+                    outputs.visit_feature(&feature, path);
+                    not_run += self.report_not_run(&feature, path, options, progress, outputs);
+                    outputs.visit_feature_end(&feature);
+                }
+                Err(e) => outputs.visit_feature_error(path, &e),
+            }
+        }
+
+        if !self.services.is_empty() {
+            outputs.visit_services(&self.services.reports());
+        }
+        outputs.visit_finish();
+
+        not_run
+    }
+
+    /// Reports the selected scenarios of `feature` not reported yet as not
+    /// run, and returns how many there were.
+    fn report_not_run(
+        &self,
+        feature: &gherkin::Feature,
+        path: &Path,
+        options: &cli::CliOptions,
+        progress: &mut Progress,
+        outputs: &mut FileOutputs,
+    ) -> usize {
+        let mut not_run = 0;
+        let groups = iter::once((None, &feature.scenarios)).chain(
+            feature
+                .rules
+                .iter()
+                .map(|rule| (Some(rule), &rule.scenarios)),
+        );
+        for (rule, scenarios) in groups {
+            for entry in self.select_scenarios(feature, path, scenarios, options) {
+                if let Some(scenario) = entry.scenario() {
+                    if progress
+                        .reported
+                        .insert((path.to_path_buf(), scenario.position.0))
+                    {
+                        outputs.visit_scenario_not_run(rule, scenario);
+                        not_run += 1;
+                    }
+                }
+            }
+        }
+        not_run
+    }
+}
+
+/// Forwards every event to the shared output.
+impl<O: OutputVisitor> OutputVisitor for Shared<O> {
+    fn new() -> Self {
+        Shared(Arc::new(Mutex::new(O::new())))
+    }
+
+    fn visit_start(&mut self, metadata: &BTreeMap<String, String>) {
+        self.lock().visit_start(metadata);
+    }
+
+    fn visit_services(&mut self, services: &[report::Service]) {
+        self.lock().visit_services(services);
+    }
+
+    fn visit_feature(&mut self, feature: &gherkin::Feature, path: &Path) {
+        self.lock().visit_feature(feature, path);
+    }
+
+    fn visit_feature_end(&mut self, feature: &gherkin::Feature) {
+        self.lock().visit_feature_end(feature);
+    }
+
+    fn visit_feature_error(&mut self, path: &Path, error: &gherkin::Error) {
+        self.lock().visit_feature_error(path, error);
+    }
+
+    fn visit_feature_hook_failed(
+        &mut self,
+        feature: &gherkin::Feature,
+        hook: report::Hook,
+        failure: &report::Failure,
+    ) {
+        self.lock()
+            .visit_feature_hook_failed(feature, hook, failure);
+    }
+
+    fn visit_dependency_cycle(
+        &mut self,
+        feature: &gherkin::Feature,
+        rule: Option<&gherkin::Rule>,
+        scenarios: &[String],
+    ) {
+        self.lock().visit_dependency_cycle(feature, rule, scenarios);
+    }
+
+    fn visit_rule(&mut self, rule: &gherkin::Rule) {
+        self.lock().visit_rule(rule);
+    }
+
+    fn visit_rule_end(&mut self, rule: &gherkin::Rule) {
+        self.lock().visit_rule_end(rule);
+    }
+
+    fn visit_scenario_outline(
+        &mut self,
+        rule: Option<&gherkin::Rule>,
+        outline: &gherkin::Scenario,
+    ) {
+        self.lock().visit_scenario_outline(rule, outline);
+    }
+
+    fn visit_scenario_outline_end(
+        &mut self,
+        rule: Option<&gherkin::Rule>,
+        outline: &gherkin::Scenario,
+    ) {
+        self.lock().visit_scenario_outline_end(rule, outline);
+    }
+
+    fn visit_scenario_example(
+        &mut self,
+        rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+        values: &[(String, String)],
+    ) {
+        self.lock().visit_scenario_example(rule, scenario, values);
+    }
+
+    fn visit_scenario(&mut self, rule: Option<&gherkin::Rule>, scenario: &gherkin::Scenario) {
+        self.lock().visit_scenario(rule, scenario);
+    }
+
+    fn visit_scenario_end(&mut self, rule: Option<&gherkin::Rule>, scenario: &gherkin::Scenario) {
+        self.lock().visit_scenario_end(rule, scenario);
+    }
+
+    fn visit_scenario_skipped(
+        &mut self,
+        rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+    ) {
+        self.lock().visit_scenario_skipped(rule, scenario);
+    }
+
+    fn visit_scenario_filtered_out(
+        &mut self,
+        rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+    ) {
+        self.lock().visit_scenario_filtered_out(rule, scenario);
+    }
+
+    fn visit_scenario_not_run(
+        &mut self,
+        rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+    ) {
+        self.lock().visit_scenario_not_run(rule, scenario);
+    }
+
+    fn visit_scenario_ignored(
+        &mut self,
+        rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+        reason: &str,
+    ) {
+        self.lock().visit_scenario_ignored(rule, scenario, reason);
+    }
+
+    fn visit_scenario_quarantined(
+        &mut self,
+        rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+    ) {
+        self.lock().visit_scenario_quarantined(rule, scenario);
+    }
+
+    fn visit_scenario_resources(
+        &mut self,
+        rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+        usage: &report::ResourceUsage,
+    ) {
+        self.lock().visit_scenario_resources(rule, scenario, usage);
+    }
+
+    fn visit_scenario_xfail(
+        &mut self,
+        rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+        passed: bool,
+    ) {
+        self.lock().visit_scenario_xfail(rule, scenario, passed);
+    }
+
+    fn visit_scenario_teardown_failed(
+        &mut self,
+        rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+        failure: &report::Failure,
+    ) {
+        self.lock()
+            .visit_scenario_teardown_failed(rule, scenario, failure);
+    }
+
+    fn visit_scenario_hook_failed(
+        &mut self,
+        rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+        hook: report::Hook,
+        failure: &report::Failure,
+    ) {
+        self.lock()
+            .visit_scenario_hook_failed(rule, scenario, hook, failure);
+    }
+
+    fn visit_step(
+        &mut self,
+        rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+        step: &gherkin::Step,
+    ) {
+        self.lock().visit_step(rule, scenario, step);
+    }
+
+    fn visit_step_resolved<'a, W: World>(
+        &mut self,
+        step: &gherkin::Step,
+        test: &crate::TestCaseType<'a, W>,
+    ) {
+        self.lock().visit_step_resolved(step, test);
+    }
+
+    fn visit_step_result(
+        &mut self,
+        rule: Option<&gherkin::Rule>,
+        scenario: &gherkin::Scenario,
+        step: &gherkin::Step,
+        result: &TestResult,
+        timing: &Timing,
+        captured: &Captured,
+    ) {
+        self.lock()
+            .visit_step_result(rule, scenario, step, result, timing, captured);
+    }
+
+    fn visit_finish(&mut self) {
+        self.lock().visit_finish();
+    }
+}
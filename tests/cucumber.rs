@@ -6,7 +6,17 @@ pub struct MyWorld {
     foo: String,
 }
 
-impl cucumber::World for MyWorld {}
+impl cucumber::World for MyWorld {
+    // Runs after each scenario, even a failed one
+    fn teardown(&mut self) -> cucumber::StepFuture<'_> {
+        Box::pin(async move { self.foo.clear() })
+    }
+}
+impl shared_steps::HasFoo for MyWorld {
+    fn foo(&mut self) -> &mut String {
+        &mut self.foo
+    }
+}
 impl std::default::Default for MyWorld {
     fn default() -> MyWorld {
         // This function is called every time a new scenario is started
@@ -19,6 +29,10 @@ impl std::default::Default for MyWorld {
 mod example_steps {
     use cucumber::steps;
 
+    async fn wait_for_function(world: &mut crate::MyWorld, _step: &cucumber::Step) {
+        world.foo = async { "42".to_string() }.await;
+    }
+
     // Any type that implements cucumber::World + Default can be the world
     steps!(crate::MyWorld => {
         given "I am trying out Cucumber" |world, _step| {
@@ -26,12 +40,17 @@ mod example_steps {
             // Set up your context in given steps
         };
 
+        // Doc comments describe the step in `--list-steps` and `--export-steps`
+        /// Adds a full stop to the string of the world.
         when "I consider what I am doing" |world, _step| {
             // Take actions
             let new_string = format!("{}.", &world.foo);
             world.foo = new_string;
         };
 
+        // Another pattern for the step above, sharing its handler
+        when alias "I think about what I am doing" => "I consider what I am doing";
+
         then "I am interested in ATDD" |world, _step| {
             // Check that the outcomes to be observed have occurred
             assert_eq!(world.foo, "Some string.");
@@ -48,6 +67,63 @@ mod example_steps {
             assert_eq!(word, "olika");
         };
 
+        // Named groups are given by name
+        then regex r"^(?P<buyer>\w+) buys (?P<count>\d+) cucumbers$" named |_world, captures, _step| {
+            assert_eq!(&captures["buyer"], "Ann");
+            assert_eq!(captures.parse::<u32>("count"), Ok(3));
+        };
+
+        then expression "we can match {int} {string} cucumber(s) with an expression" (u32, String) |_world, count, name, _step| {
+            // `{string}` is given without its quotes
+            assert_eq!(count, 3);
+            assert_eq!(name, "small");
+        };
+
+        then "steps can return a Result" |_world, _step| -> Result<(), std::num::ParseIntError> {
+            // An `Err` fails the step with its message, without panicking
+            let _number: u32 = "42".parse()?;
+            Ok(())
+        };
+
+        then regex r"^the clock reads (.+)$" |_world, matches, _step| {
+            let time = cucumber::clock::parse_time(&matches[1]).unwrap();
+            assert_eq!(cucumber::Clock::current().now(), time);
+        };
+
+        then "we can leave a step for later" |_world, _step| {
+            // Skips the rest of the scenario, reported as pending
+            cucumber::pending!("not written yet");
+        };
+
+        then "the step knows where it runs" |_world, step| {
+            // The feature, scenario, tags and example values of the running step
+            let context = cucumber::StepContext::current();
+            assert_eq!(context.step_line, step.position.0);
+            assert!(context.step_index < context.step_count);
+        };
+
+        then "the before hook left the name of the scenario" |_world, _step| {
+            let name = cucumber::Context::with(|context| context.get::<String>().cloned());
+            assert_eq!(name, Some(cucumber::StepContext::current().scenario));
+        };
+
+        when async "we wait for something" |world, _step| {
+            // `.await` can be used in the body of asynchronous steps
+            let answer = async { 42 }.await;
+            world.foo = answer.to_string();
+        };
+
+        // Or be an `async fn` taking the world and the step
+        when async "we wait for a function" wait_for_function;
+
+        then async regex r"^we waited for (\d+)$" |world, matches, _step| {
+            assert_eq!(world.foo, matches[1]);
+        };
+
+        // Fails the step with a clear message when it has no table, so the
+        // `unwrap` below cannot panic
+        requires table;
+
         then "we can use data tables to provide more parameters" |_world, step| {
             let table = step.table().unwrap().clone();
 
@@ -59,31 +135,95 @@ mod example_steps {
             assert_eq!(expected_keys, vec!["a", "b"]);
             assert_eq!(expected_values, vec!["fizz", "buzz"]);
         };
+
+        then regex r"^the answer is (\d+) and the question is (\d+)$" (u32, u32) |_world, answer, question, _step| {
+            // Soft assertions let the step go on, to report every mismatch at once
+            cucumber::soft_assert_eq!(answer, 42);
+            cucumber::soft_assert!(question == 6 * 9, "the question is {}", question);
+        };
+
+        requires table;
+
+        then "we can compare data tables with the expected rows" |_world, step| {
+            // A failure shows the cells which differ
+            use cucumber::TableExt;
+            let table = step.table().unwrap();
+            table.assert_rows(&[("a", "fizz"), ("b", "buzz")], cucumber::TableMatch::default());
+        };
     });
 }
 
-// Declares a before handler function named `a_before_fn`
-before!(a_before_fn => |_scenario| {
+mod anchored_steps {
+    use cucumber::steps;
+
+    steps!(crate::MyWorld => {
+        // Options for the regular expressions of the steps after them
+        regex_options cucumber::RegexOptions {
+            anchored: true,
+            ..Default::default()
+        };
+
+        // Matches the whole text of the step, without `^` and `$`
+        then regex r"the pattern of this step is (\w+)" |_world, matches, _step| {
+            assert_eq!(matches[1], "anchored");
+        };
+    });
+}
 
+// Steps can also be written for any world giving what they need, e.g. in a
+// crate shared between projects
+mod shared_steps {
+    use cucumber::steps;
+
+    pub trait HasFoo {
+        fn foo(&mut self) -> &mut String;
+    }
+
+    steps!(W: HasFoo => {
+        when "we forget about foo" |world, _step| {
+            world.foo().clear();
+        };
+
+        then regex r"^foo is (.*)$" |world, matches, _step| {
+            assert_eq!(*world.foo(), matches[1]);
+        };
+    });
+}
+
+// Declares a before handler function named `a_before_fn`
+before!(a_before_fn => |scenario| {
+    // Values kept in the context last until the end of the scenario
+    cucumber::Context::with(|context| context.insert(scenario.name.clone()));
 });
 
-// Declares an after handler function named `an_after_fn`
-after!(an_after_fn => |_scenario| {
+// Runs only before the scenarios whose tags, or their feature's, match
+before!(a_tagged_before_fn: "@foo and not @wip" => |scenario| {
+    assert_eq!(scenario.name, "foo");
+});
 
+// Declares an after handler function named `an_after_fn`, told how the scenario went
+after!(an_after_fn => |_scenario, outcome| {
+    if outcome.is_failed() {
+        assert!(outcome.failure.is_some());
+    }
 });
 
 // A setup function to be called before everything else
 fn setup() {}
 
 cucumber! {
-    features: "./features", // Path to our feature files
+    features: "./features/cucumber", // Path to our feature files
     world: ::MyWorld, // The world needs to be the same for steps and the main cucumber call
     steps: &[
-        example_steps::steps // the `steps!` macro creates a `steps` function in a module
+        example_steps::steps, // the `steps!` macro creates a `steps` function in a module
+        anchored_steps::steps,
+        shared_steps::steps::<MyWorld>, // generic ones are given the world
+        cucumber::clock::steps::<MyWorld> // "Given the current time is ..." and the like
     ],
     setup: setup, // Optional; called once before everything
     before: &[
-        a_before_fn // Optional; called before each scenario
+        a_before_fn, // Optional; called before each scenario
+        a_tagged_before_fn
     ],
     after: &[
         an_after_fn // Optional; called after each scenario
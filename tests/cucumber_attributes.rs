@@ -0,0 +1,103 @@
+extern crate cucumber_rust as cucumber;
+use cucumber::cucumber;
+
+// Set up and torn down with the components of the world, in order
+#[derive(cucumber::World)]
+pub struct MyWorld {
+    // You can use this struct for mutable context in scenarios.
+    #[world(skip)]
+    foo: String,
+    scratch: Scratch,
+}
+
+impl std::default::Default for MyWorld {
+    fn default() -> MyWorld {
+        // This function is called every time a new scenario is started
+        MyWorld {
+            foo: "a default string".to_string(),
+            scratch: Scratch::default(),
+        }
+    }
+}
+
+// A component which step libraries can require alone, with `Has<Scratch>`
+#[derive(Default)]
+pub struct Scratch(Vec<String>);
+
+impl cucumber::Component for Scratch {
+    fn teardown(&mut self) -> cucumber::StepFuture<'_> {
+        Box::pin(async move { self.0.clear() })
+    }
+}
+
+mod example_steps {
+    use cucumber::{given, then, when, Captures, Has, Step};
+
+    // Steps are plain functions taking the world first
+    #[given("I am trying out Cucumber")]
+    fn trying_out(world: &mut crate::MyWorld) {
+        world.foo = "Some string".to_string();
+    }
+
+    // Doc comments describe the step in `--list-steps` and `--export-steps`
+    /// Adds a full stop to the string of the world.
+    #[when("I consider what I am doing")]
+    fn consider(world: &mut crate::MyWorld, _step: &Step) {
+        let new_string = format!("{}.", &world.foo);
+        world.foo = new_string;
+    }
+
+    #[then("I am interested in ATDD")]
+    fn interested(world: &mut crate::MyWorld) {
+        assert_eq!(world.foo, "Some string.");
+    }
+
+    // Captures are parsed to the types of the arguments after the world
+    #[then(regex = r"^we can also match (\d+) (.+) types$")]
+    fn match_types(_world: &mut crate::MyWorld, num: usize, word: String) {
+        assert_eq!(num, 42);
+        assert_eq!(word, "olika");
+    }
+
+    #[then(expression = "we can match {int} {string} cucumber(s) with an expression")]
+    fn match_expression(_world: &mut crate::MyWorld, count: u32, name: String) {
+        assert_eq!(count, 3);
+        assert_eq!(name, "small");
+    }
+
+    // A step without a docstring fails before the function is called
+    #[then("we can read a docstring", requires = "docstring")]
+    fn read_docstring(_world: &mut crate::MyWorld, step: &Step) {
+        assert!(!step.docstring.as_ref().unwrap().is_empty());
+    }
+
+    // Named groups can be taken by name
+    #[then(regex = r"^(?P<buyer>\w+) sells (?P<count>\d+) cucumbers$")]
+    fn sells(_world: &mut crate::MyWorld, captures: &Captures) {
+        assert_eq!(captures.get("buyer"), Some("Bob"));
+    }
+
+    // Components are reached through `Has`, as step libraries do
+    #[when(regex = r"^we note (\w+)$")]
+    fn note(world: &mut crate::MyWorld, word: String) {
+        let scratch: &mut crate::Scratch = world.component();
+        scratch.0.push(word);
+    }
+
+    #[then(regex = r"^we have noted (\d+) words?$")]
+    fn noted(world: &mut crate::MyWorld, count: usize) {
+        let scratch: &mut crate::Scratch = world.component();
+        assert_eq!(scratch.0.len(), count);
+    }
+
+    // Or taken as strings
+    #[then(regex = r"^we can (.*) rules with regex$")]
+    fn rules(_world: &mut crate::MyWorld, matches: &[String]) {
+        assert_eq!(matches[1], "implement");
+    }
+}
+
+cucumber! {
+    features: "./features/cucumber_attributes", // Path to our feature files
+    world: crate::MyWorld // Steps defined with the attributes for this world are collected
+}
@@ -17,7 +17,13 @@ impl std::default::Default for MyWorld {
 }
 
 mod example_steps {
-    use cucumber::{typed_regex, Steps, StepsBuilder};
+    use cucumber::{typed_regex, typed_step, Steps, StepsBuilder};
+
+    // Typed handlers take the captured values as arguments, parsed with `FromStr`
+    fn counted(_world: &mut crate::MyWorld, count: u32, what: String) {
+        assert_eq!(count, 2);
+        assert_eq!(what, "cucumbers");
+    }
 
     pub fn steps() -> Steps<crate::MyWorld> {
         let mut builder: StepsBuilder<crate::MyWorld> = StepsBuilder::new();
@@ -53,6 +59,10 @@ mod example_steps {
                         assert_eq!(word, "olika");
                     }
                 ),
+            )
+            .then_typed(
+                r"^we counted (\d+) (\w+)$",
+                typed_step!(crate::MyWorld, (u32, String) counted),
             );
 
         builder.build()
@@ -115,7 +125,7 @@ after!(an_after_fn => |_scenario| {
 fn setup() {}
 
 cucumber! {
-    features: "./features", // Path to our feature files
+    features: "./features/cucumber_builder", // Path to our feature files
     world: ::MyWorld, // The world needs to be the same for steps and the main cucumber call
     steps: &[
         example_steps::steps // the `steps!` macro creates a `steps` function in a module